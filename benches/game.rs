@@ -0,0 +1,103 @@
+#[macro_use] extern crate criterion;
+extern crate rocket;
+extern crate rock_paper_scissors;
+
+use std::sync::Arc;
+use std::thread;
+
+use criterion::Criterion;
+use rocket::http::{ContentType, Cookie};
+use rocket::local::Client;
+
+use rock_paper_scissors::commitment::{self, MatchSeed};
+use rock_paper_scissors::game::{Hand, Round, Scoreboard, Strategy, Verdict};
+use rock_paper_scissors::shard::ShardedMap;
+use rock_paper_scissors::util::gen_random_bytes;
+
+fn commitments(c: &mut Criterion) {
+    c.bench_function("Round::new", |b| {
+        b.iter(|| Round::new(Hand::random(), &gen_random_bytes(32), Strategy::Uniform))
+    });
+
+    let nonce = "00112233445566778899aabbccddeeff00112233445566778899aabbccddeeff";
+    c.bench_function("commitment::digest", move |b| {
+        b.iter(|| commitment::digest(nonce, Hand::Scissors))
+    });
+
+    let mut seed = MatchSeed::random();
+    c.bench_function("MatchSeed::next_round", move |b| b.iter(|| seed.next_round()));
+
+    c.bench_function("Verdict::of", |b| b.iter(|| Verdict::of(Hand::Rock, Hand::random())));
+}
+
+/// One logged-in player playing rounds through the whole Rocket stack, without the network.
+fn play_path(c: &mut Criterion) {
+    let client = Client::new(rock_paper_scissors::rocket()).expect("valid rocket instance");
+    let cookies: Vec<Cookie<'static>> = {
+        let login = client.post("/login")
+            .header(ContentType::Form)
+            .body("user_name=bench")
+            .dispatch();
+        let cookies = login.headers()
+            .get("Set-Cookie")
+            .filter_map(|value| Cookie::parse_encoded(value.to_owned()).ok())
+            .collect();
+        cookies
+    };
+
+    {
+        let mut index = client.get("/");
+        for cookie in &cookies {
+            index = index.cookie(cookie.clone());
+        }
+        index.dispatch();
+    }
+
+    c.bench_function("GET /?hand", move |b| {
+        b.iter(|| {
+            let mut request = client.get("/?hand=rock");
+            for cookie in &cookies {
+                request = request.cookie(cookie.clone());
+            }
+            request.dispatch().status()
+        })
+    });
+}
+
+/// Simulated concurrent play on the session map: `PLAYERS` threads each recording rounds in their
+/// own entries, with a leaderboard scan of every entry now and then.
+fn session_shards(c: &mut Criterion) {
+    const PLAYERS: usize = 8;
+    const SESSIONS_PER_PLAYER: usize = 32;
+    const ROUNDS: usize = 500;
+
+    c.bench_function_over_inputs("ShardedMap, 8 players", |b, &&shards| {
+        let map = Arc::new(ShardedMap::new(shards));
+        for i in 0..PLAYERS * SESSIONS_PER_PLAYER {
+            map.write_all().insert(format!("player{}", i), Scoreboard::default());
+        }
+        b.iter(|| {
+            let handles: Vec<_> = (0..PLAYERS)
+                .map(|player| {
+                    let map = map.clone();
+                    thread::spawn(move || {
+                        for round in 0..ROUNDS {
+                            let session = round % SESSIONS_PER_PLAYER * PLAYERS + player;
+                            let key = format!("player{}", session);
+                            map.write(&key).get_mut(&key).unwrap().record(Verdict::Win);
+                            if round % 100 == 0 {
+                                map.read_all().values().map(|score| score.wins).max();
+                            }
+                        }
+                    })
+                })
+                .collect();
+            for handle in handles {
+                handle.join().unwrap();
+            }
+        })
+    }, &[1, 8, 64]);
+}
+
+criterion_group!(benches, commitments, play_path, session_shards);
+criterion_main!(benches);
@@ -0,0 +1,34 @@
+//! The `hand` forms and queries, strict and lax: parsing never panics, and what parses is made of
+//! fields that parse on their own.
+
+#![no_main]
+#[macro_use] extern crate libfuzzer_sys;
+extern crate rock_paper_scissors;
+extern crate rocket;
+
+use std::str;
+
+use rocket::request::{FormItems, FromForm, FromFormValue};
+use rock_paper_scissors::params::{HandParam, UserHands};
+
+fuzz_target!(|data: &[u8]| {
+    let form = match str::from_utf8(data) {
+        Ok(form) => form,
+        Err(_) => return,
+    };
+    for &strict in &[false, true] {
+        let param = HandParam::from_form(&mut FormItems::from(form), strict);
+        let hands = UserHands::from_form(&mut FormItems::from(form), strict);
+        let values: Vec<_> = FormItems::from(form)
+            .filter(|&(key, _)| key == "hand")
+            .map(|(_, value)| HandParam::from_form_value(value).ok())
+            .collect();
+        if let Ok(param) = param {
+            assert!(values.contains(&Some(param)));
+        }
+        if let Ok(UserHands(hands)) = hands {
+            let expected: Vec<_> = hands.iter().map(|&hand| Some(HandParam(hand))).collect();
+            assert_eq!(values, expected);
+        }
+    }
+});
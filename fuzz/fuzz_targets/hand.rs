@@ -0,0 +1,23 @@
+//! `Hand::from_str` and `HandParam`: whatever parses names a hand, and the name parses back to it.
+
+#![no_main]
+#[macro_use] extern crate libfuzzer_sys;
+extern crate rock_paper_scissors;
+
+use std::str;
+
+use rock_paper_scissors::game::Hand;
+use rock_paper_scissors::params::HandParam;
+
+fuzz_target!(|data: &[u8]| {
+    let text = match str::from_utf8(data) {
+        Ok(text) => text,
+        Err(_) => return,
+    };
+    let hand = text.parse::<Hand>().ok();
+    if let Some(hand) = hand {
+        assert!(text.eq_ignore_ascii_case(hand.as_ref()));
+        assert_eq!(hand.as_ref().parse::<Hand>().ok(), Some(hand));
+    }
+    assert_eq!(text.parse::<HandParam>().ok().map(|param| param.0), hand);
+});
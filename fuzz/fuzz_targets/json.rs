@@ -0,0 +1,30 @@
+//! The JSON documents players hand back: round proofs, history exports and their hex fields.
+
+#![no_main]
+#[macro_use] extern crate libfuzzer_sys;
+extern crate rock_paper_scissors;
+extern crate serde_json;
+
+use std::str;
+
+use rock_paper_scissors::history::{self, Export, History};
+use rock_paper_scissors::proof::RoundProof;
+use rock_paper_scissors::util;
+
+fuzz_target!(|data: &[u8]| {
+    let text = match str::from_utf8(data) {
+        Ok(text) => text,
+        Err(_) => return,
+    };
+    if let Ok(proof) = RoundProof::parse(text) {
+        assert_eq!(proof.to_canonical(), text);
+        let _ = proof.verify();
+    }
+    if let Ok(export) = serde_json::from_slice::<Export>(data) {
+        let _ = history::verify(&export);
+        let _ = History::from_records(&export.rounds);
+    }
+    if let Some(bytes) = util::hex_to_bytes(text) {
+        assert_eq!(util::bytes_to_hex(&bytes), text.to_ascii_lowercase());
+    }
+});
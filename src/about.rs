@@ -0,0 +1,125 @@
+//! Public aggregate stats, `/about/stats`
+//!
+//! Totals over every profile ever saved, so that visitors see how much the game is played before
+//! they log in, in full here and as a teaser on the landing page. No player is named. Summing
+//! every profile takes a while once there are many, so the totals are computed at most once per
+//! `CACHE_SECS`.
+
+use std::f64::consts::PI;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use rocket_contrib::{Template, Value};
+
+use game::Hand;
+use poison::Recover;
+use profile;
+use render::render;
+
+const CACHE_SECS: u64 = 60;
+
+lazy_static! {
+    static ref CACHE: Mutex<Option<(Instant, Arc<GlobalStats>)>> = Mutex::new(None);
+}
+
+#[derive(Default)]
+struct GlobalStats {
+    players: usize,
+    rounds: usize,
+    /// Indexed like `Hand::CHOICES`.
+    hands: [u64; 3],
+    best_streak: u32,
+}
+
+impl GlobalStats {
+    fn compute() -> GlobalStats {
+        profile::fold(GlobalStats::default(), |mut stats, profile| {
+            stats.players += 1;
+            for scoreboard in &[profile.classic, profile.chaos] {
+                stats.rounds += scoreboard.wins + scoreboard.ties + scoreboard.losses;
+            }
+            for (total, count) in stats.hands.iter_mut().zip(&profile.hands) {
+                *total += count;
+            }
+            stats.best_streak = stats.best_streak.max(profile.best_streak);
+            stats
+        })
+    }
+}
+
+fn global_stats() -> Arc<GlobalStats> {
+    let mut cache = CACHE.lock().recover();
+    if let Some((ref computed_at, ref stats)) = *cache {
+        if computed_at.elapsed() < Duration::from_secs(CACHE_SECS) {
+            return stats.clone();
+        }
+    }
+    let stats = Arc::new(GlobalStats::compute());
+    *cache = Some((Instant::now(), stats.clone()));
+    stats
+}
+
+/// A few of the totals, for the landing page: players, rounds and the favourite hand, if any.
+pub fn teaser() -> Value {
+    let stats = global_stats();
+    let favourite = Hand::CHOICES.iter()
+        .filter(|hand| stats.hands[hand.index()] > 0)
+        .max_by_key(|hand| stats.hands[hand.index()]);
+    json!({
+        "players": stats.players,
+        "rounds": stats.rounds,
+        "favourite_hand": favourite.map(|hand| format!("{} {}", hand.as_icon(), hand.as_ref())),
+    })
+}
+
+/// The point of the pie chart, of radius 40 around (50, 50), at `turns` of a turn clockwise from
+/// the top.
+fn pie_point(turns: f64) -> (f64, f64) {
+    let angle = 2.0 * PI * turns;
+    (50.0 + 40.0 * angle.sin(), 50.0 - 40.0 * angle.cos())
+}
+
+/// The SVG path of the slice of the pie chart from `from` to `to`, both in turns.
+fn pie_slice(from: f64, to: f64) -> String {
+    if to - from > 0.999 {
+        // An arc cannot end where it starts: draw the full disc as two halves.
+        return "M 50 10 A 40 40 0 1 1 50 90 A 40 40 0 1 1 50 10 Z".to_owned();
+    }
+    let ((x0, y0), (x1, y1)) = (pie_point(from), pie_point(to));
+    let large_arc = if to - from > 0.5 { 1 } else { 0 };
+    format!("M 50 50 L {:.2} {:.2} A 40 40 0 {} 1 {:.2} {:.2} Z", x0, y0, large_arc, x1, y1)
+}
+
+// ===== Routers =====
+
+#[get("/stats")]
+pub fn stats() -> Template {
+    let stats = global_stats();
+    let total_hands: u64 = stats.hands.iter().sum();
+    let mut from = 0.0;
+    let slices: Vec<_> = Hand::CHOICES.iter()
+        .map(|hand| {
+            let count = stats.hands[hand.index()];
+            let share = if total_hands == 0 { 0.0 } else { count as f64 / total_hands as f64 };
+            let slice = json!({
+                "hand": hand.as_ref(),
+                "icon": hand.as_icon(),
+                "count": count,
+                "percent": format!("{:.1}%", 100.0 * share),
+                "path": if count == 0 { None } else { Some(pie_slice(from, from + share)) },
+            });
+            from += share;
+            slice
+        })
+        .collect();
+
+    let context = json!({
+        "players": stats.players,
+        "rounds": stats.rounds,
+        "best_streak": stats.best_streak,
+        "hands": total_hands,
+        "slices": slices,
+        "cache_secs": CACHE_SECS,
+    });
+    render("about_stats", &context)
+}
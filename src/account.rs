@@ -0,0 +1,152 @@
+//! Deleting an account
+//!
+//! A player can delete their account from their profile page. Everything kept under their name or
+//! their sessions goes, in an order that leaves nothing to bring it back: their sessions first
+//! (with the round history, API keys and cookies' worth of state), so that no request of theirs
+//! writes anything anymore, then their team, PvP matches and invite links, emailed ones included,
+//! registered bots and their standings, recovery codes, pending login handoffs and failed login
+//! counts. Last, `Event::AccountDeleted` lets the subscribers drop the rest: the profile, cheat
+//! detection records and the cached leaderboards.
+//!
+//! Anonymized analytics were never tied to the name and stay. So do the sealed entries of the
+//! escrow log, which settle disputes over rounds already played, and bans.
+//!
+//! Typing the name is not enough, since anyone can log in under any name: the player redeems a
+//! recovery code, or their session logged in with a passkey within `REAUTHENTICATION_WINDOW`.
+//! Every deletion is first appended to the `deletion_log`, if set, with when it happened, how the
+//! player proved it was them and the SHA-256 of the name rather than the name itself. A deletion
+//! that cannot be logged does not happen. Until it completes, nothing recreates the profile. The
+//! log is read back at startup, so that restoring a backup taken before a deletion does not bring
+//! the account back (see `deleted_since`).
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use rocket::Config;
+use serde_json;
+use sha2::{Digest, Sha256};
+
+use events::{self, Event};
+#[cfg(feature = "templates")]
+use handoff;
+#[cfg(feature = "templates")]
+use lockout;
+use poison::Recover;
+use profile;
+#[cfg(feature = "templates")]
+use pvp;
+#[cfg(feature = "templates")]
+use recovery;
+use relay;
+use revocation::Revocations;
+#[cfg(feature = "templates")]
+use team;
+use tournament;
+use util::{bytes_to_hex, unix_time};
+use end_sessions_of;
+
+/// How recent a passkey login stands in for a recovery code.
+pub const REAUTHENTICATION_WINDOW: Duration = Duration::from_secs(10 * 60);
+
+lazy_static! {
+    static ref LOG: Mutex<Option<File>> = Mutex::new(None);
+    /// Every deletion logged, and those of this run: Unix time and hex SHA-256 of the name.
+    static ref DELETIONS: Mutex<Vec<(u64, String)>> = Mutex::new(Vec::new());
+}
+
+/// How the player proved they own the account.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Proof {
+    RecoveryCode,
+    Passkey,
+}
+
+/// A line of the `deletion_log`.
+#[derive(Serialize, Deserialize)]
+struct Record {
+    /// Unix time of the deletion.
+    time: u64,
+    /// The hex SHA-256 of the name.
+    name: String,
+    proof: Proof,
+}
+
+fn name_digest(user_name: &str) -> String {
+    bytes_to_hex(&Sha256::digest(user_name.as_bytes()))
+}
+
+/// Reads the deletions logged to the `deletion_log`, if set, and opens it to append to.
+pub fn configure(config: &Config) -> Result<(), String> {
+    let path = match config.get_str("deletion_log") {
+        Ok(path) => Path::new(path),
+        Err(_) => return Ok(()),
+    };
+    if path.exists() {
+        let reader = File::open(path).map(BufReader::new)
+            .map_err(|e| format!("unable to read {}: {}", path.display(), e))?;
+        let mut deletions = DELETIONS.lock().recover();
+        for (number, line) in reader.lines().enumerate() {
+            let line = line.map_err(|e| format!("unable to read {}: {}", path.display(), e))?;
+            // A line cut short by a crash was never acknowledged: the deletion did not happen.
+            match serde_json::from_str::<Record>(&line) {
+                Ok(record) => deletions.push((record.time, record.name)),
+                Err(e) => eprintln!("Skipping line {} of {}: {}", number + 1, path.display(), e),
+            }
+        }
+    }
+    let log = OpenOptions::new().create(true).append(true).open(path)
+        .map_err(|e| format!("unable to open {}: {}", path.display(), e))?;
+    *LOG.lock().recover() = Some(log);
+    Ok(())
+}
+
+/// Appends the deletion of `user_name` to the log, and waits until it is on disk.
+fn record(user_name: &str, proof: Proof) -> io::Result<()> {
+    let record = Record {
+        time: unix_time(),
+        name: name_digest(user_name),
+        proof: proof,
+    };
+    if let Some(ref mut log) = *LOG.lock().recover() {
+        writeln!(log, "{}", serde_json::to_string(&record).unwrap())?;
+        log.sync_data()?;
+    }
+    DELETIONS.lock().recover().push((record.time, record.name));
+    Ok(())
+}
+
+/// Whether the account of `user_name` was deleted at `since`, a Unix time, or later.
+pub fn deleted_since(user_name: &str, since: u64) -> bool {
+    let name = name_digest(user_name);
+    DELETIONS.lock().recover().iter().any(|&(time, ref deleted)| time >= since && *deleted == name)
+}
+
+/// Deletes the account of `user_name`, who proved it is theirs with `proof`. Fails, deleting
+/// nothing, if the deletion could not be logged.
+pub fn delete(user_name: &str, proof: Proof, revocations: &Revocations) -> io::Result<()> {
+    record(user_name, proof)?;
+    profile::hold_for_deletion(user_name);
+    #[cfg(feature = "templates")]
+    for user_id in end_sessions_of(user_name, revocations) {
+        team::leave(&user_id);
+        pvp::forget(&user_id);
+    }
+    #[cfg(not(feature = "templates"))]
+    end_sessions_of(user_name, revocations);
+    #[cfg(feature = "templates")]
+    pvp::forget_player(user_name);
+    tournament::forget_owner(user_name);
+    relay::forget_member(user_name);
+    #[cfg(feature = "templates")]
+    recovery::forget(user_name);
+    #[cfg(feature = "templates")]
+    handoff::forget(user_name);
+    #[cfg(feature = "templates")]
+    lockout::forget(user_name);
+    events::publish(Event::AccountDeleted { user_name: user_name.to_owned() });
+    Ok(())
+}
@@ -0,0 +1,366 @@
+//! Administration routes under `/admin`
+//!
+//! They are disabled unless the `admin_token` config extra is set, and then require that token in
+//! an `X-Admin-Token` header. Their failures answer like the API's, with an `ApiError`.
+
+use std::net::IpAddr;
+
+use rocket::http::Status;
+use rocket::request::{self, FromRequest, Request, State};
+use rocket::response::{content, status};
+use rocket::{Config, Outcome};
+#[cfg(feature = "templates")]
+use rocket_contrib::Template;
+use rocket_contrib::{Json, Value};
+
+use analytics::ANALYTICS;
+use api::ApiError;
+use anticheat::{Flag, ANTICHEAT};
+use backup::{self, BackupError, BackupFile, Restored};
+use escrow::{self, Deposit};
+use experiment::{self, VariantResults};
+use game::Strategy;
+use maintenance;
+use metrics;
+use moderation::{Ban, BanKind, Bans};
+use notification::{Notification, Outbox};
+#[cfg(feature = "templates")]
+use quota::Quotas;
+#[cfg(feature = "templates")]
+use render::render;
+use revocation::Revocations;
+use util::{constant_time_eq, unix_time};
+use webhook::{self, Delivery};
+
+const ADMIN_TOKEN_HEADER: &str = "X-Admin-Token";
+#[cfg(feature = "templates")]
+const TOP_CONSUMERS: usize = 20;
+
+/// The `admin_token` config extra.
+pub struct AdminToken(Option<String>);
+
+impl AdminToken {
+    pub fn from_config(config: &Config) -> AdminToken {
+        AdminToken(config.get_str("admin_token").ok().map(|token| token.to_owned()))
+    }
+}
+
+/// A request carrying the admin token. Fails with `404 Not Found` if no token is configured, and
+/// with `403 Forbidden` if the token is missing or wrong.
+pub struct Admin;
+
+impl<'a, 'r> FromRequest<'a, 'r> for Admin {
+    type Error = ();
+
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<Admin, ()> {
+        let token = request.guard::<State<AdminToken>>()?;
+        let expected = match token.0 {
+            Some(ref expected) => expected,
+            None => return Outcome::Failure((Status::NotFound, ())),
+        };
+        match request.headers().get_one(ADMIN_TOKEN_HEADER) {
+            Some(given) if constant_time_eq(given, expected) => Outcome::Success(Admin),
+            _ => Outcome::Failure((Status::Forbidden, ())),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct NewBan {
+    kind: BanKind,
+    value: String,
+    #[serde(default)]
+    reason: Option<String>,
+    /// Duration of a timed ban. Permanent if absent.
+    #[serde(default)]
+    minutes: Option<u64>,
+}
+
+#[derive(Deserialize)]
+pub struct MaintenanceMode {
+    on: bool,
+}
+
+/// How the computer fares with one strategy.
+#[derive(Serialize)]
+pub struct StrategyStats {
+    strategy: Strategy,
+    rounds: usize,
+    computer_wins: usize,
+    ties: usize,
+    computer_losses: usize,
+    /// Of the rounds not tied. `None` until one isn't.
+    computer_win_rate: Option<f64>,
+}
+
+fn strategy_stats() -> Vec<StrategyStats> {
+    let outcomes = ANALYTICS.strategy_outcomes();
+    Strategy::ALL.iter()
+        .map(|&strategy| {
+            let score = outcomes[strategy.index()];
+            let decided = score.wins + score.losses;
+            StrategyStats {
+                strategy: strategy,
+                rounds: decided + score.ties,
+                computer_wins: score.losses,
+                ties: score.ties,
+                computer_losses: score.wins,
+                computer_win_rate: if decided > 0 {
+                    Some(score.losses as f64 / decided as f64)
+                } else {
+                    None
+                },
+            }
+        })
+        .collect()
+}
+
+// ===== Routers =====
+
+/// Today's top API consumers.
+#[cfg(feature = "templates")]
+#[get("/quotas")]
+pub fn quotas(_admin: Admin, quotas: State<Quotas>) -> Template {
+    let limit = quotas.daily_limit();
+    let consumers: Vec<_> = quotas.top(TOP_CONSUMERS)
+        .into_iter()
+        .map(|(label, used)| json!({
+            "label": label,
+            "used": used,
+            "remaining": limit.saturating_sub(used),
+        }))
+        .collect();
+
+    let context = json!({
+        "daily_limit": limit,
+        "consumers": consumers,
+    });
+    render("admin_quotas", &context)
+}
+
+#[get("/bans")]
+pub fn bans(_admin: Admin, bans: State<Bans>) -> Json<Vec<Ban>> {
+    Json(bans.list())
+}
+
+/// Bans a user name or an IP address. Sessions of a banned name end immediately.
+#[post("/bans", format = "application/json", data = "<ban>")]
+pub fn ban(_admin: Admin, bans: State<Bans>, revocations: State<Revocations>, ban: Json<NewBan>)
+    -> Result<Json<Ban>, ApiError>
+{
+    let NewBan { kind, value, reason, minutes } = ban.into_inner();
+    let value = match kind {
+        BanKind::Name => value,
+        BanKind::Ip => {
+            value.parse::<IpAddr>().map_err(|_| ApiError::Status(Status::BadRequest))?.to_string()
+        }
+    };
+    let ban = Ban {
+        kind: kind,
+        value: value,
+        reason: reason,
+        until: minutes.map(|minutes| unix_time() + minutes * 60),
+    };
+
+    bans.add(ban.clone()).map_err(|e| {
+        eprintln!("Unable to save bans: {}", e);
+        ApiError::Status(Status::InternalServerError)
+    })?;
+    if ban.kind == BanKind::Name {
+        ::end_sessions_of(&ban.value, &revocations);
+    }
+    Ok(Json(ban))
+}
+
+#[delete("/bans/<kind>/<value>")]
+pub fn unban(_admin: Admin, bans: State<Bans>, kind: BanKind, value: String)
+    -> Result<status::NoContent, ApiError>
+{
+    match bans.remove(kind, &value) {
+        Ok(true) => Ok(status::NoContent),
+        Ok(false) => Err(ApiError::Status(Status::NotFound)),
+        Err(e) => {
+            eprintln!("Unable to save bans: {}", e);
+            Err(ApiError::Status(Status::InternalServerError))
+        }
+    }
+}
+
+/// Logs a user out everywhere, revoking all of their sessions and API keys.
+#[post("/users/<name>/revoke")]
+pub fn revoke_sessions(_admin: Admin, revocations: State<Revocations>, name: String)
+    -> Json<Value>
+{
+    let revoked = ::end_sessions_of(&name, &revocations).len();
+    Json(json!({ "revoked": revoked }))
+}
+
+/// Opens the escrowed seed of a disputed round. Rounds not played yet are refused with
+/// `409 Conflict`.
+#[get("/escrow/<digest>")]
+pub fn open_escrow(_admin: Admin, digest: String) -> Result<Json<Deposit>, ApiError> {
+    if ::is_pending(&digest) {
+        return Err(ApiError::Status(Status::Conflict));
+    }
+    match escrow::open(&digest) {
+        Ok(Some(deposit)) => Ok(Json(deposit)),
+        Ok(None) => Err(ApiError::Status(Status::NotFound)),
+        Err(e) => {
+            eprintln!("Unable to read escrow log: {}", e);
+            Err(ApiError::Status(Status::InternalServerError))
+        }
+    }
+}
+
+/// Classic round outcomes by strategy of the computer, one series per strategy, for charting.
+#[get("/strategies")]
+pub fn strategies(_admin: Admin) -> Json<Vec<StrategyStats>> {
+    Json(strategy_stats())
+}
+
+/// How the players of every variant of the running experiments are doing, see `experiment`.
+#[get("/experiments")]
+pub fn experiments(_admin: Admin) -> Json<Vec<VariantResults>> {
+    Json(experiment::results())
+}
+
+/// Decision times of the computer's strategies, for Prometheus to scrape, see `metrics`.
+#[get("/metrics")]
+pub fn metrics(_admin: Admin) -> content::Plain<String> {
+    content::Plain(metrics::render())
+}
+
+/// Accounts flagged by cheat detection, oldest flag first.
+#[get("/flags")]
+pub fn flags(_admin: Admin) -> Json<Vec<Flag>> {
+    Json(ANTICHEAT.flags())
+}
+
+/// Clears the flags of a reviewed account, and the evidence gathered so far.
+#[delete("/flags/<name>")]
+pub fn dismiss_flag(_admin: Admin, name: String) -> Result<status::NoContent, ApiError> {
+    if ANTICHEAT.dismiss(&name) {
+        Ok(status::NoContent)
+    } else {
+        Err(ApiError::Status(Status::NotFound))
+    }
+}
+
+/// Turns maintenance mode on, to drain the server before a redeploy, or off again.
+#[post("/maintenance", data = "<mode>")]
+pub fn maintenance(_admin: Admin, mode: Json<MaintenanceMode>)
+    -> Result<Json<maintenance::Status>, ApiError>
+{
+    maintenance::set(mode.on).map(Json).map_err(|e| {
+        eprintln!("Unable to update the maintenance snapshot: {}", e);
+        ApiError::Status(Status::InternalServerError)
+    })
+}
+
+/// What is left to drain; `ready` once the snapshot can be saved.
+#[get("/maintenance")]
+pub fn maintenance_status(_admin: Admin) -> Json<maintenance::Status> {
+    Json(maintenance::status())
+}
+
+/// Saves the PvP matches once drained, after which the server can be shut down; `409 Conflict`
+/// before.
+#[post("/maintenance/snapshot")]
+pub fn maintenance_snapshot(_admin: Admin) -> Result<Json<maintenance::Status>, ApiError> {
+    match maintenance::snapshot() {
+        Ok(Some(status)) => Ok(Json(status)),
+        Ok(None) => Err(ApiError::Status(Status::Conflict)),
+        Err(e) => {
+            eprintln!("Unable to save the maintenance snapshot: {}", e);
+            Err(ApiError::Status(Status::InternalServerError))
+        }
+    }
+}
+
+/// The state backups, the latest first, see `backup`.
+#[get("/backups")]
+pub fn backups(_admin: Admin) -> Result<Json<Vec<BackupFile>>, ApiError> {
+    backup::list().map(Json).map_err(backup_failure)
+}
+
+/// Backs the state up now, besides the scheduled backups.
+#[post("/backups")]
+pub fn take_backup(_admin: Admin) -> Result<Json<BackupFile>, ApiError> {
+    backup::take().map(Json).map_err(backup_failure)
+}
+
+/// Rolls the sessions and the profiles back to a backup. A backup whose hash does not match is
+/// refused with `422 Unprocessable Entity`, leaving the state as it is.
+#[post("/backups/<name>/restore")]
+pub fn restore_backup(_admin: Admin, revocations: State<Revocations>, name: String)
+    -> Result<Json<Restored>, ApiError>
+{
+    backup::restore(&name, &revocations).map(Json).map_err(backup_failure)
+}
+
+fn backup_failure(e: BackupError) -> ApiError {
+    match e {
+        BackupError::Corrupt(ref reason) => eprintln!("Refusing a corrupt backup: {}", reason),
+        BackupError::Io(ref reason) => eprintln!("Unable to access the backups: {}", reason),
+        BackupError::Disabled | BackupError::NoSuchBackup => (),
+    }
+    ApiError::Status(e.status())
+}
+
+/// Hands the queued posts of the players' webhooks over to the gateway delivering them.
+#[post("/webhooks/drain")]
+pub fn drain_webhooks(_admin: Admin) -> Json<Vec<Delivery>> {
+    Json(webhook::drain())
+}
+
+/// Hands the queued notifications over to the gateway delivering them.
+#[post("/outbox/drain")]
+pub fn drain_outbox(_admin: Admin, outbox: State<Outbox>) -> Json<Vec<Notification>> {
+    Json(outbox.drain())
+}
+
+/// The anonymized analytics aggregates.
+#[cfg(feature = "templates")]
+#[get("/analytics")]
+pub fn analytics(_admin: Admin) -> Template {
+    let hours: Vec<_> = ANALYTICS.hands_by_hour()
+        .iter()
+        .enumerate()
+        .map(|(hour, hands)| json!({
+            "hour": format!("{:02}:00", hour),
+            "rock": hands.rock,
+            "paper": hands.paper,
+            "scissors": hands.scissors,
+        }))
+        .collect();
+    let (ended_sessions, average) = ANALYTICS.session_lengths();
+    let strategies: Vec<_> = strategy_stats()
+        .into_iter()
+        .map(|stats| json!({
+            "strategy": stats.strategy.name(),
+            "rounds": stats.rounds,
+            "computer_win_rate": stats.computer_win_rate.unwrap_or(0.0),
+            "percent": stats.computer_win_rate.map(|rate| format!("{:.0}%", rate * 100.0)),
+        }))
+        .collect();
+    let experiments: Vec<_> = experiment::results()
+        .into_iter()
+        .map(|results| json!({
+            "experiment": results.experiment,
+            "variant": results.variant,
+            "players": results.players,
+            "rounds": results.rounds,
+            "win_rate": results.win_rate.unwrap_or(0.0),
+            "percent": results.win_rate.map(|rate| format!("{:.0}%", rate * 100.0)),
+        }))
+        .collect();
+
+    let context = json!({
+        "hours": hours,
+        "strategies": strategies,
+        "experiments": experiments,
+        "ended_sessions": ended_sessions,
+        "average_session": average.map(|d| format!("{}m {:02}s", d.as_secs() / 60, d.as_secs() % 60)),
+    });
+    render("admin_analytics", &context)
+}
@@ -0,0 +1,149 @@
+//! Notices of suspicious activity on an account
+//!
+//! Whenever someone gets into an account in a way its player might not have, the account gets an
+//! `Alert`: a login from a device it was never logged in from, or a recovery code redeemed. The
+//! latest `RECENT_ALERTS` are kept in the profile. Until the player has looked at them on
+//! `/profile`, the latest one is shown as a warning atop the game page, in every session of the
+//! account, so that the player hears of it wherever they play. Only a verified session sees where
+//! they came from, and looking marks them seen, or whoever logged in under the name would learn
+//! the owner's whereabouts and silence the warning.
+//!
+//! A device is told apart by a fingerprint of the IP address and the user agent it logs in from.
+//! Only the digests of the latest `MAX_DEVICES` are kept, and the first login of an account, or
+//! the first since devices were fingerprinted, is no news. Alerts only keep the network of the
+//! address, see `network`, and go after `ALERT_RETENTION` seconds.
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use rocket::request::{self, FromRequest, Request};
+use rocket::Outcome;
+use sha2::{Digest, Sha256};
+
+#[cfg(feature = "templates")]
+use flash::Notice;
+use profile;
+use util::unix_time;
+
+/// Alerts kept per account, the latest ones.
+pub const RECENT_ALERTS: usize = 20;
+/// Fingerprints kept per account, of the devices that logged in last.
+pub const MAX_DEVICES: usize = 20;
+/// Longest user agent kept in an alert; the fingerprint takes all of it.
+const MAX_USER_AGENT_LEN: usize = 200;
+/// How long alerts are kept, in seconds.
+pub const ALERT_RETENTION: u64 = 90 * 24 * 3600;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AlertKind {
+    NewDevice,
+    RecoveryCodeUsed,
+}
+
+impl AlertKind {
+    pub fn describe(&self) -> &'static str {
+        match *self {
+            AlertKind::NewDevice => "a login from a new device",
+            AlertKind::RecoveryCodeUsed => "a recovery code was used",
+        }
+    }
+
+    /// The code of the warning about it, see `flash`.
+    #[cfg(feature = "templates")]
+    fn notice_code(&self) -> &'static str {
+        match *self {
+            AlertKind::NewDevice => "alerts.new-device",
+            AlertKind::RecoveryCodeUsed => "alerts.recovery-code-used",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Alert {
+    pub kind: AlertKind,
+    /// Unix time.
+    pub at: u64,
+    /// The network of the device it came from, e.g. `203.0.113.0/24`.
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+    /// Whether the player looked at it on their profile.
+    #[serde(default)]
+    pub seen: bool,
+}
+
+/// The device a request comes from, as far as the server can tell.
+pub struct Device {
+    ip: Option<IpAddr>,
+    user_agent: Option<String>,
+}
+
+/// The network of `ip`, which is all that is kept of it: its /24 if it is an IPv4 address, its /48
+/// if it is an IPv6 one.
+fn network(ip: IpAddr) -> (IpAddr, u8) {
+    match ip {
+        IpAddr::V4(ip) => {
+            let octets = ip.octets();
+            (IpAddr::V4(Ipv4Addr::new(octets[0], octets[1], octets[2], 0)), 24)
+        }
+        IpAddr::V6(ip) => {
+            let segments = ip.segments();
+            (IpAddr::V6(Ipv6Addr::new(segments[0], segments[1], segments[2], 0, 0, 0, 0, 0)), 48)
+        }
+    }
+}
+
+impl Device {
+    fn fingerprint(&self) -> String {
+        let ip = self.ip.map(|ip| ip.to_string()).unwrap_or_default();
+        let user_agent = self.user_agent.as_ref().map_or("", String::as_str);
+        format!("{:x}", Sha256::digest(format!("{}\n{}", ip, user_agent).as_bytes()))
+    }
+
+    fn alert(&self, kind: AlertKind) -> Alert {
+        Alert {
+            kind: kind,
+            at: unix_time(),
+            ip: self.ip.map(|ip| {
+                let (network, prefix) = network(ip);
+                format!("{}/{}", network, prefix)
+            }),
+            user_agent: self.user_agent.as_ref()
+                .map(|user_agent| user_agent.chars().take(MAX_USER_AGENT_LEN).collect()),
+            seen: false,
+        }
+    }
+}
+
+impl<'a, 'r> FromRequest<'a, 'r> for Device {
+    type Error = ();
+
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<Device, ()> {
+        Outcome::Success(Device {
+            ip: request.remote().map(|addr| addr.ip()),
+            user_agent: request.headers().get_one("User-Agent").map(str::to_owned),
+        })
+    }
+}
+
+/// Records that `user_name` logged in from `device`, alerting them if it is a new one.
+pub fn logged_in(user_name: &str, device: &Device) {
+    if profile::add_device(user_name, &device.fingerprint()) {
+        profile::add_alert(user_name, device.alert(AlertKind::NewDevice));
+    }
+}
+
+/// Alerts `user_name` that one of their recovery codes was redeemed from `device`. The device is
+/// known from then on.
+#[cfg(feature = "templates")]
+pub fn recovery_code_used(user_name: &str, device: &Device) {
+    profile::add_device(user_name, &device.fingerprint());
+    profile::add_alert(user_name, device.alert(AlertKind::RecoveryCodeUsed));
+}
+
+/// The warning about the latest alert of `user_name`, unless they saw it.
+#[cfg(feature = "templates")]
+pub fn unseen(user_name: &str) -> Option<Notice> {
+    let profile = profile::get(user_name)?;
+    let alert = profile.alerts.last().filter(|alert| !alert.seen)?;
+    Some(Notice::warning(alert.kind.notice_code()))
+}
@@ -0,0 +1,108 @@
+//! Anonymized gameplay analytics
+//!
+//! Only aggregates are kept, never user names or IDs: how often each hand is played per hour of
+//! the day (UTC), how long sessions last, and how classic rounds end for each strategy of the
+//! computer, to tune those that are too strong. Players who opted out are not counted anywhere.
+
+use std::sync::Mutex;
+use std::time::Duration;
+
+use events::{Event, Subscriber};
+use game::{Hand, Scoreboard, Strategy, Verdict};
+use poison::Recover;
+use util::unix_time;
+
+lazy_static! {
+    pub static ref ANALYTICS: Analytics = Analytics::new();
+}
+
+#[derive(Clone, Copy, Default, Serialize)]
+pub struct HourlyHands {
+    pub rock: u64,
+    pub paper: u64,
+    pub scissors: u64,
+}
+
+#[derive(Clone, Default)]
+struct Aggregates {
+    hands_by_hour: [HourlyHands; 24],
+    ended_sessions: u64,
+    total_session_secs: u64,
+    /// From the human's side, indexed like `Strategy::ALL`.
+    by_strategy: [Scoreboard; 7],
+}
+
+pub struct Analytics(Mutex<Aggregates>);
+
+impl Analytics {
+    fn new() -> Analytics {
+        Analytics(Mutex::new(Aggregates::default()))
+    }
+
+    /// Counts hands played by the human.
+    pub fn record_hands(&self, hands: &[Hand]) {
+        let hour = (unix_time() / 3600 % 24) as usize;
+        let mut aggregates = self.0.lock().recover();
+        let slot = &mut aggregates.hands_by_hour[hour];
+        for hand in hands {
+            match *hand {
+                Hand::Rock => slot.rock += 1,
+                Hand::Paper => slot.paper += 1,
+                Hand::Scissors => slot.scissors += 1,
+            }
+        }
+    }
+
+    pub fn record_strategy(&self, strategy: Strategy, verdict: Verdict) {
+        self.0.lock().recover().by_strategy[strategy.index()].record(verdict);
+    }
+
+    pub fn record_session(&self, length: Duration) {
+        let mut aggregates = self.0.lock().recover();
+        aggregates.ended_sessions += 1;
+        aggregates.total_session_secs += length.as_secs();
+    }
+
+    /// Hand counts for each hour of the day, starting at midnight UTC.
+    #[cfg(feature = "templates")]
+    pub fn hands_by_hour(&self) -> [HourlyHands; 24] {
+        self.0.lock().recover().hands_by_hour
+    }
+
+    /// How classic rounds ended, from the human's side, for each strategy in `Strategy::ALL`.
+    pub fn strategy_outcomes(&self) -> [Scoreboard; 7] {
+        self.0.lock().recover().by_strategy
+    }
+
+    /// The number of ended sessions and their average length.
+    #[cfg(feature = "templates")]
+    pub fn session_lengths(&self) -> (u64, Option<Duration>) {
+        let aggregates = self.0.lock().recover();
+        let average = if aggregates.ended_sessions > 0 {
+            Some(Duration::from_secs(aggregates.total_session_secs / aggregates.ended_sessions))
+        } else {
+            None
+        };
+        (aggregates.ended_sessions, average)
+    }
+}
+
+/// Feeds `ANALYTICS` from game events.
+pub struct Recorder;
+
+impl Subscriber for Recorder {
+    fn notify(&self, event: &Event) {
+        match *event {
+            Event::RoundPlayed { ref human, verdict, analytics_opt_out: false, strategy, .. } => {
+                ANALYTICS.record_hands(human);
+                if let Some(strategy) = strategy {
+                    ANALYTICS.record_strategy(strategy, verdict);
+                }
+            }
+            Event::SessionEnded { length, analytics_opt_out: false, .. } => {
+                ANALYTICS.record_session(length);
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,201 @@
+//! Cheat detection
+//!
+//! Watches classic rounds for play no human could produce, and flags the accounts for review in
+//! `/admin/flags`; nothing is blocked automatically. Three signals, per user name:
+//!
+//! - a win rate the commitment scheme makes implausible: against hands nobody could predict, the
+//!   chance to win is a third, whatever the player does;
+//! - responses, from the computer's commitment to the human's hand, faster than a human reacts;
+//! - a honeypot: the index page hides a hint naming a hand the computer is "weak against". It is
+//!   a random decoy that only a script reading the markup would follow.
+//!
+//! Rates are flagged once they are `MAX_Z` standard deviations above chance, which honest players
+//! practically never are. Evidence is kept across sessions, so logging in again doesn't reset it.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use events::{Event, Subscriber};
+use game::{RoundKind, Verdict};
+use poison::Recover;
+use util::unix_time;
+
+const MIN_ROUNDS: u64 = 30;
+const MAX_Z: f64 = 4.0;
+const HUMAN_RESPONSE: Duration = Duration::from_millis(150);
+const MIN_FAST_PLAYS: u64 = 20;
+
+lazy_static! {
+    pub static ref ANTICHEAT: Monitor = Monitor::new();
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FlagReason {
+    WinRate,
+    ResponseTime,
+    Honeypot,
+}
+
+#[derive(Clone, Serialize)]
+pub struct Flag {
+    pub user_name: String,
+    pub reason: FlagReason,
+    /// The evidence, for the reviewer.
+    pub detail: String,
+    /// When it was raised, as a Unix timestamp.
+    pub flagged_at: u64,
+}
+
+#[derive(Default)]
+struct Record {
+    committed_at: Option<Instant>,
+    plays: u64,
+    fast_plays: u64,
+    /// Unpredictable rounds and how many of them were won.
+    rounds: u64,
+    wins: u64,
+    /// Rounds played from a page with a decoy hint and how many of them followed it.
+    decoys: u64,
+    baited: u64,
+}
+
+/// How many standard deviations `hits` out of `trials` lie above the chance of `1 / 3`.
+fn z_score(hits: u64, trials: u64) -> f64 {
+    let n = trials as f64;
+    (hits as f64 - n / 3.0) / (n * 2.0 / 9.0).sqrt()
+}
+
+#[derive(Default)]
+struct State {
+    records: HashMap<String, Record>,
+    flags: Vec<Flag>,
+}
+
+impl State {
+    fn flag(&mut self, user_name: &str, reason: FlagReason, detail: String) {
+        if self.flags.iter().any(|flag| flag.user_name == user_name && flag.reason == reason) {
+            return;
+        }
+        self.flags.push(Flag {
+            user_name: user_name.to_owned(),
+            reason: reason,
+            detail: detail,
+            flagged_at: unix_time(),
+        });
+    }
+}
+
+pub struct Monitor(Mutex<State>);
+
+impl Monitor {
+    fn new() -> Monitor {
+        Monitor(Mutex::new(State::default()))
+    }
+
+    fn committed(&self, user_name: &str) {
+        let mut state = self.0.lock().recover();
+        state.records.entry(user_name.to_owned()).or_insert_with(Record::default).committed_at =
+            Some(Instant::now());
+    }
+
+    /// Judges a played classic round. `baited` is whether it followed the decoy hint, if there
+    /// was one.
+    fn played(&self, user_name: &str, verdict: Verdict, unpredictable: bool, baited: Option<bool>) {
+        let mut state = self.0.lock().recover();
+        let mut flags = Vec::new();
+        {
+            let record = state.records.entry(user_name.to_owned()).or_insert_with(Record::default);
+            if let Some(committed_at) = record.committed_at.take() {
+                record.plays += 1;
+                if committed_at.elapsed() < HUMAN_RESPONSE {
+                    record.fast_plays += 1;
+                }
+                if record.fast_plays >= MIN_FAST_PLAYS && record.fast_plays * 2 > record.plays {
+                    flags.push((FlagReason::ResponseTime,
+                                format!("{} of {} plays within {} ms of the commitment",
+                                        record.fast_plays, record.plays,
+                                        HUMAN_RESPONSE.subsec_millis())));
+                }
+            }
+            if unpredictable {
+                record.rounds += 1;
+                if verdict == Verdict::Win {
+                    record.wins += 1;
+                }
+                if record.rounds >= MIN_ROUNDS && z_score(record.wins, record.rounds) > MAX_Z {
+                    flags.push((FlagReason::WinRate,
+                                format!("won {} of {} unpredictable rounds", record.wins,
+                                        record.rounds)));
+                }
+            }
+            if let Some(baited) = baited {
+                record.decoys += 1;
+                if baited {
+                    record.baited += 1;
+                }
+                if record.decoys >= MIN_ROUNDS && z_score(record.baited, record.decoys) > MAX_Z {
+                    flags.push((FlagReason::Honeypot,
+                                format!("followed {} of {} hidden decoy hints", record.baited,
+                                        record.decoys)));
+                }
+            }
+        }
+        for (reason, detail) in flags {
+            state.flag(user_name, reason, detail);
+        }
+    }
+
+    /// Takes back a loss voided by an arcade redo.
+    fn voided(&self, user_name: &str) {
+        let mut state = self.0.lock().recover();
+        if let Some(record) = state.records.get_mut(user_name) {
+            record.rounds = record.rounds.saturating_sub(1);
+        }
+    }
+
+    /// Raised flags, oldest first.
+    pub fn flags(&self) -> Vec<Flag> {
+        self.0.lock().recover().flags.clone()
+    }
+
+    /// Clears the flags of `user_name` after review, and the evidence behind them. Returns whether
+    /// there were any.
+    pub fn dismiss(&self, user_name: &str) -> bool {
+        let mut state = self.0.lock().recover();
+        let count = state.flags.len();
+        state.flags.retain(|flag| flag.user_name != user_name);
+        state.records.remove(user_name);
+        state.flags.len() < count
+    }
+}
+
+/// Feeds `ANTICHEAT` from game events.
+pub struct Watcher;
+
+impl Subscriber for Watcher {
+    fn notify(&self, event: &Event) {
+        match *event {
+            Event::RoundCommitted { ref user_name, .. } => ANTICHEAT.committed(user_name),
+            Event::RoundPlayed {
+                ref user_name,
+                kind: RoundKind::Classic,
+                ref human,
+                verdict,
+                unpredictable,
+                decoy,
+                ranked: true,
+                ..
+            } => {
+                let baited = decoy.map(|decoy| human.first() == Some(&decoy));
+                ANTICHEAT.played(user_name, verdict, unpredictable, baited);
+            }
+            Event::RoundVoided { ref user_name } => ANTICHEAT.voided(user_name),
+            Event::AccountDeleted { ref user_name } => {
+                ANTICHEAT.dismiss(user_name);
+            }
+            _ => {}
+        }
+    }
+}
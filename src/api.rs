@@ -0,0 +1,1919 @@
+//! JSON API under `/api/v1`
+
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+use rocket::http::{ContentType, Cookies, Status};
+use rocket::request::{self, FromRequest, Request, State};
+use rocket::response::content::{Content, Html};
+use rocket::response::{self, status, Responder, Response};
+use rocket::{Catcher, Outcome};
+#[cfg(feature = "templates")]
+use rocket_contrib::Template;
+use rocket_contrib::{Json, Value};
+use sha2::{Digest, Sha256};
+
+use commitment::RevealPadding;
+use conditional::{Conditional, Preconditions};
+use entropy::Entropy;
+use events;
+use game::{Hand, RoundKind, Scoreboard, Verdict};
+use history::{self, Export, RoundRecord};
+use ids::{SessionToken, UserId};
+use params::{HandParam, HistoryQuery, QueryError};
+use poison::Recover;
+use pow;
+use profile::{self, UserProfile};
+use quota::{self, Quotas};
+use relay::{self, LegRound, RelayError, TeamView};
+#[cfg(feature = "templates")]
+use render::render;
+use simulation::{self, Computer, Policy, Simulation};
+use snapshot;
+use strategy::{Adaptive, ModelSnapshot, Opponent, Rotation};
+use tournament::{self, BotError, BotStatus, Picks};
+use util::bytes_to_hex;
+use {house_scripts, Session, User, REPEAT_WINDOW, SESSIONS};
+
+pub const API_KEY_HEADER: &str = "X-Api-Key";
+/// Rounds covered by `GET /stats/sparkline`.
+const SPARKLINE_LEN: usize = 50;
+
+lazy_static! {
+    // API key -> User ID
+    pub static ref API_KEYS: RwLock<HashMap<SessionToken, UserId>> = RwLock::new(HashMap::new());
+}
+
+/// Returns the user owning the API key `key`, as long as their session is alive.
+pub(crate) fn key_user(key: &SessionToken) -> Option<User> {
+    let user_id = API_KEYS.read().recover().get(key)?.clone();
+    let sessions = SESSIONS.read(&user_id);
+    let session = sessions.get(&user_id)?;
+    session.touch();
+    Some(User {
+        id: user_id,
+        name: session.user_name.clone(),
+        verified: session.verified,
+    })
+}
+
+/// Like `User`, but also accepts an `X-Api-Key` header, and fails with `401 Unauthorized` instead
+/// of forwarding, with `403 Forbidden` if banned, or with `429 Too Many Requests` once the daily
+/// quota is used up.
+pub struct ApiUser(User);
+
+impl<'a, 'r> FromRequest<'a, 'r> for ApiUser {
+    type Error = ();
+
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<ApiUser, ()> {
+        let user = match request.headers().get_one(API_KEY_HEADER) {
+            Some(key) => key_user(&SessionToken::from_string(key.to_owned())),
+            None => User::from_request(request).succeeded(),
+        };
+        let user = match user {
+            Some(user) => user,
+            None if ::active_ban(request, None).is_some() => {
+                return Outcome::Failure((Status::Forbidden, ()));
+            }
+            None => return Outcome::Failure((Status::Unauthorized, ())),
+        };
+        if ::active_ban(request, Some(&user.name)).is_some() {
+            return Outcome::Failure((Status::Forbidden, ()));
+        }
+
+        let quotas = request.guard::<State<Quotas>>()?;
+        if let Some(consumer) = quota::consumer(request) {
+            if quotas.exceeded(&consumer.id) {
+                return Outcome::Failure((Status::TooManyRequests, ()));
+            }
+        }
+        Outcome::Success(ApiUser(user))
+    }
+}
+
+/// Why an API request failed. It answers with its status and a JSON body
+/// `{"code": ..., "message": ..., "details": ...}`: clients branch on the `code`, which stays the
+/// same across releases, whereas the `message` is meant for people and may change. `details`,
+/// when not `null`, says more, e.g. which field of a query is invalid.
+#[derive(Debug)]
+pub enum ApiError {
+    /// The session behind the cookies or the API key has ended.
+    NoSession,
+    /// No round is pending, or not the one played against.
+    NoPendingRound,
+    /// The nonce does not solve the proof of work of the pending round.
+    ProofOfWorkFailed,
+    /// The route only serves sessions in practice mode.
+    PracticeOnly,
+    NoProfile,
+    NoSuchReceipt,
+    /// No house script has the name picked.
+    NoSuchScript,
+    InvalidQuery(QueryError),
+    /// The named parameter of a simulation is unknown.
+    InvalidSimulation(&'static str),
+    Bot(BotError),
+    Relay(RelayError),
+    /// A failure no route chose, e.g. of a request guard or of a body that does not parse, as
+    /// caught by `catchers`.
+    Status(Status),
+}
+
+impl ApiError {
+    pub fn status(&self) -> Status {
+        match *self {
+            ApiError::NoSession => Status::Unauthorized,
+            ApiError::NoPendingRound => Status::Conflict,
+            ApiError::ProofOfWorkFailed | ApiError::PracticeOnly => Status::Forbidden,
+            ApiError::NoProfile | ApiError::NoSuchReceipt | ApiError::NoSuchScript => {
+                Status::NotFound
+            }
+            ApiError::InvalidQuery(_) | ApiError::InvalidSimulation(_) => Status::BadRequest,
+            ApiError::Bot(ref e) => e.status(),
+            ApiError::Relay(ref e) => e.status(),
+            ApiError::Status(status) => status,
+        }
+    }
+
+    /// The stable code of the error, e.g. `bot.name-taken`.
+    pub fn code(&self) -> &'static str {
+        match *self {
+            ApiError::NoSession => "no-session",
+            ApiError::NoPendingRound => "no-pending-round",
+            ApiError::ProofOfWorkFailed => "proof-of-work-failed",
+            ApiError::PracticeOnly => "practice-only",
+            ApiError::NoProfile => "no-profile",
+            ApiError::NoSuchReceipt => "no-such-receipt",
+            ApiError::NoSuchScript => "no-such-script",
+            ApiError::InvalidQuery(_) => "invalid-query",
+            ApiError::InvalidSimulation(_) => "invalid-simulation",
+            ApiError::Bot(ref e) => match *e {
+                BotError::InvalidName => "bot.invalid-name",
+                BotError::NameTaken => "bot.name-taken",
+                BotError::NoSuchBot => "bot.no-such-bot",
+                BotError::TooFast => "bot.too-fast",
+                BotError::NotPlaying => "bot.not-playing",
+                BotError::NoSuchOpponent => "bot.no-such-opponent",
+            },
+            ApiError::Relay(ref e) => match *e {
+                RelayError::InvalidName => "relay.invalid-name",
+                RelayError::NameTaken => "relay.name-taken",
+                RelayError::AlreadyInTeam => "relay.already-in-team",
+                RelayError::NoSuchTeam => "relay.no-such-team",
+                RelayError::TeamFull => "relay.team-full",
+                RelayError::NotInTeam => "relay.not-in-team",
+                RelayError::NotCaptain => "relay.not-captain",
+                RelayError::NoSuchMember => "relay.no-such-member",
+                RelayError::RosterLocked => "relay.roster-locked",
+                RelayError::NotPlaying => "relay.not-playing",
+                RelayError::NotYourLeg => "relay.not-your-leg",
+                RelayError::Unverified => "relay.unverified",
+            },
+            ApiError::Status(status) => match status.code {
+                400 => "bad-request",
+                403 => "forbidden",
+                404 => "not-found",
+                409 => "conflict",
+                422 => "invalid-body",
+                429 => "quota-exceeded",
+                500 => "internal-error",
+                _ => "error",
+            },
+        }
+    }
+
+    fn message(&self) -> String {
+        let message = match *self {
+            ApiError::NoSession => "The session has ended; log in again.",
+            ApiError::NoPendingRound => "No round is pending, or not that one.",
+            ApiError::ProofOfWorkFailed => "The nonce does not solve the round's proof of work.",
+            ApiError::PracticeOnly => "Only available in practice mode.",
+            ApiError::NoProfile => "The player has no profile yet.",
+            ApiError::NoSuchReceipt => "No round of the session has that receipt.",
+            ApiError::NoSuchScript => "No house script has that name.",
+            ApiError::InvalidQuery(ref e) => return format!("Invalid query: {}.", e),
+            ApiError::InvalidSimulation(parameter) => {
+                return format!("Unknown simulation `{}`.", parameter);
+            }
+            ApiError::Bot(ref e) => match *e {
+                BotError::InvalidName => "Bot names are lowercase letters, digits and dashes.",
+                BotError::NameTaken => "That bot name is taken.",
+                BotError::NoSuchBot => "You have no bot of that name.",
+                BotError::TooFast => "The bot plays faster than it may.",
+                BotError::NotPlaying => "The bot is not in the running tournament.",
+                BotError::NoSuchOpponent => "That is not an opponent of the bot.",
+            },
+            ApiError::Relay(ref e) => match *e {
+                RelayError::InvalidName => "Team names are lowercase letters, digits and dashes.",
+                RelayError::NameTaken => "That team name is taken.",
+                RelayError::AlreadyInTeam => "You are in a team already.",
+                RelayError::NoSuchTeam => "No team has that invite code.",
+                RelayError::TeamFull => "The team is full.",
+                RelayError::NotInTeam => "You are not in a team.",
+                RelayError::NotCaptain => "Only the captain may do that.",
+                RelayError::NoSuchMember => "No member of the team has that name.",
+                RelayError::RosterLocked => "The team plays in the running relay.",
+                RelayError::NotPlaying => "Your team has no relay going on.",
+                RelayError::NotYourLeg => "Another member runs the current leg.",
+                RelayError::Unverified => {
+                    "Log in with a recovery code or a passkey to play in relays."
+                }
+            },
+            ApiError::Status(status) => status.reason,
+        };
+        message.to_owned()
+    }
+
+    fn details(&self) -> Value {
+        match *self {
+            ApiError::InvalidQuery(QueryError::Unexpected(ref field)) => json!({ "field": field }),
+            ApiError::InvalidQuery(QueryError::Invalid(ref field, ref value)) => {
+                json!({ "field": field, "value": value })
+            }
+            ApiError::InvalidSimulation(parameter) => json!({ "field": parameter }),
+            _ => Value::Null,
+        }
+    }
+}
+
+impl<'r> Responder<'r> for ApiError {
+    fn respond_to(self, request: &Request) -> response::Result<'r> {
+        let body = json!({
+            "code": self.code(),
+            "message": self.message(),
+            "details": self.details(),
+        });
+        Response::build_from(Json(body).respond_to(request)?)
+            .status(self.status())
+            .ok()
+    }
+}
+
+/// Whether `request` is one for the API or the admin routes, whose failures are `ApiError`s.
+pub fn is_api_request(request: &Request) -> bool {
+    let path = request.uri().path();
+    path.starts_with("/api/") || path.starts_with("/admin/")
+}
+
+/// What a catcher answers: an `ApiError` for the API, a bare page like Rocket's own for the rest.
+type Caught = Result<status::Custom<Html<String>>, ApiError>;
+
+fn caught(request: &Request, status: Status) -> Caught {
+    if is_api_request(request) {
+        // `ApiUser` fails with `401 Unauthorized` when there is no session to act for.
+        if status == Status::Unauthorized {
+            return Err(ApiError::NoSession);
+        }
+        return Err(ApiError::Status(status));
+    }
+    let title = format!("{} {}", status.code, status.reason);
+    let page = format!("<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>{0}</title>\
+                        </head>\n<body><h1>{0}</h1></body>\n</html>\n", title);
+    Ok(status::Custom(status, Html(page)))
+}
+
+/// Answers failures no route chose, e.g. of `ApiUser` or of a JSON body that does not parse, with
+/// an `ApiError` on API paths. `404 Not Found` is left to the pages, which hand API paths back to
+/// `not_found`.
+#[catch(400)]
+fn bad_request(request: &Request) -> Caught {
+    caught(request, Status::BadRequest)
+}
+
+#[catch(401)]
+fn unauthorized(request: &Request) -> Caught {
+    caught(request, Status::Unauthorized)
+}
+
+#[catch(403)]
+fn forbidden(request: &Request) -> Caught {
+    caught(request, Status::Forbidden)
+}
+
+#[catch(422)]
+fn unprocessable_entity(request: &Request) -> Caught {
+    caught(request, Status::UnprocessableEntity)
+}
+
+#[catch(429)]
+fn too_many_requests(request: &Request) -> Caught {
+    caught(request, Status::TooManyRequests)
+}
+
+#[cfg(feature = "templates")]
+pub fn not_found() -> ApiError {
+    ApiError::Status(Status::NotFound)
+}
+
+/// Without the pages, nothing else answers unknown paths.
+#[cfg(not(feature = "templates"))]
+#[catch(404)]
+fn unknown_path(request: &Request) -> Caught {
+    caught(request, Status::NotFound)
+}
+
+pub fn catchers() -> Vec<Catcher> {
+    let mut catchers =
+        catchers![bad_request, unauthorized, forbidden, unprocessable_entity, too_many_requests];
+    #[cfg(not(feature = "templates"))]
+    catchers.extend(catchers![unknown_path]);
+    catchers
+}
+
+#[derive(Serialize)]
+pub struct ApiKey {
+    key: SessionToken,
+}
+
+#[derive(Serialize)]
+pub struct Commitment {
+    digest: String,
+    /// The zero bits the play against it must prove, see `pow`; left out if none.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pow_difficulty: Option<u32>,
+}
+
+impl Commitment {
+    fn new(digest: String) -> Commitment {
+        let difficulty = pow::difficulty();
+        Commitment {
+            digest: digest,
+            pow_difficulty: if difficulty > 0 { Some(difficulty) } else { None },
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct Play {
+    hand: HandParam,
+    /// The commitment being played against. Makes retries safe: replaying the round just resolved
+    /// within `REPEAT_WINDOW` returns its recorded result instead of playing the next round, and
+    /// is refused later. An earlier round still pending, e.g. committed to for another client of
+    /// the session, is played then.
+    #[serde(default)]
+    digest: Option<String>,
+    /// The proof of work against the round's digest, if the server asks for one.
+    #[serde(default)]
+    nonce: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct RoundResult {
+    human: Hand,
+    computer: Hand,
+    verdict: Verdict,
+    random: String,
+    digest: String,
+    next_digest: String,
+    receipt: String,
+    /// Whether the round was a tie that goes to a sudden throw, committed to as `next_digest`.
+    sudden_throw: bool,
+}
+
+impl RoundResult {
+    fn from_record(record: &RoundRecord, next_digest: String) -> Option<RoundResult> {
+        if record.kind != RoundKind::Classic {
+            return None;
+        }
+        Some(RoundResult {
+            human: record.human.parse().ok()?,
+            computer: record.computer.parse().ok()?,
+            verdict: record.verdict,
+            random: record.random.clone(),
+            digest: record.digest.clone(),
+            next_digest: next_digest,
+            receipt: record.link.clone(),
+            sudden_throw: false,
+        })
+    }
+}
+
+#[derive(Deserialize)]
+pub struct OpponentChoice {
+    opponent: Opponent,
+    /// Weights to rotate among the opponents with, from the pending round on.
+    #[serde(default)]
+    rotation: Option<Rotation>,
+    /// A house script to play instead, by name, see `opponent_scripts`.
+    #[serde(default)]
+    script: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct Stats {
+    #[serde(flatten)]
+    classic: Scoreboard,
+    chaos: Scoreboard,
+    chain_head: String,
+    trainer: Trainer,
+    opponent: Opponent,
+    /// The weights the computer rotates among the opponents with, if it does.
+    rotation: Option<Rotation>,
+    /// The house script played instead, if any.
+    script: Option<String>,
+    /// Classic rounds against each opponent, seeded matches aside.
+    opponents: Vec<OpponentScore>,
+    /// The opponent with the most wins over the player.
+    toughest_opponent: Option<Opponent>,
+    /// Penalty points charged by the house rules the player turned on.
+    penalties: usize,
+}
+
+#[derive(Serialize)]
+pub struct OpponentScore {
+    opponent: Opponent,
+    #[serde(flatten)]
+    score: Scoreboard,
+}
+
+#[derive(Serialize)]
+pub struct Trainer {
+    rounds: u32,
+    regret: u32,
+    average_regret: f64,
+    /// The computer's mixed strategy, indexed like `Hand::CHOICES`.
+    computer_strategy: [f64; 3],
+}
+
+// ===== Routers =====
+
+/// Issues an API key for the session, to be sent as `X-Api-Key`. Keys die with the session.
+#[post("/keys")]
+pub fn create_key(user: ApiUser, entropy: State<Entropy>) -> Json<ApiKey> {
+    let key = SessionToken::generate(&entropy);
+    API_KEYS.write().recover().insert(key.clone(), user.0.id);
+    Json(ApiKey { key: key })
+}
+
+/// Commits the computer to a new round.
+#[get("/round")]
+pub fn round(user: ApiUser, mut cookies: Cookies) -> Result<Json<Commitment>, ApiError> {
+    let _events = events::batch().defer();
+    let mut sessions = SESSIONS.write(&user.0.id);
+    let session = sessions.get_mut(&user.0.id).ok_or(ApiError::NoSession)?;
+    let digest = session.start_round().digest.clone();
+    snapshot::save(&mut cookies, session);
+    Ok(Json(Commitment::new(digest)))
+}
+
+/// Plays the human's hand against the pending round.
+#[post("/play", format = "application/json", data = "<play>")]
+pub fn play(user: ApiUser, play: Json<Play>, mut cookies: Cookies)
+    -> Result<Json<RoundResult>, ApiError>
+{
+    let _padding = RevealPadding::start();
+    let _events = events::batch().defer();
+    let mut sessions = SESSIONS.write(&user.0.id);
+    let session = sessions.get_mut(&user.0.id).ok_or(ApiError::NoSession)?;
+
+    // Against the round asked for, before it is resumed, so that a refused play leaves the pending
+    // round as it was.
+    let difficulty = pow::difficulty();
+    if difficulty > 0 {
+        let solved = {
+            let challenge = match play.digest {
+                Some(ref digest) => digest,
+                None => &session.last_round.as_ref().ok_or(ApiError::NoPendingRound)?.digest,
+            };
+            let nonce = play.nonce.as_ref().map_or("", String::as_str);
+            pow::solves(challenge, nonce, difficulty)
+        };
+        if !solved {
+            return Err(ApiError::ProofOfWorkFailed);
+        }
+    }
+
+    if let Some(ref digest) = play.digest {
+        if !session.resume_round(digest) {
+            let next_digest = session.last_round.as_ref()
+                .map(|round| round.digest.clone())
+                .unwrap_or_default();
+            return session.repeated_play(digest, play.hand.0, REPEAT_WINDOW)
+                .and_then(|record| RoundResult::from_record(&record, next_digest))
+                .map(Json)
+                .ok_or(ApiError::NoPendingRound);
+        }
+    }
+
+    session.play(play.hand.0).ok_or(ApiError::NoPendingRound)?;
+    snapshot::save(&mut cookies, session);
+    let next_digest = session.last_round.as_ref().unwrap().digest.clone();
+    let record = session.history.last().unwrap();
+    let mut result = RoundResult::from_record(&record, next_digest).unwrap();
+    result.sudden_throw = session.in_sudden_throw;
+    Ok(Json(result))
+}
+
+/// Confirms that the round with this receipt is part of the session's history.
+#[get("/receipts/<receipt>")]
+pub fn receipt(user: ApiUser, receipt: String) -> Result<Json<RoundRecord>, ApiError> {
+    let sessions = SESSIONS.read(&user.0.id);
+    let session = sessions.get(&user.0.id).ok_or(ApiError::NoSession)?;
+    session.history.by_link(&receipt).map(Json).ok_or(ApiError::NoSuchReceipt)
+}
+
+/// The round with this receipt as a proof in canonical JSON, see `proof`.
+#[get("/receipts/<receipt>/proof")]
+pub fn receipt_proof(user: ApiUser, receipt: String) -> Result<Content<String>, ApiError> {
+    let sessions = SESSIONS.read(&user.0.id);
+    let session = sessions.get(&user.0.id).ok_or(ApiError::NoSession)?;
+    session.history.proof(&receipt)
+        .map(|proof| Content(ContentType::JSON, proof.to_canonical()))
+        .ok_or(ApiError::NoSuchReceipt)
+}
+
+/// The version of the stats: the history's, along with what changes between rounds, e.g. the
+/// opponent.
+fn stats_version(session: &Session) -> String {
+    let between_rounds = format!("{:?} {:?} {:?} {:?} {:?} {:?}", session.score,
+                                 session.chaos_score, session.opponent, session.rotation,
+                                 session.script, session.round_opponent);
+    let digest = Sha256::digest(between_rounds.as_bytes());
+    format!("{}.{}", session.history.version(), bytes_to_hex(&digest[..8]))
+}
+
+#[get("/stats")]
+pub fn stats(user: ApiUser, preconditions: Preconditions)
+    -> Result<Conditional<Json<Stats>>, ApiError>
+{
+    let sessions = SESSIONS.read(&user.0.id);
+    let session = sessions.get(&user.0.id).ok_or(ApiError::NoSession)?;
+    let version = stats_version(session);
+    let last_modified = session.history.last_played_at();
+    Ok(Conditional::new(&preconditions, &version, last_modified, || Json(Stats {
+        classic: session.score,
+        chaos: session.chaos_score,
+        chain_head: session.history.head().to_owned(),
+        trainer: Trainer {
+            rounds: session.regret.rounds,
+            regret: session.regret.total,
+            average_regret: session.regret.average(),
+            computer_strategy: session.computer_strategy(),
+        },
+        opponent: session.opponent,
+        rotation: session.rotation,
+        script: session.script.clone(),
+        opponents: Opponent::ALL.iter()
+            .map(|&opponent| OpponentScore {
+                opponent: opponent,
+                score: session.by_opponent[opponent.index()],
+            })
+            .collect(),
+        toughest_opponent: session.toughest_opponent().map(|(opponent, _)| opponent),
+        penalties: session.house_rules.penalties,
+    })))
+}
+
+/// Outcomes of the last classic rounds, oldest first, for drawing a sparkline.
+#[get("/stats/sparkline")]
+pub fn sparkline(user: ApiUser, preconditions: Preconditions)
+    -> Result<Conditional<Json<Vec<i32>>>, ApiError>
+{
+    let sessions = SESSIONS.read(&user.0.id);
+    let session = sessions.get(&user.0.id).ok_or(ApiError::NoSession)?;
+    let history = &session.history;
+    Ok(Conditional::new(&preconditions, &history.version(), history.last_played_at(), || {
+        Json(history.outcomes(RoundKind::Classic, SPARKLINE_LEN))
+    }))
+}
+
+/// Switches the computer to another strategy family and commits it to a new round, unless a
+/// seeded match is on: its hands keep coming from the seed, and the pending round stays.
+#[post("/opponent", format = "application/json", data = "<choice>")]
+pub fn opponent(user: ApiUser, choice: Json<OpponentChoice>, mut cookies: Cookies)
+    -> Result<Json<Commitment>, ApiError>
+{
+    if let Some(ref script) = choice.script {
+        if !house_scripts().contains(script) {
+            return Err(ApiError::NoSuchScript);
+        }
+    }
+    let _events = events::batch().defer();
+    let mut sessions = SESSIONS.write(&user.0.id);
+    let session = sessions.get_mut(&user.0.id).ok_or(ApiError::NoSession)?;
+    session.opponent = choice.opponent;
+    session.script = choice.script.clone();
+    session.rotation = choice.rotation
+        .and_then(|weights| Rotation::new(weights.random, weights.mirror, weights.counter_mirror));
+    if session.seeded_match.is_none() {
+        session.start_round();
+    }
+    snapshot::save(&mut cookies, session);
+    let digest = session.last_round.as_ref().map(|round| round.digest.clone());
+    Ok(Json(Commitment::new(digest.unwrap_or_default())))
+}
+
+/// The house scripts `opponent` takes as `script`, by name.
+#[get("/opponent/scripts")]
+pub fn opponent_scripts(_user: ApiUser) -> Json<Vec<String>> {
+    Json(house_scripts())
+}
+
+/// Every revealed round of the session, with its chain link. Check it with `verify-export`.
+#[get("/export")]
+pub fn export(user: ApiUser) -> Result<Json<Export>, ApiError> {
+    let sessions = SESSIONS.read(&user.0.id);
+    let session = sessions.get(&user.0.id).ok_or(ApiError::NoSession)?;
+    Ok(Json(Export {
+        user_name: session.user_name.clone(),
+        chain_head: session.history.head().to_owned(),
+        first_round: session.history.first(),
+        prev_link: session.history.prev_link().to_owned(),
+        rounds: session.history.records(),
+    }))
+}
+
+#[derive(Serialize)]
+pub struct HistoryPage {
+    /// Latest first.
+    rounds: Vec<HistoryEntry>,
+    /// Whether the next page holds older matches.
+    more: bool,
+    /// The next page, by cursor, if there is one.
+    next: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct HistoryEntry {
+    /// The round's index in the history, from 0 for the first; what `after` takes.
+    id: usize,
+    #[serde(flatten)]
+    record: RoundRecord,
+}
+
+/// The session's latest revealed rounds.
+#[get("/history")]
+pub fn history(user: ApiUser, preconditions: Preconditions)
+    -> Result<Conditional<Json<HistoryPage>>, ApiError>
+{
+    search_history(&user, &preconditions, HistoryQuery::default())
+}
+
+/// A page of the session's revealed rounds matching the query, see `HistoryQuery`. Pages by
+/// `after` stay put while rounds are played, unlike pages by `page`.
+#[get("/history?<query>")]
+pub fn history_search(user: ApiUser, preconditions: Preconditions,
+                      query: Result<HistoryQuery, QueryError>)
+    -> Result<Conditional<Json<HistoryPage>>, ApiError>
+{
+    search_history(&user, &preconditions, query.map_err(ApiError::InvalidQuery)?)
+}
+
+fn search_history(user: &ApiUser, preconditions: &Preconditions, query: HistoryQuery)
+    -> Result<Conditional<Json<HistoryPage>>, ApiError>
+{
+    let sessions = SESSIONS.read(&user.0.id);
+    let session = sessions.get(&user.0.id).ok_or(ApiError::NoSession)?;
+    let version = session.history.version();
+    Ok(Conditional::new(preconditions, &version, session.history.last_played_at(), || {
+        let history = &session.history;
+        let limit = query.limit.unwrap_or(history::PAGE_LEN).max(1).min(history::PAGE_LEN);
+        let skip = query.page.saturating_mul(limit);
+        let before = query.after.unwrap_or_else(|| history.len());
+        let mut rounds: Vec<_> = history.search_before(&query.filter, before, skip, limit + 1)
+            .into_iter()
+            .map(|(id, record)| HistoryEntry { id: id, record: record })
+            .collect();
+        let more = rounds.len() > limit;
+        rounds.truncate(limit);
+        let next = match rounds.last() {
+            Some(last) if more => {
+                let next = HistoryQuery {
+                    after: Some(last.id),
+                    limit: Some(limit),
+                    filter: query.filter,
+                    ..HistoryQuery::default()
+                };
+                Some(format!("/api/v1/history?{}", next.to_query()))
+            }
+            _ => None,
+        };
+        Json(HistoryPage { rounds: rounds, more: more, next: next })
+    }))
+}
+
+/// The player model the computer keeps of this session. Only available in practice mode.
+#[get("/model")]
+pub fn model(user: ApiUser) -> Result<Json<ModelSnapshot>, ApiError> {
+    let sessions = SESSIONS.read(&user.0.id);
+    let session = sessions.get(&user.0.id).ok_or(ApiError::NoSession)?;
+    if !session.practice {
+        return Err(ApiError::PracticeOnly);
+    }
+    let mut snapshot = session.model.snapshot();
+    snapshot.adaptive = session.adaptive.as_ref().map(Adaptive::snapshot);
+    Ok(Json(snapshot))
+}
+
+/// The player's lifetime stats, kept across sessions, as `profile_view` shows them.
+#[get("/profile")]
+pub fn profile(user: ApiUser) -> Result<Json<Value>, ApiError> {
+    let profile = profile::get(&user.0.name).ok_or(ApiError::NoProfile)?;
+    Ok(Json(profile_view(&profile, user.0.verified)))
+}
+
+/// Fields of a profile only its owner may see: where the account was logged in from, the devices
+/// and passkeys it logs in with, see `alerts` and `passkey`.
+const OWNER_FIELDS: &[&str] = &["alerts", "devices", "passkeys"];
+
+/// `profile` as the owner sees it if `verified`, see `Session::verified`; otherwise without
+/// `OWNER_FIELDS` or the webhook secret, since whoever logged in under the name, or holds a key
+/// of such a session, is not known to be the owner.
+fn profile_view(profile: &UserProfile, verified: bool) -> Value {
+    let mut view = ::serde_json::to_value(profile).expect("Unable to serialize a profile");
+    if !verified {
+        let fields = view.as_object_mut().unwrap();
+        for field in OWNER_FIELDS {
+            fields.remove(*field);
+        }
+        if let Some(webhook) = fields.get_mut("webhook").and_then(Value::as_object_mut) {
+            webhook.remove("secret");
+        }
+    }
+    view
+}
+
+/// When the player plays: rounds by day of the week, Monday first, and hour of the day, at the
+/// player's UTC offset.
+#[get("/profile/activity")]
+pub fn activity(user: ApiUser) -> Result<Json<[[u32; 24]; 7]>, ApiError> {
+    let profile = profile::get(&user.0.name).ok_or(ApiError::NoProfile)?;
+    Ok(Json(profile.activity))
+}
+
+#[derive(FromForm)]
+pub struct SimulationQuery {
+    strategy: Option<String>,
+    policy: Option<String>,
+    rounds: Option<usize>,
+    /// The weights of the `custom` policy.
+    rock: Option<u32>,
+    paper: Option<u32>,
+    scissors: Option<u32>,
+}
+
+/// Plays the computer's `strategy` (`random` by default) against the player `policy` (`uniform`
+/// by default), see `simulation`.
+#[get("/simulate?<query>")]
+pub fn simulate(_user: ApiUser, query: SimulationQuery) -> Result<Json<Simulation>, ApiError> {
+    let computer = Computer::from_name(query.strategy.as_ref().map_or("random", |s| s.as_str()))
+        .ok_or(ApiError::InvalidSimulation("strategy"))?;
+    let weights = [query.rock.unwrap_or(0), query.paper.unwrap_or(0), query.scissors.unwrap_or(0)];
+    let policy = Policy::from_name(query.policy.as_ref().map_or("uniform", |s| s.as_str()), weights)
+        .ok_or(ApiError::InvalidSimulation("policy"))?;
+    let rounds = query.rounds.unwrap_or(simulation::DEFAULT_ROUNDS);
+    Ok(Json(simulation::run(computer, policy, rounds)))
+}
+
+#[derive(Deserialize)]
+pub struct BotRegistration {
+    name: String,
+}
+
+/// Enters a bot into the next tournaments, see `tournament`.
+#[post("/bots", format = "application/json", data = "<bot>")]
+pub fn register_bot(user: ApiUser, bot: Json<BotRegistration>)
+    -> Result<Json<BotStatus>, ApiError>
+{
+    tournament::register(&bot.name, &user.0.id, &user.0.name).map(Json).map_err(ApiError::Bot)
+}
+
+#[delete("/bots/<name>")]
+pub fn withdraw_bot(user: ApiUser, name: String) -> Result<status::NoContent, ApiError> {
+    tournament::withdraw(&name, &user.0.id).map(|()| status::NoContent).map_err(ApiError::Bot)
+}
+
+#[get("/bots/<name>/turn")]
+pub fn bot_turn(user: ApiUser, name: String) -> Result<Json<BotStatus>, ApiError> {
+    tournament::status(&name, &user.0.id).map(Json).map_err(ApiError::Bot)
+}
+
+#[post("/bots/<name>/turn", format = "application/json", data = "<picks>")]
+pub fn play_bot_turn(user: ApiUser, name: String, picks: Json<Picks>)
+    -> Result<Json<BotStatus>, ApiError>
+{
+    tournament::play(&name, &user.0.id, &picks).map(Json).map_err(ApiError::Bot)
+}
+
+#[derive(Deserialize)]
+pub struct TeamRegistration {
+    name: String,
+}
+
+#[derive(Deserialize)]
+pub struct TeamInvite {
+    code: String,
+}
+
+#[derive(Deserialize)]
+pub struct LegPlay {
+    hand: HandParam,
+}
+
+/// The name `user` plays relays under. Teams are kept by name, so a login by name only, which
+/// anyone can make, would otherwise act for whoever owns it.
+fn runner_name(user: &ApiUser) -> Result<&str, ApiError> {
+    if !user.0.verified {
+        return Err(ApiError::Relay(RelayError::Unverified));
+    }
+    Ok(&user.0.name)
+}
+
+/// Registers a relay team captained by the player, see `relay`.
+#[post("/relay/teams", format = "application/json", data = "<team>")]
+pub fn register_team(user: ApiUser, team: Json<TeamRegistration>)
+    -> Result<Json<TeamView>, ApiError>
+{
+    relay::register(&team.name, runner_name(&user)?).map(Json).map_err(ApiError::Relay)
+}
+
+#[post("/relay/teams/join", format = "application/json", data = "<invite>")]
+pub fn join_team(user: ApiUser, invite: Json<TeamInvite>) -> Result<Json<TeamView>, ApiError> {
+    relay::join(runner_name(&user)?, &invite.code).map(Json).map_err(ApiError::Relay)
+}
+
+#[post("/relay/teams/leave")]
+pub fn leave_team(user: ApiUser) -> Result<status::NoContent, ApiError> {
+    relay::leave(runner_name(&user)?).map(|()| status::NoContent).map_err(ApiError::Relay)
+}
+
+#[delete("/relay/teams/members/<member>")]
+pub fn drop_team_member(user: ApiUser, member: String) -> Result<Json<TeamView>, ApiError> {
+    relay::drop_member(runner_name(&user)?, &member).map(Json).map_err(ApiError::Relay)
+}
+
+#[get("/relay/team")]
+pub fn team(user: ApiUser) -> Result<Json<TeamView>, ApiError> {
+    relay::team(runner_name(&user)?).map(Json).map_err(ApiError::Relay)
+}
+
+#[post("/relay/play", format = "application/json", data = "<play>")]
+pub fn play_leg(user: ApiUser, play: Json<LegPlay>) -> Result<Json<LegRound>, ApiError> {
+    let runner = runner_name(&user)?;
+    relay::play(runner, play.hand.0, profile::opted_out_of_analytics(runner))
+        .map(Json)
+        .map_err(ApiError::Relay)
+}
+
+#[get("/openapi.json")]
+pub fn openapi() -> Json<Value> {
+    Json(spec())
+}
+
+#[cfg(feature = "templates")]
+#[get("/docs")]
+pub fn docs() -> Template {
+    let spec = spec();
+    let mut operations = Vec::new();
+    if let Some(paths) = spec["paths"].as_object() {
+        for (path, methods) in paths {
+            if let Some(methods) = methods.as_object() {
+                for (method, op) in methods {
+                    operations.push(json!({
+                        "method": method.to_uppercase(),
+                        "path": format!("/api/v1{}", path),
+                        "summary": op["summary"],
+                    }));
+                }
+            }
+        }
+    }
+
+    let context = json!({
+        "operations": operations,
+        "spec": ::serde_json::to_string_pretty(&spec).unwrap(),
+    });
+    render("api_docs", &context)
+}
+
+/// The OpenAPI 3.0 description of the routes above.
+///
+/// Keep this in sync by hand when a route or schema changes.
+pub fn spec() -> Value {
+    json!({
+        "openapi": "3.0.0",
+        "info": {
+            "title": "Rock-Paper-Scissors",
+            "version": "1",
+            "description": "Authentication uses the private cookies set by `POST /login`, or an \
+                            `X-Api-Key` header. Each consumer has a daily quota, reported in \
+                            `X-RateLimit-Limit`, `X-RateLimit-Remaining` and `X-RateLimit-Reset`. \
+                            Failures answer with an `Error`, whose `code` says what went wrong.",
+        },
+        "servers": [{ "url": "/api/v1" }],
+        "paths": paths(),
+        "components": { "schemas": schemas() },
+    })
+}
+
+fn paths() -> Value {
+    let mut paths = merge(vec![game_paths(), bot_paths(), relay_paths()]);
+    with_error_bodies(&mut paths);
+    paths
+}
+
+/// Gives every 4xx response of `paths` the body of an `ApiError`.
+fn with_error_bodies(paths: &mut Value) {
+    let operations = paths.as_object_mut().into_iter()
+        .flat_map(|paths| paths.values_mut())
+        .filter_map(Value::as_object_mut)
+        .flat_map(|methods| methods.values_mut());
+    for operation in operations {
+        if let Some(responses) = operation["responses"].as_object_mut() {
+            for (status, response) in responses.iter_mut() {
+                if status.starts_with('4') {
+                    response["content"] = json_content("Error");
+                }
+            }
+        }
+    }
+}
+
+fn game_paths() -> Value {
+    json!({
+        "/keys": {
+            "post": {
+                "summary": "Issues an API key for the session.",
+                "responses": {
+                    "200": { "description": "The key.", "content": json_content("ApiKey") },
+                    "401": { "description": "Not logged in." },
+                    "429": { "description": "Daily quota exceeded." },
+                },
+            },
+        },
+        "/round": {
+            "get": {
+                "summary": "Commits the computer to a new round.",
+                "responses": {
+                    "200": { "description": "The commitment.", "content": json_content("Commitment") },
+                    "401": { "description": "Not logged in." },
+                    "429": { "description": "Daily quota exceeded." },
+                },
+            },
+        },
+        "/play": {
+            "post": {
+                "summary": "Plays a hand against the pending round.",
+                "requestBody": { "required": true, "content": json_content("Play") },
+                "responses": {
+                    "200": { "description": "The revealed round.", "content": json_content("RoundResult") },
+                    "401": { "description": "Not logged in." },
+                    "429": { "description": "Daily quota exceeded." },
+                    "403": { "description": "`nonce` is missing or does not prove the work asked." },
+                    "409": {
+                        "description": "No round is pending (call `GET /round` first), or `digest` \
+                                        names neither the pending nor the last resolved round.",
+                    },
+                },
+            },
+        },
+        "/receipts/{receipt}": {
+            "get": {
+                "summary": "Confirms that a played round was recorded.",
+                "parameters": [{
+                    "name": "receipt",
+                    "in": "path",
+                    "required": true,
+                    "schema": { "type": "string" },
+                }],
+                "responses": {
+                    "200": { "description": "The recorded round.", "content": json_content("RoundRecord") },
+                    "401": { "description": "Not logged in." },
+                    "404": { "description": "No such round in the session's history." },
+                    "429": { "description": "Daily quota exceeded." },
+                },
+            },
+        },
+        "/receipts/{receipt}/proof": {
+            "get": {
+                "summary": "Returns a played round as a self-contained proof in canonical JSON.",
+                "parameters": [{
+                    "name": "receipt",
+                    "in": "path",
+                    "required": true,
+                    "schema": { "type": "string" },
+                }],
+                "responses": {
+                    "200": { "description": "The proof.", "content": json_content("RoundProof") },
+                    "401": { "description": "Not logged in." },
+                    "404": {
+                        "description": "No such round in the session's history, or one whose nonce \
+                                        is not lower hex.",
+                    },
+                    "429": { "description": "Daily quota exceeded." },
+                },
+            },
+        },
+        "/stats": {
+            "get": {
+                "summary": "Returns the session's scoreboard.",
+                "parameters": conditional_parameters(),
+                "responses": {
+                    "200": { "description": "The scoreboard.", "content": json_content("Stats") },
+                    "304": not_modified(),
+                    "401": { "description": "Not logged in." },
+                    "429": { "description": "Daily quota exceeded." },
+                },
+            },
+        },
+        "/stats/sparkline": {
+            "get": {
+                "summary": "Returns the outcomes of the last 50 classic rounds, oldest first.",
+                "parameters": conditional_parameters(),
+                "responses": {
+                    "200": {
+                        "description": "1 for a win, 0 for a tie and -1 for a loss, per round.",
+                        "content": {
+                            "application/json": {
+                                "schema": { "type": "array", "items": { "type": "integer" } },
+                            },
+                        },
+                    },
+                    "304": not_modified(),
+                    "401": { "description": "Not logged in." },
+                    "429": { "description": "Daily quota exceeded." },
+                },
+            },
+        },
+        "/opponent": {
+            "post": {
+                "summary": "Switches the computer's strategy family and commits it to a new round.",
+                "description": "During a seeded match the hands keep coming from the seed and the \
+                                pending round stays; the choice applies once the match is over. \
+                                With a `rotation`, the opponent of every round is drawn by weight \
+                                instead, starting with the new round. With a `script`, the house \
+                                script of that name plays instead of the opponent, in unranked \
+                                rounds.",
+                "requestBody": { "required": true, "content": json_content("OpponentChoice") },
+                "responses": {
+                    "200": { "description": "The pending commitment.", "content": json_content("Commitment") },
+                    "401": { "description": "Not logged in." },
+                    "404": { "description": "No house script has that name." },
+                    "429": { "description": "Daily quota exceeded." },
+                },
+            },
+        },
+        "/opponent/scripts": {
+            "get": {
+                "summary": "Lists the house scripts that can play instead of the opponent.",
+                "responses": {
+                    "200": {
+                        "description": "Their names.",
+                        "content": {
+                            "application/json": {
+                                "schema": { "type": "array", "items": { "type": "string" } },
+                            },
+                        },
+                    },
+                    "401": { "description": "Not logged in." },
+                    "429": { "description": "Daily quota exceeded." },
+                },
+            },
+        },
+        "/export": {
+            "get": {
+                "summary": "Exports every revealed round with its history chain link.",
+                "responses": {
+                    "200": { "description": "The history.", "content": json_content("Export") },
+                    "401": { "description": "Not logged in." },
+                    "429": { "description": "Daily quota exceeded." },
+                },
+            },
+        },
+        "/history": {
+            "get": {
+                "summary": "Searches the revealed rounds, latest first, a page at a time.",
+                "description": "Every parameter may be left out. Rounds of an imported history \
+                                match no `opponent`.",
+                "parameters": [
+                    history_parameter("verdict", "`win`, `tie` or `loss`."),
+                    history_parameter("hand", "A hand the player played, in any bout."),
+                    history_parameter("opponent", "The computer's strategy, e.g. `mirror`."),
+                    history_parameter("since", "The first day searched, `YYYY-MM-DD` in UTC."),
+                    history_parameter("until", "The last day searched, `YYYY-MM-DD` in UTC."),
+                    history_parameter("page", "Pages of `limit` matches to skip, 0 by default."),
+                    history_parameter("after", "Only rounds older than the one with this `id`, \
+                                                as in `next`."),
+                    history_parameter("limit", "Matches per page, 100 by default and at most."),
+                    conditional_parameters()[0],
+                    conditional_parameters()[1],
+                ],
+                "responses": {
+                    "200": { "description": "The matching rounds.", "content": json_content("HistoryPage") },
+                    "304": not_modified(),
+                    "400": { "description": "A parameter is invalid or unknown." },
+                    "401": { "description": "Not logged in." },
+                    "429": { "description": "Daily quota exceeded." },
+                },
+            },
+        },
+        "/model": {
+            "get": {
+                "summary": "Returns the computer's model of the player (practice mode only).",
+                "responses": {
+                    "200": { "description": "The model snapshot.", "content": json_content("ModelSnapshot") },
+                    "401": { "description": "Not logged in." },
+                    "429": { "description": "Daily quota exceeded." },
+                    "403": { "description": "Practice mode is off." },
+                },
+            },
+        },
+        "/profile": {
+            "get": {
+                "summary": "Returns the player's lifetime stats, kept across sessions.",
+                "description": "Sessions not logged in with a recovery code or a passkey, and \
+                                their API keys, get neither the alerts, the devices and the \
+                                passkeys of the account, nor the secret of its webhook.",
+                "responses": {
+                    "200": { "description": "The profile.", "content": json_content("UserProfile") },
+                    "401": { "description": "Not logged in." },
+                    "404": { "description": "No profile under this name." },
+                    "429": { "description": "Daily quota exceeded." },
+                },
+            },
+        },
+        "/profile/activity": {
+            "get": {
+                "summary": "Returns when the player plays, for a heatmap.",
+                "responses": {
+                    "200": { "description": "The activity matrix.", "content": json_content("Activity") },
+                    "401": { "description": "Not logged in." },
+                    "404": { "description": "No profile under this name." },
+                    "429": { "description": "Daily quota exceeded." },
+                },
+            },
+        },
+        "/simulate": {
+            "get": {
+                "summary": "Plays a strategy of the computer against a player policy, server-side.",
+                "description": "Nothing is recorded. The `markov` strategy plays the hand beating \
+                                the one the player's Markov model finds most likely next.",
+                "parameters": [
+                    history_parameter("strategy", "`random` (the default), `mirror`, \
+                                                   `counter-mirror` or `markov`."),
+                    history_parameter("policy", "`uniform` (the default), `last-move-repeat` or \
+                                                 `custom`."),
+                    history_parameter("rounds", "Rounds to play, 1000 by default, 10000 at most."),
+                    history_parameter("rock", "The weight of rock in the `custom` policy."),
+                    history_parameter("paper", "The weight of paper in the `custom` policy."),
+                    history_parameter("scissors", "The weight of scissors in the `custom` policy."),
+                ],
+                "responses": {
+                    "200": { "description": "The outcomes.", "content": json_content("Simulation") },
+                    "400": { "description": "Unknown strategy or policy, or no custom weights." },
+                    "401": { "description": "Not logged in." },
+                    "429": { "description": "Daily quota exceeded." },
+                },
+            },
+        },
+    })
+}
+
+fn bot_paths() -> Value {
+    json!({
+        "/bots": {
+            "post": {
+                "summary": "Registers a bot for the next round-robin tournaments.",
+                "requestBody": { "required": true, "content": json_content("BotRegistration") },
+                "responses": {
+                    "200": { "description": "The bot.", "content": json_content("BotStatus") },
+                    "400": { "description": "Not a valid bot name." },
+                    "401": { "description": "Not logged in." },
+                    "409": { "description": "The name is taken." },
+                    "429": { "description": "Daily quota exceeded." },
+                },
+            },
+        },
+        "/bots/{name}": {
+            "delete": {
+                "summary": "Withdraws a bot; it forfeits the rest of the running tournament.",
+                "responses": {
+                    "204": { "description": "Withdrawn." },
+                    "401": { "description": "Not logged in." },
+                    "404": { "description": "No such bot of yours." },
+                    "429": { "description": "Daily quota exceeded, or the bot calls too often." },
+                },
+            },
+        },
+        "/bots/{name}/turn": {
+            "get": {
+                "summary": "Returns what the bot has to play in the running tournament.",
+                "responses": {
+                    "200": { "description": "The bot's turn.", "content": json_content("BotStatus") },
+                    "401": { "description": "Not logged in." },
+                    "404": { "description": "No such bot of yours." },
+                    "429": { "description": "Daily quota exceeded, or the bot calls too often." },
+                },
+            },
+            "post": {
+                "summary": "Posts the bot's hands for the current round, by opponent.",
+                "requestBody": { "required": true, "content": json_content("Picks") },
+                "responses": {
+                    "200": { "description": "The bot's turn.", "content": json_content("BotStatus") },
+                    "400": { "description": "Not an opponent of the bot." },
+                    "401": { "description": "Not logged in." },
+                    "404": { "description": "No such bot of yours." },
+                    "409": { "description": "The bot is not in the running tournament." },
+                    "429": { "description": "Daily quota exceeded, or the bot calls too often." },
+                },
+            },
+        },
+    })
+}
+
+fn relay_paths() -> Value {
+    json!({
+        "/relay/teams": {
+            "post": {
+                "summary": "Registers a relay team, captained by the player.",
+                "requestBody": { "required": true, "content": json_content("TeamRegistration") },
+                "responses": {
+                    "200": { "description": "The team.", "content": json_content("Team") },
+                    "400": { "description": "Not a valid team name." },
+                    "401": { "description": "Not logged in." },
+                    "403": { "description": "Not a verified session." },
+                    "409": { "description": "The name is taken, or the player is in a team." },
+                    "429": { "description": "Daily quota exceeded." },
+                },
+            },
+        },
+        "/relay/teams/join": {
+            "post": {
+                "summary": "Joins the team with this invite code.",
+                "requestBody": { "required": true, "content": json_content("TeamInvite") },
+                "responses": {
+                    "200": { "description": "The team.", "content": json_content("Team") },
+                    "401": { "description": "Not logged in." },
+                    "403": { "description": "Not a verified session." },
+                    "404": { "description": "No team has this code." },
+                    "409": { "description": "The team is full or playing, or the player is in \
+                                              a team." },
+                    "429": { "description": "Daily quota exceeded." },
+                },
+            },
+        },
+        "/relay/teams/leave": {
+            "post": {
+                "summary": "Leaves the player's team; the next member becomes captain.",
+                "responses": {
+                    "204": { "description": "Left." },
+                    "401": { "description": "Not logged in." },
+                    "403": { "description": "Not a verified session." },
+                    "404": { "description": "Not in a team." },
+                    "409": { "description": "The team is playing." },
+                    "429": { "description": "Daily quota exceeded." },
+                },
+            },
+        },
+        "/relay/teams/members/{member}": {
+            "delete": {
+                "summary": "Drops a member from the team the player captains.",
+                "responses": {
+                    "200": { "description": "The team.", "content": json_content("Team") },
+                    "401": { "description": "Not logged in." },
+                    "403": { "description": "Not the captain, or not a verified session." },
+                    "404": { "description": "Not in a team, or no such other member." },
+                    "409": { "description": "The team is playing." },
+                    "429": { "description": "Daily quota exceeded." },
+                },
+            },
+        },
+        "/relay/team": {
+            "get": {
+                "summary": "Returns the player's team, and its relay in the running tournament.",
+                "responses": {
+                    "200": { "description": "The team.", "content": json_content("Team") },
+                    "401": { "description": "Not logged in." },
+                    "403": { "description": "Not a verified session." },
+                    "404": { "description": "Not in a team." },
+                    "429": { "description": "Daily quota exceeded." },
+                },
+            },
+        },
+        "/relay/play": {
+            "post": {
+                "summary": "Plays a round of the player's leg of the team's relay.",
+                "requestBody": { "required": true, "content": json_content("LegPlay") },
+                "responses": {
+                    "200": { "description": "The round.", "content": json_content("LegRound") },
+                    "401": { "description": "Not logged in." },
+                    "403": { "description": "Not a verified session." },
+                    "404": { "description": "Not in a team." },
+                    "409": { "description": "The team has no relay going on, or another member \
+                                              runs the current leg." },
+                    "429": { "description": "Daily quota exceeded." },
+                },
+            },
+        },
+    })
+}
+
+fn schemas() -> Value {
+    merge(vec![error_schemas(), round_schemas(), stats_schemas(), profile_schemas(),
+               history_schemas(), bot_schemas(), relay_schemas()])
+}
+
+fn error_schemas() -> Value {
+    json!({
+        "Error": {
+            "type": "object",
+            "properties": {
+                "code": {
+                    "type": "string",
+                    "description": "Stable across releases, e.g. `no-pending-round` or \
+                                    `bot.name-taken`; branch on it rather than on the message.",
+                },
+                "message": { "type": "string", "description": "For people; may change." },
+                "details": {
+                    "type": "object",
+                    "nullable": true,
+                    "description": "More about the error, e.g. the `field` and the `value` of an \
+                                    invalid query.",
+                },
+            },
+        },
+    })
+}
+
+fn round_schemas() -> Value {
+    json!({
+        "Hand": {
+            "type": "string",
+            "enum": ["rock", "paper", "scissors"],
+            "description": "Accepted in any case.",
+        },
+        "ApiKey": {
+            "type": "object",
+            "required": ["key"],
+            "properties": { "key": { "type": "string", "description": "Send it as `X-Api-Key`." } },
+        },
+        "Verdict": { "type": "string", "enum": ["win", "tie", "loss"] },
+        "Commitment": {
+            "type": "object",
+            "required": ["digest"],
+            "properties": {
+                "digest": {
+                    "type": "string",
+                    "description": "Hex SHA-256 of the nonce followed by the computer's hand.",
+                },
+                "pow_difficulty": {
+                    "type": "integer",
+                    "description": "Present if plays must prove work: the leading zero bits of \
+                                    SHA-256 of `digest` followed by the play's `nonce`.",
+                },
+            },
+        },
+        "Play": {
+            "type": "object",
+            "required": ["hand"],
+            "properties": {
+                "hand": schema_ref("Hand"),
+                "digest": {
+                    "type": "string",
+                    "description": "The commitment played against, the latest one if left out; \
+                                    retrying with it is idempotent.",
+                },
+                "nonce": {
+                    "type": "string",
+                    "description": "Proof of work against the digest played against, if the \
+                                    commitment asked for it with `pow_difficulty`.",
+                },
+            },
+        },
+        "RoundResult": {
+            "type": "object",
+            "required": ["human", "computer", "verdict", "random", "digest", "next_digest", "receipt"],
+            "properties": {
+                "human": schema_ref("Hand"),
+                "computer": schema_ref("Hand"),
+                "verdict": schema_ref("Verdict"),
+                "random": { "type": "string", "description": "The revealed hex nonce." },
+                "digest": { "type": "string", "description": "The commitment being revealed." },
+                "next_digest": { "type": "string", "description": "Commitment for the next round." },
+                "receipt": {
+                    "type": "string",
+                    "description": "History chain link of the round; see `GET /receipts/{receipt}`.",
+                },
+                "sudden_throw": {
+                    "type": "boolean",
+                    "description": "The round was a tie, not counted, and `next_digest` commits to its \
+                                    sudden throw, to be played right away.",
+                },
+            },
+        },
+    })
+}
+
+fn stats_schemas() -> Value {
+    json!({
+        "Scoreboard": {
+            "type": "object",
+            "required": ["wins", "ties", "losses"],
+            "properties": {
+                "wins": { "type": "integer" },
+                "ties": { "type": "integer" },
+                "losses": { "type": "integer" },
+            },
+        },
+        "Stats": {
+            "description": "The classic scoreboard, plus the one of chaos rounds.",
+            "allOf": [
+                schema_ref("Scoreboard"),
+                {
+                    "type": "object",
+                    "required": ["chaos", "chain_head", "trainer", "opponent", "rotation", "script",
+                                 "opponents", "toughest_opponent", "penalties"],
+                    "properties": {
+                        "chaos": schema_ref("Scoreboard"),
+                        "chain_head": { "type": "string", "description": "The latest history chain link." },
+                        "trainer": schema_ref("Trainer"),
+                        "opponent": schema_ref("Opponent"),
+                        "rotation": {
+                            "allOf": [schema_ref("Rotation")],
+                            "nullable": true,
+                            "description": "The weights the computer rotates among the opponents with.",
+                        },
+                        "script": {
+                            "type": "string",
+                            "nullable": true,
+                            "description": "The house script played instead of the opponent.",
+                        },
+                        "opponents": {
+                            "type": "array",
+                            "items": schema_ref("OpponentScore"),
+                            "description": "Classic rounds against each opponent, seeded matches aside.",
+                        },
+                        "toughest_opponent": {
+                            "allOf": [schema_ref("Opponent")],
+                            "nullable": true,
+                            "description": "The opponent with the most wins over the player.",
+                        },
+                        "penalties": {
+                            "type": "integer",
+                            "description": "Penalty points charged by the house rules turned on at `/`.",
+                        },
+                    },
+                },
+            ],
+        },
+        "Opponent": {
+            "type": "string",
+            "enum": ["random", "mirror", "counter-mirror"],
+            "description": "`mirror` plays the player's last hand, `counter-mirror` the hand beating it.",
+        },
+        "OpponentChoice": {
+            "type": "object",
+            "required": ["opponent"],
+            "properties": {
+                "opponent": schema_ref("Opponent"),
+                "rotation": schema_ref("Rotation"),
+                "script": {
+                    "type": "string",
+                    "description": "A house script to play instead, from `/opponent/scripts`.",
+                },
+            },
+        },
+        "OpponentScore": {
+            "allOf": [
+                schema_ref("Scoreboard"),
+                {
+                    "type": "object",
+                    "required": ["opponent"],
+                    "properties": { "opponent": schema_ref("Opponent") },
+                },
+            ],
+        },
+        "Rotation": {
+            "type": "object",
+            "properties": {
+                "random": { "type": "integer", "minimum": 0, "maximum": 100 },
+                "mirror": { "type": "integer", "minimum": 0, "maximum": 100 },
+                "counter-mirror": { "type": "integer", "minimum": 0, "maximum": 100 },
+            },
+            "description": "Opponent weights; each classic round's opponent is drawn in proportion \
+                            to them. Missing weights are 0, and all 0 stops the rotation.",
+        },
+        "Simulation": {
+            "description": "The outcomes of a simulation, from the player's side.",
+            "allOf": [
+                schema_ref("Scoreboard"),
+                {
+                    "type": "object",
+                    "required": ["strategy", "policy", "rounds", "win_rate", "tie_rate",
+                                 "loss_rate"],
+                    "properties": {
+                        "strategy": { "type": "string" },
+                        "policy": { "type": "string" },
+                        "rounds": { "type": "integer" },
+                        "win_rate": { "type": "number", "description": "Share of rounds won." },
+                        "tie_rate": { "type": "number", "description": "Share of rounds tied." },
+                        "loss_rate": { "type": "number", "description": "Share of rounds lost." },
+                    },
+                },
+            ],
+        },
+        "Trainer": {
+            "type": "object",
+            "description": "Regret of classic rounds against the best response to each revealed hand.",
+            "required": ["rounds", "regret", "average_regret", "computer_strategy"],
+            "properties": {
+                "rounds": { "type": "integer" },
+                "regret": { "type": "integer", "description": "Sum of the per-round regrets (0 to 2)." },
+                "average_regret": { "type": "number" },
+                "computer_strategy": {
+                    "type": "array",
+                    "items": { "type": "number" },
+                    "description": "Probabilities of rock, paper and scissors.",
+                },
+            },
+        },
+        "ModelSnapshot": {
+            "type": "object",
+            "required": ["observations", "last", "rock", "paper", "scissors", "likely", "counter"],
+            "properties": {
+                "observations": { "type": "integer", "description": "Hands observed so far." },
+                "last": { "allOf": [schema_ref("Hand")], "nullable": true },
+                "rock": { "type": "number", "description": "Predicted probability of rock next." },
+                "paper": { "type": "number", "description": "Predicted probability of paper next." },
+                "scissors": { "type": "number", "description": "Predicted probability of scissors next." },
+                "likely": schema_ref("Hand"),
+                "counter": schema_ref("Hand"),
+                "adaptive": {
+                    "type": "object",
+                    "description": "Fun mode's controller, present while it is on.",
+                    "properties": {
+                        "target": { "type": "number", "description": "Win rate aimed at." },
+                        "exploitation": {
+                            "type": "number",
+                            "description": "From -1, throwing every round, to 1, countering \
+                                            every predicted hand.",
+                        },
+                        "win_rate": {
+                            "type": "number",
+                            "nullable": true,
+                            "description": "Moving average of the rounds won, ties aside.",
+                        },
+                        "decided": { "type": "integer" },
+                    },
+                },
+            },
+        },
+    })
+}
+
+fn profile_schemas() -> Value {
+    json!({
+        "Activity": {
+            "type": "array",
+            "description": "Rounds played by day of the week, Monday first, then by hour of the day \
+                            at the player's UTC offset when they played: 7 rows of 24 counts.",
+            "items": { "type": "array", "items": { "type": "integer" } },
+        },
+        "UserProfile": {
+            "type": "object",
+            "required": ["classic", "chaos", "streak", "best_streak", "rating", "achievements",
+                         "hands", "activity", "first_seen", "last_seen"],
+            "properties": {
+                "classic": schema_ref("Scoreboard"),
+                "chaos": schema_ref("Scoreboard"),
+                "streak": { "type": "integer", "description": "Classic wins in a row, ties aside." },
+                "best_streak": { "type": "integer" },
+                "rating": {
+                    "type": "number",
+                    "description": "Rating against the computer, from 1500, by the server's \
+                                    rating system: Elo or Glicko-2.",
+                },
+                "rating_deviation": {
+                    "type": "number",
+                    "description": "Glicko-2 deviation of the rating, while the server rates \
+                                    with Glicko-2.",
+                },
+                "rating_volatility": {
+                    "type": "number",
+                    "description": "Glicko-2 volatility of the rating, while the server rates \
+                                    with Glicko-2.",
+                },
+                "achievements": {
+                    "type": "array",
+                    "items": {
+                        "type": "string",
+                        "enum": ["first-win", "hat-trick", "unstoppable", "centurion", "chaos-tamer"],
+                    },
+                    "description": "In the order they were earned.",
+                },
+                "hands": {
+                    "type": "array",
+                    "items": { "type": "integer" },
+                    "description": "How many times rock, paper and scissors were played.",
+                },
+                "activity": schema_ref("Activity"),
+                "first_seen": { "type": "integer", "description": "Unix time of the first login." },
+                "last_seen": { "type": "integer", "description": "Unix time of the latest login." },
+                "webhook": {
+                    "type": "object",
+                    "properties": {
+                        "url": { "type": "string" },
+                        "secret": { "type": "string", "description": "Hex; signs the posts." },
+                    },
+                    "description": "Where the player's rounds are posted, if set.",
+                },
+                "recent_matches": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "commitment": { "type": "string" },
+                            "rounds": { "type": "integer" },
+                            "finished_at": { "type": "integer", "description": "Unix time." },
+                        },
+                    },
+                    "description": "The latest finished seeded matches, the latest last.",
+                },
+                "hidden": { "type": "boolean", "description": "Whether `/u/<name>` is hidden." },
+                "analytics_opt_out": {
+                    "type": "boolean",
+                    "description": "Whether the player's rounds and sessions are left out of the \
+                                    anonymized analytics.",
+                },
+                "locale": {
+                    "type": "object",
+                    "properties": {
+                        "language": { "type": "string", "enum": ["en", "de", "fr", "es"] },
+                        "utc_offset": { "type": "integer", "description": "Minutes east of UTC." },
+                    },
+                    "description": "The language and the UTC offset of the player, if set. Their \
+                                    days, e.g. in the activity, start at midnight at that offset.",
+                },
+            },
+        },
+    })
+}
+
+fn history_schemas() -> Value {
+    json!({
+        "RoundRecord": {
+            "type": "object",
+            "required": ["kind", "human", "computer", "verdict", "random", "digest", "link"],
+            "properties": {
+                "kind": { "type": "string", "enum": ["classic", "chaos"] },
+                "human": { "type": "string", "description": "Hand(s) joined with commas." },
+                "computer": { "type": "string", "description": "Hand(s) joined with commas." },
+                "verdict": schema_ref("Verdict"),
+                "random": { "type": "string", "description": "The revealed hex nonce." },
+                "digest": { "type": "string", "description": "The commitment of the round." },
+                "link": {
+                    "type": "string",
+                    "description": "Hex SHA-256 of the previous link, the nonce and the computer's hand(s).",
+                },
+                "played_at": { "type": "integer", "description": "Unix time; not covered by `link`." },
+                "latency": {
+                    "type": "object",
+                    "description": "How long the round took, if it was timed; not covered by `link`.",
+                    "properties": {
+                        "decided_ms": { "type": "integer", "description": "From the commitment to the hand." },
+                        "server_us": { "type": "integer", "description": "Spent by the server on the round." },
+                    },
+                },
+                "note": {
+                    "type": "string",
+                    "description": "The player's note on the round, if any; not covered by `link`.",
+                },
+            },
+        },
+        "RoundProof": {
+            "type": "object",
+            "description": "Canonical: no whitespace, keys sorted bytewise, hashes and the nonce in \
+                            lower hex. Its SHA-256 is the same for every verifier.",
+            "required": ["algorithm", "computer", "digest", "kind", "link", "prev_link", "random"],
+            "properties": {
+                "algorithm": { "type": "string", "enum": ["sha256"] },
+                "computer": { "type": "string", "description": "Hand(s) joined with commas." },
+                "digest": { "type": "string", "description": "The commitment of the round." },
+                "kind": { "type": "string", "enum": ["classic", "chaos"] },
+                "link": { "type": "string", "description": "The round's history chain link." },
+                "prev_link": { "type": "string", "description": "The link before the round's." },
+                "random": { "type": "string", "description": "The revealed hex nonce." },
+            },
+        },
+        "HistoryPage": {
+            "type": "object",
+            "required": ["rounds", "more"],
+            "properties": {
+                "rounds": {
+                    "type": "array",
+                    "items": {
+                        "allOf": [
+                            schema_ref("RoundRecord"),
+                            {
+                                "type": "object",
+                                "required": ["id"],
+                                "properties": {
+                                    "id": {
+                                        "type": "integer",
+                                        "description": "The round's index in the history, from 0.",
+                                    },
+                                },
+                            },
+                        ],
+                    },
+                },
+                "more": { "type": "boolean", "description": "Whether the next page holds more." },
+                "next": {
+                    "type": "string",
+                    "nullable": true,
+                    "description": "The path of the next page, by cursor, if there is one.",
+                },
+            },
+        },
+        "Export": {
+            "type": "object",
+            "required": ["user_name", "chain_head", "rounds"],
+            "properties": {
+                "user_name": { "type": "string" },
+                "chain_head": { "type": "string" },
+                "first_round": {
+                    "type": "integer",
+                    "description": "The index of the first round: the oldest are dropped from \
+                                    long histories.",
+                },
+                "prev_link": {
+                    "type": "string",
+                    "description": "The chain link preceding the first round, all zeros unless \
+                                    rounds were dropped.",
+                },
+                "rounds": { "type": "array", "items": schema_ref("RoundRecord") },
+            },
+        },
+    })
+}
+
+fn history_parameter(name: &str, description: &str) -> Value {
+    json!({
+        "name": name,
+        "in": "query",
+        "schema": { "type": "string" },
+        "description": description,
+    })
+}
+
+/// The headers of a conditional GET, see `conditional`.
+fn conditional_parameters() -> Value {
+    json!([
+        {
+            "name": "If-None-Match",
+            "in": "header",
+            "schema": { "type": "string" },
+            "description": "The `ETag` of the response the client has.",
+        },
+    ])
+}
+
+fn not_modified() -> Value {
+    json!({ "description": "The client has the latest response, which `ETag` tags. No body." })
+}
+
+fn bot_schemas() -> Value {
+    let throw = json!({ "type": "string", "enum": ["rock", "paper", "scissors"], "nullable": true });
+    json!({
+        "BotRegistration": {
+            "type": "object",
+            "required": ["name"],
+            "properties": {
+                "name": { "type": "string", "description": "1 to 32 lowercase letters, digits and dashes." },
+            },
+        },
+        "Picks": {
+            "type": "object",
+            "required": ["hands"],
+            "properties": {
+                "hands": { "type": "object", "additionalProperties": schema_ref("Hand") },
+            },
+        },
+        "Matchup": {
+            "type": "object",
+            "required": ["opponent", "past"],
+            "properties": {
+                "opponent": { "type": "string" },
+                "past": {
+                    "type": "array",
+                    "items": { "type": "array", "items": throw },
+                    "description": "Own hand first; null for a forfeited hand.",
+                },
+                "picked": { "type": "string", "nullable": true },
+            },
+        },
+        "Turn": {
+            "type": "object",
+            "description": "Null unless the bot plays in the running tournament.",
+            "nullable": true,
+            "properties": {
+                "tournament": { "type": "integer" },
+                "round": { "type": "integer", "description": "From 1." },
+                "rounds": { "type": "integer" },
+                "deadline": { "type": "integer", "description": "Unix time the round closes." },
+                "matchups": { "type": "array", "items": schema_ref("Matchup") },
+            },
+        },
+        "BotStatus": {
+            "type": "object",
+            "required": ["name"],
+            "properties": {
+                "name": { "type": "string" },
+                "turn": schema_ref("Turn"),
+                "next_start": { "type": "integer", "nullable": true },
+            },
+        },
+    })
+}
+
+fn relay_schemas() -> Value {
+    json!({
+        "TeamRegistration": {
+            "type": "object",
+            "required": ["name"],
+            "properties": {
+                "name": { "type": "string", "description": "1 to 32 lowercase letters, digits and dashes." },
+            },
+        },
+        "TeamInvite": {
+            "type": "object",
+            "required": ["code"],
+            "properties": { "code": { "type": "string" } },
+        },
+        "LegPlay": {
+            "type": "object",
+            "required": ["hand"],
+            "properties": { "hand": schema_ref("Hand") },
+        },
+        "Leg": {
+            "type": "object",
+            "required": ["runner", "score"],
+            "properties": {
+                "runner": { "type": "string" },
+                "score": schema_ref("Scoreboard"),
+            },
+        },
+        "Relay": {
+            "type": "object",
+            "description": "Null unless the team plays in the running tournament.",
+            "nullable": true,
+            "properties": {
+                "tournament": { "type": "integer" },
+                "team": { "type": "string" },
+                "leg": { "type": "integer", "nullable": true, "description": "From 1." },
+                "runner": { "type": "string", "nullable": true },
+                "digest": {
+                    "type": "string",
+                    "nullable": true,
+                    "description": "Commitment for the next round of the leg.",
+                },
+                "legs": { "type": "array", "items": schema_ref("Leg") },
+                "score": { "type": "integer", "description": "Rounds won minus rounds lost." },
+                "deadline": { "type": "integer", "description": "Unix time the relay closes." },
+            },
+        },
+        "Team": {
+            "type": "object",
+            "required": ["name", "code", "captain", "members", "full"],
+            "properties": {
+                "name": { "type": "string" },
+                "code": { "type": "string", "description": "The invite code." },
+                "captain": { "type": "string" },
+                "members": { "type": "array", "items": { "type": "string" } },
+                "full": { "type": "boolean" },
+                "relay": schema_ref("Relay"),
+            },
+        },
+        "LegRound": {
+            "type": "object",
+            "required": ["human", "computer", "verdict", "random", "digest", "relay"],
+            "properties": {
+                "human": schema_ref("Hand"),
+                "computer": schema_ref("Hand"),
+                "verdict": schema_ref("Verdict"),
+                "random": { "type": "string", "description": "The revealed hex nonce." },
+                "digest": { "type": "string", "description": "The commitment being revealed." },
+                "relay": schema_ref("Relay"),
+            },
+        },
+    })
+}
+
+/// Merges JSON objects; `json!` cannot expand large literals in one go.
+fn merge(parts: Vec<Value>) -> Value {
+    let mut merged = ::serde_json::Map::new();
+    for part in parts {
+        if let Value::Object(map) = part {
+            merged.extend(map);
+        }
+    }
+    Value::Object(merged)
+}
+
+fn schema_ref(name: &str) -> Value {
+    json!({ "$ref": format!("#/components/schemas/{}", name) })
+}
+
+fn json_content(schema: &str) -> Value {
+    json!({ "application/json": { "schema": schema_ref(schema) } })
+}
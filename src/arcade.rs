@@ -0,0 +1,182 @@
+//! Arcade mode: win streaks grant one-time power-ups
+//!
+//! - *peek*: learn one hand the computer did not pick this round.
+//! - *redo*: void the round just lost, restoring the streak it broke.
+//!
+//! Every win also earns points, spent at the shop on *streak insurance*: the next loss leaves the
+//! streak as it is, using the insurance up.
+
+use std::collections::VecDeque;
+
+#[cfg(feature = "templates")]
+use rand::Rng;
+
+#[cfg(feature = "templates")]
+use entropy;
+use game::{Hand, Verdict};
+
+/// Streak lengths at which a power-up is granted.
+const PEEK_STREAK: u32 = 3;
+const REDO_STREAK: u32 = 5;
+/// Points earned by a win.
+const POINTS_PER_WIN: u32 = 10;
+/// Latest changes of the points kept in the ledger.
+const LEDGER_LEN: usize = 5;
+
+/// What the shop sells.
+#[cfg(feature = "templates")]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Item {
+    /// Keeps the streak through the next loss.
+    StreakInsurance,
+}
+
+#[cfg(feature = "templates")]
+impl Item {
+    pub fn parse(name: &str) -> Option<Item> {
+        match name {
+            "streak-insurance" => Some(Item::StreakInsurance),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Item::StreakInsurance => "streak insurance",
+        }
+    }
+
+    pub fn price(&self) -> u32 {
+        match *self {
+            Item::StreakInsurance => 50,
+        }
+    }
+}
+
+/// A change of the points.
+#[derive(Clone, Debug)]
+pub struct LedgerEntry {
+    /// Positive when earned, negative when spent.
+    pub change: i64,
+    pub reason: &'static str,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Arcade {
+    pub streak: u32,
+    pub peeks: u32,
+    pub redos: u32,
+    /// The hand eliminated by a peek at the pending round.
+    pub peeked: Option<Hand>,
+    /// The streak broken by the last round, if it was a loss that can still be redone.
+    broken_streak: Option<u32>,
+    pub points: u32,
+    /// Whether streak insurance was bought and not used up yet.
+    pub insured: bool,
+    /// At most `LEDGER_LEN` entries, the latest last.
+    ledger: VecDeque<LedgerEntry>,
+}
+
+impl Arcade {
+    #[cfg(feature = "templates")]
+    pub fn new() -> Arcade {
+        Arcade::default()
+    }
+
+    /// Updates the streak after a round, granting power-ups at the thresholds.
+    pub fn record(&mut self, verdict: Verdict) {
+        self.peeked = None;
+        self.broken_streak = None;
+        match verdict {
+            Verdict::Win => {
+                self.streak += 1;
+                self.points += POINTS_PER_WIN;
+                self.log(i64::from(POINTS_PER_WIN), "win");
+                if self.streak % PEEK_STREAK == 0 {
+                    self.peeks += 1;
+                }
+                if self.streak % REDO_STREAK == 0 {
+                    self.redos += 1;
+                }
+            }
+            Verdict::Tie => {}
+            Verdict::Loss if self.insured => self.insured = false,
+            Verdict::Loss => {
+                self.broken_streak = Some(self.streak);
+                self.streak = 0;
+            }
+        }
+    }
+
+    fn log(&mut self, change: i64, reason: &'static str) {
+        if self.ledger.len() >= LEDGER_LEN {
+            self.ledger.pop_front();
+        }
+        self.ledger.push_back(LedgerEntry { change: change, reason: reason });
+    }
+
+    /// The latest changes of the points, the latest last.
+    #[cfg(feature = "templates")]
+    pub fn ledger(&self) -> &VecDeque<LedgerEntry> {
+        &self.ledger
+    }
+
+    /// Whether `item` can be bought now: it is affordable and not held already.
+    #[cfg(feature = "templates")]
+    pub fn can_buy(&self, item: Item) -> bool {
+        let held = match item {
+            Item::StreakInsurance => self.insured,
+        };
+        !held && self.points >= item.price()
+    }
+
+    /// Buys `item` with points. Returns whether it could.
+    #[cfg(feature = "templates")]
+    pub fn buy(&mut self, item: Item) -> bool {
+        if !self.can_buy(item) {
+            return false;
+        }
+        self.points -= item.price();
+        self.log(-i64::from(item.price()), item.name());
+        match item {
+            Item::StreakInsurance => self.insured = true,
+        }
+        true
+    }
+
+    /// Forgets about the pending round, which is being replaced.
+    pub fn new_round(&mut self) {
+        self.peeked = None;
+    }
+
+    /// Spends a peek on the pending round, against which the computer plays `computer`. Peeking
+    /// twice at the same round shows the same hand for free.
+    #[cfg(feature = "templates")]
+    pub fn peek(&mut self, computer: Hand) -> Option<Hand> {
+        if self.peeked.is_some() {
+            return self.peeked;
+        }
+        if self.peeks == 0 {
+            return None;
+        }
+        self.peeks -= 1;
+        let others: Vec<Hand> = Hand::CHOICES.iter().cloned().filter(|&h| h != computer).collect();
+        self.peeked = Some(*entropy::get().choose(&others).unwrap());
+        self.peeked
+    }
+
+    pub fn can_redo(&self) -> bool {
+        self.redos > 0 && self.broken_streak.is_some()
+    }
+
+    /// Spends a redo on the last round. Returns `false` if it wasn't a loss or no redo is left;
+    /// the caller then takes the loss off the scoreboard.
+    pub fn redo(&mut self) -> bool {
+        if !self.can_redo() {
+            return false;
+        }
+        self.redos -= 1;
+        self.streak = self.broken_streak.take().unwrap();
+        true
+    }
+}
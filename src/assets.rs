@@ -0,0 +1,170 @@
+//! Static asset serving
+
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use rocket::http::{ContentType, Status};
+use rocket::http::uri::{Segments, URI};
+use rocket::request::{self, FromRequest, Request};
+use rocket::response::{self, Body, Responder, Response};
+use rocket::{Config, Outcome};
+
+const DEFAULT_STATIC_DIR: &str = "static/";
+
+/// The static root, read from the `static_dir` config extra.
+pub struct StaticDir(pub PathBuf);
+
+impl StaticDir {
+    pub fn from_config(config: &Config) -> StaticDir {
+        StaticDir(PathBuf::from(config.get_str("static_dir").unwrap_or(DEFAULT_STATIC_DIR)))
+    }
+}
+
+/// Resolves the requested segments against the static root.
+///
+/// Returns `None` for any path that tries to escape the root (`..`, hidden files, symlinks pointing
+/// outside) or that is not a regular file.
+pub fn resolve(root: &Path, segments: Segments) -> Option<PathBuf> {
+    let mut relative = PathBuf::new();
+    for segment in segments {
+        let decoded = URI::percent_decode(segment.as_bytes()).ok()?;
+        if decoded.starts_with('.') || decoded.contains('/') || decoded.contains('\\') {
+            return None;
+        }
+        relative.push(decoded.as_ref());
+    }
+
+    let root = root.canonicalize().ok()?;
+    let path = root.join(relative).canonicalize().ok()?;
+    if path.starts_with(&root) && path.is_file() {
+        Some(path)
+    } else {
+        None
+    }
+}
+
+/// Maps a file extension to its `Content-Type`.
+///
+/// Rocket's built-in table still uses the legacy `application/font-*` types, so fonts are
+/// overridden here.
+fn content_type_for(path: &Path) -> Option<ContentType> {
+    let ext = path.extension()?.to_string_lossy().to_lowercase();
+    Some(match ext.as_ref() {
+        "svg" => ContentType::new("image", "svg+xml"),
+        "ttf" => ContentType::new("font", "ttf"),
+        "otf" => ContentType::new("font", "otf"),
+        "woff" => ContentType::new("font", "woff"),
+        "woff2" => ContentType::new("font", "woff2"),
+        _ => return ContentType::from_extension(&ext),
+    })
+}
+
+/// A single byte range taken from the `Range` request header.
+///
+/// Only `bytes=<start>-<end>`, `bytes=<start>-` and `bytes=-<suffix>` are understood. Multi-range
+/// requests are ignored and answered with the full body.
+#[derive(Clone, Copy, Debug)]
+pub enum ByteRange {
+    From(u64, Option<u64>),
+    Suffix(u64),
+}
+
+impl ByteRange {
+    fn parse(s: &str) -> Option<ByteRange> {
+        let spec = s.trim();
+        if !spec.starts_with("bytes=") {
+            return None;
+        }
+        let spec = &spec["bytes=".len()..];
+        if spec.contains(',') {
+            return None;
+        }
+
+        let mut parts = spec.splitn(2, '-');
+        let start = parts.next()?.trim();
+        let end = parts.next()?.trim();
+        if start.is_empty() {
+            end.parse().ok().map(ByteRange::Suffix)
+        } else {
+            let start = start.parse().ok()?;
+            let end = if end.is_empty() { None } else { Some(end.parse().ok()?) };
+            Some(ByteRange::From(start, end))
+        }
+    }
+
+    /// Returns the inclusive `(first, last)` offsets within a file of `len` bytes, or `None` if
+    /// the range is not satisfiable.
+    fn bounds(&self, len: u64) -> Option<(u64, u64)> {
+        if len == 0 {
+            return None;
+        }
+        match *self {
+            ByteRange::From(start, end) => {
+                let last = end.map_or(len - 1, |end| end.min(len - 1));
+                if start <= last { Some((start, last)) } else { None }
+            }
+            ByteRange::Suffix(0) => None,
+            ByteRange::Suffix(n) => Some((len - n.min(len), len - 1)),
+        }
+    }
+}
+
+/// Request guard for an optional `Range` header. Never fails.
+pub struct RangeHeader(pub Option<ByteRange>);
+
+impl<'a, 'r> FromRequest<'a, 'r> for RangeHeader {
+    type Error = ();
+
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<RangeHeader, ()> {
+        let range = request.headers().get_one("Range").and_then(ByteRange::parse);
+        Outcome::Success(RangeHeader(range))
+    }
+}
+
+/// A file under the static root, optionally restricted to a byte range.
+pub struct StaticFile {
+    path: PathBuf,
+    file: File,
+    range: Option<ByteRange>,
+}
+
+impl StaticFile {
+    pub fn open(path: PathBuf, range: Option<ByteRange>) -> io::Result<StaticFile> {
+        let file = File::open(&path)?;
+        Ok(StaticFile { path, file, range })
+    }
+}
+
+impl<'r> Responder<'r> for StaticFile {
+    fn respond_to(self, _: &Request) -> response::Result<'r> {
+        let StaticFile { path, mut file, range } = self;
+        let len = file.metadata().map_err(|_| Status::InternalServerError)?.len();
+
+        let mut response = Response::build();
+        response.raw_header("Accept-Ranges", "bytes");
+        if let Some(ct) = content_type_for(&path) {
+            response.header(ct);
+        }
+
+        match range.map(|r| r.bounds(len)) {
+            None => {
+                response.sized_body(file);
+            }
+            Some(None) => {
+                response
+                    .status(Status::RangeNotSatisfiable)
+                    .raw_header("Content-Range", format!("bytes */{}", len));
+            }
+            Some(Some((first, last))) => {
+                file.seek(SeekFrom::Start(first)).map_err(|_| Status::InternalServerError)?;
+                let part_len = last - first + 1;
+                response
+                    .status(Status::PartialContent)
+                    .raw_header("Content-Range", format!("bytes {}-{}/{}", first, last, len))
+                    .raw_body(Body::Sized(file.take(part_len), part_len));
+            }
+        }
+        response.ok()
+    }
+}
@@ -0,0 +1,301 @@
+//! Point-in-time backups of the server's state
+//!
+//! With the `backup_dir` config extra set, the server writes a backup there every
+//! `backup_interval_secs` seconds (an hour by default), as `backup-<unix time>.json`, and keeps the
+//! latest `backup_keep` of them (24 by default). A backup holds every session, with its whole round
+//! history, and every profile. Its file is `{"sha256": ..., "backup": ...}`, where `sha256` is the
+//! hex SHA-256 of the canonical JSON of `backup` (compact, with sorted keys), so that a file cut
+//! short or edited by hand is caught before anything is restored from it. Since it holds the ids
+//! of the sessions, the file is readable by the server's user only.
+//!
+//! `GET /admin/backups` lists the backups, the latest first, and `POST /admin/backups` takes one
+//! at once. `POST /admin/backups/<name>/restore` rolls the server back to one: once the hash and
+//! every history chain check out, the sessions and the profiles are replaced by those of the
+//! backup as a whole. Sessions started since are revoked, so that their cookies don't bring them
+//! back, and their API keys go with them. Sessions revoked since, and the sessions and profile of
+//! accounts deleted since, are left out of what is restored. The rest, e.g. PvP matches,
+//! tournaments or bans, is left as it is. Turn maintenance mode on first, so that nothing is
+//! played in the meantime.
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use std::time::Duration;
+
+use rocket::http::Status;
+use rocket::Config;
+use serde_json::{self, Value};
+use sha2::{Digest, Sha256};
+
+use account;
+use api;
+use history::{self, History, RoundRecord};
+use ids::UserId;
+use poison::Recover;
+use profile::{self, UserProfile};
+use revocation::Revocations;
+use scheduler;
+use snapshot::Snapshot;
+use store;
+use util::{bytes_to_hex, unix_time};
+use {Session, SESSIONS};
+
+const DEFAULT_INTERVAL_SECS: u64 = 3600;
+const DEFAULT_KEEP: usize = 24;
+const PREFIX: &str = "backup-";
+const EXTENSION: &str = ".json";
+
+lazy_static! {
+    static ref SETTINGS: RwLock<Option<Settings>> = RwLock::new(None);
+}
+
+struct Settings {
+    dir: PathBuf,
+    keep: usize,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SavedSession {
+    user_id: UserId,
+    session: Snapshot,
+    /// Where `history` starts, see `History::first` and `History::prev_link`.
+    #[serde(default)]
+    first_round: usize,
+    #[serde(default = "history::genesis")]
+    prev_link: String,
+    history: Vec<RoundRecord>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Backup {
+    taken_at: u64,
+    sessions: Vec<SavedSession>,
+    profiles: HashMap<String, UserProfile>,
+}
+
+/// A backup file, as listed by `GET /admin/backups`.
+#[derive(Serialize)]
+pub struct BackupFile {
+    /// What `POST /admin/backups/<name>/restore` takes.
+    name: String,
+    /// Unix time.
+    taken_at: u64,
+    size: u64,
+}
+
+/// What a restore brought back.
+#[derive(Serialize)]
+pub struct Restored {
+    taken_at: u64,
+    sessions: usize,
+    profiles: usize,
+    /// Sessions started since the backup, now ended.
+    revoked: usize,
+    /// Sessions and profiles of the backup left out, as revoked or deleted since.
+    left_out: usize,
+}
+
+#[derive(Debug)]
+pub enum BackupError {
+    /// No `backup_dir` is set.
+    Disabled,
+    NoSuchBackup,
+    /// The hash does not match, or a history does not form a chain.
+    Corrupt(String),
+    Io(String),
+}
+
+impl BackupError {
+    pub fn status(&self) -> Status {
+        match *self {
+            BackupError::Disabled | BackupError::NoSuchBackup => Status::NotFound,
+            BackupError::Corrupt(_) => Status::UnprocessableEntity,
+            BackupError::Io(_) => Status::InternalServerError,
+        }
+    }
+}
+
+/// Reads the `backup_dir`, `backup_interval_secs` and `backup_keep` config extras, and schedules
+/// the backups.
+pub fn configure(config: &Config) -> Result<(), String> {
+    let dir = match config.get_str("backup_dir") {
+        Ok(dir) => PathBuf::from(dir),
+        Err(_) => return Ok(()),
+    };
+    let interval = match config.get_int("backup_interval_secs") {
+        Ok(secs) if secs > 0 => secs as u64,
+        Ok(secs) => return Err(format!("`backup_interval_secs` must be positive, got {}", secs)),
+        Err(_) => DEFAULT_INTERVAL_SECS,
+    };
+    let keep = match config.get_int("backup_keep") {
+        Ok(keep) if keep > 0 => keep as usize,
+        Ok(keep) => return Err(format!("`backup_keep` must be positive, got {}", keep)),
+        Err(_) => DEFAULT_KEEP,
+    };
+    fs::create_dir_all(&dir).map_err(|e| format!("unable to create {}: {}", dir.display(), e))?;
+    *SETTINGS.write().recover() = Some(Settings { dir: dir, keep: keep });
+    scheduler::every("backup", Duration::from_secs(interval), || {
+        if let Err(e) = take() {
+            eprintln!("Unable to back the state up: {:?}", e);
+        }
+    });
+    Ok(())
+}
+
+/// The hex SHA-256 of the canonical JSON of `backup`.
+fn digest(backup: &Value) -> String {
+    // Without serde_json's `preserve_order`, objects keep their keys sorted.
+    let canonical = serde_json::to_string(backup).expect("Unable to serialize a backup");
+    bytes_to_hex(&Sha256::digest(canonical.as_bytes()))
+}
+
+fn dir() -> Result<(PathBuf, usize), BackupError> {
+    let settings = SETTINGS.read().recover();
+    let settings = settings.as_ref().ok_or(BackupError::Disabled)?;
+    Ok((settings.dir.clone(), settings.keep))
+}
+
+/// Writes a backup of the sessions and the profiles, then drops the oldest beyond `backup_keep`.
+pub fn take() -> Result<BackupFile, BackupError> {
+    let (dir, keep) = dir()?;
+    let sessions: Vec<_> = SESSIONS.read_all().iter()
+        .map(|(user_id, session)| SavedSession {
+            user_id: user_id.clone(),
+            session: Snapshot::of(session),
+            first_round: session.history.first(),
+            prev_link: session.history.prev_link().to_owned(),
+            history: session.history.records(),
+        })
+        .collect();
+    let taken_at = unix_time();
+    let backup = Backup {
+        taken_at: taken_at,
+        sessions: sessions,
+        profiles: profile::all(),
+    };
+    let backup = serde_json::to_value(&backup).map_err(|e| BackupError::Io(e.to_string()))?;
+    let file = json!({ "sha256": digest(&backup), "backup": backup });
+
+    let name = format!("{}{}{}", PREFIX, taken_at, EXTENSION);
+    let path = dir.join(&name);
+    store::write(&path, &file)
+        .map_err(|e| BackupError::Io(format!("unable to write {}: {}", path.display(), e)))?;
+
+    for stale in list()?.into_iter().skip(keep) {
+        if let Err(e) = fs::remove_file(dir.join(&stale.name)) {
+            eprintln!("Unable to remove the backup {}: {}", stale.name, e);
+        }
+    }
+    let size = fs::metadata(&path).map(|metadata| metadata.len()).unwrap_or(0);
+    Ok(BackupFile { name: name, taken_at: taken_at, size: size })
+}
+
+/// The Unix time in the name of a backup file, if it is one.
+fn backup_time(path: &Path) -> Option<u64> {
+    let name = path.file_name()?.to_str()?;
+    if !name.starts_with(PREFIX) || !name.ends_with(EXTENSION) {
+        return None;
+    }
+    name[PREFIX.len()..name.len() - EXTENSION.len()].parse().ok()
+}
+
+/// The backups, the latest first.
+pub fn list() -> Result<Vec<BackupFile>, BackupError> {
+    let (dir, _) = dir()?;
+    let entries = fs::read_dir(&dir)
+        .map_err(|e| BackupError::Io(format!("unable to list {}: {}", dir.display(), e)))?;
+    let mut backups: Vec<_> = entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let path = entry.path();
+            let taken_at = backup_time(&path)?;
+            Some(BackupFile {
+                name: path.file_name()?.to_str()?.to_owned(),
+                taken_at: taken_at,
+                size: entry.metadata().ok()?.len(),
+            })
+        })
+        .collect();
+    backups.sort_by(|a, b| b.taken_at.cmp(&a.taken_at));
+    Ok(backups)
+}
+
+/// Reads the backup `name` and checks its hash.
+fn read(name: &str) -> Result<Backup, BackupError> {
+    let (dir, _) = dir()?;
+    let path = dir.join(name);
+    if name.contains('/') || name.contains('\\') || backup_time(&path).is_none() || !path.exists() {
+        return Err(BackupError::NoSuchBackup);
+    }
+    let reader = File::open(&path).map(BufReader::new)
+        .map_err(|e| BackupError::Io(format!("unable to read {}: {}", path.display(), e)))?;
+    let mut file: Value = serde_json::from_reader(reader)
+        .map_err(|e| BackupError::Corrupt(format!("invalid JSON: {}", e)))?;
+    let expected = file["sha256"].as_str().map(str::to_owned)
+        .ok_or_else(|| BackupError::Corrupt("no hash".to_owned()))?;
+    let backup = file["backup"].take();
+    if digest(&backup) != expected {
+        return Err(BackupError::Corrupt("the hash does not match".to_owned()));
+    }
+    serde_json::from_value(backup).map_err(|e| BackupError::Corrupt(e.to_string()))
+}
+
+/// Rolls the sessions and the profiles back to the backup `name`.
+pub fn restore(name: &str, revocations: &Revocations) -> Result<Restored, BackupError> {
+    let backup = read(name)?;
+    let taken_at = backup.taken_at;
+    let mut restored = Vec::with_capacity(backup.sessions.len());
+    let mut left_out = 0;
+    for saved in backup.sessions {
+        if revocations.is_revoked(&saved.user_id)
+            || account::deleted_since(&saved.session.user_name, taken_at)
+        {
+            left_out += 1;
+            continue;
+        }
+        let history = History::from_records_at(saved.first_round, &saved.prev_link, &saved.history);
+        let history = history.map_err(|e| {
+            BackupError::Corrupt(format!("session {}: {}", saved.user_id.as_str(), e))
+        })?;
+        let mut session = saved.session.restore();
+        session.history = history;
+        restored.push((saved.user_id, session));
+    }
+
+    let sessions = restored.len();
+    let revoked = {
+        let mut current = SESSIONS.write_all();
+        let ids: Vec<UserId> = current.iter().map(|(id, _)| id.clone()).collect();
+        let mut revoked = Vec::new();
+        for id in ids {
+            let session = current.remove(&id);
+            if !restored.iter().any(|&(ref restored_id, _)| *restored_id == id) {
+                revocations.revoke(&id);
+                session.map(Session::end);
+                revoked.push(id);
+            }
+        }
+        for (id, session) in restored {
+            current.insert(id, session);
+        }
+        ::recount_sessions(current.values());
+        api::API_KEYS.write().recover().retain(|_, id| !revoked.contains(id));
+        revoked.len()
+    };
+    let total = backup.profiles.len();
+    let profiles: HashMap<_, _> = backup.profiles.into_iter()
+        .filter(|&(ref user_name, _)| !account::deleted_since(user_name, taken_at))
+        .collect();
+    left_out += total - profiles.len();
+    let restored_profiles = profiles.len();
+    profile::replace_all(profiles);
+    Ok(Restored {
+        taken_at: taken_at,
+        sessions: sessions,
+        profiles: restored_profiles,
+        revoked: revoked,
+        left_out: left_out,
+    })
+}
@@ -0,0 +1,43 @@
+//! Verifies a history export, as downloaded from `/api/v1/export`.
+//!
+//! Usage: `verify-export [FILE]`, reading standard input if no file is given.
+
+extern crate rock_paper_scissors;
+extern crate serde_json;
+
+use std::env;
+use std::fs::File;
+use std::io::{self, Read};
+use std::process;
+
+use rock_paper_scissors::history::{self, Export};
+
+fn main() {
+    let mut input = String::new();
+    let read = match env::args().nth(1) {
+        Some(path) => File::open(&path).and_then(|mut f| f.read_to_string(&mut input)),
+        None => io::stdin().read_to_string(&mut input),
+    };
+    if let Err(e) = read {
+        eprintln!("Unable to read the export: {}", e);
+        process::exit(2);
+    }
+
+    let export: Export = match serde_json::from_str(&input) {
+        Ok(export) => export,
+        Err(e) => {
+            eprintln!("Not a valid export: {}", e);
+            process::exit(2);
+        }
+    };
+
+    match history::verify(&export) {
+        Ok(()) => {
+            println!("OK: {} rounds, chain head {}", export.rounds.len(), export.chain_head);
+        }
+        Err(e) => {
+            println!("FAILED: {}", e);
+            process::exit(1);
+        }
+    }
+}
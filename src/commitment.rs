@@ -0,0 +1,223 @@
+//! Commitment scheme for the computer's hands
+//!
+//! A round is committed to as `SHA-256(hex(nonce) || hand)` and revealed by disclosing the nonce
+//! and the hand. Chaos rounds commit to their hands joined with commas instead. In a seeded match,
+//! the nonce and the hand of every round are derived from a single match seed, which is itself
+//! committed to as `SHA-256(hex(seed))` when the match starts. Revealed rounds are then chained
+//! per session, see `history`.
+//!
+//! How long a reveal takes must not tell anything either, e.g. whether a retried play was answered
+//! from the history or made the server draw the next round. Played-against digests are compared
+//! with `same_digest`, in constant time, and every reveal is padded by a `RevealPadding` to the
+//! `reveal_delay_ms` config extra (0, i.e. no padding, by default): set it above the slowest
+//! reveal to hide the difference.
+
+use std::sync::atomic::{AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rocket::request::{self, FromRequest, Request};
+use rocket::{Config, Outcome};
+use sha2::{Sha256, Digest};
+
+use game::{Hand, Verdict};
+use util::{bytes_to_hex, constant_time_eq, gen_random_bytes};
+
+const SHA256_BLOCK_SIZE: usize = 64;
+const SEED_SIZE: usize = 32;
+const NONCE_SIZE: usize = 32;
+
+/// Salt of the extract step, fixed so that verifiers don't need anything but the seed.
+const HKDF_SALT: &[u8] = b"rock-paper-scissors";
+
+/// The `reveal_delay_ms` config extra.
+static REVEAL_DELAY_MS: AtomicUsize = ATOMIC_USIZE_INIT;
+
+/// Reads the `reveal_delay_ms` config extra.
+pub fn configure(config: &Config) -> Result<(), String> {
+    let delay = match config.get_int("reveal_delay_ms") {
+        Ok(ms) if ms >= 0 => ms as usize,
+        Ok(ms) => return Err(format!("`reveal_delay_ms` must not be negative, got {}", ms)),
+        Err(_) => 0,
+    };
+    REVEAL_DELAY_MS.store(delay, Ordering::SeqCst);
+    Ok(())
+}
+
+/// Whether `given` is the digest `expected`, compared in constant time.
+pub fn same_digest(given: &str, expected: &str) -> bool {
+    constant_time_eq(given, expected)
+}
+
+/// Pads a reveal: once dropped, it has taken at least the reveal delay since it was started.
+///
+/// Start it before anything that depends on the round, and before taking the session lock, so
+/// that it is dropped after the lock is released. As a request guard, it starts padding then: put
+/// it before the session guard.
+pub struct RevealPadding {
+    started: Instant,
+    delay: Duration,
+}
+
+impl RevealPadding {
+    /// Pads to `reveal_delay_ms`.
+    pub fn start() -> RevealPadding {
+        let delay = REVEAL_DELAY_MS.load(Ordering::SeqCst) as u64;
+        RevealPadding::with_delay(Duration::from_millis(delay))
+    }
+
+    pub fn with_delay(delay: Duration) -> RevealPadding {
+        RevealPadding {
+            started: Instant::now(),
+            delay: delay,
+        }
+    }
+}
+
+impl<'a, 'r> FromRequest<'a, 'r> for RevealPadding {
+    type Error = ();
+
+    fn from_request(_: &'a Request<'r>) -> request::Outcome<RevealPadding, ()> {
+        Outcome::Success(RevealPadding::start())
+    }
+}
+
+impl Drop for RevealPadding {
+    fn drop(&mut self) {
+        let elapsed = self.started.elapsed();
+        if elapsed < self.delay {
+            thread::sleep(self.delay - elapsed);
+        }
+    }
+}
+
+/// Returns the lower hex commitment to `hand` under the nonce `nonce_hex`.
+pub fn digest(nonce_hex: &str, hand: Hand) -> String {
+    digest_str(nonce_hex, hand.as_ref())
+}
+
+/// Returns the lower hex commitment to several hands, which are joined with commas.
+pub fn digest_multi(nonce_hex: &str, hands: &[Hand]) -> String {
+    digest_str(nonce_hex, &join_hands(hands))
+}
+
+/// Formats hands the way `digest_multi` commits to them, e.g. `rock,paper,rock`.
+pub fn join_hands(hands: &[Hand]) -> String {
+    hands.iter().map(|hand| hand.as_ref()).collect::<Vec<_>>().join(",")
+}
+
+/// Returns the lower hex commitment to an already formatted hand (or hands) string.
+pub fn digest_str(nonce_hex: &str, committed: &str) -> String {
+    let concat_str = format!("{}{}", nonce_hex, committed);
+    format!("{:x}", Sha256::digest(concat_str.as_bytes()))
+}
+
+/// Returns the next link of a session's history chain,
+/// `SHA-256(prev || hex(nonce) || hand || ":" || human || ":" || verdict)`: the human's hand(s) are
+/// formatted like the computer's, and the verdict as serialized.
+pub fn chain_link(prev: &str, nonce_hex: &str, committed: &str, human: &str, verdict: Verdict)
+    -> String
+{
+    let concat_str = format!("{}{}{}:{}:{}", prev, nonce_hex, committed, human, verdict.name());
+    format!("{:x}", Sha256::digest(concat_str.as_bytes()))
+}
+
+/// HMAC-SHA256 as defined in RFC 2104.
+pub fn hmac_sha256(key: &[u8], message: &[u8]) -> Vec<u8> {
+    let mut block = [0u8; SHA256_BLOCK_SIZE];
+    if key.len() > SHA256_BLOCK_SIZE {
+        block[..32].copy_from_slice(&Sha256::digest(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+
+    let mut inner = Sha256::default();
+    inner.input(&block.iter().map(|b| b ^ 0x36).collect::<Vec<u8>>());
+    inner.input(message);
+
+    let mut outer = Sha256::default();
+    outer.input(&block.iter().map(|b| b ^ 0x5c).collect::<Vec<u8>>());
+    outer.input(&inner.result());
+    outer.result().to_vec()
+}
+
+/// HKDF-SHA256 (RFC 5869) with the fixed salt above.
+fn hkdf(ikm: &[u8], info: &[u8], len: usize) -> Vec<u8> {
+    let prk = hmac_sha256(HKDF_SALT, ikm);
+    let mut okm = Vec::with_capacity(len);
+    let mut t = Vec::new();
+    let mut counter = 1u8;
+    while okm.len() < len {
+        let mut message = t.clone();
+        message.extend_from_slice(info);
+        message.push(counter);
+        t = hmac_sha256(&prk, &message);
+        okm.extend_from_slice(&t);
+        counter += 1;
+    }
+    okm.truncate(len);
+    okm
+}
+
+/// Derives the nonce and the hand of round `index` from a match seed.
+///
+/// The HKDF info is `"round"` followed by `index` as a big-endian `u64`. The first 32 bytes of
+/// output are the nonce, the next 8 bytes (big-endian, modulo 3) pick the hand.
+fn derive_round(seed: &[u8], index: u64) -> (Vec<u8>, Hand) {
+    let mut info = b"round".to_vec();
+    for i in (0..8).rev() {
+        info.push((index >> (i * 8)) as u8);
+    }
+
+    let okm = hkdf(seed, &info, NONCE_SIZE + 8);
+    let nonce = okm[..NONCE_SIZE].to_vec();
+    let pick = okm[NONCE_SIZE..].iter().fold(0u64, |acc, &b| (acc << 8) | u64::from(b));
+    (nonce, Hand::CHOICES[(pick % 3) as usize])
+}
+
+/// The seed of a match whose rounds are all derived from it.
+#[derive(Clone)]
+pub struct MatchSeed {
+    seed: Vec<u8>,
+    next_index: u64,
+}
+
+impl MatchSeed {
+    pub fn random() -> MatchSeed {
+        MatchSeed {
+            seed: gen_random_bytes(SEED_SIZE),
+            next_index: 0,
+        }
+    }
+
+    pub fn seed_hex(&self) -> String {
+        bytes_to_hex(&self.seed)
+    }
+
+    /// The public commitment to the seed, shown when the match starts.
+    pub fn commitment(&self) -> String {
+        format!("{:x}", Sha256::digest(self.seed_hex().as_bytes()))
+    }
+
+    /// Number of rounds derived so far.
+    pub fn round_count(&self) -> u64 {
+        self.next_index
+    }
+
+    /// Derives the nonce and the hand of round `index`.
+    pub fn derive(&self, index: u64) -> (Vec<u8>, Hand) {
+        derive_round(&self.seed, index)
+    }
+
+    /// Derives the nonce and the hand of the next round.
+    pub fn next_round(&mut self) -> (Vec<u8>, Hand) {
+        let round = self.derive(self.next_index);
+        self.advance();
+        round
+    }
+
+    /// Moves on to the next round without deriving the current one.
+    pub fn advance(&mut self) {
+        self.next_index += 1;
+    }
+}
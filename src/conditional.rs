@@ -0,0 +1,89 @@
+//! Conditional GET for what clients poll
+//!
+//! The leaderboard exports and the API's stats and history carry an `ETag` naming the version of
+//! what they show and, where it is known, a `Last-Modified` time. A client sending the tag back as
+//! `If-None-Match` gets `304 Not Modified`, without a body, until the version moves; the body is
+//! not even computed then. Versions are cheap to tell, e.g. the round count of a session, and only
+//! hold within a run of the server, which tags name too.
+//!
+//! `Last-Modified` is when the latest round was played, which notes and the state between rounds
+//! do not move, so `If-Modified-Since` is ignored: it would answer `304` for a changed version.
+
+use rocket::http::Status;
+use rocket::request::{self, FromRequest, Request};
+use rocket::response::{self, Responder, Response};
+use rocket::Outcome;
+
+use util::{bytes_to_hex, format_http_date, gen_random_bytes};
+
+lazy_static! {
+    /// Tells apart the tags of different runs of the server, whose versions may repeat.
+    static ref RUN: String = bytes_to_hex(&gen_random_bytes(4));
+}
+
+/// The `If-None-Match` header of a request, if any.
+pub struct Preconditions {
+    if_none_match: Option<String>,
+}
+
+impl<'a, 'r> FromRequest<'a, 'r> for Preconditions {
+    type Error = ();
+
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<Preconditions, ()> {
+        Outcome::Success(Preconditions {
+            if_none_match: request.headers().get_one("If-None-Match").map(str::to_owned),
+        })
+    }
+}
+
+impl Preconditions {
+    /// Whether the client has the representation tagged `etag` already.
+    pub fn hold(&self, etag: &str) -> bool {
+        self.if_none_match.as_ref().map_or(false, |tags| {
+            tags.split(',').any(|tag| tag.trim() == etag || tag.trim() == "*")
+        })
+    }
+}
+
+/// The tag of `version`, e.g. `"1a2b3c4d-42"`.
+pub fn etag(version: &str) -> String {
+    format!("\"{}-{}\"", *RUN, version)
+}
+
+/// A response tagged with its version, or `304 Not Modified` if the client has it already.
+pub struct Conditional<R> {
+    etag: String,
+    last_modified: Option<u64>,
+    body: Option<R>,
+}
+
+impl<R> Conditional<R> {
+    /// The response `body` computes, unless `preconditions` hold for `version`.
+    pub fn new<F>(preconditions: &Preconditions, version: &str, last_modified: Option<u64>,
+                  body: F) -> Conditional<R>
+        where F: FnOnce() -> R
+    {
+        let etag = etag(version);
+        let body = if preconditions.hold(&etag) { None } else { Some(body()) };
+        Conditional {
+            etag: etag,
+            last_modified: last_modified,
+            body: body,
+        }
+    }
+}
+
+impl<'r, R: Responder<'r>> Responder<'r> for Conditional<R> {
+    fn respond_to(self, request: &Request) -> response::Result<'r> {
+        let mut response = match self.body {
+            Some(body) => body.respond_to(request)?,
+            None => Response::build().status(Status::NotModified).finalize(),
+        };
+        response.set_raw_header("ETag", self.etag);
+        response.set_raw_header("Cache-Control", "no-cache");
+        if let Some(time) = self.last_modified {
+            response.set_raw_header("Last-Modified", format_http_date(time));
+        }
+        Ok(response)
+    }
+}
@@ -0,0 +1,79 @@
+//! Attributes of the cookies the server sets
+//!
+//! Rocket only sets `Path=/` and `SameSite=Strict` on private cookies. Behind a TLS-terminating
+//! proxy, the server cannot tell that it is served over HTTPS and mark its cookies `Secure`, and
+//! a deployment spanning subdomains needs a `Domain`. The `cookie_same_site` (`"strict"`, the
+//! default, or `"lax"`), `cookie_secure` (`false` by default) and `cookie_domain` config extras
+//! set those attributes on every cookie the server sets, and on the removal cookies too, whose
+//! `Path` and `Domain` must match for browsers to drop the originals.
+
+use std::sync::RwLock;
+
+use cookie::SameSite;
+use rocket::http::Cookie;
+use rocket::Config;
+
+use poison::Recover;
+
+lazy_static! {
+    static ref POLICY: RwLock<CookiePolicy> = RwLock::new(CookiePolicy::default());
+}
+
+struct CookiePolicy {
+    same_site: SameSite,
+    secure: bool,
+    domain: Option<String>,
+}
+
+impl Default for CookiePolicy {
+    fn default() -> CookiePolicy {
+        CookiePolicy {
+            same_site: SameSite::Strict,
+            secure: false,
+            domain: None,
+        }
+    }
+}
+
+/// Reads the cookie attributes from the config extras.
+pub fn configure(config: &Config) -> Result<(), String> {
+    let same_site = match config.get_str("cookie_same_site") {
+        Ok(value) if value.eq_ignore_ascii_case("strict") => SameSite::Strict,
+        Ok(value) if value.eq_ignore_ascii_case("lax") => SameSite::Lax,
+        Ok(value) => {
+            return Err(format!("`cookie_same_site` must be \"strict\" or \"lax\", got {:?}", value))
+        }
+        Err(_) => SameSite::Strict,
+    };
+    let secure = config.get_bool("cookie_secure").unwrap_or(false);
+    let domain = match config.get_str("cookie_domain") {
+        Ok("") => return Err("`cookie_domain` must not be empty".to_owned()),
+        Ok(domain) => Some(domain.to_owned()),
+        Err(_) => None,
+    };
+    *POLICY.write().recover() = CookiePolicy {
+        same_site: same_site,
+        secure: secure,
+        domain: domain,
+    };
+    Ok(())
+}
+
+/// The cookie `name` holding `value`, with the configured attributes.
+pub fn build(name: &'static str, value: String) -> Cookie<'static> {
+    let policy = POLICY.read().recover();
+    let mut cookie = Cookie::build(name, value)
+        .path("/")
+        .same_site(policy.same_site)
+        .secure(policy.secure)
+        .finish();
+    if let Some(ref domain) = policy.domain {
+        cookie.set_domain(domain.clone());
+    }
+    cookie
+}
+
+/// What to pass to `Cookies::remove` or `remove_private` to drop the cookie `name`.
+pub fn named(name: &'static str) -> Cookie<'static> {
+    build(name, String::new())
+}
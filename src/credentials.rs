@@ -0,0 +1,101 @@
+//! The login cookie
+//!
+//! Who a client is logged in as travels in a single private, i.e. encrypted and authenticated,
+//! cookie: the session id, the user name and when the cookie was issued, as JSON. Since the parts
+//! are sealed together, none can be swapped for another client's, and the `User` guard only has
+//! to check them against the session. Rotating the cookie is reissuing it.
+//!
+//! A cookie issued more than `MAX_AGE_SECS` ago is not accepted, a replayed one included, and
+//! neither is one issued more than `CLOCK_SKEW_SECS` in the future, as by a server whose clock is
+//! ahead of this one's. So that players are not logged out while they play, the `User` guard
+//! reissues the cookie of a session it accepts once it is `REISSUE_AFTER_SECS` old.
+//!
+//! Earlier releases kept the id and the name in a `user_id` and a `user_name` cookie. A client
+//! still sending that pair has it replaced with the login cookie on its next request, until
+//! `LEGACY_UNTIL`: the pair says nothing of its age, so a copy of it would otherwise get a new
+//! cookie however often it is replayed.
+
+use rocket::http::Cookies;
+use serde_json;
+
+use cookie_policy;
+use ids::UserId;
+use util::unix_time;
+
+const COOKIE_NAME: &str = "login";
+const LEGACY_ID: &str = "user_id";
+const LEGACY_NAME: &str = "user_name";
+
+/// A week, as long as browsers keep a private cookie Rocket sets.
+pub const MAX_AGE_SECS: u64 = 7 * 24 * 3600;
+pub const CLOCK_SKEW_SECS: u64 = 5 * 60;
+/// Half of `MAX_AGE_SECS`.
+pub const REISSUE_AFTER_SECS: u64 = MAX_AGE_SECS / 2;
+/// 2026-11-15, Unix time: a month after the login cookie replaced the legacy pair, which browsers
+/// keep for a week.
+pub const LEGACY_UNTIL: u64 = 1_794_700_800;
+
+/// Field names are kept short, since the cookie travels with every request.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Credentials {
+    /// The key of the session in `SESSIONS`.
+    #[serde(rename = "i")]
+    pub user_id: UserId,
+    #[serde(rename = "n")]
+    pub user_name: String,
+    /// Unix time.
+    #[serde(rename = "t")]
+    pub issued_at: u64,
+}
+
+impl Credentials {
+    /// Whether the cookie was issued at most `MAX_AGE_SECS` before `now`, and at most
+    /// `CLOCK_SKEW_SECS` after it.
+    pub fn is_current_at(&self, now: u64) -> bool {
+        now.saturating_sub(self.issued_at) <= MAX_AGE_SECS
+            && self.issued_at <= now.saturating_add(CLOCK_SKEW_SECS)
+    }
+
+    /// Whether the cookie is `REISSUE_AFTER_SECS` old at `now`.
+    pub fn is_due_at(&self, now: u64) -> bool {
+        now.saturating_sub(self.issued_at) >= REISSUE_AFTER_SECS
+    }
+}
+
+/// Issues the login cookie of the session `user_id` of `user_name`.
+pub fn save(cookies: &mut Cookies, user_id: &UserId, user_name: &str) -> Credentials {
+    let credentials = Credentials {
+        user_id: user_id.clone(),
+        user_name: user_name.to_owned(),
+        issued_at: unix_time(),
+    };
+    let value = serde_json::to_string(&credentials).unwrap();
+    cookies.add_private(cookie_policy::build(COOKIE_NAME, value));
+    credentials
+}
+
+/// The credentials of the login cookie unless it is too old or too new, or of the legacy pair,
+/// which is migrated then, until `LEGACY_UNTIL`.
+pub fn load(cookies: &mut Cookies) -> Option<Credentials> {
+    if let Some(cookie) = cookies.get_private(COOKIE_NAME) {
+        return serde_json::from_str(cookie.value()).ok()
+            .filter(|credentials: &Credentials| credentials.is_current_at(unix_time()));
+    }
+    if unix_time() >= LEGACY_UNTIL {
+        return None;
+    }
+    let user_id = UserId::from_string(cookies.get_private(LEGACY_ID)?.value().to_owned());
+    let user_name = cookies.get_private(LEGACY_NAME)?.value().to_owned();
+    remove_legacy(cookies);
+    Some(save(cookies, &user_id, &user_name))
+}
+
+pub fn remove(cookies: &mut Cookies) {
+    cookies.remove_private(cookie_policy::named(COOKIE_NAME));
+    remove_legacy(cookies);
+}
+
+fn remove_legacy(cookies: &mut Cookies) {
+    cookies.remove_private(cookie_policy::named(LEGACY_ID));
+    cookies.remove_private(cookie_policy::named(LEGACY_NAME));
+}
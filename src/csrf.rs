@@ -0,0 +1,28 @@
+//! Tokens against cross-site request forgery
+//!
+//! The session cookies are `SameSite=Strict` unless `cookie_same_site` makes them `Lax`, under
+//! which other sites still cannot post forms with them. Forms whose effect reaches beyond the
+//! session posting them, e.g. logging out of every device, carry a token as well: the HMAC-SHA256
+//! of the session's user id under a key drawn at launch, which never leaves the server, so another
+//! site has no way to make one, even knowing the id. The token lives as long as the session and
+//! the process, so pages render it as they are; a form left open across a restart is refused once.
+
+use commitment::{self, hmac_sha256};
+use ids::UserId;
+use util::{bytes_to_hex, gen_random_bytes};
+
+const KEY_SIZE: usize = 32;
+
+lazy_static! {
+    static ref KEY: Vec<u8> = gen_random_bytes(KEY_SIZE);
+}
+
+/// The token of the session `user_id`, posted as the `csrf` field of a form.
+pub fn token(user_id: &UserId) -> String {
+    bytes_to_hex(&hmac_sha256(&KEY, format!("csrf:{}", user_id.as_str()).as_bytes()))
+}
+
+/// Whether `token` is the one of the session `user_id`.
+pub fn verify(user_id: &UserId, token: &str) -> bool {
+    commitment::same_digest(token, &self::token(user_id))
+}
@@ -0,0 +1,125 @@
+//! Play by email
+//!
+//! An email gateway turns each incoming email into a JSON payload and posts it to
+//! `/inbound/email`, signed with the `email_webhook_secret` config extra: the `X-Signature` header
+//! holds `sha256=` followed by the hex HMAC-SHA256 of the body. The move is resolved against the
+//! pending round of the session owning `key`, and the result is mailed back through the outbox.
+
+use std::io::Read;
+
+use rocket::data::{self, FromData};
+use rocket::http::Status;
+use rocket::request::State;
+use rocket::response::{status, Failure};
+use rocket::{Config, Data, Outcome, Request};
+use serde_json;
+
+use api;
+use events;
+use ids::SessionToken;
+use moderation::Bans;
+use notification::{Notification, Outbox};
+use params::HandParam;
+use util::constant_time_eq;
+use webhook::{self, SIGNATURE_HEADER, SIGNATURE_PREFIX};
+use SESSIONS;
+
+const PAYLOAD_LIMIT: u64 = 8 * 1024;
+
+/// The `email_webhook_secret` config extra. The webhook is disabled without it.
+pub struct WebhookSecret(Option<String>);
+
+impl WebhookSecret {
+    pub fn from_config(config: &Config) -> WebhookSecret {
+        WebhookSecret(config.get_str("email_webhook_secret").ok().map(|secret| secret.to_owned()))
+    }
+}
+
+#[derive(Deserialize)]
+pub struct EmailedMove {
+    /// An API key of the player's session, see `POST /api/v1/keys`.
+    key: SessionToken,
+    hand: HandParam,
+    /// Where to send the result.
+    reply_to: String,
+}
+
+/// A payload whose signature has been checked.
+pub struct Signed(EmailedMove);
+
+impl FromData for Signed {
+    type Error = ();
+
+    fn from_data(request: &Request, data: Data) -> data::Outcome<Signed, ()> {
+        let secret = match request.guard::<State<WebhookSecret>>() {
+            Outcome::Success(ref secret) if secret.0.is_some() => {
+                secret.0.as_ref().unwrap().clone()
+            }
+            _ => return Outcome::Failure((Status::NotFound, ())),
+        };
+        let signature = match request.headers().get_one(SIGNATURE_HEADER) {
+            Some(signature) if signature.starts_with(SIGNATURE_PREFIX) => {
+                let hex = signature[SIGNATURE_PREFIX.len()..].to_lowercase();
+                format!("{}{}", SIGNATURE_PREFIX, hex)
+            }
+            _ => return Outcome::Failure((Status::Unauthorized, ())),
+        };
+
+        let mut body = Vec::new();
+        if data.open().take(PAYLOAD_LIMIT).read_to_end(&mut body).is_err() {
+            return Outcome::Failure((Status::BadRequest, ()));
+        }
+        let expected = webhook::signature(&secret, &body);
+        if !constant_time_eq(&signature, &expected) {
+            return Outcome::Failure((Status::Unauthorized, ()));
+        }
+
+        match serde_json::from_slice(&body) {
+            Ok(emailed) => Outcome::Success(Signed(emailed)),
+            Err(_) => Outcome::Failure((Status::BadRequest, ())),
+        }
+    }
+}
+
+// ===== Routers =====
+
+#[post("/email", data = "<emailed>")]
+pub fn inbound(emailed: Signed, outbox: State<Outbox>, bans: State<Bans>)
+    -> Result<status::Accepted<()>, Failure>
+{
+    let emailed = emailed.0;
+    let user = api::key_user(&emailed.key).ok_or(Failure(Status::Forbidden))?;
+    if bans.find(Some(&user.name), None).is_some() {
+        return Err(Failure(Status::Forbidden));
+    }
+
+    let _events = events::batch().defer();
+    let mut sessions = SESSIONS.write(&user.id);
+    let session = sessions.get_mut(&user.id).ok_or(Failure(Status::Forbidden))?;
+    let (subject, mut body) = match session.play(emailed.hand.0) {
+        Some((round, verdict)) => (
+            format!("Rock-Paper-Scissors: {}", verdict.describe()),
+            format!("You played {}, the computer played {}.\n\
+                     Verify it: SHA-256(\"{}{}\") = {}\n\n",
+                    emailed.hand.0.as_ref(), round.computer.as_ref(),
+                    round.random_bytes, round.computer.as_ref(), round.digest),
+        ),
+        None => {
+            session.start_round();
+            ("Rock-Paper-Scissors: no pending round".to_owned(),
+             "There was no round to play against, so your move was not counted.\n\n".to_owned())
+        }
+    };
+    let score = session.score;
+    body.push_str(&format!("Scoreboard: wins = {}, ties = {}, losses = {}.\n",
+                           score.wins, score.ties, score.losses));
+    body.push_str(&format!("Next round, the computer has committed to {}.\n",
+                           session.last_round.as_ref().unwrap().digest));
+
+    outbox.send(Notification {
+        to: emailed.reply_to,
+        subject: subject,
+        body: body,
+    });
+    Ok(status::Accepted(None))
+}
@@ -0,0 +1,56 @@
+//! The minimal server, built without the `templates` feature
+//!
+//! Besides the JSON API, it only serves a single page playing through the API. The page is
+//! compiled into the binary, so that the server needs no files at all.
+
+use std::net::SocketAddr;
+
+use rocket::http::{Cookies, Status};
+use rocket::request::{Form, State};
+use rocket::response::content::{Html, JavaScript};
+use rocket::response::{Failure, Redirect};
+use rocket::Rocket;
+
+use alerts::{self, Device};
+use entropy::Entropy;
+use moderation::Bans;
+use {open_session, Login, LoginRefusal, SessionLimits};
+
+const PAGE: &str = include_str!("../static/embedded.html");
+const SCRIPT: &str = include_str!("../static/embedded.js");
+
+// ===== Routers =====
+
+#[get("/")]
+fn page() -> Html<&'static str> {
+    Html(PAGE)
+}
+
+#[get("/embedded.js")]
+fn script() -> JavaScript<&'static str> {
+    JavaScript(SCRIPT)
+}
+
+#[post("/login", data = "<login>")]
+fn login(mut cookies: Cookies, login: Form<Login>, limits: State<SessionLimits>,
+         bans: State<Bans>, entropy: State<Entropy>, remote: Option<SocketAddr>, device: Device)
+    -> Result<Redirect, Failure>
+{
+    let user_name = &login.get().user_name;
+    match open_session(&mut cookies, user_name, false, &limits, &bans, &entropy, remote) {
+        Ok(_) => {
+            alerts::logged_in(user_name, &device);
+            Ok(Redirect::to("/"))
+        }
+        Err(LoginRefusal::Banned(_)) | Err(LoginRefusal::PasskeyRequired) => {
+            Err(Failure(Status::Forbidden))
+        }
+        Err(LoginRefusal::Full(_)) | Err(LoginRefusal::Maintenance) => {
+            Err(Failure(Status::ServiceUnavailable))
+        }
+    }
+}
+
+pub fn mount(rocket: Rocket) -> Rocket {
+    rocket.mount("/", routes![page, script, login])
+}
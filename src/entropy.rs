@@ -0,0 +1,124 @@
+//! The single source of randomness: nonces, seeds, ids, keys and the computer's hands
+//!
+//! Everything draws from the `Entropy` installed for the process, by default a CSPRNG reseeded
+//! from the operating system every `entropy_reseed_interval` seconds (an hour unless configured).
+//! Tests can install a seeded, deterministic one instead. Routes may also take it as managed state.
+
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+use rand::rngs::StdRng;
+use rand::{Error, FromEntropy, RngCore, SeedableRng};
+use rocket::Config;
+
+use poison::Recover;
+
+pub const DEFAULT_RESEED_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+lazy_static! {
+    static ref ENTROPY: RwLock<Entropy> =
+        RwLock::new(Entropy::new(Csprng::new(DEFAULT_RESEED_INTERVAL)));
+}
+
+/// Where `Entropy` gets its bytes from. Shared by every worker, hence `&self`.
+pub trait Source: Send + Sync {
+    fn fill(&self, dest: &mut [u8]);
+}
+
+/// `StdRng`, currently HC-128, seeded from the operating system and reseeded once `interval` has
+/// passed since the last time.
+pub struct Csprng {
+    interval: Duration,
+    state: Mutex<(StdRng, Instant)>,
+}
+
+impl Csprng {
+    pub fn new(interval: Duration) -> Csprng {
+        Csprng {
+            interval: interval,
+            state: Mutex::new((StdRng::from_entropy(), Instant::now())),
+        }
+    }
+}
+
+impl Source for Csprng {
+    fn fill(&self, dest: &mut [u8]) {
+        let mut state = self.state.lock().recover();
+        if state.1.elapsed() >= self.interval {
+            *state = (StdRng::from_entropy(), Instant::now());
+        }
+        state.0.fill_bytes(dest);
+    }
+}
+
+/// Replays the same bytes for the same seed. For tests only: everything it produces is
+/// predictable.
+pub struct Seeded(Mutex<StdRng>);
+
+impl Seeded {
+    pub fn new(seed: [u8; 32]) -> Seeded {
+        Seeded(Mutex::new(StdRng::from_seed(seed)))
+    }
+}
+
+impl Source for Seeded {
+    fn fill(&self, dest: &mut [u8]) {
+        self.0.lock().recover().fill_bytes(dest);
+    }
+}
+
+/// A handle to a `Source`; clones share it. Also an `RngCore`, so that `rand::Rng` methods work
+/// on it.
+#[derive(Clone)]
+pub struct Entropy(Arc<Source>);
+
+impl Entropy {
+    pub fn new<S: Source + 'static>(source: S) -> Entropy {
+        Entropy(Arc::new(source))
+    }
+
+    /// The CSPRNG configured by the `entropy_reseed_interval` config extra, in seconds, or `None`
+    /// if it isn't set.
+    pub fn from_config(config: &Config) -> Result<Option<Entropy>, String> {
+        match config.get_int("entropy_reseed_interval") {
+            Ok(n) if n > 0 => Ok(Some(Entropy::new(Csprng::new(Duration::from_secs(n as u64))))),
+            Ok(n) => Err(format!("`entropy_reseed_interval` must be positive, got {}", n)),
+            Err(_) => Ok(None),
+        }
+    }
+
+    pub fn bytes(&self, size: usize) -> Vec<u8> {
+        let mut bytes = vec![0; size];
+        self.0.fill(&mut bytes);
+        bytes
+    }
+}
+
+impl RngCore for Entropy {
+    fn next_u32(&mut self) -> u32 {
+        self.bytes(4).iter().fold(0, |n, &b| n << 8 | u32::from(b))
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.bytes(8).iter().fold(0, |n, &b| n << 8 | u64::from(b))
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.0.fill(dest);
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.0.fill(dest);
+        Ok(())
+    }
+}
+
+/// Makes `entropy` the process's source of randomness from now on.
+pub fn install(entropy: Entropy) {
+    *ENTROPY.write().recover() = entropy;
+}
+
+/// The installed source of randomness.
+pub fn get() -> Entropy {
+    ENTROPY.read().recover().clone()
+}
@@ -0,0 +1,167 @@
+//! Escrow of round seeds for dispute resolution
+//!
+//! If the `escrow_log` and `escrow_key` config extras are set, the nonce and hand(s) of every
+//! committed round are sealed with ChaCha20-Poly1305 and appended to `escrow_log`, one JSON line
+//! per round keyed by the round's commitment. The key is unrelated to Rocket's `secret_key`, so
+//! only whoever holds it can open the log. An operator settles a dispute with
+//! `GET /admin/escrow/<digest>`, which refuses rounds still pending so that no hand is revealed
+//! before it is played.
+//!
+//! Sealing and appending happen as the round is committed, on the request's own thread, so that
+//! no round is shown to a player before its deposit is in the log: a deposit waiting on a queue
+//! would be lost to a crash, and the round with it when disputed.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::sync::{Mutex, RwLock};
+
+use ring::aead::{self, OpeningKey, SealingKey, CHACHA20_POLY1305};
+use rocket::Config;
+use serde_json;
+
+use commitment;
+use events::{Event, Subscriber};
+use poison::Recover;
+use util::{bytes_to_hex, gen_random_bytes, hex_to_bytes, unix_time};
+
+const KEY_SIZE: usize = 32;
+const NONCE_SIZE: usize = 12;
+
+lazy_static! {
+    static ref ESCROW: RwLock<Option<Escrow>> = RwLock::new(None);
+}
+
+/// What is sealed for each round.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Deposit {
+    pub user_name: String,
+    /// The hex nonce of the commitment.
+    pub nonce: String,
+    /// The committed hand, or hands joined with commas.
+    pub committed: String,
+    /// Unix time of the commitment.
+    pub time: u64,
+}
+
+/// A line of the log.
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    round: String,
+    /// Hex of the AEAD nonce followed by the ciphertext and tag.
+    sealed: String,
+}
+
+struct Escrow {
+    path: PathBuf,
+    log: Mutex<File>,
+    sealing_key: SealingKey,
+    opening_key: OpeningKey,
+}
+
+/// Enables the escrow if it is configured. Both extras must be set, or neither.
+pub fn configure(config: &Config) -> Result<(), String> {
+    let (path, key_hex) = match (config.get_str("escrow_log"), config.get_str("escrow_key")) {
+        (Ok(path), Ok(key_hex)) => (PathBuf::from(path), key_hex),
+        (Err(_), Err(_)) => return Ok(()),
+        _ => return Err("`escrow_log` and `escrow_key` must be set together".to_owned()),
+    };
+    let key = match hex_to_bytes(key_hex) {
+        Some(ref key) if key.len() == KEY_SIZE => key.clone(),
+        _ => return Err(format!("`escrow_key` must be {} hex digits", KEY_SIZE * 2)),
+    };
+    let log = OpenOptions::new().create(true).append(true).open(&path)
+        .map_err(|e| format!("unable to open {}: {}", path.display(), e))?;
+
+    *ESCROW.write().recover() = Some(Escrow {
+        path: path,
+        log: Mutex::new(log),
+        sealing_key: SealingKey::new(&CHACHA20_POLY1305, &key).unwrap(),
+        opening_key: OpeningKey::new(&CHACHA20_POLY1305, &key).unwrap(),
+    });
+    Ok(())
+}
+
+/// Seals the deposit of the round committed to as `digest` into the log, if the escrow is enabled.
+fn seal(digest: &str, deposit: &Deposit) {
+    let guard = ESCROW.read().recover();
+    let escrow = match *guard {
+        Some(ref escrow) => escrow,
+        None => return,
+    };
+
+    let nonce = gen_random_bytes(NONCE_SIZE);
+    let mut in_out = serde_json::to_vec(deposit).unwrap();
+    let tag_len = CHACHA20_POLY1305.tag_len();
+    in_out.extend(vec![0; tag_len]);
+    // The round id is authenticated along, so that entries cannot be swapped around.
+    let len = aead::seal_in_place(&escrow.sealing_key, &nonce, digest.as_bytes(), &mut in_out,
+                                  tag_len).unwrap();
+    let mut sealed = nonce;
+    sealed.extend_from_slice(&in_out[..len]);
+
+    let entry = Entry {
+        round: digest.to_owned(),
+        sealed: bytes_to_hex(&sealed),
+    };
+    let line = serde_json::to_string(&entry).unwrap();
+    let mut log = escrow.log.lock().recover();
+    if let Err(e) = writeln!(log, "{}", line).and_then(|()| log.sync_data()) {
+        eprintln!("Unable to write escrow log: {}", e);
+    }
+}
+
+/// Deposits every committed round.
+pub struct Depositor;
+
+impl Subscriber for Depositor {
+    fn notify(&self, event: &Event) {
+        if let Event::RoundCommitted { ref user_name, ref digest, ref nonce, ref committed } = *event {
+            if ESCROW.read().recover().is_none() {
+                return;
+            }
+            let deposit = Deposit {
+                user_name: user_name.clone(),
+                nonce: nonce.clone(),
+                committed: committed.clone(),
+                time: unix_time(),
+            };
+            seal(digest, &deposit);
+        }
+    }
+}
+
+/// Opens the deposit of the round committed to as `digest`. Returns `Ok(None)` if the escrow is
+/// disabled or has no such round.
+pub fn open(digest: &str) -> io::Result<Option<Deposit>> {
+    let guard = ESCROW.read().recover();
+    let escrow = match *guard {
+        Some(ref escrow) => escrow,
+        None => return Ok(None),
+    };
+
+    let reader = BufReader::new(File::open(&escrow.path)?);
+    for line in reader.lines() {
+        let entry: Entry = match serde_json::from_str(&line?) {
+            Ok(entry) => entry,
+            Err(_) => continue,
+        };
+        if entry.round != digest {
+            continue;
+        }
+        let deposit = hex_to_bytes(&entry.sealed)
+            .filter(|sealed| sealed.len() > NONCE_SIZE)
+            .and_then(|mut sealed| {
+                let (nonce, ciphertext) = sealed.split_at_mut(NONCE_SIZE);
+                let plaintext = aead::open_in_place(&escrow.opening_key, nonce, digest.as_bytes(),
+                                                    0, ciphertext).ok()?;
+                serde_json::from_slice::<Deposit>(plaintext).ok()
+            })
+            // Only a deposit matching its commitment settles anything.
+            .filter(|deposit| commitment::digest_str(&deposit.nonce, &deposit.committed) == digest);
+        if deposit.is_some() {
+            return Ok(deposit);
+        }
+    }
+    Ok(None)
+}
@@ -0,0 +1,201 @@
+//! Internal event bus
+//!
+//! Game code publishes what happened and nothing more; everything else (analytics, the seed
+//! escrow, live streams, ...) subscribes to the events it cares about and reacts independently.
+//! Registered subscribers are notified synchronously, in registration order. Streams, which have
+//! to wait for events, get them through a channel instead and are dropped once it is closed.
+//!
+//! While a `Batch` is open on a thread, e.g. for the request the thread is serving, what it
+//! publishes is held back from subscribers and streams until the batch is released, and never
+//! reaches them if it is dropped instead. What the thread reads back meanwhile, e.g. the player's
+//! profile, may take the events still held back into account, see `held`. Subscribers may write
+//! to files, e.g. the escrow, so the requests that change a session release their batch once the
+//! session's lock is released, see `Batch::defer`, rather than holding up the other sessions.
+
+use std::cell::RefCell;
+use std::mem;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Mutex, RwLock};
+use std::time::Duration;
+
+use game::{Hand, RoundKind, Strategy, Verdict};
+use poison::Recover;
+
+lazy_static! {
+    static ref SUBSCRIBERS: RwLock<Vec<Box<Subscriber>>> = RwLock::new(Vec::new());
+    static ref CHANNELS: Mutex<Vec<Sender<Event>>> = Mutex::new(Vec::new());
+}
+
+thread_local! {
+    /// The events held back by the innermost open `Batch` of the thread.
+    static HELD: RefCell<Option<Vec<Event>>> = RefCell::new(None);
+}
+
+#[derive(Clone, Debug)]
+pub enum Event {
+    SessionStarted {
+        user_name: String,
+    },
+    SessionEnded {
+        user_name: String,
+        length: Duration,
+        analytics_opt_out: bool,
+    },
+    /// The computer committed to the hand(s) of a new round.
+    RoundCommitted {
+        user_name: String,
+        digest: String,
+        nonce: String,
+        /// The committed hand, or hands joined with commas.
+        committed: String,
+    },
+    RoundPlayed {
+        user_name: String,
+        kind: RoundKind,
+        human: Vec<Hand>,
+        computer: Vec<Hand>,
+        verdict: Verdict,
+        analytics_opt_out: bool,
+        /// Whether nothing gave the computer's hands away: they were drawn uniformly, and neither
+        /// a peek nor a predictable opponent revealed them.
+        unpredictable: bool,
+        /// The hand named by the hidden decoy hint on the page the round was played from, if any.
+        decoy: Option<Hand>,
+        /// What generated the computer's hand of a classic round.
+        strategy: Option<Strategy>,
+        /// Whether the round counts toward the player's stats, rating and leaderboard place, see
+        /// `Strategy::is_ranked`. Subscribers keeping those skip the rounds that do not.
+        ranked: bool,
+    },
+    /// A lost classic round was taken back with an arcade redo.
+    RoundVoided {
+        user_name: String,
+    },
+    /// A seeded match ended and its seed was revealed.
+    MatchFinished {
+        user_name: String,
+        commitment: String,
+        rounds: u64,
+    },
+    /// A co-op team got its second member.
+    TeamFormed {
+        name: String,
+    },
+    /// A member left their co-op team.
+    TeamDisbanded {
+        name: String,
+    },
+    /// A player deleted their account; whatever is kept under their name goes too. The deletion
+    /// is logged beforehand, see `account`.
+    AccountDeleted {
+        user_name: String,
+    },
+}
+
+pub trait Subscriber: Send + Sync {
+    fn notify(&self, event: &Event);
+}
+
+/// Adds a subscriber for good.
+pub fn register(subscriber: Box<Subscriber>) {
+    SUBSCRIBERS.write().recover().push(subscriber);
+}
+
+pub fn publish(event: Event) {
+    let unheld = HELD.with(|held| match *held.borrow_mut() {
+        Some(ref mut events) => {
+            events.push(event);
+            None
+        }
+        None => Some(event),
+    });
+    if let Some(event) = unheld {
+        deliver(event);
+    }
+}
+
+/// Notifies the subscribers and the streams of `event`.
+fn deliver(event: Event) {
+    for subscriber in SUBSCRIBERS.read().recover().iter() {
+        subscriber.notify(&event);
+    }
+    let mut channels = CHANNELS.lock().recover();
+    channels.retain(|channel| channel.send(event.clone()).is_ok());
+}
+
+/// The events the innermost batch open on this thread holds back, oldest first.
+pub fn held() -> Vec<Event> {
+    HELD.with(|held| held.borrow().clone().unwrap_or_default())
+}
+
+/// Events published on a thread since the batch was opened there, held back. Batches nest: an
+/// inner one releases its events into the outer one.
+pub struct Batch {
+    /// What the outer batch had held back when this one was opened, `None` once this one is closed.
+    outer: Option<Option<Vec<Event>>>,
+}
+
+/// Opens a batch on this thread.
+pub fn batch() -> Batch {
+    Batch {
+        outer: Some(HELD.with(|held| mem::replace(&mut *held.borrow_mut(), Some(Vec::new())))),
+    }
+}
+
+impl Batch {
+    /// Closes the batch, passing its events on.
+    pub fn release(mut self) {
+        let events = self.close();
+        let nested = HELD.with(|held| match *held.borrow_mut() {
+            Some(ref mut outer) => {
+                outer.extend(events.iter().cloned());
+                true
+            }
+            None => false,
+        });
+        if !nested {
+            events.into_iter().for_each(deliver);
+        }
+    }
+
+    /// Releases the batch once the returned `Deferred` is dropped rather than now, e.g. after a
+    /// lock taken since it was opened.
+    pub fn defer(self) -> Deferred {
+        Deferred(Some(self))
+    }
+
+    /// Makes the outer batch, if any, the open one again. Returns the events of this one.
+    fn close(&mut self) -> Vec<Event> {
+        let events = match self.outer.take() {
+            Some(outer) => HELD.with(|held| mem::replace(&mut *held.borrow_mut(), outer)),
+            None => None,
+        };
+        events.unwrap_or_default()
+    }
+}
+
+/// Dropped unreleased, a batch discards its events.
+impl Drop for Batch {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+/// A batch released, rather than discarded, once dropped. Declared before a lock is taken, it
+/// outlives the lock's guard, so that its events go out once the lock is released.
+pub struct Deferred(Option<Batch>);
+
+impl Drop for Deferred {
+    fn drop(&mut self) {
+        if let Some(batch) = self.0.take() {
+            batch.release();
+        }
+    }
+}
+
+/// Receives every event from now on, until the receiver is dropped.
+pub fn subscribe() -> Receiver<Event> {
+    let (sender, receiver) = channel();
+    CHANNELS.lock().recover().push(sender);
+    receiver
+}
@@ -0,0 +1,182 @@
+//! A/B experiments
+//!
+//! An experiment splits players into variants, e.g. of the opponent a new session starts against,
+//! to find out how that changes the way their rounds go. Experiments are defined in `EXPERIMENTS`
+//! and only run once named in the `experiments` config extra. When a player logs in, they are
+//! assigned a variant of every running experiment they are not in yet, at random, and the
+//! assignment is kept in their profile, so that it holds across sessions and restarts.
+//!
+//! Pages see the variants through `experiment_<name>` keys, left out for the control, see
+//! `Experiment::context_key`. Ranked classic rounds are tallied in the profile too, next to the
+//! assignment, except those of players who opted out of analytics, and summed up per variant on
+//! `/admin/experiments` and the analytics page. The tallies last as long as the profiles do.
+
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+use rand::Rng;
+use rocket::Config;
+
+use entropy;
+use game::Scoreboard;
+use poison::Recover;
+use profile;
+
+pub struct Experiment {
+    pub name: &'static str,
+    /// The control first.
+    pub variants: &'static [&'static str],
+    /// The key of the variant in the context of pages.
+    #[cfg_attr(not(feature = "templates"), allow(dead_code))]
+    pub context_key: &'static str,
+}
+
+impl Experiment {
+    #[cfg(feature = "templates")]
+    pub fn control(&self) -> &'static str {
+        self.variants[0]
+    }
+
+    fn variant(&self, name: &str) -> Option<&'static str> {
+        self.variants.iter().cloned().find(|&variant| variant == name)
+    }
+}
+
+pub const EXPERIMENTS: &[Experiment] = &[
+    // The opponent a new session starts against.
+    Experiment {
+        name: "default-opponent",
+        variants: &["random", "mirror"],
+        context_key: "experiment_default_opponent",
+    },
+    // A reminder that the computer commits to its hand before the player picks theirs.
+    Experiment {
+        name: "first-move-hint",
+        variants: &["control", "hint"],
+        context_key: "experiment_first_move_hint",
+    },
+];
+
+lazy_static! {
+    static ref RUNNING: RwLock<Vec<&'static Experiment>> = RwLock::new(Vec::new());
+}
+
+#[derive(Clone, Copy, Default)]
+struct Tally {
+    /// In the variant.
+    players: u64,
+    /// From the players' side.
+    classic: Scoreboard,
+}
+
+/// How one variant of a running experiment is doing.
+#[derive(Serialize)]
+pub struct VariantResults {
+    pub experiment: &'static str,
+    pub variant: &'static str,
+    pub players: u64,
+    pub rounds: usize,
+    #[serde(flatten)]
+    pub classic: Scoreboard,
+    /// Of the rounds not tied. `None` until one isn't.
+    pub win_rate: Option<f64>,
+}
+
+/// Starts the experiments named in the `experiments` config extra, e.g. `["first-move-hint"]`.
+/// None run by default.
+pub fn configure(config: &Config) -> Result<(), String> {
+    let values = match config.get_slice("experiments") {
+        Ok(values) => values,
+        Err(_) => return Ok(()),
+    };
+    let running = values.iter()
+        .map(|value| {
+            value.as_str()
+                .and_then(|name| EXPERIMENTS.iter().find(|experiment| experiment.name == name))
+                .ok_or_else(|| format!("unknown experiment {}", value))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    *RUNNING.write().recover() = running;
+    Ok(())
+}
+
+/// The variant `user_name` is in of every running experiment, drawing those they are not in yet.
+pub fn assign(user_name: &str) -> Vec<(&'static Experiment, &'static str)> {
+    let running = RUNNING.read().recover().clone();
+    let assigned = profile::with(user_name, |profile| profile.experiments.clone())
+        .unwrap_or_default();
+    let mut variants = Vec::new();
+    let mut drawn = Vec::new();
+    for experiment in running {
+        // A variant since dropped from the experiment is drawn anew.
+        let kept = assigned.get(experiment.name).and_then(|name| experiment.variant(name));
+        let variant = match kept {
+            Some(variant) => variant,
+            None => {
+                let variant = *entropy::get().choose(experiment.variants).unwrap();
+                drawn.push((experiment.name, variant));
+                variant
+            }
+        };
+        variants.push((experiment, variant));
+    }
+    if !drawn.is_empty() {
+        profile::join_experiments(user_name, &drawn);
+    }
+    variants
+}
+
+/// The variant `user_name` is in of every running experiment they were assigned to.
+#[cfg(feature = "templates")]
+pub fn assignments(user_name: &str) -> Vec<(&'static Experiment, &'static str)> {
+    let running = RUNNING.read().recover().clone();
+    profile::with(user_name, |profile| {
+        running.into_iter()
+            .filter_map(|experiment| Some((experiment, variant_of(profile, experiment)?)))
+            .collect()
+    }).unwrap_or_default()
+}
+
+fn variant_of(profile: &profile::UserProfile, experiment: &Experiment) -> Option<&'static str> {
+    profile.experiments.get(experiment.name).and_then(|name| experiment.variant(name))
+}
+
+/// Every variant of the running experiments, in the order they were configured, summed up from
+/// the profiles of the players in it.
+pub fn results() -> Vec<VariantResults> {
+    let running = RUNNING.read().recover().clone();
+    let tallies = profile::fold(HashMap::new(), |mut tallies, profile| {
+        for &experiment in &running {
+            let variant = match variant_of(profile, experiment) {
+                Some(variant) => variant,
+                None => continue,
+            };
+            let tally = tallies.entry((experiment.name, variant)).or_insert_with(Tally::default);
+            tally.players += 1;
+            if let Some(rounds) = profile.experiment_rounds.get(experiment.name) {
+                tally.classic.wins += rounds.wins;
+                tally.classic.ties += rounds.ties;
+                tally.classic.losses += rounds.losses;
+            }
+        }
+        tallies
+    });
+    running.iter()
+        .flat_map(|&experiment| {
+            experiment.variants.iter().map(move |&variant| (experiment, variant))
+        })
+        .map(|(experiment, variant)| {
+            let tally = tallies.get(&(experiment.name, variant)).cloned().unwrap_or_default();
+            let score = tally.classic;
+            let decided = score.wins + score.losses;
+            VariantResults {
+                experiment: experiment.name,
+                variant: variant,
+                players: tally.players,
+                rounds: decided + score.ties,
+                classic: score,
+                win_rate: if decided > 0 { Some(score.wins as f64 / decided as f64) } else { None },
+            }
+        })
+        .collect()
+}
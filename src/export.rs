@@ -0,0 +1,189 @@
+//! Leaderboard exports, `/leaderboard.csv` and `/leaderboard.json`
+//!
+//! Both list every live session and co-op team, not only the widget's top ten, and are computed
+//! from one consistent snapshot of the scores: a version counter moves whenever an event moves the
+//! leaderboard, and a snapshot only counts if the version did not move while it was taken. The
+//! `ETag` names that version, so that polling clients get `304 Not Modified` until the scores
+//! change, see `conditional`. The `leaderboard_columns` config extra picks the columns.
+
+use std::sync::atomic::{AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
+use std::sync::{Arc, Mutex};
+
+use rocket::http::ContentType;
+use rocket::request::State;
+use rocket::response::content::Content;
+use rocket::Config;
+use serde_json::{self, Map, Value};
+
+use conditional::{Conditional, Preconditions};
+use events::{Event, Subscriber};
+use poison::Recover;
+use widget::{self, Entry};
+
+static VERSION: AtomicUsize = ATOMIC_USIZE_INIT;
+
+lazy_static! {
+    static ref SNAPSHOT: Mutex<Option<Arc<Snapshot>>> = Mutex::new(None);
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Column {
+    Rank,
+    UserName,
+    Team,
+    Wins,
+    Ties,
+    Losses,
+}
+
+impl Column {
+    const ALL: [Column; 6] =
+        [Column::Rank, Column::UserName, Column::Team, Column::Wins, Column::Ties, Column::Losses];
+
+    fn name(&self) -> &'static str {
+        match *self {
+            Column::Rank => "rank",
+            Column::UserName => "user_name",
+            Column::Team => "team",
+            Column::Wins => "wins",
+            Column::Ties => "ties",
+            Column::Losses => "losses",
+        }
+    }
+
+    fn value(&self, entry: &Entry) -> Value {
+        match *self {
+            Column::Rank => json!(entry.rank),
+            Column::UserName => json!(entry.user_name),
+            Column::Team => json!(entry.team),
+            Column::Wins => json!(entry.wins),
+            Column::Ties => json!(entry.ties),
+            Column::Losses => json!(entry.losses),
+        }
+    }
+}
+
+/// The `leaderboard_columns` config extra, e.g. `["rank", "user_name", "wins"]`. All columns, in
+/// the order of `Column::ALL`, by default.
+pub struct ExportColumns(Vec<Column>);
+
+impl ExportColumns {
+    pub fn from_config(config: &Config) -> Result<ExportColumns, String> {
+        let values = match config.get_slice("leaderboard_columns") {
+            Ok(values) => values,
+            Err(_) => return Ok(ExportColumns(Column::ALL.to_vec())),
+        };
+        let columns = values.iter()
+            .map(|value| {
+                value.as_str()
+                    .and_then(|name| Column::ALL.iter().find(|column| column.name() == name))
+                    .cloned()
+                    .ok_or_else(|| format!("unknown leaderboard column {}", value))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if columns.is_empty() {
+            return Err("`leaderboard_columns` must not be empty".to_owned());
+        }
+        Ok(ExportColumns(columns))
+    }
+}
+
+struct Snapshot {
+    version: usize,
+    entries: Vec<Entry>,
+}
+
+/// The standings as of the latest version, reused until the version moves.
+fn snapshot() -> Arc<Snapshot> {
+    let mut cached = SNAPSHOT.lock().recover();
+    loop {
+        let version = VERSION.load(Ordering::SeqCst);
+        if let Some(ref snapshot) = *cached {
+            if snapshot.version == version {
+                return snapshot.clone();
+            }
+        }
+        let entries = widget::standings();
+        // Scores move under the sessions' or the teams' lock, which is only released once the
+        // event is published: an unchanged version means no write interleaved with the snapshot.
+        if VERSION.load(Ordering::SeqCst) == version {
+            *cached = Some(Arc::new(Snapshot {
+                version: version,
+                entries: entries,
+            }));
+        }
+    }
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains(|c| c == ',' || c == '"' || c == '\n' || c == '\r') {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+fn to_csv(entries: &[Entry], columns: &[Column]) -> String {
+    let header: Vec<_> = columns.iter().map(|column| column.name()).collect();
+    let mut csv = header.join(",");
+    csv.push_str("\r\n");
+    for entry in entries {
+        let row: Vec<_> = columns.iter()
+            .map(|column| match column.value(entry) {
+                Value::String(s) => csv_field(&s),
+                value => value.to_string(),
+            })
+            .collect();
+        csv.push_str(&row.join(","));
+        csv.push_str("\r\n");
+    }
+    csv
+}
+
+fn to_json(entries: &[Entry], columns: &[Column]) -> String {
+    let rows: Vec<_> = entries.iter()
+        .map(|entry| {
+            let row: Map<_, _> = columns.iter()
+                .map(|column| (column.name().to_owned(), column.value(entry)))
+                .collect();
+            Value::Object(row)
+        })
+        .collect();
+    serde_json::to_string(&rows).unwrap()
+}
+
+/// An export, or `304 Not Modified` if the client has it already.
+pub type Export = Conditional<Content<String>>;
+
+fn export<F>(preconditions: &Preconditions, content_type: ContentType, render: F) -> Export
+    where F: FnOnce(&[Entry]) -> String
+{
+    let snapshot = snapshot();
+    Conditional::new(preconditions, &snapshot.version.to_string(), None, || {
+        Content(content_type, render(&snapshot.entries))
+    })
+}
+
+/// Moves the version along with the leaderboard.
+pub struct Versioner;
+
+impl Subscriber for Versioner {
+    fn notify(&self, event: &Event) {
+        if widget::moves_leaderboard(event) {
+            VERSION.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+}
+
+// ===== Routers =====
+
+#[get("/leaderboard.csv")]
+pub fn leaderboard_csv(preconditions: Preconditions, columns: State<ExportColumns>) -> Export {
+    let content_type = ContentType::with_params("text", "csv", ("charset", "utf-8"));
+    export(&preconditions, content_type, |entries| to_csv(entries, &columns.0))
+}
+
+#[get("/leaderboard.json")]
+pub fn leaderboard_json(preconditions: Preconditions, columns: State<ExportColumns>) -> Export {
+    export(&preconditions, ContentType::JSON, |entries| to_json(entries, &columns.0))
+}
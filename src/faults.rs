@@ -0,0 +1,110 @@
+//! Fault injection for testing clients, in debug builds only
+//!
+//! If the `fault_percent` config extra is set, that share of requests meets one fault, picked at
+//! random, so that frontend retries and the transactional play path can be exercised:
+//!
+//! - latency: the request waits `fault_latency_ms` milliseconds (500 by default) first;
+//! - contention: the request holds the sessions' write lock as long, stalling every other player;
+//! - an error: the handler runs, then its response is replaced with a bare `500`, as if the
+//!   connection broke on the way back. A play may thus be recorded although the client saw it
+//!   fail, which retries sending the played-against `digest` have to cope with.
+//!
+//! Release builds leave this module out, whatever the configuration.
+
+use std::cell::Cell;
+use std::io::Cursor;
+use std::thread;
+use std::time::Duration;
+
+use rand::Rng;
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::Status;
+use rocket::{Config, Data, Request, Response, State};
+
+use entropy;
+use SESSIONS;
+
+const DEFAULT_LATENCY_MS: u64 = 500;
+
+thread_local! {
+    // Rocket hands a request and its response to the same worker thread.
+    static FAIL_RESPONSE: Cell<bool> = Cell::new(false);
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Fault {
+    Latency,
+    Contention,
+    Error,
+}
+
+/// The `fault_percent` and `fault_latency_ms` config extras.
+pub struct FaultConfig {
+    percent: u32,
+    latency: Duration,
+}
+
+impl FaultConfig {
+    /// `None` unless `fault_percent` is set.
+    pub fn from_config(config: &Config) -> Result<Option<FaultConfig>, String> {
+        let percent = match config.get_int("fault_percent") {
+            Ok(percent) if percent >= 0 && percent <= 100 => percent as u32,
+            Ok(percent) => return Err(format!("`fault_percent` must be 0 to 100, got {}", percent)),
+            Err(_) => return Ok(None),
+        };
+        let latency = match config.get_int("fault_latency_ms") {
+            Ok(ms) if ms >= 0 => ms as u64,
+            Ok(ms) => return Err(format!("`fault_latency_ms` must not be negative, got {}", ms)),
+            Err(_) => DEFAULT_LATENCY_MS,
+        };
+        Ok(Some(FaultConfig {
+            percent: percent,
+            latency: Duration::from_millis(latency),
+        }))
+    }
+
+    fn roll(&self) -> Option<Fault> {
+        let mut entropy = entropy::get();
+        if entropy.gen_range(0, 100) >= self.percent {
+            return None;
+        }
+        entropy.choose(&[Fault::Latency, Fault::Contention, Fault::Error]).cloned()
+    }
+}
+
+pub struct FaultInjection;
+
+impl Fairing for FaultInjection {
+    fn info(&self) -> Info {
+        Info {
+            name: "Fault injection",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    fn on_request(&self, request: &mut Request, _: &Data) {
+        FAIL_RESPONSE.with(|fail| fail.set(false));
+        let config = match request.guard::<State<FaultConfig>>().succeeded() {
+            Some(config) => config,
+            None => return,
+        };
+        match config.roll() {
+            Some(Fault::Latency) => thread::sleep(config.latency),
+            Some(Fault::Contention) => {
+                let _sessions = SESSIONS.write_all();
+                thread::sleep(config.latency);
+            }
+            Some(Fault::Error) => FAIL_RESPONSE.with(|fail| fail.set(true)),
+            None => {}
+        }
+    }
+
+    fn on_response(&self, _: &Request, response: &mut Response) {
+        if FAIL_RESPONSE.with(|fail| fail.replace(false)) {
+            *response = Response::build()
+                .status(Status::InternalServerError)
+                .sized_body(Cursor::new("Injected fault"))
+                .finalize();
+        }
+    }
+}
@@ -0,0 +1,139 @@
+//! Public feed of pre-published commitments
+//!
+//! Uniformly random classic rounds are not committed to on demand but drawn from a pool of rounds
+//! committed to ahead of time, and each commitment is appended to a public feed, numbered in
+//! sequence, before any session can draw its round. Anyone can watch `GET /commitments.json`, so a
+//! player can check that the commitment they played against was published before their move, and
+//! so could not have been picked in reaction to it. Rounds whose hand depends on the player's
+//! moves, against the mirroring opponents or in seeded matches, are committed to on demand and are
+//! not in the feed.
+//!
+//! The feed is append-only but only keeps the latest `FEED_RETENTION` entries in memory. Nor does
+//! it outlive the server, but its numbers still only go up: each run numbers on from its start
+//! time, shifted left by `SEQ_SHIFT` bits, so that a client paging with `after` across a restart
+//! neither skips nor repeats entries as long as fewer than 2^20 rounds a second were committed to.
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use rocket_contrib::Json;
+
+use game::{Hand, Round, Strategy};
+use poison::Recover;
+use util::{gen_random_bytes, unix_time};
+
+/// Rounds kept committed to ahead of time.
+const POOL_SIZE: usize = 32;
+const FEED_RETENTION: usize = 10_000;
+const DEFAULT_PAGE_LEN: usize = 100;
+const MAX_PAGE_LEN: usize = 1000;
+/// Room for the numbers of a run between the start times of two; numbers stay below 2^53, which
+/// JavaScript reads exactly, until the year 2242.
+const SEQ_SHIFT: u32 = 20;
+
+lazy_static! {
+    static ref POOL: Mutex<Pool> = Mutex::new(Pool::new());
+}
+
+#[derive(Clone, Serialize)]
+pub struct FeedEntry {
+    pub seq: u64,
+    pub digest: String,
+    /// Unix time at which the commitment was published.
+    pub published_at: u64,
+}
+
+#[derive(Serialize)]
+pub struct FeedPage {
+    pub entries: Vec<FeedEntry>,
+    /// The `after` of the next page, if there are more entries already.
+    pub next: Option<u64>,
+}
+
+struct Pool {
+    next_seq: u64,
+    rounds: VecDeque<Round>,
+    feed: VecDeque<FeedEntry>,
+}
+
+impl Pool {
+    fn new() -> Pool {
+        Pool {
+            next_seq: unix_time() << SEQ_SHIFT,
+            rounds: VecDeque::new(),
+            feed: VecDeque::new(),
+        }
+    }
+
+    fn fill(&mut self) {
+        while self.rounds.len() < POOL_SIZE {
+            let round = Round::new(Hand::random(), &gen_random_bytes(32), Strategy::Uniform);
+            if self.feed.len() == FEED_RETENTION {
+                self.feed.pop_front();
+            }
+            self.feed.push_back(FeedEntry {
+                seq: self.next_seq,
+                digest: round.digest.clone(),
+                published_at: unix_time(),
+            });
+            self.next_seq += 1;
+            self.rounds.push_back(round);
+        }
+    }
+}
+
+/// Commits to the first rounds of the pool, so that they are published well before anyone
+/// plays them.
+pub fn prepare() {
+    POOL.lock().recover().fill();
+}
+
+/// The oldest round of the pool, whose commitment has been in the feed the longest.
+pub fn take() -> Round {
+    let mut pool = POOL.lock().recover();
+    pool.fill();
+    let round = pool.rounds.pop_front().unwrap();
+    pool.fill();
+    round
+}
+
+#[derive(FromForm)]
+pub struct PageQuery {
+    after: Option<u64>,
+    limit: Option<usize>,
+}
+
+fn page(after: Option<u64>, limit: usize) -> FeedPage {
+    let mut pool = POOL.lock().recover();
+    pool.fill();
+    let limit = limit.min(MAX_PAGE_LEN);
+    let mut entries: Vec<_> = pool.feed.iter()
+        .filter(|entry| after.map_or(true, |after| entry.seq > after))
+        .take(limit + 1)
+        .cloned()
+        .collect();
+    let next = if entries.len() > limit {
+        entries.truncate(limit);
+        entries.last().map(|entry| entry.seq)
+    } else {
+        None
+    };
+    FeedPage {
+        entries: entries,
+        next: next,
+    }
+}
+
+// ===== Routers =====
+
+/// The oldest retained commitments.
+#[get("/commitments.json")]
+pub fn commitments() -> Json<FeedPage> {
+    Json(page(None, DEFAULT_PAGE_LEN))
+}
+
+/// Commitments numbered after `after`, at most `limit` of them (100 by default, 1000 at most).
+#[get("/commitments.json?<query>")]
+pub fn commitments_page(query: PageQuery) -> Json<FeedPage> {
+    Json(page(query.after, query.limit.unwrap_or(DEFAULT_PAGE_LEN)))
+}
@@ -0,0 +1,489 @@
+//! Flash messages, as codes of a message catalog
+//!
+//! Rather than text, the flash cookie carries a `Notice`: a level, the code of a message in the
+//! catalogs and the parameters to fill it with, as JSON. The pages look the text up only when
+//! they render it, in the language the player picked, see `locale`, or in English for visitors
+//! not logged in, so that every message shown to players is in one place. They add the level for
+//! styling as `flash_level`. Parameters, e.g. names and numbers, are filled in as they are.
+
+#[cfg(feature = "templates")]
+use rocket::request::FlashMessage;
+use rocket::response::Flash;
+use serde_json;
+
+#[cfg(feature = "templates")]
+use locale::Language;
+
+/// Message codes and their English texts; every `{}` takes the next parameter of the notice.
+#[cfg(feature = "templates")]
+const EN: &[(&str, &str)] = &[
+    ("logged-out", "Successfully logged out."),
+    ("account.deleted", "Your account and everything kept under it are deleted."),
+    ("account.unconfirmed", "Type your name to confirm the deletion."),
+    ("account.unproven", "Enter one of your recovery codes, or log in with a passkey first."),
+    ("account.not-deleted", "Your account could not be deleted; try again later."),
+    ("account.logged-out-everywhere", "Logged out of {} session(s) on all your devices."),
+    ("account.unverified", "Log in with a recovery code or a passkey to log out of all devices."),
+    ("csrf.invalid", "That form has expired; reload the page and try again."),
+    ("passkey.required",
+     "This account logs in with a passkey, or with a recovery code at /recover, not its name."),
+    ("passkey.removed", "Passkey removed; it no longer logs you in."),
+    ("passkey.unknown", "You have no such passkey."),
+    ("passkey.unverified", "Log in with a recovery code or a passkey to remove passkeys."),
+    ("alerts.new-device",
+     "Heads up: a login from a new device on your account. If it was not you, check your profile."),
+    ("alerts.recovery-code-used",
+     "Heads up: a recovery code was used on your account. If it was not you, check your profile."),
+    ("webhook.saved", "Webhook saved; a new secret signs its posts."),
+    ("webhook.removed", "Webhook removed; your rounds are no longer posted."),
+    ("webhook.invalid-url", "The webhook needs an http:// or https:// URL."),
+    ("webhook.unknown-host", "The host of the webhook URL could not be found."),
+    ("webhook.private-address", "The webhook URL must point to a public address."),
+    ("webhook.test-queued", "Test post queued."),
+    ("webhook.none", "Set a webhook first."),
+    ("webhook.too-many-tests", "That is enough test posts for this hour."),
+    ("webhook.unverified",
+     "Log in with a recovery code or a passkey to change your webhook, so nobody else gets your \
+      rounds."),
+    ("locale.saved", "Saved; times and numbers are written your way from now on."),
+    ("locale.invalid-language", "That language is not offered."),
+    ("locale.invalid-offset", "Give the offset from UTC as e.g. +02:00, from -12:00 to +14:00."),
+    ("handoff.login-first", "Log in on this device first, then scan the code again."),
+    ("recovery.bad-code", "That code does not work for that name, or has been used already."),
+    ("recovery.locked-out", "Too many failed attempts; try again in {} min."),
+    ("recovery.unverified", "Log in with a recovery code or a passkey to issue new codes."),
+    ("history.note-saved", "Note saved."),
+    ("history.no-such-round", "No such round in your history."),
+    ("history.bad-search", "Cannot search the history: {}."),
+    ("team.formed", "Team formed; pass the invite code on."),
+    ("team.joined", "Welcome to the team!"),
+    ("team.no-such-team", "There is no team with that invite code."),
+    ("team.already-in-team", "You are in a team already."),
+    ("team.full", "That team is full."),
+    ("team.not-in-team", "You are not in a team."),
+    ("team.incomplete", "Your teammate has not joined yet."),
+    ("team.not-your-turn", "It is your teammate's turn."),
+    ("pvp.invite-ready", "Invite link ready; pass it on."),
+    ("pvp.invite-emailed", "Invite sent to {}; it works for {} days."),
+    ("pvp.invalid-email", "That does not look like an email address."),
+    ("pvp.too-many-invites", "Wait for your emailed invites to be accepted or to expire first."),
+    ("pvp.too-many-emails", "Too many invites were emailed lately; try again in an hour."),
+    ("pvp.unfit-name", "Your name cannot go into an email; invite by link instead."),
+    ("pvp.unverified",
+     "Log in with a recovery code or a passkey to email invites, so nobody sends them as you."),
+    ("pvp.login-to-accept", "Log in to accept the invitation."),
+    ("pvp.match-on", "Your match against {} is on!"),
+    ("pvp.picked", "Hand picked; waiting for your opponent."),
+    ("pvp.both-picked", "Both hands are in."),
+    ("pvp.reacted", "The players will hear of it once the round is over."),
+    ("pvp.sent", "Sent."),
+    ("pvp.no-such-invite", "That invite link has expired or has been used already."),
+    ("pvp.own-invite", "That is your own invite link; pass it on to your opponent."),
+    ("pvp.already-in-match", "You are in a match already."),
+    ("pvp.host-busy", "Whoever invited you is in another match; try again later."),
+    ("pvp.not-in-match", "You are not in a match."),
+    ("pvp.already-picked", "You have picked your hand already; wait for your opponent."),
+    ("pvp.maintenance",
+     "The server is about to restart; no new match or round can start until it has."),
+    ("pvp.no-such-match", "That match is over."),
+    ("pvp.own-match", "That is your own match; the crowd is watching you."),
+    ("pvp.already-reacted", "You have reacted to this round already."),
+    ("pvp.empty-message", "Say something first."),
+];
+
+#[cfg(feature = "templates")]
+const DE: &[(&str, &str)] = &[
+    ("logged-out", "Erfolgreich abgemeldet."),
+    ("account.deleted", "Dein Konto und alles, was darunter gespeichert war, sind gelöscht."),
+    ("account.unconfirmed", "Gib deinen Namen ein, um das Löschen zu bestätigen."),
+    ("account.unproven",
+     "Gib zuerst einen deiner Wiederherstellungscodes ein, oder melde dich mit einem Passkey an."),
+    ("account.not-deleted",
+     "Dein Konto konnte nicht gelöscht werden; versuche es später noch einmal."),
+    ("account.logged-out-everywhere", "Auf all deinen Geräten aus {} Sitzung(en) abgemeldet."),
+    ("account.unverified",
+     "Melde dich mit einem Wiederherstellungscode oder einem Passkey an, um dich auf allen Geräten \
+      abzumelden."),
+    ("csrf.invalid",
+     "Dieses Formular ist abgelaufen; lade die Seite neu und versuche es noch einmal."),
+    ("passkey.required",
+     "Dieses Konto meldet sich mit einem Passkey an, oder mit einem Wiederherstellungscode unter \
+      /recover, nicht mit seinem Namen."),
+    ("passkey.removed", "Passkey entfernt; er meldet dich nicht mehr an."),
+    ("passkey.unknown", "Du hast keinen solchen Passkey."),
+    ("passkey.unverified",
+     "Melde dich mit einem Wiederherstellungscode oder einem Passkey an, um Passkeys zu \
+      entfernen."),
+    ("alerts.new-device",
+     "Achtung: eine Anmeldung bei deinem Konto von einem neuen Gerät. Warst du das nicht, sieh in \
+      deinem Profil nach."),
+    ("alerts.recovery-code-used",
+     "Achtung: ein Wiederherstellungscode deines Kontos wurde benutzt. Warst du das nicht, sieh in \
+      deinem Profil nach."),
+    ("webhook.saved", "Webhook gespeichert; ein neues Secret signiert seine Anfragen."),
+    ("webhook.removed", "Webhook entfernt; deine Runden werden nicht mehr gesendet."),
+    ("webhook.invalid-url", "Der Webhook braucht eine http://- oder https://-URL."),
+    ("webhook.unknown-host", "Der Host der Webhook-URL wurde nicht gefunden."),
+    ("webhook.private-address", "Die Webhook-URL muss auf eine öffentliche Adresse zeigen."),
+    ("webhook.test-queued", "Testanfrage eingereiht."),
+    ("webhook.none", "Richte zuerst einen Webhook ein."),
+    ("webhook.too-many-tests", "Genug Testanfragen für diese Stunde."),
+    ("webhook.unverified",
+     "Melde dich mit einem Wiederherstellungscode oder einem Passkey an, um deinen Webhook zu \
+      ändern, damit niemand sonst deine Runden bekommt."),
+    ("locale.saved", "Gespeichert; Zeiten und Zahlen werden ab jetzt auf deine Art geschrieben."),
+    ("locale.invalid-language", "Diese Sprache wird nicht angeboten."),
+    ("locale.invalid-offset", "Gib den Abstand zu UTC z. B. als +02:00 an, von -12:00 bis +14:00."),
+    ("handoff.login-first",
+     "Melde dich zuerst auf diesem Gerät an, und scanne den Code dann noch einmal."),
+    ("recovery.bad-code", "Dieser Code gilt nicht für diesen Namen, oder er wurde schon benutzt."),
+    ("recovery.locked-out", "Zu viele Fehlversuche; versuche es in {} Min. noch einmal."),
+    ("recovery.unverified",
+     "Melde dich mit einem Wiederherstellungscode oder einem Passkey an, um neue Codes \
+      auszustellen."),
+    ("history.note-saved", "Notiz gespeichert."),
+    ("history.no-such-round", "Diese Runde gibt es in deinem Verlauf nicht."),
+    ("history.bad-search", "Der Verlauf kann nicht durchsucht werden: {}."),
+    ("team.formed", "Team gegründet; gib den Einladungscode weiter."),
+    ("team.joined", "Willkommen im Team!"),
+    ("team.no-such-team", "Es gibt kein Team mit diesem Einladungscode."),
+    ("team.already-in-team", "Du bist schon in einem Team."),
+    ("team.full", "Dieses Team ist voll."),
+    ("team.not-in-team", "Du bist in keinem Team."),
+    ("team.incomplete", "Dein Teamkollege ist noch nicht beigetreten."),
+    ("team.not-your-turn", "Dein Teamkollege ist am Zug."),
+    ("pvp.invite-ready", "Einladungslink bereit; gib ihn weiter."),
+    ("pvp.invite-emailed", "Einladung an {} gesendet; sie gilt {} Tage."),
+    ("pvp.invalid-email", "Das sieht nicht nach einer E-Mail-Adresse aus."),
+    ("pvp.too-many-invites",
+     "Warte zuerst, bis deine per E-Mail verschickten Einladungen angenommen werden oder \
+      ablaufen."),
+    ("pvp.too-many-emails",
+     "Zuletzt wurden zu viele Einladungen per E-Mail verschickt; versuche es in einer Stunde noch \
+      einmal."),
+    ("pvp.unfit-name", "Dein Name passt in keine E-Mail; lade stattdessen per Link ein."),
+    ("pvp.unverified",
+     "Melde dich mit einem Wiederherstellungscode oder einem Passkey an, um per E-Mail einzuladen, \
+      damit niemand es in deinem Namen tut."),
+    ("pvp.login-to-accept", "Melde dich an, um die Einladung anzunehmen."),
+    ("pvp.match-on", "Dein Match gegen {} läuft!"),
+    ("pvp.picked", "Hand gewählt; warte auf deinen Gegner."),
+    ("pvp.both-picked", "Beide Hände sind gewählt."),
+    ("pvp.reacted", "Die Spieler erfahren davon, sobald die Runde vorbei ist."),
+    ("pvp.sent", "Gesendet."),
+    ("pvp.no-such-invite", "Dieser Einladungslink ist abgelaufen oder wurde schon benutzt."),
+    ("pvp.own-invite", "Das ist dein eigener Einladungslink; gib ihn an deinen Gegner weiter."),
+    ("pvp.already-in-match", "Du bist schon in einem Match."),
+    ("pvp.host-busy",
+     "Wer dich eingeladen hat, ist in einem anderen Match; versuche es später noch einmal."),
+    ("pvp.not-in-match", "Du bist in keinem Match."),
+    ("pvp.already-picked", "Du hast deine Hand schon gewählt; warte auf deinen Gegner."),
+    ("pvp.maintenance",
+     "Der Server startet gleich neu; bis dahin kann kein neues Match und keine neue Runde \
+      beginnen."),
+    ("pvp.no-such-match", "Dieses Match ist vorbei."),
+    ("pvp.own-match", "Das ist dein eigenes Match; das Publikum schaut dir zu."),
+    ("pvp.already-reacted", "Du hast auf diese Runde schon reagiert."),
+    ("pvp.empty-message", "Sag zuerst etwas."),
+];
+
+#[cfg(feature = "templates")]
+const FR: &[(&str, &str)] = &[
+    ("logged-out", "Déconnexion réussie."),
+    ("account.deleted", "Ton compte et tout ce qui était conservé sous son nom sont supprimés."),
+    ("account.unconfirmed", "Tape ton nom pour confirmer la suppression."),
+    ("account.unproven",
+     "Saisis d'abord l'un de tes codes de récupération, ou connecte-toi avec une clé d'accès."),
+    ("account.not-deleted", "Ton compte n'a pas pu être supprimé ; réessaie plus tard."),
+    ("account.logged-out-everywhere", "Déconnecté de {} session(s) sur tous tes appareils."),
+    ("account.unverified",
+     "Connecte-toi avec un code de récupération ou une clé d'accès pour te déconnecter de tous tes \
+      appareils."),
+    ("csrf.invalid", "Ce formulaire a expiré ; recharge la page et réessaie."),
+    ("passkey.required",
+     "Ce compte se connecte avec une clé d'accès, ou avec un code de récupération sur /recover, \
+      pas avec son nom."),
+    ("passkey.removed", "Clé d'accès supprimée ; elle ne te connecte plus."),
+    ("passkey.unknown", "Tu n'as pas cette clé d'accès."),
+    ("passkey.unverified",
+     "Connecte-toi avec un code de récupération ou une clé d'accès pour supprimer des clés \
+      d'accès."),
+    ("alerts.new-device",
+     "Attention : une connexion à ton compte depuis un nouvel appareil. Si ce n'était pas toi, \
+      vérifie ton profil."),
+    ("alerts.recovery-code-used",
+     "Attention : un code de récupération de ton compte a été utilisé. Si ce n'était pas toi, \
+      vérifie ton profil."),
+    ("webhook.saved", "Webhook enregistré ; un nouveau secret signe ses envois."),
+    ("webhook.removed", "Webhook supprimé ; tes manches ne sont plus envoyées."),
+    ("webhook.invalid-url", "Le webhook a besoin d'une URL en http:// ou https://."),
+    ("webhook.unknown-host", "L'hôte de l'URL du webhook est introuvable."),
+    ("webhook.private-address", "L'URL du webhook doit mener à une adresse publique."),
+    ("webhook.test-queued", "Envoi de test mis en file d'attente."),
+    ("webhook.none", "Configure d'abord un webhook."),
+    ("webhook.too-many-tests", "Assez d'envois de test pour cette heure."),
+    ("webhook.unverified",
+     "Connecte-toi avec un code de récupération ou une clé d'accès pour modifier ton webhook, afin \
+      que personne d'autre ne reçoive tes manches."),
+    ("locale.saved", "Enregistré ; les heures et les nombres s'écrivent désormais à ta façon."),
+    ("locale.invalid-language", "Cette langue n'est pas proposée."),
+    ("locale.invalid-offset",
+     "Indique le décalage par rapport à UTC, p. ex. +02:00, de -12:00 à +14:00."),
+    ("handoff.login-first",
+     "Connecte-toi d'abord sur cet appareil, puis scanne à nouveau le code."),
+    ("recovery.bad-code", "Ce code ne fonctionne pas pour ce nom, ou a déjà été utilisé."),
+    ("recovery.locked-out", "Trop de tentatives échouées ; réessaie dans {} min."),
+    ("recovery.unverified",
+     "Connecte-toi avec un code de récupération ou une clé d'accès pour émettre de nouveaux \
+      codes."),
+    ("history.note-saved", "Note enregistrée."),
+    ("history.no-such-round", "Cette manche n'est pas dans ton historique."),
+    ("history.bad-search", "Impossible de chercher dans l'historique : {}."),
+    ("team.formed", "Équipe formée ; transmets le code d'invitation."),
+    ("team.joined", "Bienvenue dans l'équipe !"),
+    ("team.no-such-team", "Aucune équipe n'a ce code d'invitation."),
+    ("team.already-in-team", "Tu es déjà dans une équipe."),
+    ("team.full", "Cette équipe est complète."),
+    ("team.not-in-team", "Tu n'es dans aucune équipe."),
+    ("team.incomplete", "Ton coéquipier n'a pas encore rejoint l'équipe."),
+    ("team.not-your-turn", "C'est au tour de ton coéquipier."),
+    ("pvp.invite-ready", "Lien d'invitation prêt ; transmets-le."),
+    ("pvp.invite-emailed", "Invitation envoyée à {} ; elle est valable {} jours."),
+    ("pvp.invalid-email", "Cela ne ressemble pas à une adresse e-mail."),
+    ("pvp.too-many-invites",
+     "Attends d'abord que tes invitations par e-mail soient acceptées ou expirent."),
+    ("pvp.too-many-emails",
+     "Trop d'invitations ont été envoyées par e-mail récemment ; réessaie dans une heure."),
+    ("pvp.unfit-name", "Ton nom ne peut pas figurer dans un e-mail ; invite plutôt par lien."),
+    ("pvp.unverified",
+     "Connecte-toi avec un code de récupération ou une clé d'accès pour inviter par e-mail, afin \
+      que personne ne le fasse à ta place."),
+    ("pvp.login-to-accept", "Connecte-toi pour accepter l'invitation."),
+    ("pvp.match-on", "Ton match contre {} commence !"),
+    ("pvp.picked", "Main choisie ; en attente de ton adversaire."),
+    ("pvp.both-picked", "Les deux mains sont choisies."),
+    ("pvp.reacted", "Les joueurs le sauront une fois la manche terminée."),
+    ("pvp.sent", "Envoyé."),
+    ("pvp.no-such-invite", "Ce lien d'invitation a expiré ou a déjà été utilisé."),
+    ("pvp.own-invite", "C'est ton propre lien d'invitation ; transmets-le à ton adversaire."),
+    ("pvp.already-in-match", "Tu es déjà dans un match."),
+    ("pvp.host-busy", "La personne qui t'a invité est dans un autre match ; réessaie plus tard."),
+    ("pvp.not-in-match", "Tu n'es dans aucun match."),
+    ("pvp.already-picked", "Tu as déjà choisi ta main ; attends ton adversaire."),
+    ("pvp.maintenance",
+     "Le serveur va redémarrer ; aucun nouveau match ni aucune nouvelle manche ne peut commencer \
+      d'ici là."),
+    ("pvp.no-such-match", "Ce match est terminé."),
+    ("pvp.own-match", "C'est ton propre match ; le public te regarde."),
+    ("pvp.already-reacted", "Tu as déjà réagi à cette manche."),
+    ("pvp.empty-message", "Dis d'abord quelque chose."),
+];
+
+#[cfg(feature = "templates")]
+const ES: &[(&str, &str)] = &[
+    ("logged-out", "Sesión cerrada correctamente."),
+    ("account.deleted", "Tu cuenta y todo lo que se guardaba con ella se han borrado."),
+    ("account.unconfirmed", "Escribe tu nombre para confirmar el borrado."),
+    ("account.unproven",
+     "Introduce primero uno de tus códigos de recuperación, o inicia sesión con una llave de \
+      acceso."),
+    ("account.not-deleted", "No se pudo borrar tu cuenta; inténtalo más tarde."),
+    ("account.logged-out-everywhere", "Se cerraron {} sesión(es) en todos tus dispositivos."),
+    ("account.unverified",
+     "Inicia sesión con un código de recuperación o una llave de acceso para cerrar la sesión en \
+      todos tus dispositivos."),
+    ("csrf.invalid", "Ese formulario ha caducado; recarga la página e inténtalo de nuevo."),
+    ("passkey.required",
+     "Esta cuenta inicia sesión con una llave de acceso, o con un código de recuperación en \
+      /recover, no con su nombre."),
+    ("passkey.removed", "Llave de acceso eliminada; ya no inicia tu sesión."),
+    ("passkey.unknown", "No tienes esa llave de acceso."),
+    ("passkey.unverified",
+     "Inicia sesión con un código de recuperación o una llave de acceso para eliminar llaves de \
+      acceso."),
+    ("alerts.new-device",
+     "Atención: un inicio de sesión en tu cuenta desde un dispositivo nuevo. Si no fuiste tú, \
+      revisa tu perfil."),
+    ("alerts.recovery-code-used",
+     "Atención: se usó un código de recuperación de tu cuenta. Si no fuiste tú, revisa tu perfil."),
+    ("webhook.saved", "Webhook guardado; un secreto nuevo firma sus envíos."),
+    ("webhook.removed", "Webhook eliminado; tus rondas ya no se envían."),
+    ("webhook.invalid-url", "El webhook necesita una URL http:// o https://."),
+    ("webhook.unknown-host", "No se encontró el host de la URL del webhook."),
+    ("webhook.private-address", "La URL del webhook debe apuntar a una dirección pública."),
+    ("webhook.test-queued", "Envío de prueba en cola."),
+    ("webhook.none", "Configura primero un webhook."),
+    ("webhook.too-many-tests", "Basta de envíos de prueba por esta hora."),
+    ("webhook.unverified",
+     "Inicia sesión con un código de recuperación o una llave de acceso para cambiar tu webhook, \
+      para que nadie más reciba tus rondas."),
+    ("locale.saved",
+     "Guardado; a partir de ahora las horas y los números se escriben a tu manera."),
+    ("locale.invalid-language", "Ese idioma no está disponible."),
+    ("locale.invalid-offset",
+     "Indica la diferencia con UTC como p. ej. +02:00, de -12:00 a +14:00."),
+    ("handoff.login-first",
+     "Inicia sesión primero en este dispositivo, y luego vuelve a escanear el código."),
+    ("recovery.bad-code", "Ese código no sirve para ese nombre, o ya se ha usado."),
+    ("recovery.locked-out", "Demasiados intentos fallidos; inténtalo de nuevo en {} min."),
+    ("recovery.unverified",
+     "Inicia sesión con un código de recuperación o una llave de acceso para emitir códigos \
+      nuevos."),
+    ("history.note-saved", "Nota guardada."),
+    ("history.no-such-round", "Esa ronda no está en tu historial."),
+    ("history.bad-search", "No se puede buscar en el historial: {}."),
+    ("team.formed", "Equipo formado; pasa el código de invitación."),
+    ("team.joined", "¡Bienvenido al equipo!"),
+    ("team.no-such-team", "No hay ningún equipo con ese código de invitación."),
+    ("team.already-in-team", "Ya estás en un equipo."),
+    ("team.full", "Ese equipo está completo."),
+    ("team.not-in-team", "No estás en ningún equipo."),
+    ("team.incomplete", "Tu compañero aún no se ha unido."),
+    ("team.not-your-turn", "Es el turno de tu compañero."),
+    ("pvp.invite-ready", "Enlace de invitación listo; pásalo."),
+    ("pvp.invite-emailed", "Invitación enviada a {}; vale durante {} días."),
+    ("pvp.invalid-email", "Eso no parece una dirección de correo electrónico."),
+    ("pvp.too-many-invites",
+     "Espera primero a que tus invitaciones por correo se acepten o caduquen."),
+    ("pvp.too-many-emails",
+     "Se han enviado demasiadas invitaciones por correo últimamente; inténtalo de nuevo dentro de \
+      una hora."),
+    ("pvp.unfit-name", "Tu nombre no cabe en un correo; invita con un enlace."),
+    ("pvp.unverified",
+     "Inicia sesión con un código de recuperación o una llave de acceso para invitar por correo, \
+      para que nadie lo haga en tu nombre."),
+    ("pvp.login-to-accept", "Inicia sesión para aceptar la invitación."),
+    ("pvp.match-on", "¡Tu partida contra {} ha empezado!"),
+    ("pvp.picked", "Mano elegida; esperando a tu rival."),
+    ("pvp.both-picked", "Las dos manos están elegidas."),
+    ("pvp.reacted", "Los jugadores lo sabrán cuando termine la ronda."),
+    ("pvp.sent", "Enviado."),
+    ("pvp.no-such-invite", "Ese enlace de invitación ha caducado o ya se ha usado."),
+    ("pvp.own-invite", "Es tu propio enlace de invitación; pásaselo a tu rival."),
+    ("pvp.already-in-match", "Ya estás en una partida."),
+    ("pvp.host-busy", "Quien te invitó está en otra partida; inténtalo más tarde."),
+    ("pvp.not-in-match", "No estás en ninguna partida."),
+    ("pvp.already-picked", "Ya has elegido tu mano; espera a tu rival."),
+    ("pvp.maintenance",
+     "El servidor está a punto de reiniciarse; no puede empezar ninguna partida ni ronda nueva \
+      hasta entonces."),
+    ("pvp.no-such-match", "Esa partida ha terminado."),
+    ("pvp.own-match", "Es tu propia partida; el público te está mirando."),
+    ("pvp.already-reacted", "Ya has reaccionado a esta ronda."),
+    ("pvp.empty-message", "Di algo primero."),
+];
+
+/// The catalog of `language`. Each has the codes of `EN`, in the same order.
+#[cfg(feature = "templates")]
+fn catalog(language: Language) -> &'static [(&'static str, &'static str)] {
+    match language {
+        Language::En => EN,
+        Language::De => DE,
+        Language::Fr => FR,
+        Language::Es => ES,
+    }
+}
+
+#[cfg(feature = "templates")]
+fn lookup(catalog: &[(&str, &'static str)], code: &str) -> Option<&'static str> {
+    catalog.iter().find(|&&(c, _)| c == code).map(|&(_, template)| template)
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    Success,
+    Warning,
+    Error,
+}
+
+impl Level {
+    /// The name of the flash cookie's kind, and of the level in templates.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Level::Success => "success",
+            Level::Warning => "warning",
+            Level::Error => "error",
+        }
+    }
+
+    #[cfg(feature = "templates")]
+    fn from_name(name: &str) -> Level {
+        match name {
+            "success" => Level::Success,
+            "warning" => Level::Warning,
+            _ => Level::Error,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Notice {
+    pub level: Level,
+    /// A code of the catalogs, e.g. of `EN`.
+    pub code: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub params: Vec<String>,
+}
+
+impl Notice {
+    fn new(level: Level, code: &str) -> Notice {
+        Notice {
+            level: level,
+            code: code.to_owned(),
+            params: Vec::new(),
+        }
+    }
+
+    pub fn success(code: &str) -> Notice {
+        Notice::new(Level::Success, code)
+    }
+
+    #[cfg(feature = "templates")]
+    pub fn warning(code: &str) -> Notice {
+        Notice::new(Level::Warning, code)
+    }
+
+    #[cfg(feature = "templates")]
+    pub fn error(code: &str) -> Notice {
+        Notice::new(Level::Error, code)
+    }
+
+    /// Adds the next parameter of the message.
+    pub fn with(mut self, param: &str) -> Notice {
+        self.params.push(param.to_owned());
+        self
+    }
+
+    /// The message in `language`, falling back on English. An unknown code, e.g. of a release
+    /// that has since dropped it, is shown as it is.
+    #[cfg(feature = "templates")]
+    pub fn text(&self, language: Language) -> String {
+        let template = lookup(catalog(language), &self.code)
+            .or_else(|| lookup(EN, &self.code))
+            .unwrap_or(self.code.as_str());
+        let mut parts = template.split("{}");
+        let mut text = parts.next().unwrap_or("").to_owned();
+        for (i, part) in parts.enumerate() {
+            text.push_str(self.params.get(i).map_or("", |param| param.as_str()));
+            text.push_str(part);
+        }
+        text
+    }
+
+    /// Sets the notice as the flash message of `responder`.
+    pub fn to<R>(self, responder: R) -> Flash<R> {
+        let payload = serde_json::to_string(&self).unwrap();
+        Flash::new(responder, self.level.name(), payload)
+    }
+}
+
+/// The level and the text of a flash message, in `language`. One that is not a `Notice`, e.g. set
+/// before notices were, is taken as text.
+#[cfg(feature = "templates")]
+pub fn read(flash: &FlashMessage, language: Language) -> (Level, String) {
+    match serde_json::from_str::<Notice>(flash.msg()) {
+        Ok(notice) => (notice.level, notice.text(language)),
+        Err(_) => (Level::from_name(flash.name()), flash.msg().to_owned()),
+    }
+}
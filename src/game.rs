@@ -1,12 +1,14 @@
 use std::cmp::Ordering;
 use std::str::FromStr;
 
-use rand::{thread_rng, Rng};
-use sha2::{Sha256, Digest};
+use rand::Rng;
 
+use commitment;
+use entropy;
 use util::{bytes_to_hex, gen_random_bytes};
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Hand {
     Rock,
     Paper,
@@ -37,11 +39,28 @@ impl Hand {
         }
     }
 
-    const CHOICES: [Hand; 3] = [Hand::Rock, Hand::Paper, Hand::Scissors];
+    pub const CHOICES: [Hand; 3] = [Hand::Rock, Hand::Paper, Hand::Scissors];
+
+    /// Position in `CHOICES`.
+    pub fn index(&self) -> usize {
+        match *self {
+            Hand::Rock => 0,
+            Hand::Paper => 1,
+            Hand::Scissors => 2,
+        }
+    }
+
+    /// The hand that beats this one.
+    pub fn counter(&self) -> Hand {
+        match *self {
+            Hand::Rock => Hand::Paper,
+            Hand::Paper => Hand::Scissors,
+            Hand::Scissors => Hand::Rock,
+        }
+    }
 
     pub fn random() -> Hand {
-        let mut rng = thread_rng();
-        *rng.choose(&Self::CHOICES).unwrap()
+        *entropy::get().choose(&Self::CHOICES).unwrap()
     }
 }
 
@@ -61,33 +80,241 @@ impl FromStr for Hand {
     type Err = ParseHandError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Ok(match s.to_lowercase().as_ref() {
-            "rock" => Hand::Rock,
-            "paper" => Hand::Paper,
-            "scissors" => Hand::Scissors,
-            _ => return Err(ParseHandError),
-        })
+        // Only ASCII case is ignored: `to_lowercase` would also take e.g. the Kelvin sign for a `k`.
+        [Hand::Rock, Hand::Paper, Hand::Scissors].iter()
+            .find(|hand| s.eq_ignore_ascii_case(hand.as_ref()))
+            .cloned()
+            .ok_or(ParseHandError)
+    }
+}
+
+/// The result of a round from the human player's point of view.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Verdict {
+    Win,
+    Tie,
+    Loss,
+}
+
+impl Verdict {
+    pub const ALL: [Verdict; 3] = [Verdict::Win, Verdict::Tie, Verdict::Loss];
+
+    pub fn of(human: Hand, computer: Hand) -> Verdict {
+        match computer.vs(&human) {
+            Ordering::Greater => Verdict::Loss,
+            Ordering::Equal => Verdict::Tie,
+            Ordering::Less => Verdict::Win,
+        }
+    }
+
+    /// The verdict of a round of one or more bouts, won by the side that won more of them.
+    pub fn overall(human: &[Hand], computer: &[Hand]) -> Verdict {
+        let balance: i32 = human.iter().zip(computer)
+            .map(|(&human, &computer)| Verdict::of(human, computer).payoff())
+            .sum();
+        match balance.cmp(&0) {
+            Ordering::Greater => Verdict::Win,
+            Ordering::Equal => Verdict::Tie,
+            Ordering::Less => Verdict::Loss,
+        }
+    }
+
+    /// The human's payoff: 1 for a win, 0 for a tie and -1 for a loss.
+    pub fn payoff(&self) -> i32 {
+        match *self {
+            Verdict::Win => 1,
+            Verdict::Tie => 0,
+            Verdict::Loss => -1,
+        }
+    }
+
+    /// As serialized.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Verdict::Win => "win",
+            Verdict::Tie => "tie",
+            Verdict::Loss => "loss",
+        }
+    }
+
+    pub fn describe(&self) -> &'static str {
+        match *self {
+            Verdict::Win => "You won",
+            Verdict::Tie => "Tie",
+            Verdict::Loss => "Computer won",
+        }
+    }
+}
+
+/// The kinds of rounds, which are scored separately.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RoundKind {
+    /// One hand per side.
+    Classic,
+    /// `MULTI_HANDS` hands per side, see `MultiRound`.
+    Chaos,
+}
+
+/// What generated the computer's hand of a classic round, for balancing.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Strategy {
+    /// Every hand with the same probability, as the random opponent always plays and the
+    /// mirroring ones do before the human's first hand.
+    Uniform,
+    Mirror,
+    CounterMirror,
+    /// Derived from the seed of a seeded match.
+    Seeded,
+    /// The scripted opponent of the tutorial, see `onboarding`.
+    Scripted,
+    /// Fun mode's meta-strategy, see `strategy::Adaptive`.
+    Adaptive,
+    /// A house script the player picked, see `scripting`.
+    House,
+}
+
+/// How many strategies there are, the length of `Strategy::ALL`.
+pub const STRATEGIES: usize = 7;
+
+impl Default for Strategy {
+    fn default() -> Strategy {
+        Strategy::Uniform
     }
 }
 
+impl Strategy {
+    pub const ALL: [Strategy; STRATEGIES] = [
+        Strategy::Uniform,
+        Strategy::Mirror,
+        Strategy::CounterMirror,
+        Strategy::Seeded,
+        Strategy::Scripted,
+        Strategy::Adaptive,
+        Strategy::House,
+    ];
+
+    /// Position in `ALL`.
+    pub fn index(&self) -> usize {
+        match *self {
+            Strategy::Uniform => 0,
+            Strategy::Mirror => 1,
+            Strategy::CounterMirror => 2,
+            Strategy::Seeded => 3,
+            Strategy::Scripted => 4,
+            Strategy::Adaptive => 5,
+            Strategy::House => 6,
+        }
+    }
+
+    /// Whether rounds against it count toward the player's stats, rating and leaderboard place.
+    /// The tutorial's are practice, fun mode's are played to a win rate set by the server, and
+    /// house scripts are whatever the admins put up.
+    pub fn is_ranked(&self) -> bool {
+        *self != Strategy::Scripted && *self != Strategy::Adaptive && *self != Strategy::House
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Strategy::Uniform => "uniform",
+            Strategy::Mirror => "mirror",
+            Strategy::CounterMirror => "counter-mirror",
+            Strategy::Seeded => "seeded",
+            Strategy::Scripted => "scripted",
+            Strategy::Adaptive => "adaptive",
+            Strategy::House => "house",
+        }
+    }
+}
+
+/// Win/tie/loss counts of one kind of round.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub struct Scoreboard {
+    pub wins: usize,
+    pub ties: usize,
+    pub losses: usize,
+}
+
+impl Scoreboard {
+    pub fn record(&mut self, verdict: Verdict) {
+        match verdict {
+            Verdict::Win => self.wins += 1,
+            Verdict::Tie => self.ties += 1,
+            Verdict::Loss => self.losses += 1,
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct Round {
     pub computer: Hand,
     pub random_bytes: String,
     pub digest: String,
+    pub strategy: Strategy,
+    /// The round of the sudden throw that breaks a tie, committed to alongside this one.
+    pub backup: Option<Box<Round>>,
 }
 
 impl Round {
-    pub fn random() -> Round {
-        let hand = Hand::random();
-        let random_bytes = gen_random_bytes(32);
-        let random_bytes_hex = bytes_to_hex(&random_bytes[..]);
-        let concat_str = format!("{}{}", random_bytes_hex, hand.as_ref());
-
-        let digest = format!("{:x}", Sha256::digest(concat_str.as_bytes()));
+    /// Commits to `hand`, picked by `strategy`, under the given nonce.
+    pub fn new(hand: Hand, nonce: &[u8], strategy: Strategy) -> Round {
+        let random_bytes_hex = bytes_to_hex(nonce);
+        let digest = commitment::digest(&random_bytes_hex, hand);
         Round {
             computer: hand,
             random_bytes: random_bytes_hex,
             digest: digest,
+            strategy: strategy,
+            backup: None,
+        }
+    }
+
+    /// Also commits to a uniformly random hand for a sudden throw, should this round be a tie.
+    /// The sudden throw has no backup of its own.
+    pub fn with_backup(mut self) -> Round {
+        let backup = Round::new(Hand::random(), &gen_random_bytes(32), Strategy::Uniform);
+        self.backup = Some(Box::new(backup));
+        self
+    }
+}
+
+/// Number of hands each side throws in a chaos round.
+pub const MULTI_HANDS: usize = 3;
+
+/// A chaos round: both sides throw `MULTI_HANDS` hands, which meet position by position, and
+/// whoever wins more of those bouts wins the round.
+#[derive(Clone)]
+pub struct MultiRound {
+    pub computer: [Hand; MULTI_HANDS],
+    pub random_bytes: String,
+    pub digest: String,
+}
+
+impl MultiRound {
+    /// Commits to `hands` under the given nonce.
+    pub fn new(hands: [Hand; MULTI_HANDS], nonce: &[u8]) -> MultiRound {
+        let random_bytes_hex = bytes_to_hex(nonce);
+        let digest = commitment::digest_multi(&random_bytes_hex, &hands);
+        MultiRound {
+            computer: hands,
+            random_bytes: random_bytes_hex,
+            digest: digest,
+        }
+    }
+
+    pub fn random() -> MultiRound {
+        let hands = [Hand::random(), Hand::random(), Hand::random()];
+        MultiRound::new(hands, &gen_random_bytes(32))
+    }
+
+    /// Returns the verdict of every bout and of the round as a whole.
+    pub fn verdict(&self, human: &[Hand; MULTI_HANDS]) -> ([Verdict; MULTI_HANDS], Verdict) {
+        let mut bouts = [Verdict::Tie; MULTI_HANDS];
+        for i in 0..MULTI_HANDS {
+            bouts[i] = Verdict::of(human[i], self.computer[i]);
         }
+        (bouts, Verdict::overall(human, &self.computer))
     }
 }
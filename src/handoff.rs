@@ -0,0 +1,147 @@
+//! Logging in on another device from a phone that is already logged in
+//!
+//! The login page shows a QR code of `/handoff/<token>`, where the token is a one-time secret kept
+//! in a private cookie of the browser that shows it. Opening that link on a device logged in as
+//! someone, and approving, logs the first browser in under the same name: its login page polls
+//! `GET /login/handoff` and, once the token is approved, opens a session there as a login would.
+//! A token expires after `HANDOFF_TTL` seconds, and is used up once its login went through.
+//!
+//! Anyone can open the login page, so an address only gets `PENDING_PER_ADDRESS` tokens at once,
+//! and a browser reloading the page keeps its token for as long as it is pending: nobody can use
+//! up the `MAX_PENDING` tokens of everyone else.
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+
+use rocket::request::{self, FromRequest, Request};
+use rocket::Outcome;
+
+use poison::Recover;
+use proxy;
+use util::{bytes_to_hex, gen_random_bytes, unix_time};
+
+/// How long a handoff token can be approved and used, in seconds.
+pub const HANDOFF_TTL: u64 = 120;
+/// Tokens waiting at once, beyond which login pages go without a QR code.
+const MAX_PENDING: usize = 10_000;
+/// Tokens waiting at once for login pages shown to the same address.
+const PENDING_PER_ADDRESS: usize = 5;
+
+lazy_static! {
+    static ref HANDOFFS: Mutex<HashMap<String, Handoff>> = Mutex::new(HashMap::new());
+}
+
+struct Handoff {
+    /// Unix time after which the token no longer works.
+    expires_at: u64,
+    /// Where the login page that shows it was sent.
+    address: Option<IpAddr>,
+    /// Who approved it, if anyone did.
+    approved_by: Option<String>,
+    /// Whether the approving session was verified, see `Session::verified`, which the new one
+    /// then is too.
+    verified: bool,
+}
+
+/// Where a handoff token stands, as seen by the browser that shows it.
+#[derive(Debug, Eq, PartialEq)]
+pub enum Claim {
+    Pending,
+    /// Approved by this user, whom the browser may now log in as, from a verified session or not.
+    /// The token is used up.
+    Approved { user_name: String, verified: bool },
+    /// Expired, used up or never issued.
+    Gone,
+}
+
+fn prune(handoffs: &mut HashMap<String, Handoff>) {
+    let now = unix_time();
+    handoffs.retain(|_, handoff| handoff.expires_at > now);
+}
+
+/// Issues a new token for a login page shown to `address`, unless too many are waiting already,
+/// from the address or overall.
+pub fn issue(address: Option<IpAddr>) -> Option<String> {
+    let mut handoffs = HANDOFFS.lock().recover();
+    prune(&mut handoffs);
+    if handoffs.len() >= MAX_PENDING {
+        return None;
+    }
+    if address.is_some() {
+        let from_address = handoffs.values().filter(|handoff| handoff.address == address).count();
+        if from_address >= PENDING_PER_ADDRESS {
+            return None;
+        }
+    }
+    let token = bytes_to_hex(&gen_random_bytes(16));
+    handoffs.insert(token.clone(), Handoff {
+        expires_at: unix_time() + HANDOFF_TTL,
+        address: address,
+        approved_by: None,
+        verified: false,
+    });
+    Some(token)
+}
+
+/// Whether `token` can still be approved.
+pub fn is_pending(token: &str) -> bool {
+    let mut handoffs = HANDOFFS.lock().recover();
+    prune(&mut handoffs);
+    handoffs.get(token).map_or(false, |handoff| handoff.approved_by.is_none())
+}
+
+/// Approves `token` on behalf of `user_name`, from a verified session or not. Returns whether it
+/// could still be approved.
+pub fn approve(token: &str, user_name: &str, verified: bool) -> bool {
+    let mut handoffs = HANDOFFS.lock().recover();
+    prune(&mut handoffs);
+    match handoffs.get_mut(token).filter(|handoff| handoff.approved_by.is_none()) {
+        Some(handoff) => {
+            handoff.approved_by = Some(user_name.to_owned());
+            handoff.verified = verified;
+            true
+        }
+        None => false,
+    }
+}
+
+/// Checks on `token` for the browser that shows it, using it up if it was approved.
+pub fn claim(token: &str) -> Claim {
+    let mut handoffs = HANDOFFS.lock().recover();
+    prune(&mut handoffs);
+    let approved = match handoffs.get(token) {
+        Some(handoff) => handoff.approved_by.is_some(),
+        None => return Claim::Gone,
+    };
+    if !approved {
+        return Claim::Pending;
+    }
+    let handoff = handoffs.remove(token).unwrap();
+    Claim::Approved { user_name: handoff.approved_by.unwrap(), verified: handoff.verified }
+}
+
+/// Voids the tokens approved by `user_name` and not used yet, e.g. when their account is deleted.
+pub fn forget(user_name: &str) {
+    let mut handoffs = HANDOFFS.lock().recover();
+    handoffs.retain(|_, handoff| {
+        handoff.approved_by.as_ref().map(String::as_str) != Some(user_name)
+    });
+}
+
+/// Where the client reached the site, e.g. `https://example.com`, for links to open elsewhere.
+pub struct SiteUrl(pub String);
+
+impl<'a, 'r> FromRequest<'a, 'r> for SiteUrl {
+    type Error = ();
+
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<SiteUrl, ()> {
+        let host = match request.headers().get_one("Host") {
+            Some(host) if !host.is_empty() => host,
+            _ => return Outcome::Forward(()),
+        };
+        let default_scheme = if cfg!(feature = "tls") { "https" } else { "http" };
+        let scheme = proxy::forwarded_proto(request).unwrap_or(default_scheme);
+        Outcome::Success(SiteUrl(format!("{}://{}", scheme, host)))
+    }
+}
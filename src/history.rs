@@ -0,0 +1,786 @@
+//! Per-session history of revealed rounds
+//!
+//! Every revealed round extends a hash chain from `GENESIS`, each link hashing the previous one
+//! with the round's nonce, both sides' hands and its verdict, see `commitment::chain_link`, so
+//! that rewriting any past round changes every later link and the chain head.
+//! The player's notes on rounds are left out of the chain, so they can be edited at any time, and
+//! so are the times rounds were played at and how long they took. So is whether a round was voided
+//! by an arcade redo: it was played all the same, and its link stands.
+//!
+//! Rounds can be searched by verdict, hand, opponent and date, see `History::search`: a byte per
+//! round and the dates spanned by each chunk of rounds are kept aside as an index, so that only
+//! the chunks holding matches are decoded.
+//!
+//! A history keeps at most `MAX_ROUNDS` rounds, so that a session's memory stays bounded however
+//! long it plays: past that, the oldest chunk of rounds is dropped, with its notes, whenever a new
+//! one starts. Rounds keep their indices, and the rest of the chain starts from the link preceding
+//! the oldest round kept, which exports and backups carry along, see `Export::prev_link`.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::Instant;
+
+use commitment;
+use game::{Hand, RoundKind, Strategy, Verdict, MULTI_HANDS, STRATEGIES};
+use proof::RoundProof;
+use util::{bytes_to_hex, hex_to_bytes, unix_time};
+
+/// The link preceding the first round of every session.
+pub const GENESIS: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+/// Longest note, in characters, a round can carry.
+pub const MAX_NOTE_LEN: usize = 200;
+/// Rounds per page of the history, on `/history` and from `GET /api/v1/history`.
+pub const PAGE_LEN: usize = 100;
+/// Most rounds a history keeps, about 2.5 MB of them; a multiple of `CHECKPOINT_INTERVAL`.
+pub const MAX_ROUNDS: usize = 1024 * CHECKPOINT_INTERVAL;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RoundRecord {
+    pub kind: RoundKind,
+    /// The human's hand(s), formatted like the computer's.
+    pub human: String,
+    /// The computer's revealed hand(s), in the committed format.
+    pub computer: String,
+    pub verdict: Verdict,
+    pub random: String,
+    pub digest: String,
+    pub link: String,
+    /// Unix time at which the round was played; 0 in exports that predate it.
+    #[serde(default)]
+    pub played_at: u64,
+    /// How long the round took, if it was timed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latency: Option<Latency>,
+    /// The player's own note on the round.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    /// Whether an arcade redo voided the round, taking it out of the scores.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub voided: bool,
+}
+
+fn is_false(value: &bool) -> bool {
+    !*value
+}
+
+/// How long a round took.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Latency {
+    /// Milliseconds from the computer's commitment to the player's hand.
+    pub decided_ms: u64,
+    /// Microseconds the server spent resolving the round.
+    pub server_us: u64,
+}
+
+impl Latency {
+    /// The latency of a round committed to at `committed_at`, whose hand the server received at
+    /// `received` and has just resolved.
+    pub fn since(committed_at: Instant, received: Instant) -> Latency {
+        let decided = received.duration_since(committed_at);
+        let server = received.elapsed();
+        Latency {
+            decided_ms: decided.as_secs() * 1000 + u64::from(decided.subsec_millis()),
+            server_us: server.as_secs() * 1_000_000 + u64::from(server.subsec_micros()),
+        }
+    }
+}
+
+/// Rounds between two checkpoints, from which decoding can start.
+const CHECKPOINT_INTERVAL: usize = 64;
+
+// Flags of the first byte of a packed round; bits 1 and 2 hold the verdict.
+const CHAOS: u8 = 1;
+/// The nonce is kept as given rather than as the bytes of its lower hex.
+const RAW_NONCE: u8 = 1 << 3;
+/// The digest is kept as given, since it is not the commitment of the revealed hand(s).
+const KEPT_DIGEST: u8 = 1 << 4;
+/// The round carries its `Latency`.
+const TIMED: u8 = 1 << 5;
+
+/// Where decoding can start: a packed round and what the rounds before it leave behind.
+#[derive(Clone)]
+struct Checkpoint {
+    offset: usize,
+    prev_link: String,
+    prev_played_at: u64,
+    /// When the rounds up to the next checkpoint were played, earliest and latest, since clocks
+    /// may step back.
+    earliest: u64,
+    latest: u64,
+}
+
+/// Where a history stood, to rewind it to; see `History::mark`. It keeps what rounds and notes
+/// added since would overwrite, not the rounds themselves.
+pub struct Mark {
+    len: usize,
+    bytes: usize,
+    checkpoints: usize,
+    /// What the history had dropped, since more may be dropped before it is rewound.
+    first: usize,
+    dropped_bytes: usize,
+    /// The latest checkpoint as it was, since rounds appended after it widen its times.
+    last_checkpoint: Option<Checkpoint>,
+    notes: BTreeMap<usize, String>,
+    voided: BTreeSet<usize>,
+    last_played_at: u64,
+    head: String,
+    edits: usize,
+}
+
+// The index byte of a round: the verdict, as in the flags of a packed round, in bits 0 and 1; the
+// hands the human played, one bit per hand, in bits 2 to 4; and one plus the index of the
+// computer's strategy, or 0 if not known, from bit 5.
+const TAG_HANDS_SHIFT: u8 = 2;
+const TAG_STRATEGY_SHIFT: u8 = 5;
+
+// Fails to compile unless one plus the index of every strategy fits the 3 bits from bit 5, i.e.
+// unless `Strategy::ALL` has fewer than 8 strategies.
+const _STRATEGY_TAG_FITS: [(); 0] = [(); STRATEGIES >> (8 - TAG_STRATEGY_SHIFT)];
+
+fn tag(verdict: Verdict, human: &[Hand], opponent: Option<Strategy>) -> u8 {
+    let hands = human.iter().fold(0u8, |bits, hand| bits | 1 << hand.index());
+    let strategy = opponent.map_or(0, |strategy| strategy.index() as u8 + 1);
+    verdict_bits(verdict) | hands << TAG_HANDS_SHIFT | strategy << TAG_STRATEGY_SHIFT
+}
+
+/// What to look for in a history; every criterion left out matches any round.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Filter {
+    pub verdict: Option<Verdict>,
+    /// A hand the player played, in any bout of a chaos round.
+    pub hand: Option<Hand>,
+    /// The computer's strategy. Rounds of imported histories have none.
+    pub opponent: Option<Strategy>,
+    /// Unix time from which on rounds were played.
+    pub since: Option<u64>,
+    /// Unix time before which rounds were played.
+    pub until: Option<u64>,
+}
+
+impl Filter {
+    fn matches_tag(&self, tag: u8) -> bool {
+        self.verdict.map_or(true, |verdict| tag & 3 == verdict_bits(verdict))
+            && self.hand.map_or(true, |hand| (tag >> TAG_HANDS_SHIFT) & (1 << hand.index()) != 0)
+            && self.opponent.map_or(true, |strategy| {
+                tag >> TAG_STRATEGY_SHIFT == strategy.index() as u8 + 1
+            })
+    }
+
+    fn matches_time(&self, played_at: u64) -> bool {
+        self.since.map_or(true, |since| played_at >= since)
+            && self.until.map_or(true, |until| played_at < until)
+    }
+
+    fn may_match_chunk(&self, checkpoint: &Checkpoint) -> bool {
+        self.since.map_or(true, |since| checkpoint.latest >= since)
+            && self.until.map_or(true, |until| checkpoint.earliest < until)
+    }
+}
+
+/// The revealed rounds of a session, packed.
+///
+/// A round takes under 40 bytes rather than the few hundred of a `RoundRecord`: the kind and the
+/// verdict share a byte, hands take 2 bits each, the nonce is kept as bytes, the time as the
+/// difference from the previous round's, and digests and links are not kept at all but
+/// recomputed while decoding, starting from the closest checkpoint. Notes are kept separately.
+#[derive(Clone)]
+pub struct History {
+    /// The rounds kept, from `first` on.
+    bytes: Vec<u8>,
+    /// One per `CHECKPOINT_INTERVAL` rounds kept.
+    checkpoints: Vec<Checkpoint>,
+    /// By round index.
+    notes: BTreeMap<usize, String>,
+    /// The indices of the rounds voided by an arcade redo.
+    voided: BTreeSet<usize>,
+    /// By round index from `first` on, see `tag`.
+    tags: Vec<u8>,
+    /// The index of the oldest round kept, a multiple of `CHECKPOINT_INTERVAL`.
+    first: usize,
+    /// The size of the rounds dropped, packed.
+    dropped_bytes: usize,
+    len: usize,
+    last_played_at: u64,
+    head: String,
+    /// Notes set or cleared, and rounds voided.
+    edits: usize,
+}
+
+fn verdict_bits(verdict: Verdict) -> u8 {
+    match verdict {
+        Verdict::Win => 0,
+        Verdict::Tie => 1,
+        Verdict::Loss => 2,
+    }
+}
+
+fn hand_count(kind: RoundKind) -> usize {
+    match kind {
+        RoundKind::Classic => 1,
+        RoundKind::Chaos => MULTI_HANDS,
+    }
+}
+
+/// Parses the hands of a round of `kind`, formatted as `commitment::join_hands` does. `None` if
+/// they are not given exactly so, or not as many as the round has.
+pub fn parse_hands(hands: &str, kind: RoundKind) -> Option<Vec<Hand>> {
+    let parsed: Vec<Hand> = hands.split(',')
+        .map(|hand| hand.parse().ok())
+        .collect::<Option<_>>()?;
+    if commitment::join_hands(&parsed) == hands && parsed.len() == hand_count(kind) {
+        Some(parsed)
+    } else {
+        None
+    }
+}
+
+fn write_varint(bytes: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        bytes.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+fn write_bytes(bytes: &mut Vec<u8>, data: &[u8]) {
+    write_varint(bytes, data.len() as u64);
+    bytes.extend_from_slice(data);
+}
+
+/// Maps small differences of either sign to small numbers, since clocks may step back.
+fn zigzag(delta: i64) -> u64 {
+    ((delta << 1) ^ (delta >> 63)) as u64
+}
+
+fn unzigzag(value: u64) -> i64 {
+    (value >> 1) as i64 ^ -((value & 1) as i64)
+}
+
+/// Reads a packed round at a time, see `History::pack`.
+struct Unpacker<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    prev_link: String,
+    prev_played_at: u64,
+}
+
+impl<'a> Unpacker<'a> {
+    fn byte(&mut self) -> u8 {
+        self.pos += 1;
+        self.bytes[self.pos - 1]
+    }
+
+    fn varint(&mut self) -> u64 {
+        let mut value = 0;
+        let mut shift = 0;
+        loop {
+            let byte = self.byte();
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return value;
+            }
+            shift += 7;
+        }
+    }
+
+    fn data(&mut self) -> &'a [u8] {
+        let len = self.varint() as usize;
+        self.pos += len;
+        &self.bytes[self.pos - len..self.pos]
+    }
+
+    fn string(&mut self) -> String {
+        String::from_utf8_lossy(self.data()).into_owned()
+    }
+
+    /// The next round, without its note.
+    fn next_record(&mut self) -> RoundRecord {
+        let flags = self.byte();
+        let kind = if flags & CHAOS == 0 { RoundKind::Classic } else { RoundKind::Chaos };
+        let verdict = match (flags >> 1) & 3 {
+            0 => Verdict::Win,
+            1 => Verdict::Tie,
+            _ => Verdict::Loss,
+        };
+        let count = hand_count(kind);
+        let mut hands = Vec::with_capacity(2 * count);
+        let mut bits = 0u32;
+        for i in 0..2 * count {
+            if i % 4 == 0 {
+                bits = u32::from(self.byte());
+            }
+            hands.push(Hand::CHOICES[(bits >> (2 * (i % 4)) & 3) as usize]);
+        }
+        let random = if flags & RAW_NONCE == 0 { bytes_to_hex(self.data()) } else { self.string() };
+        let played_at = self.prev_played_at.wrapping_add(unzigzag(self.varint()) as u64);
+        let latency = if flags & TIMED == 0 {
+            None
+        } else {
+            let decided_ms = self.varint();
+            Some(Latency { decided_ms: decided_ms, server_us: self.varint() })
+        };
+        let human = commitment::join_hands(&hands[..count]);
+        let computer = commitment::join_hands(&hands[count..]);
+        let digest = if flags & KEPT_DIGEST == 0 {
+            commitment::digest_str(&random, &computer)
+        } else {
+            self.string()
+        };
+        let link = commitment::chain_link(&self.prev_link, &random, &computer, &human, verdict);
+        self.prev_link = link.clone();
+        self.prev_played_at = played_at;
+        RoundRecord {
+            kind: kind,
+            human: human,
+            computer: computer,
+            verdict: verdict,
+            random: random,
+            digest: digest,
+            link: link,
+            played_at: played_at,
+            latency: latency,
+            note: None,
+            voided: false,
+        }
+    }
+}
+
+impl History {
+    pub fn new() -> History {
+        History {
+            bytes: Vec::new(),
+            checkpoints: Vec::new(),
+            notes: BTreeMap::new(),
+            voided: BTreeSet::new(),
+            tags: Vec::new(),
+            first: 0,
+            dropped_bytes: 0,
+            len: 0,
+            last_played_at: 0,
+            head: GENESIS.to_owned(),
+            edits: 0,
+        }
+    }
+
+    /// Packs a history from its records, e.g. those of an export. Fails if the records do not
+    /// form a chain, or hold hands this history could not give back as they are.
+    pub fn from_records(records: &[RoundRecord]) -> Result<History, String> {
+        History::from_records_at(0, GENESIS, records)
+    }
+
+    /// Like `from_records`, for records the first of which has the index `first` and follows
+    /// `prev_link`, since older rounds were dropped. Fails if `first` does not start a chunk.
+    pub fn from_records_at(first: usize, prev_link: &str, records: &[RoundRecord])
+        -> Result<History, String>
+    {
+        if first % CHECKPOINT_INTERVAL != 0 {
+            return Err(format!("round {}: not the first of a chunk", first));
+        }
+        let mut history = History::new();
+        history.first = first;
+        history.len = first;
+        history.head = prev_link.to_owned();
+        for (i, record) in (first..).zip(records) {
+            let (human, computer) = match (parse_hands(&record.human, record.kind),
+                                           parse_hands(&record.computer, record.kind)) {
+                (Some(human), Some(computer)) => (human, computer),
+                _ => return Err(format!("round {}: unexpected hands", i)),
+            };
+            if Verdict::overall(&human, &computer) != record.verdict {
+                return Err(format!("round {}: the verdict does not follow from the hands", i));
+            }
+            history.pack(record.kind, &human, &computer, record.verdict, &record.random,
+                         &record.digest, record.played_at, record.latency, None);
+            if history.head != record.link {
+                return Err(format!("round {}: broken chain link", i));
+            }
+            if let Some(ref note) = record.note {
+                history.notes.insert(i, note.clone());
+            }
+            if record.voided {
+                history.voided.insert(i);
+            }
+        }
+        Ok(history)
+    }
+
+    /// The latest link, or `GENESIS` if no round was revealed yet.
+    pub fn head(&self) -> &str {
+        &self.head
+    }
+
+    /// How many rounds were revealed, the dropped ones included.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// The index of the oldest round kept, 0 unless rounds were dropped, see `MAX_ROUNDS`.
+    pub fn first(&self) -> usize {
+        self.first
+    }
+
+    /// The link preceding the oldest round kept, `GENESIS` unless rounds were dropped.
+    pub fn prev_link(&self) -> &str {
+        self.checkpoints.first().map_or(&self.head, |checkpoint| &checkpoint.prev_link)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// When the latest round was played, if any was.
+    pub fn last_played_at(&self) -> Option<u64> {
+        if self.is_empty() { None } else { Some(self.last_played_at) }
+    }
+
+    /// Names the history as it is: moves with every round revealed and every note changed, and
+    /// tells apart histories that are not continuations of each other, e.g. of other sessions.
+    pub fn version(&self) -> String {
+        format!("{}.{}.{}", self.len, self.edits, &self.head[..16])
+    }
+
+    /// The rounds kept from `start` on, at most `count` of them, oldest first. Only those are
+    /// decoded, from the checkpoint before them on.
+    pub fn range(&self, start: usize, count: usize) -> Vec<RoundRecord> {
+        let end = self.len.min(start.saturating_add(count));
+        let start = start.max(self.first);
+        if start >= end {
+            return Vec::new();
+        }
+        let checkpoint = &self.checkpoints[(start - self.first) / CHECKPOINT_INTERVAL];
+        let mut unpacker = Unpacker {
+            bytes: &self.bytes,
+            pos: checkpoint.offset,
+            prev_link: checkpoint.prev_link.clone(),
+            prev_played_at: checkpoint.prev_played_at,
+        };
+        let first = start / CHECKPOINT_INTERVAL * CHECKPOINT_INTERVAL;
+        (first..end)
+            .map(|index| (index, unpacker.next_record()))
+            .filter(|&(index, _)| index >= start)
+            .map(|(index, mut record)| {
+                record.note = self.notes.get(&index).cloned();
+                record.voided = self.voided.contains(&index);
+                record
+            })
+            .collect()
+    }
+
+    /// Every round kept, oldest first, as by `range`.
+    pub fn records(&self) -> Vec<RoundRecord> {
+        self.range(0, self.len)
+    }
+
+    /// The last revealed round, if any.
+    pub fn last(&self) -> Option<RoundRecord> {
+        self.range(self.len.saturating_sub(1), 1).pop()
+    }
+
+    /// The rounds in chunks between checkpoints, latest chunk first, each decoded on demand.
+    fn chunks_rev<'a>(&'a self) -> impl Iterator<Item = (usize, Vec<RoundRecord>)> + 'a {
+        (0..self.checkpoints.len()).rev().map(move |checkpoint| {
+            let start = self.first + checkpoint * CHECKPOINT_INTERVAL;
+            (start, self.range(start, CHECKPOINT_INTERVAL))
+        })
+    }
+
+    /// Payoffs (1 for a win, 0 for a tie, -1 for a loss) of the last `n` rounds of `kind` not
+    /// voided, oldest first.
+    pub fn outcomes(&self, kind: RoundKind, n: usize) -> Vec<i32> {
+        let mut outcomes: Vec<_> = self.chunks_rev()
+            .flat_map(|(_, records)| records.into_iter().rev())
+            .filter(|record| record.kind == kind && !record.voided)
+            .take(n)
+            .map(|record| record.verdict.payoff())
+            .collect();
+        outcomes.reverse();
+        outcomes
+    }
+
+    /// The human's hands in the last `n` classic rounds, oldest first.
+    pub fn recent_hands(&self, n: usize) -> Vec<Hand> {
+        let mut hands: Vec<Hand> = self.chunks_rev()
+            .flat_map(|(_, records)| records.into_iter().rev())
+            .filter(|record| record.kind == RoundKind::Classic)
+            .take(n)
+            .filter_map(|record| record.human.parse().ok())
+            .collect();
+        hands.reverse();
+        hands
+    }
+
+    /// The rounds matching `filter`, latest first, with their indices: at most `count` of them,
+    /// after skipping the `skip` latest. Chunks are ruled out by their dates and the index bytes
+    /// of their rounds, and decoded only if some round in them may match.
+    pub fn search(&self, filter: &Filter, skip: usize, count: usize)
+        -> Vec<(usize, RoundRecord)>
+    {
+        self.search_before(filter, self.len, skip, count)
+    }
+
+    /// Like `search`, but only among the rounds older than the one at index `before`. Since rounds
+    /// are only ever appended, paging on from the last index found neither skips nor repeats a
+    /// round, however many are played meanwhile.
+    pub fn search_before(&self, filter: &Filter, before: usize, skip: usize, count: usize)
+        -> Vec<(usize, RoundRecord)>
+    {
+        let before = before.min(self.len);
+        let mut skip = skip;
+        let mut found = Vec::new();
+        for (checkpoint, chunk) in self.checkpoints.iter().enumerate().rev() {
+            if found.len() >= count {
+                break;
+            }
+            let start = self.first + checkpoint * CHECKPOINT_INTERVAL;
+            let end = before.min(start + CHECKPOINT_INTERVAL);
+            if start >= end {
+                continue;
+            }
+            let tags = &self.tags[start - self.first..end - self.first];
+            if !filter.may_match_chunk(chunk) || !tags.iter().any(|&tag| filter.matches_tag(tag)) {
+                continue;
+            }
+            let records = self.range(start, end - start);
+            for (offset, record) in records.into_iter().enumerate().rev() {
+                if !filter.matches_tag(tags[offset])
+                    || !filter.matches_time(record.played_at)
+                {
+                    continue;
+                }
+                if skip > 0 {
+                    skip -= 1;
+                } else if found.len() < count {
+                    found.push((start + offset, record));
+                }
+            }
+        }
+        found
+    }
+
+    fn index_of(&self, link: &str) -> Option<usize> {
+        self.chunks_rev()
+            .filter_map(|(start, records)| {
+                records.iter().rposition(|record| record.link == link).map(|i| start + i)
+            })
+            .next()
+    }
+
+    /// Looks a round up by its link, which serves as its receipt.
+    pub fn by_link(&self, link: &str) -> Option<RoundRecord> {
+        self.index_of(link).and_then(|index| self.range(index, 1).pop())
+    }
+
+    /// The proof of the round with this link, see `proof`.
+    pub fn proof(&self, link: &str) -> Option<RoundProof> {
+        let index = self.index_of(link)?;
+        let start = index.saturating_sub(1);
+        let mut records = self.range(start, index + 1 - start);
+        let record = records.pop()?;
+        let prev_link = records.pop().map_or_else(|| self.prev_link().to_owned(), |prev| prev.link);
+        RoundProof::from_record(&record, &prev_link)
+    }
+
+    /// Appends a round played just now against `opponent`, the computer's strategy if known.
+    pub fn push(&mut self, kind: RoundKind, human: &[Hand], computer: &[Hand], verdict: Verdict,
+                random: &str, digest: &str, latency: Option<Latency>,
+                opponent: Option<Strategy>) {
+        self.pack(kind, human, computer, verdict, random, digest, unix_time(), latency, opponent);
+    }
+
+    /// Appends a round, packed as `Unpacker::next_record` reads it back, and indexes it.
+    fn pack(&mut self, kind: RoundKind, human: &[Hand], computer: &[Hand], verdict: Verdict,
+            random: &str, digest: &str, played_at: u64, latency: Option<Latency>,
+            opponent: Option<Strategy>) {
+        debug_assert!(human.len() == hand_count(kind) && computer.len() == hand_count(kind));
+        if self.len % CHECKPOINT_INTERVAL == 0 {
+            if self.len - self.first >= MAX_ROUNDS {
+                self.drop_oldest_chunk();
+            }
+            self.checkpoints.push(Checkpoint {
+                offset: self.bytes.len(),
+                prev_link: self.head.clone(),
+                prev_played_at: self.last_played_at,
+                earliest: played_at,
+                latest: played_at,
+            });
+        }
+        {
+            let chunk = self.checkpoints.last_mut().unwrap();
+            chunk.earliest = chunk.earliest.min(played_at);
+            chunk.latest = chunk.latest.max(played_at);
+        }
+        self.tags.push(tag(verdict, human, opponent));
+
+        let committed = commitment::join_hands(computer);
+        let nonce = hex_to_bytes(random).filter(|bytes| bytes_to_hex(bytes) == random);
+        let kept_digest = commitment::digest_str(random, &committed) != digest;
+        let mut flags = verdict_bits(verdict) << 1;
+        if kind == RoundKind::Chaos {
+            flags |= CHAOS;
+        }
+        if nonce.is_none() {
+            flags |= RAW_NONCE;
+        }
+        if kept_digest {
+            flags |= KEPT_DIGEST;
+        }
+        if latency.is_some() {
+            flags |= TIMED;
+        }
+        self.bytes.push(flags);
+        for chunk in human.iter().chain(computer).collect::<Vec<_>>().chunks(4) {
+            let bits = chunk.iter().enumerate()
+                .fold(0, |bits, (i, hand)| bits | (hand.index() as u8) << (2 * i));
+            self.bytes.push(bits);
+        }
+        match nonce {
+            Some(ref nonce) => write_bytes(&mut self.bytes, nonce),
+            None => write_bytes(&mut self.bytes, random.as_bytes()),
+        }
+        write_varint(&mut self.bytes, zigzag(played_at.wrapping_sub(self.last_played_at) as i64));
+        if let Some(latency) = latency {
+            write_varint(&mut self.bytes, latency.decided_ms);
+            write_varint(&mut self.bytes, latency.server_us);
+        }
+        if kept_digest {
+            write_bytes(&mut self.bytes, digest.as_bytes());
+        }
+
+        self.head = commitment::chain_link(&self.head, random, &committed,
+                                           &commitment::join_hands(human), verdict);
+        self.last_played_at = played_at;
+        self.len += 1;
+    }
+
+    /// Drops the oldest chunk of rounds kept, with their notes.
+    fn drop_oldest_chunk(&mut self) {
+        self.checkpoints.remove(0);
+        let dropped = self.checkpoints.first().map_or(self.bytes.len(), |next| next.offset);
+        self.bytes.drain(..dropped);
+        for checkpoint in &mut self.checkpoints {
+            checkpoint.offset -= dropped;
+        }
+        self.dropped_bytes += dropped;
+        self.tags.drain(..CHECKPOINT_INTERVAL);
+        self.first += CHECKPOINT_INTERVAL;
+        self.notes = self.notes.split_off(&self.first);
+        self.voided = self.voided.split_off(&self.first);
+    }
+
+    /// Where the history stands now.
+    pub fn mark(&self) -> Mark {
+        Mark {
+            len: self.len,
+            bytes: self.bytes.len(),
+            checkpoints: self.checkpoints.len(),
+            first: self.first,
+            dropped_bytes: self.dropped_bytes,
+            last_checkpoint: self.checkpoints.last().cloned(),
+            notes: self.notes.clone(),
+            voided: self.voided.clone(),
+            last_played_at: self.last_played_at,
+            head: self.head.clone(),
+            edits: self.edits,
+        }
+    }
+
+    /// Takes back the rounds appended and the notes changed since `mark`, of this history. The
+    /// rounds dropped meanwhile stay dropped.
+    pub fn rewind(&mut self, mark: Mark) {
+        debug_assert!(mark.len <= self.len, "rewinding to a mark of another history");
+        let dropped_bytes = self.dropped_bytes - mark.dropped_bytes;
+        let dropped_chunks = (self.first - mark.first) / CHECKPOINT_INTERVAL;
+        self.bytes.truncate(mark.bytes - dropped_bytes);
+        self.checkpoints.truncate(mark.checkpoints - dropped_chunks);
+        if let Some(mut checkpoint) = mark.last_checkpoint {
+            checkpoint.offset -= dropped_bytes;
+            *self.checkpoints.last_mut().unwrap() = checkpoint;
+        }
+        self.tags.truncate(mark.len - self.first);
+        let mut notes = mark.notes;
+        self.notes = notes.split_off(&self.first);
+        let mut voided = mark.voided;
+        self.voided = voided.split_off(&self.first);
+        self.len = mark.len;
+        self.last_played_at = mark.last_played_at;
+        self.head = mark.head;
+        self.edits = mark.edits;
+    }
+
+    /// Voids the latest round of `kind` for an arcade redo, and returns it, unless there is none.
+    pub fn void_last(&mut self, kind: RoundKind) -> Option<RoundRecord> {
+        let (index, mut record) = self.chunks_rev()
+            .flat_map(|(start, records)| {
+                records.into_iter().enumerate().rev().map(move |(i, record)| (start + i, record))
+            })
+            .find(|&(_, ref record)| record.kind == kind)?;
+        self.voided.insert(index);
+        self.edits += 1;
+        record.voided = true;
+        Some(record)
+    }
+
+    /// Sets or, if `note` is blank, clears the note on the round with this link. Returns whether
+    /// there is such a round; notes longer than `MAX_NOTE_LEN` are cut short.
+    pub fn annotate(&mut self, link: &str, note: &str) -> bool {
+        let index = match self.index_of(link) {
+            Some(index) => index,
+            None => return false,
+        };
+        let note = note.trim();
+        if note.is_empty() {
+            self.notes.remove(&index);
+        } else {
+            self.notes.insert(index, note.chars().take(MAX_NOTE_LEN).collect());
+        }
+        self.edits += 1;
+        true
+    }
+}
+
+/// A session's history as served by `GET /api/v1/export`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Export {
+    pub user_name: String,
+    pub chain_head: String,
+    /// The index of the first of `rounds`, see `History::first`.
+    #[serde(default)]
+    pub first_round: usize,
+    /// The link preceding the first of `rounds`, see `History::prev_link`.
+    #[serde(default = "genesis")]
+    pub prev_link: String,
+    pub rounds: Vec<RoundRecord>,
+}
+
+/// `GENESIS`, e.g. for the exports and backups from before rounds were dropped.
+pub fn genesis() -> String {
+    GENESIS.to_owned()
+}
+
+/// Checks every commitment, verdict and chain link of an export. Returns a description of the
+/// first inconsistency found.
+pub fn verify(export: &Export) -> Result<(), String> {
+    let mut prev = export.prev_link.clone();
+    for (i, round) in (export.first_round..).zip(&export.rounds) {
+        if commitment::digest_str(&round.random, &round.computer) != round.digest {
+            return Err(format!("round {}: the revealed hand does not match its commitment", i));
+        }
+        let (human, computer) = match (parse_hands(&round.human, round.kind),
+                                       parse_hands(&round.computer, round.kind)) {
+            (Some(human), Some(computer)) => (human, computer),
+            _ => return Err(format!("round {}: unexpected hands", i)),
+        };
+        if Verdict::overall(&human, &computer) != round.verdict {
+            return Err(format!("round {}: the verdict does not follow from the hands", i));
+        }
+        let link = commitment::chain_link(&prev, &round.random, &round.computer, &round.human,
+                                          round.verdict);
+        if link != round.link {
+            return Err(format!("round {}: broken chain link", i));
+        }
+        prev = link;
+    }
+    if prev != export.chain_head {
+        return Err("the chain head does not match the last round".to_owned());
+    }
+    Ok(())
+}
@@ -0,0 +1,64 @@
+//! Typed ids and tokens
+//!
+//! A session is found by its `UserId`, which the login cookie carries, and an API key is a
+//! `SessionToken` standing in for the session it was issued to. Both were plain strings, as were
+//! user names, invite codes and match ids, so nothing kept one from being passed for another. The
+//! types tell them apart; they serialize as the bare string, so cookies keep their format.
+//!
+//! A token compares in constant time, see `constant_time_eq`, and its `Debug` is redacted, so that
+//! logging a value that holds one does not give it away.
+
+use std::fmt;
+
+use entropy::Entropy;
+use util::{bytes_to_hex, constant_time_eq};
+
+/// The key of a session in `SESSIONS`.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub struct UserId(String);
+
+impl UserId {
+    /// A new, random id.
+    pub fn generate(entropy: &Entropy) -> UserId {
+        UserId(bytes_to_hex(&entropy.bytes(16)))
+    }
+
+    /// Takes an id kept as text, e.g. in a legacy cookie.
+    pub fn from_string(id: String) -> UserId {
+        UserId(id)
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+/// A secret standing in for a session, i.e. an API key.
+#[derive(Clone, Hash, Serialize, Deserialize)]
+pub struct SessionToken(String);
+
+impl SessionToken {
+    /// A new, random token.
+    pub fn generate(entropy: &Entropy) -> SessionToken {
+        SessionToken(bytes_to_hex(&entropy.bytes(16)))
+    }
+
+    /// Takes a token as the client sent it.
+    pub fn from_string(token: String) -> SessionToken {
+        SessionToken(token)
+    }
+}
+
+impl PartialEq for SessionToken {
+    fn eq(&self, other: &SessionToken) -> bool {
+        constant_time_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for SessionToken {}
+
+impl fmt::Debug for SessionToken {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("SessionToken(<redacted>)")
+    }
+}
@@ -0,0 +1,1564 @@
+#![feature(plugin, decl_macro, custom_derive, proc_macro_non_items)]
+#![plugin(rocket_codegen)]
+
+extern crate base64;
+#[macro_use] extern crate lazy_static;
+extern crate cookie;
+#[cfg(feature = "templates")]
+extern crate qrcode;
+extern crate rand;
+extern crate ring;
+#[cfg(feature = "scripting")]
+extern crate rhai;
+extern crate rocket;
+#[macro_use] extern crate rocket_contrib;
+extern crate serde;
+#[macro_use] extern crate serde_derive;
+extern crate serde_json;
+extern crate sha2;
+extern crate untrusted;
+
+#[cfg(feature = "templates")]
+mod about;
+mod alerts;
+pub mod account;
+mod admin;
+mod analytics;
+mod anticheat;
+mod api;
+mod arcade;
+#[cfg(feature = "templates")]
+mod assets;
+pub mod backup;
+pub mod commitment;
+mod conditional;
+mod cookie_policy;
+pub mod credentials;
+#[cfg(feature = "templates")]
+pub mod csrf;
+mod email;
+#[cfg(not(feature = "templates"))]
+mod embedded;
+pub mod entropy;
+mod escrow;
+pub mod events;
+mod experiment;
+mod export;
+#[cfg(debug_assertions)]
+mod faults;
+mod feed;
+pub mod flash;
+#[cfg(feature = "templates")]
+mod handoff;
+pub mod ids;
+pub mod locale;
+#[cfg(feature = "templates")]
+mod lockout;
+mod maintenance;
+mod metrics;
+pub mod game;
+pub mod history;
+mod moderation;
+mod notification;
+mod onboarding;
+pub mod params;
+pub mod profile;
+pub mod proof;
+pub mod rating;
+#[cfg(feature = "templates")]
+mod pages;
+pub mod passkey;
+pub mod pow;
+mod poison;
+mod proxy;
+#[cfg(feature = "templates")]
+pub mod pvp;
+#[cfg(feature = "templates")]
+mod qr;
+mod quota;
+#[cfg(feature = "templates")]
+mod recovery;
+pub mod relay;
+#[cfg(feature = "templates")]
+mod render;
+pub mod revocation;
+mod rules;
+mod scheduler;
+#[cfg(feature = "scripting")]
+mod scripting;
+mod security;
+pub mod shard;
+pub mod simulation;
+mod snapshot;
+pub mod strategy;
+pub mod store;
+#[cfg(feature = "templates")]
+mod team;
+mod throttle;
+mod tournament;
+pub mod util;
+mod webhook;
+mod widget;
+mod workers;
+
+use std::collections::HashMap;
+#[cfg(feature = "templates")]
+use std::mem;
+use std::net::SocketAddr;
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
+use std::sync::{Mutex, Once, RwLockWriteGuard, ONCE_INIT};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rocket::fairing::AdHoc;
+use rocket::http::Cookies;
+use rocket::outcome::IntoOutcome;
+use rocket::request::{self, FromRequest, Request, State};
+use rocket::response::{Flash, Redirect};
+#[cfg(feature = "templates")]
+use rocket::response::{self, Responder};
+
+use admin::AdminToken;
+use arcade::Arcade;
+use commitment::MatchSeed;
+#[cfg(feature = "templates")]
+use commitment::RevealPadding;
+use game::{Hand, MultiRound, Round, RoundKind, Scoreboard, Strategy, Verdict, MULTI_HANDS};
+use history::{History, Latency, RoundRecord};
+use ids::UserId;
+use email::WebhookSecret;
+use entropy::Entropy;
+use events::Event;
+use export::ExportColumns;
+use flash::Notice;
+use moderation::{Ban, Bans};
+use notification::{Notification, Outbox};
+use onboarding::Tutorial;
+use poison::Recover;
+use proxy::ProxyConfig;
+use widget::{CorsOrigin, EventStreams};
+use quota::Quotas;
+use security::SecurityPolicy;
+use shard::{Shard, ShardedMap};
+use revocation::Revocations;
+use rules::{HouseRules, RuleKind};
+use snapshot::Snapshot;
+use strategy::{Adaptive, Opponent, PlayerModel, Regret, Rotation};
+use util::{gen_random_bytes, unix_time};
+
+// See https://github.com/SergioBenitez/Rocket/issues/693
+
+/// The `session_shards` config extra; 0, i.e. a single shard, until configured.
+static SESSION_SHARDS: AtomicUsize = ATOMIC_USIZE_INIT;
+
+lazy_static! {
+    // User ID -> Session, in as many shards as `session_shards` said when first used
+    static ref SESSIONS: ShardedMap<Session, UserId> =
+        ShardedMap::new(SESSION_SHARDS.load(Ordering::SeqCst));
+    // "ip:<address> name:<user name>", "ip:<address>" or "name:<user name>" -> failed logins, see
+    // `lockout`
+    #[cfg(feature = "templates")]
+    static ref LOGIN_ATTEMPTS: ShardedMap<lockout::Attempts> =
+        ShardedMap::new(SESSION_SHARDS.load(Ordering::SeqCst));
+    // User name -> sessions in `SESSIONS`, see `session_count`
+    static ref SESSION_COUNTS: Mutex<HashMap<String, usize>> = Mutex::new(HashMap::new());
+}
+
+/// Pending classic rounds kept per session besides the latest.
+const MAX_PARKED_ROUNDS: usize = 8;
+/// How soon after a classic round is played the same hand against it is taken for the same
+/// submission again, e.g. a double click, rather than refused. See `Session::repeated_play`.
+const REPEAT_WINDOW: Duration = Duration::from_millis(500);
+/// Past rounds against the house scripts shown to the one picking the next hand.
+const SCRIPT_MEMORY: usize = 100;
+
+/// A pending classic round set aside, with what the session knew of it.
+#[derive(Clone)]
+struct ParkedRound {
+    round: Round,
+    opponent: Option<Opponent>,
+    committed_at: Option<Instant>,
+}
+
+/// What only lasts while logged in. Lifetime stats are kept in the user's `profile::UserProfile`.
+struct Session {
+    user_name: String,
+    score: Scoreboard,
+    chaos_score: Scoreboard,
+    last_round: Option<Round>,
+    /// Classic rounds committed to before `last_round` and not played yet, e.g. shown in other
+    /// tabs, oldest first. See `resume_round`.
+    parked_rounds: Vec<ParkedRound>,
+    last_multi_round: Option<MultiRound>,
+    /// When the pending classic and chaos rounds were committed to, for their `Latency`. Unknown
+    /// for a round restored from a snapshot.
+    round_committed_at: Option<Instant>,
+    multi_round_committed_at: Option<Instant>,
+    seeded_match: Option<MatchSeed>,
+    opponent: Opponent,
+    /// When set, `opponent` is drawn from it anew for every classic round.
+    rotation: Option<Rotation>,
+    /// Who committed to the pending classic round; `None` for seeded matches.
+    round_opponent: Option<Opponent>,
+    /// Classic rounds against each opponent, indexed like `Opponent::ALL`. Redone rounds count.
+    by_opponent: [Scoreboard; 3],
+    practice: bool,
+    /// Fun mode, when on: classic rounds come from its meta-strategy, whatever the opponent.
+    adaptive: Option<Adaptive>,
+    /// The house script picked as the opponent, if any, by name; see `scripting`.
+    script: Option<String>,
+    /// Whether classic rounds come with a backup, so that ties go to a sudden throw.
+    sudden_throws: bool,
+    /// Whether the pending classic round is the sudden throw of a tie.
+    in_sudden_throw: bool,
+    arcade: Option<Arcade>,
+    /// While the player goes through the tutorial.
+    tutorial: Option<Tutorial>,
+    house_rules: HouseRules,
+    /// The hand named by the hidden hint on the page of the pending classic round.
+    decoy: Option<Hand>,
+    model: PlayerModel,
+    regret: Regret,
+    history: History,
+    /// When the latest classic round was played, see `repeated_play`.
+    last_played_at: Option<Instant>,
+    /// Whether the session proved it belongs to the owner of its name: it opened the account, or
+    /// logged in with a recovery code or a passkey. Names alone prove nothing, since anyone can
+    /// log in under any name, so only verified sessions see or issue recovery codes.
+    verified: bool,
+    /// When the session logged in with a passkey, if it did, see `account`.
+    passkey_login_at: Option<Instant>,
+    started: Instant,
+    // Behind a mutex so that the `User` guard can touch it under the read lock.
+    last_active: Mutex<Instant>,
+}
+
+// By hand, for `last_active`.
+impl Clone for Session {
+    fn clone(&self) -> Session {
+        let mut session = self.clone_without_history();
+        session.history = self.history.clone();
+        session
+    }
+}
+
+impl Session {
+    /// A copy of everything but the history, which starts empty: what `SessionGuard` rolls back
+    /// to, along with a `history::Mark`.
+    fn clone_without_history(&self) -> Session {
+        Session {
+            user_name: self.user_name.clone(),
+            score: self.score,
+            chaos_score: self.chaos_score,
+            last_round: self.last_round.clone(),
+            parked_rounds: self.parked_rounds.clone(),
+            last_multi_round: self.last_multi_round.clone(),
+            round_committed_at: self.round_committed_at,
+            multi_round_committed_at: self.multi_round_committed_at,
+            seeded_match: self.seeded_match.clone(),
+            opponent: self.opponent,
+            rotation: self.rotation,
+            round_opponent: self.round_opponent,
+            by_opponent: self.by_opponent,
+            practice: self.practice,
+            adaptive: self.adaptive,
+            script: self.script.clone(),
+            sudden_throws: self.sudden_throws,
+            in_sudden_throw: self.in_sudden_throw,
+            arcade: self.arcade.clone(),
+            tutorial: self.tutorial.clone(),
+            house_rules: self.house_rules.clone(),
+            decoy: self.decoy,
+            model: self.model.clone(),
+            regret: self.regret,
+            history: History::new(),
+            last_played_at: self.last_played_at,
+            verified: self.verified,
+            passkey_login_at: self.passkey_login_at,
+            started: self.started,
+            last_active: Mutex::new(self.last_active()),
+        }
+    }
+}
+
+impl Session {
+    pub fn new(user_name: String) -> Session {
+        Session {
+            user_name: user_name,
+            score: Scoreboard::default(),
+            chaos_score: Scoreboard::default(),
+            last_round: None,
+            parked_rounds: Vec::new(),
+            last_multi_round: None,
+            round_committed_at: None,
+            multi_round_committed_at: None,
+            seeded_match: None,
+            opponent: Opponent::Random,
+            rotation: None,
+            round_opponent: None,
+            by_opponent: [Scoreboard::default(); 3],
+            practice: false,
+            adaptive: None,
+            script: None,
+            sudden_throws: false,
+            in_sudden_throw: false,
+            arcade: None,
+            tutorial: None,
+            house_rules: HouseRules::default(),
+            decoy: None,
+            model: PlayerModel::new(),
+            regret: Regret::default(),
+            history: History::new(),
+            last_played_at: None,
+            verified: false,
+            passkey_login_at: None,
+            started: Instant::now(),
+            last_active: Mutex::new(Instant::now()),
+        }
+    }
+
+    pub fn touch(&self) {
+        *self.last_active.lock().recover() = Instant::now();
+    }
+
+    pub fn last_active(&self) -> Instant {
+        *self.last_active.lock().recover()
+    }
+
+    /// Called when the session is removed, for whatever reason.
+    pub fn end(self) {
+        uncount_session(&self.user_name);
+        events::publish(Event::SessionEnded {
+            length: self.last_active() - self.started,
+            analytics_opt_out: profile::opted_out_of_analytics(&self.user_name),
+            user_name: self.user_name,
+        });
+    }
+
+    /// The round the computer commits to next, after the human played `last_human`. Uniformly
+    /// random rounds come from the pre-published pool; seeded matches only advance once the round
+    /// is installed.
+    fn upcoming_round(&self, last_human: Option<Hand>) -> Round {
+        match self.seeded_match {
+            Some(ref seed) => {
+                let (nonce, hand) = seed.derive(seed.round_count());
+                Round::new(hand, &nonce, Strategy::Seeded)
+            }
+            None if self.tutorial.is_some() => {
+                let hand = self.tutorial.as_ref().unwrap().hand();
+                Round::new(hand, &gen_random_bytes(32), Strategy::Scripted)
+            }
+            None if self.adaptive.is_some() => {
+                let adaptive = self.adaptive.as_ref().unwrap();
+                let hand = metrics::time_decision(Strategy::Adaptive.name(), || {
+                    adaptive.pick(&self.model)
+                });
+                Round::new(hand, &gen_random_bytes(32), Strategy::Adaptive)
+            }
+            None if self.script.is_some() => {
+                let past = self.script_past(last_human);
+                match script_hand(self.script.as_ref().unwrap(), &past) {
+                    Some(hand) => Round::new(hand, &gen_random_bytes(32), Strategy::House),
+                    // A script that failed leaves the round to the uniform strategy.
+                    None => metrics::time_decision(Strategy::Uniform.name(), feed::take),
+                }
+            }
+            None if self.opponent == Opponent::Random => {
+                metrics::time_decision(Strategy::Uniform.name(), feed::take)
+            }
+            None => {
+                let strategy = self.opponent.strategy(last_human);
+                let hand = metrics::time_decision(strategy.name(), || {
+                    self.opponent.pick(last_human)
+                });
+                Round::new(hand, &gen_random_bytes(32), strategy)
+            }
+        }
+    }
+
+    /// The classic rounds against the house scripts, the computer's hand first, as scripts see
+    /// them: the last `SCRIPT_MEMORY` of the history, then the pending one if `last_human` was just
+    /// played against it. Whichever script played them.
+    fn script_past(&self, last_human: Option<Hand>) -> Vec<(Option<Hand>, Option<Hand>)> {
+        let filter = history::Filter { opponent: Some(Strategy::House), ..Default::default() };
+        let mut past: Vec<_> = self.history.search(&filter, 0, SCRIPT_MEMORY).into_iter().rev()
+            .map(|(_, record)| (record.computer.parse().ok(), record.human.parse().ok()))
+            .collect();
+        if let (Some(round), Some(hand)) = (self.last_round.as_ref(), last_human) {
+            if round.strategy == Strategy::House {
+                past.push((Some(round.computer), Some(hand)));
+            }
+        }
+        past
+    }
+
+    /// Draws the opponent of the next classic round from the rotation, if any. Seeded matches
+    /// keep to their seed.
+    fn rotate(&mut self) {
+        if self.seeded_match.is_some() {
+            return;
+        }
+        if let Some(rotation) = self.rotation {
+            self.opponent = rotation.draw();
+        }
+    }
+
+    fn install_round(&mut self, round: Round) {
+        self.park_pending();
+        // Seeded matches stick to the rounds their seed derives.
+        let round = if self.sudden_throws && self.seeded_match.is_none() {
+            round.with_backup()
+        } else {
+            round
+        };
+        self.decoy = None;
+        self.in_sudden_throw = false;
+        self.round_opponent = match self.seeded_match {
+            Some(_) => None,
+            None if round.strategy == Strategy::Scripted => None,
+            // Fun mode plays whoever the opponent is.
+            None if round.strategy == Strategy::Adaptive => None,
+            None if round.strategy == Strategy::House => None,
+            None => Some(self.opponent),
+        };
+        if let Some(ref mut seed) = self.seeded_match {
+            seed.advance();
+        }
+        if let Some(ref mut arcade) = self.arcade {
+            arcade.new_round();
+        }
+        self.publish_commitment(&round);
+        if let Some(ref backup) = round.backup {
+            self.publish_commitment(backup);
+        }
+        self.last_round = Some(round);
+        self.round_committed_at = Some(Instant::now());
+    }
+
+    /// Moves on to the sudden throw of a tied round, against the same opponent.
+    fn install_sudden_throw(&mut self, round: Round) {
+        self.decoy = None;
+        self.in_sudden_throw = true;
+        if let Some(ref mut arcade) = self.arcade {
+            arcade.new_round();
+        }
+        self.last_round = Some(round);
+        self.round_committed_at = Some(Instant::now());
+    }
+
+    fn publish_commitment(&self, round: &Round) {
+        events::publish(Event::RoundCommitted {
+            user_name: self.user_name.clone(),
+            digest: round.digest.clone(),
+            nonce: round.random_bytes.clone(),
+            committed: round.computer.as_ref().to_owned(),
+        });
+    }
+
+    /// Keeps the pending classic round aside for `resume_round`, unless it is part of a seeded
+    /// match or a sudden throw: those are discarded when replaced, as their order matters.
+    fn park_pending(&mut self) {
+        if self.seeded_match.is_some() || self.in_sudden_throw {
+            return;
+        }
+        let round = match self.last_round.take() {
+            Some(round) => round,
+            None => return,
+        };
+        if self.parked_rounds.len() >= MAX_PARKED_ROUNDS {
+            self.parked_rounds.remove(0);
+        }
+        self.parked_rounds.push(ParkedRound {
+            round: round,
+            opponent: self.round_opponent,
+            committed_at: self.round_committed_at,
+        });
+    }
+
+    /// Makes the classic round committed to as `digest` the one `play` resolves, so that a page
+    /// left open in another tab plays the round it shows, not the latest one. Returns whether
+    /// that round is still pending.
+    ///
+    /// The round it replaces is parked in turn. Parked rounds wait for the seeded match or sudden
+    /// throw in progress to be over.
+    pub fn resume_round(&mut self, digest: &str) -> bool {
+        let is_digest = |round: &Round| commitment::same_digest(digest, &round.digest);
+        if self.last_round.as_ref().map_or(false, &is_digest) {
+            return true;
+        }
+        if self.seeded_match.is_some() || self.in_sudden_throw {
+            return false;
+        }
+        let index = match self.parked_rounds.iter().position(|parked| is_digest(&parked.round)) {
+            Some(index) => index,
+            None => return false,
+        };
+        let parked = self.parked_rounds.remove(index);
+        self.park_pending();
+        self.decoy = None;
+        if let Some(ref mut arcade) = self.arcade {
+            arcade.new_round();
+        }
+        self.round_opponent = parked.opponent;
+        self.round_committed_at = parked.committed_at;
+        self.last_round = Some(parked.round);
+        true
+    }
+
+    /// The classic round that `hand` against `digest` resolved, if that was the latest play and
+    /// less than `window` ago, e.g. `REPEAT_WINDOW`: a double click or a resent request, to be
+    /// answered with the round's result rather than sent to a new round.
+    pub fn repeated_play(&self, digest: &str, hand: Hand, window: Duration)
+        -> Option<RoundRecord>
+    {
+        if self.last_played_at.map_or(true, |at| at.elapsed() >= window) {
+            return None;
+        }
+        self.history.last().filter(|record| {
+            record.kind == RoundKind::Classic
+                && commitment::same_digest(digest, &record.digest)
+                && record.human == hand.as_ref()
+        })
+    }
+
+    /// Commits the computer to a new round, parking any unresolved one.
+    pub fn start_round(&mut self) -> &Round {
+        self.rotate();
+        let round = self.upcoming_round(self.model.last());
+        self.install_round(round);
+        self.last_round.as_ref().unwrap()
+    }
+
+    /// Resolves the pending round against the human's hand, updates the scoreboard and starts the
+    /// next round. Returns `None` if no round is pending.
+    ///
+    /// A tie of a round with a backup is revealed and chained into the history, but not counted:
+    /// its sudden throw is installed instead of the next round, see `in_sudden_throw`.
+    ///
+    /// Everything that can fail is done before the session is touched, so a round is either fully
+    /// recorded (score, model, history and next round) or not at all.
+    pub fn play(&mut self, hand: Hand) -> Option<(Round, Verdict)> {
+        let received = Instant::now();
+        let (verdict, sudden) = {
+            let pending = self.last_round.as_ref()?;
+            let verdict = Verdict::of(hand, pending.computer);
+            (verdict, verdict == Verdict::Tie && pending.backup.is_some())
+        };
+        let strategy = self.last_round.as_ref().unwrap().strategy;
+        let scripted = strategy == Strategy::Scripted;
+        if scripted && !sudden {
+            self.advance_tutorial();
+        }
+        // Fun mode picks the next hand from the model and the level this round leaves them at.
+        self.model.observe(hand);
+        if strategy == Strategy::Adaptive && !sudden {
+            if let Some(ref mut adaptive) = self.adaptive {
+                adaptive.record(verdict);
+            }
+        }
+        let next = if sudden {
+            None
+        } else {
+            self.rotate();
+            Some(self.upcoming_round(Some(hand)))
+        };
+
+        let mut round = self.last_round.take().unwrap();
+        let unpredictable = !scripted
+            && strategy != Strategy::Adaptive
+            && strategy != Strategy::House
+            && self.round_opponent.map_or(true, |o| o == Opponent::Random)
+            && self.arcade.as_ref().map_or(true, |arcade| arcade.peeked.is_none());
+        self.house_rules.judge(hand);
+        self.regret.record(hand, round.computer);
+        let latency = self.round_committed_at.map(|at| Latency::since(at, received));
+        self.history.push(RoundKind::Classic, &[hand], &[round.computer], verdict,
+                          &round.random_bytes, &round.digest, latency, Some(round.strategy));
+        self.last_played_at = Some(received);
+        match next {
+            Some(next) => {
+                self.scoreboard_mut(RoundKind::Classic).record(verdict);
+                if let Some(opponent) = self.round_opponent {
+                    self.by_opponent[opponent.index()].record(verdict);
+                }
+                if let Some(ref mut arcade) = self.arcade {
+                    arcade.record(verdict);
+                }
+                self.publish_round(RoundKind::Classic, &[hand], &[round.computer], verdict,
+                                   unpredictable, Some(round.strategy));
+                self.install_round(next);
+            }
+            None => {
+                let backup = round.backup.take().unwrap();
+                self.install_sudden_throw(*backup);
+            }
+        }
+        Some((round, verdict))
+    }
+
+    /// Counts a tutorial round as played, ending the tutorial after the last one.
+    fn advance_tutorial(&mut self) {
+        let done = match self.tutorial {
+            Some(ref mut tutorial) => tutorial.advance(),
+            None => return,
+        };
+        if done {
+            self.tutorial = None;
+            profile::complete_onboarding(&self.user_name);
+        }
+    }
+
+    /// The computer's strategy for the pending classic round, indexed like `Hand::CHOICES`.
+    pub fn computer_strategy(&self) -> [f64; 3] {
+        let adaptive = self.adaptive.as_ref().filter(|_| {
+            self.last_round.as_ref().map_or(false, |round| round.strategy == Strategy::Adaptive)
+        });
+        match (self.round_opponent, adaptive) {
+            (_, Some(adaptive)) => adaptive.distribution(&self.model),
+            (Some(opponent), None) => opponent.distribution(self.model.last()),
+            (None, None) => strategy::computer_distribution(),
+        }
+    }
+
+    /// The opponent that beat the human most often, if any did.
+    pub fn toughest_opponent(&self) -> Option<(Opponent, &Scoreboard)> {
+        Opponent::ALL.iter()
+            .map(|&opponent| (opponent, &self.by_opponent[opponent.index()]))
+            .filter(|&(_, score)| score.losses > 0)
+            .fold(None, |toughest, (opponent, score)| match toughest {
+                Some((_, most)) if most.losses >= score.losses => toughest,
+                _ => Some((opponent, score)),
+            })
+    }
+
+    /// The decoy hint for the pending classic round, picked the first time a page asks for it.
+    pub fn plant_decoy(&mut self) -> Hand {
+        *self.decoy.get_or_insert_with(Hand::random)
+    }
+
+    /// Voids the last classic round with an arcade redo, if it was a loss. Returns whether it did.
+    ///
+    /// The round stays in the history and its chain, marked as voided, but is taken out of the
+    /// scoreboards, the regret and the penalty points it cost.
+    pub fn redo(&mut self) -> bool {
+        let redone = match self.arcade {
+            Some(ref mut arcade) => arcade.redo(),
+            None => false,
+        };
+        if !redone {
+            return false;
+        }
+        self.score.losses -= 1;
+        self.house_rules.void_last();
+        if let Some(record) = self.history.void_last(RoundKind::Classic) {
+            if let (Ok(human), Ok(computer)) = (record.human.parse(), record.computer.parse()) {
+                self.regret.void(human, computer);
+            }
+        }
+        events::publish(Event::RoundVoided { user_name: self.user_name.clone() });
+        true
+    }
+
+    /// Turns the house rule `kind` on or off. Returns whether it is on now.
+    pub fn toggle_rule(&mut self, kind: RuleKind) -> bool {
+        if self.house_rules.is_on(kind) {
+            self.house_rules.turn_off(kind);
+            false
+        } else {
+            let recent = self.history.recent_hands(kind.memory());
+            self.house_rules.turn_on(kind, &recent);
+            true
+        }
+    }
+
+    /// Commits the computer to a new chaos round, discarding any unresolved one.
+    pub fn start_chaos_round(&mut self) -> &MultiRound {
+        let round = MultiRound::random();
+        events::publish(Event::RoundCommitted {
+            user_name: self.user_name.clone(),
+            digest: round.digest.clone(),
+            nonce: round.random_bytes.clone(),
+            committed: commitment::join_hands(&round.computer),
+        });
+        self.last_multi_round = Some(round);
+        self.multi_round_committed_at = Some(Instant::now());
+        self.last_multi_round.as_ref().unwrap()
+    }
+
+    /// Like `play`, for chaos rounds. Also returns the verdict of every bout.
+    pub fn play_chaos(&mut self, hands: &[Hand; MULTI_HANDS])
+        -> Option<(MultiRound, [Verdict; MULTI_HANDS], Verdict)>
+    {
+        let received = Instant::now();
+        let round = self.last_multi_round.take()?;
+        let (bouts, verdict) = round.verdict(hands);
+        self.scoreboard_mut(RoundKind::Chaos).record(verdict);
+        let latency = self.multi_round_committed_at.map(|at| Latency::since(at, received));
+        self.history.push(RoundKind::Chaos, hands, &round.computer, verdict,
+                          &round.random_bytes, &round.digest, latency, Some(Strategy::Uniform));
+        self.publish_round(RoundKind::Chaos, hands, &round.computer, verdict, true, None);
+        self.start_chaos_round();
+        Some((round, bouts, verdict))
+    }
+
+    /// Ends the seeded match, if any, and returns its seed to be revealed.
+    pub fn end_match(&mut self) -> Option<MatchSeed> {
+        let seed = self.seeded_match.take()?;
+        events::publish(Event::MatchFinished {
+            user_name: self.user_name.clone(),
+            commitment: seed.commitment(),
+            rounds: seed.round_count(),
+        });
+        Some(seed)
+    }
+
+    fn publish_round(&self, kind: RoundKind, human: &[Hand], computer: &[Hand], verdict: Verdict,
+                     unpredictable: bool, strategy: Option<Strategy>) {
+        events::publish(Event::RoundPlayed {
+            user_name: self.user_name.clone(),
+            kind: kind,
+            human: human.to_vec(),
+            computer: computer.to_vec(),
+            verdict: verdict,
+            analytics_opt_out: profile::opted_out_of_analytics(&self.user_name),
+            unpredictable: unpredictable,
+            decoy: self.decoy,
+            strategy: strategy,
+            ranked: strategy.map_or(true, |strategy| strategy.is_ranked()),
+        });
+    }
+
+    fn scoreboard_mut(&mut self, kind: RoundKind) -> &mut Scoreboard {
+        match kind {
+            RoundKind::Classic => &mut self.score,
+            RoundKind::Chaos => &mut self.chaos_score,
+        }
+    }
+}
+
+/// Where sessions are kept, read from the `store` config extra. Only the in-memory store exists
+/// for now.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum StoreBackend {
+    /// With the sessions spread over that many separately locked shards, `session_shards`.
+    Memory { shards: usize },
+}
+
+impl StoreBackend {
+    pub const NAMES: &'static [&'static str] = &["memory"];
+
+    fn from_config(config: &rocket::Config) -> Result<StoreBackend, String> {
+        match config.get_str("store").unwrap_or("memory") {
+            "memory" => match config.get_int("session_shards") {
+                Ok(n) if n > 0 => Ok(StoreBackend::Memory { shards: n as usize }),
+                Ok(n) => Err(format!("`session_shards` must be positive, got {}", n)),
+                Err(_) => Ok(StoreBackend::Memory { shards: shard::DEFAULT_SHARDS }),
+            },
+            other => Err(format!("unknown store backend {:?}", other)),
+        }
+    }
+}
+
+/// What `login` does when `max_sessions` sessions already exist.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum WhenFull {
+    /// Evicts the least recently active session.
+    Evict,
+    /// Shows a "server full" page.
+    Reject,
+}
+
+/// Every shard of `SESSIONS`, write-locked.
+type AllSessions<'a> =
+    shard::All<'a, RwLockWriteGuard<'a, Shard<Session, UserId>>, Session, UserId>;
+
+/// Session capacity, read from the `max_sessions` and `when_full` config extras.
+#[derive(Debug)]
+struct SessionLimits {
+    max_sessions: Option<usize>,
+    when_full: WhenFull,
+}
+
+impl SessionLimits {
+    fn from_config(config: &rocket::Config) -> Result<SessionLimits, String> {
+        let max_sessions = match config.get_int("max_sessions") {
+            Ok(n) if n > 0 => Some(n as usize),
+            Ok(n) => return Err(format!("`max_sessions` must be positive, got {}", n)),
+            Err(_) => None,
+        };
+        let when_full = match config.get_str("when_full").unwrap_or("evict") {
+            "evict" => WhenFull::Evict,
+            "reject" => WhenFull::Reject,
+            other => return Err(format!("`when_full` must be \"evict\" or \"reject\", got {:?}", other)),
+        };
+        Ok(SessionLimits { max_sessions, when_full })
+    }
+
+    /// Makes room for one more session. Returns `false` if the new session must be turned away.
+    fn admit(&self, sessions: &mut AllSessions) -> bool {
+        let max_sessions = match self.max_sessions {
+            Some(n) => n,
+            None => return true,
+        };
+        while sessions.len() >= max_sessions {
+            if self.when_full == WhenFull::Reject {
+                return false;
+            }
+            let idlest = sessions.iter()
+                .min_by_key(|&(_, session)| session.last_active())
+                .map(|(id, _)| id.clone());
+            match idlest {
+                Some(id) => sessions.remove(&id).map(Session::end),
+                None => return false,
+            };
+        }
+        true
+    }
+}
+
+#[derive(FromForm)]
+struct Login {
+    user_name: String,
+}
+
+#[derive(Debug)]
+struct User {
+    id: UserId,
+    name: String,
+    /// See `Session::verified`.
+    verified: bool,
+}
+
+impl<'a, 'r> FromRequest<'a, 'r> for User {
+    type Error = ();
+
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<User, ()> {
+        let mut cookies = request.cookies();
+        let mut maybe_user = None;
+        if let Some(credentials) = credentials::load(&mut cookies) {
+            let user_id = &credentials.user_id;
+            let user_name = credentials.user_name.as_str();
+            if active_ban(request, Some(user_name)).is_some() {
+                return rocket::Outcome::Forward(());
+            }
+            let revoked = request.guard::<State<Revocations>>().succeeded()
+                .map_or(false, |revocations| revocations.is_revoked(user_id));
+            if revoked {
+                return rocket::Outcome::Forward(());
+            }
+            let found = {
+                let sessions = SESSIONS.read(user_id);
+                match sessions.get(user_id) {
+                    Some(session) => {
+                        session.touch();
+                        Some(Some(session.verified).filter(|_| session.user_name == user_name))
+                    }
+                    None => None,
+                }
+            };
+            let verified = match found {
+                Some(verified) => verified,
+                None => recover_session(request, &mut cookies, user_id, user_name),
+            };
+            if let Some(verified) = verified {
+                if credentials.is_due_at(unix_time()) {
+                    credentials::save(&mut cookies, user_id, user_name);
+                }
+                maybe_user = Some(User {
+                    id: user_id.clone(),
+                    name: user_name.to_owned(),
+                    verified: verified,
+                });
+            }
+        }
+        maybe_user.or_forward(())
+    }
+}
+
+/// The logged-in user's session, write-locked for the rest of the request. Forwards like `User`.
+///
+/// The guard is the request's unit of work: the events the request publishes are held back, see
+/// `events::Batch`, and so are the profile updates they bring, and the session is kept as it was
+/// before it was first changed, but for its history, of which a `history::Mark` is kept. Once the
+/// guard is dropped, or once a `Transaction` responded, the request commits: a changed session is
+/// mirrored into the snapshot cookie and the events go out, as the guard releases its lock. A
+/// `Transaction` failing to respond, e.g. on a template error, rolls it all back instead.
+///
+/// Since it holds its shard's lock and the cookies, don't lock `SESSIONS` (or call anything that
+/// does, e.g. `team`) while it is alive, and don't take `Cookies` alongside it.
+struct SessionGuard<'a> {
+    user: User,
+    sessions: shard::WriteGuard<'static, Session, UserId>,
+    cookies: Cookies<'a>,
+    /// The session before the request changed it, `None` while unchanged.
+    before: Option<(Session, history::Mark)>,
+    /// Open until the request commits or rolls back.
+    events: Option<events::Batch>,
+    /// The batch of a committed request, released after `sessions`, since it is declared after it.
+    committed: Option<events::Deferred>,
+}
+
+impl<'a, 'r> FromRequest<'a, 'r> for SessionGuard<'a> {
+    type Error = ();
+
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<SessionGuard<'a>, ()> {
+        let user = request.guard::<User>()?;
+        let sessions = SESSIONS.write(&user.id);
+        if !sessions.contains_key(&user.id) {
+            return rocket::Outcome::Forward(());
+        }
+        rocket::Outcome::Success(SessionGuard {
+            user: user,
+            sessions: sessions,
+            cookies: request.cookies(),
+            before: None,
+            events: Some(events::batch()),
+            committed: None,
+        })
+    }
+}
+
+impl<'a> Deref for SessionGuard<'a> {
+    type Target = Session;
+
+    fn deref(&self) -> &Session {
+        &self.sessions[&self.user.id]
+    }
+}
+
+impl<'a> DerefMut for SessionGuard<'a> {
+    fn deref_mut(&mut self) -> &mut Session {
+        if self.before.is_none() {
+            let session = &self.sessions[&self.user.id];
+            self.before = Some((session.clone_without_history(), session.history.mark()));
+        }
+        self.sessions.get_mut(&self.user.id).unwrap()
+    }
+}
+
+impl<'a> SessionGuard<'a> {
+    /// Keeps what the request did. Does nothing once the request committed or rolled back.
+    fn commit(&mut self) {
+        if self.before.take().is_some() {
+            snapshot::save(&mut self.cookies, &self.sessions[&self.user.id]);
+        }
+        if let Some(events) = self.events.take() {
+            self.committed = Some(events.defer());
+        }
+    }
+
+    /// Undoes what the request did, as far as it can be.
+    #[cfg(feature = "templates")]
+    fn roll_back(&mut self) {
+        if let Some((mut before, mark)) = self.before.take() {
+            if let Some(session) = self.sessions.get_mut(&self.user.id) {
+                mem::swap(&mut before.history, &mut session.history);
+                before.history.rewind(mark);
+                *session = before;
+            }
+        }
+        // Along with the profile updates they bring.
+        self.events = None;
+    }
+}
+
+impl<'a> Drop for SessionGuard<'a> {
+    fn drop(&mut self) {
+        // A panicking request leaves the session to be dropped, see `shard::WriteGuard`, and its
+        // events to be discarded.
+        if !thread::panicking() {
+            self.commit();
+        }
+    }
+}
+
+/// A response with the request's `SessionGuard`, which commits once the response is ready, or
+/// rolls back if it fails. Pages that change the session and then render a template return one,
+/// so that a template error does not leave half of a round recorded.
+#[cfg(feature = "templates")]
+struct Transaction<'a, R> {
+    session: SessionGuard<'a>,
+    responder: R,
+    /// Dropped once the session's lock is released, see `RevealPadding`.
+    padding: Option<RevealPadding>,
+}
+
+#[cfg(feature = "templates")]
+impl<'a, R> Transaction<'a, R> {
+    fn new(session: SessionGuard<'a>, responder: R) -> Transaction<'a, R> {
+        Transaction {
+            session: session,
+            responder: responder,
+            padding: None,
+        }
+    }
+
+    /// Pads the response with `padding`, the way a reveal handler taking it as a guard would.
+    fn padded(mut self, padding: RevealPadding) -> Transaction<'a, R> {
+        self.padding = Some(padding);
+        self
+    }
+}
+
+#[cfg(feature = "templates")]
+impl<'a, 'r, R: Responder<'r>> Responder<'r> for Transaction<'a, R> {
+    fn respond_to(self, request: &Request) -> response::Result<'r> {
+        let Transaction { mut session, responder, padding } = self;
+        let response = responder.respond_to(request);
+        match response {
+            Ok(_) => session.commit(),
+            Err(status) => {
+                eprintln!("Rolled back a request that failed to respond: {}", status);
+                session.roll_back();
+            }
+        }
+        drop(session);
+        drop(padding);
+        response
+    }
+}
+
+/// The ban applying to the request's IP address or to `user_name`, if any.
+fn active_ban(request: &Request, user_name: Option<&str>) -> Option<Ban> {
+    let bans = request.guard::<State<Bans>>().succeeded()?;
+    bans.find(user_name, request.remote().map(|addr| addr.ip()))
+}
+
+/// Ends every session of `user_name`, revoking their API keys too. Returns the ids of the sessions.
+fn end_sessions_of(user_name: &str, revocations: &Revocations) -> Vec<UserId> {
+    let mut sessions = SESSIONS.write_all();
+    let ids: Vec<UserId> = sessions.iter()
+        .filter(|&(_, session)| session.user_name == user_name)
+        .map(|(id, _)| id.clone())
+        .collect();
+    for id in &ids {
+        revocations.revoke(id);
+        sessions.remove(id).map(Session::end);
+    }
+    api::API_KEYS.write().recover().retain(|_, id| !ids.contains(id));
+    ids
+}
+
+/// How many sessions `user_name` has, one per device or browser they logged in on. Kept up to
+/// date as sessions are added and ended, under the lock of `SESSIONS`, rather than counted.
+#[cfg(feature = "templates")]
+fn session_count(user_name: &str) -> usize {
+    SESSION_COUNTS.lock().recover().get(user_name).cloned().unwrap_or(0)
+}
+
+fn count_session(user_name: &str) {
+    *SESSION_COUNTS.lock().recover().entry(user_name.to_owned()).or_insert(0) += 1;
+}
+
+fn uncount_session(user_name: &str) {
+    let mut counts = SESSION_COUNTS.lock().recover();
+    let gone = match counts.get_mut(user_name) {
+        Some(count) => {
+            *count -= 1;
+            *count == 0
+        }
+        None => false,
+    };
+    if gone {
+        counts.remove(user_name);
+    }
+}
+
+/// Counts `sessions` anew, once they were replaced wholesale, e.g. from a backup.
+fn recount_sessions<'a, I: Iterator<Item = &'a Session>>(sessions: I) {
+    let mut counts = SESSION_COUNTS.lock().recover();
+    counts.clear();
+    for session in sessions {
+        *counts.entry(session.user_name.clone()).or_insert(0) += 1;
+    }
+}
+
+/// Whether some session has yet to play the round committed to as `digest`.
+fn is_pending(digest: &str) -> bool {
+    let by_session = {
+        let sessions = SESSIONS.read_all();
+        sessions.values().any(|session| {
+            session.last_round.as_ref().map_or(false, |round| round.digest == digest) ||
+                session.parked_rounds.iter().any(|parked| parked.round.digest == digest) ||
+                session.last_multi_round.as_ref().map_or(false, |round| round.digest == digest)
+        })
+    };
+    #[cfg(feature = "templates")]
+    let by_team = team::is_pending(digest);
+    #[cfg(not(feature = "templates"))]
+    let by_team = false;
+    by_session || by_team || relay::is_pending(digest)
+}
+
+/// Rebuilds a lost session from its snapshot cookie. Returns whether the session exists now.
+fn recover_session(request: &Request, cookies: &mut Cookies, user_id: &UserId, user_name: &str)
+    -> Option<bool>
+{
+    let snapshot = match snapshot::load(cookies) {
+        Some(ref snapshot) if snapshot.user_name != user_name => return None,
+        Some(snapshot) => snapshot,
+        None => return None,
+    };
+    let limits = request.guard::<State<SessionLimits>>().succeeded()?;
+
+    let mut sessions = SESSIONS.write_all();
+    if let Some(session) = sessions.get(user_id) {
+        // Recovered by a concurrent request.
+        return Some(session.verified);
+    }
+    if !limits.admit(&mut sessions) {
+        return None;
+    }
+    let session = Snapshot::restore(snapshot);
+    let verified = session.verified;
+    sessions.insert(user_id.clone(), session);
+    count_session(user_name);
+    drop(sessions);
+    events::publish(Event::SessionStarted { user_name: user_name.to_owned() });
+    Some(verified)
+}
+
+/// Why `open_session` turned a login away.
+enum LoginRefusal {
+    Banned(Ban),
+    /// `max_sessions` is reached, with that many sessions.
+    Full(usize),
+    /// The server is in maintenance mode, see `maintenance`.
+    Maintenance,
+    /// The account has passkeys, so its name alone does not log in, see `passkey`.
+    PasskeyRequired,
+}
+
+/// Starts a session for `user_name` and sets its cookies. It is `verified` if the login proved
+/// ownership of the name, or if it opens the account: nobody played, holds recovery codes or is
+/// logged in under the name yet. Returns its id and whether it is verified.
+fn open_session(cookies: &mut Cookies, user_name: &str, verified: bool, limits: &SessionLimits,
+                bans: &Bans, entropy: &Entropy, remote: Option<SocketAddr>)
+    -> Result<(UserId, bool), LoginRefusal>
+{
+    if let Some(ban) = bans.find(Some(user_name), remote.map(|addr| addr.ip())) {
+        return Err(LoginRefusal::Banned(ban));
+    }
+    if maintenance::is_on() {
+        return Err(LoginRefusal::Maintenance);
+    }
+    let profile = profile::get(user_name);
+    if !verified && profile.as_ref().map_or(false, |profile| !profile.passkeys.is_empty()) {
+        return Err(LoginRefusal::PasskeyRequired);
+    }
+
+    let mut sessions = SESSIONS.write_all();
+    if !limits.admit(&mut sessions) {
+        return Err(LoginRefusal::Full(sessions.len()));
+    }
+
+    let user_id = UserId::generate(entropy);
+    credentials::save(cookies, &user_id, user_name);
+
+    let opens_account = profile.is_none() && !has_recovery_codes(user_name)
+        && !sessions.values().any(|session| session.user_name == user_name);
+    let mut session = Session::new(user_name.to_owned());
+    let verified = verified || opens_account;
+    session.verified = verified;
+    for (experiment, variant) in experiment::assign(user_name) {
+        if experiment.name == "default-opponent" {
+            session.opponent = variant.parse().unwrap_or_default();
+        }
+    }
+    if profile.map_or(true, |profile| !profile.onboarded) {
+        session.tutorial = Some(Tutorial::new());
+    }
+    sessions.insert(user_id.clone(), session);
+    count_session(user_name);
+    drop(sessions);
+    events::publish(Event::SessionStarted { user_name: user_name.to_owned() });
+    Ok((user_id, verified))
+}
+
+/// Notes that the session `user_id` just logged in with a passkey.
+#[cfg(feature = "templates")]
+fn note_passkey_login(user_id: &UserId) {
+    if let Some(session) = SESSIONS.write(user_id).get_mut(user_id) {
+        session.passkey_login_at = Some(Instant::now());
+    }
+}
+
+/// Reads the session `user_id`, if it is alive, under its shard's read lock. Unlike a
+/// `SessionGuard`, it leaves the cookies alone, e.g. to a `FlashMessage`.
+#[cfg(feature = "templates")]
+fn with_session<T, F: FnOnce(&Session) -> T>(user_id: &UserId, f: F) -> Option<T> {
+    SESSIONS.read(user_id).get(user_id).map(f)
+}
+
+/// Sets or clears the note on a round of the session `user_id`, see `History::annotate`.
+#[cfg(feature = "templates")]
+fn annotate(user_id: &UserId, link: &str, note: &str) -> bool {
+    SESSIONS.write(user_id).get_mut(user_id)
+        .map_or(false, |session| session.history.annotate(link, note))
+}
+
+/// Whether the session `user_id` logged in with a passkey less than `window` ago.
+#[cfg(feature = "templates")]
+fn passkey_login_within(user_id: &UserId, window: Duration) -> bool {
+    SESSIONS.read(user_id).get(user_id)
+        .and_then(|session| session.passkey_login_at)
+        .map_or(false, |at| at.elapsed() < window)
+}
+
+#[cfg(feature = "templates")]
+fn has_recovery_codes(user_name: &str) -> bool {
+    recovery::remaining(user_name) > 0
+}
+
+/// There are no recovery codes without the pages.
+#[cfg(not(feature = "templates"))]
+fn has_recovery_codes(_user_name: &str) -> bool {
+    false
+}
+
+/// The names of the house scripts players may pick.
+#[cfg(feature = "scripting")]
+fn house_scripts() -> Vec<String> {
+    scripting::names()
+}
+
+#[cfg(not(feature = "scripting"))]
+fn house_scripts() -> Vec<String> {
+    Vec::new()
+}
+
+#[cfg(feature = "scripting")]
+fn script_hand(name: &str, past: &[(Option<Hand>, Option<Hand>)]) -> Option<Hand> {
+    scripting::pick(name, past)
+}
+
+#[cfg(not(feature = "scripting"))]
+fn script_hand(_name: &str, _past: &[(Option<Hand>, Option<Hand>)]) -> Option<Hand> {
+    None
+}
+
+#[cfg(feature = "templates")]
+const LOGIN_PAGE: &str = "/login";
+/// The embedded page doubles as the login page.
+#[cfg(not(feature = "templates"))]
+const LOGIN_PAGE: &str = "/";
+
+// ===== Routers =====
+
+/// Revokes the session server-side first, so that it stays logged out even if the cookies survive.
+#[post("/logout")]
+fn logout(mut cookies: Cookies, revocations: State<Revocations>) -> Flash<Redirect> {
+    if let Some(credentials) = credentials::load(&mut cookies) {
+        let user_id = &credentials.user_id;
+        revocations.revoke(user_id);
+        let mut sessions = SESSIONS.write(user_id);
+        sessions.remove(user_id).map(Session::end);
+        api::API_KEYS.write().recover().retain(|_, id| id != user_id);
+    }
+
+    credentials::remove(&mut cookies);
+    snapshot::remove(&mut cookies);
+
+    Notice::success("logged-out").to(Redirect::to(LOGIN_PAGE))
+}
+
+/// Hooks the side effects of the game up to its events, once per process.
+fn register_subscribers() {
+    static REGISTER: Once = ONCE_INIT;
+    REGISTER.call_once(|| {
+        events::register(Box::new(analytics::Recorder));
+        events::register(Box::new(escrow::Depositor));
+        events::register(Box::new(anticheat::Watcher));
+        events::register(Box::new(profile::Keeper));
+        events::register(Box::new(export::Versioner));
+        events::register(Box::new(webhook::Dispatcher));
+    });
+}
+
+pub fn rocket() -> rocket::Rocket {
+    register_subscribers();
+    #[cfg(feature = "templates")]
+    scheduler::every("lockout-sweep", Duration::from_secs(3600), lockout::sweep);
+    scheduler::every("alert-sweep", Duration::from_secs(24 * 3600), profile::expire_alerts);
+    feed::prepare();
+    let rocket = rocket::ignite()
+        .attach(AdHoc::on_attach(|rocket| {
+            match ProxyConfig::from_config(rocket.config()) {
+                Ok(proxy) => Ok(rocket.manage(proxy)),
+                Err(e) => {
+                    eprintln!("Invalid proxy settings: {}", e);
+                    Err(rocket)
+                }
+            }
+        }))
+        // First of all, so that every later fairing and guard sees the client's address.
+        .attach(proxy::ForwardedHeaders)
+        .attach(AdHoc::on_attach(|rocket| {
+            match Entropy::from_config(rocket.config()) {
+                Ok(configured) => {
+                    // Keeps whatever was installed, e.g. by a test, unless configured otherwise.
+                    let entropy = configured.unwrap_or_else(entropy::get);
+                    entropy::install(entropy.clone());
+                    Ok(rocket.manage(entropy))
+                }
+                Err(e) => {
+                    eprintln!("Invalid entropy settings: {}", e);
+                    Err(rocket)
+                }
+            }
+        }))
+        .attach(AdHoc::on_attach(|rocket| {
+            match SessionLimits::from_config(rocket.config()) {
+                Ok(limits) => Ok(rocket.manage(limits)),
+                Err(e) => {
+                    eprintln!("Invalid session limits: {}", e);
+                    Err(rocket)
+                }
+            }
+        }))
+        .attach(AdHoc::on_attach(|rocket| {
+            match StoreBackend::from_config(rocket.config()) {
+                Ok(store) => {
+                    let StoreBackend::Memory { shards } = store;
+                    // Only takes effect if no session was stored yet, e.g. by an earlier instance.
+                    SESSION_SHARDS.store(shards, Ordering::SeqCst);
+                    Ok(rocket.manage(store))
+                }
+                Err(e) => {
+                    eprintln!("Invalid store: {}", e);
+                    Err(rocket)
+                }
+            }
+        }))
+        .attach(AdHoc::on_attach(|rocket| {
+            match Quotas::from_config(rocket.config()) {
+                Ok(quotas) => {
+                    let admin_token = AdminToken::from_config(rocket.config());
+                    Ok(rocket.manage(quotas).manage(admin_token))
+                }
+                Err(e) => {
+                    eprintln!("Invalid API quota: {}", e);
+                    Err(rocket)
+                }
+            }
+        }))
+        .attach(AdHoc::on_attach(|rocket| {
+            match Bans::from_config(rocket.config()) {
+                Ok(bans) => {
+                    let webhook_secret = WebhookSecret::from_config(rocket.config());
+                    let cors_origin = CorsOrigin::from_config(rocket.config());
+                    Ok(rocket.manage(bans).manage(webhook_secret).manage(cors_origin))
+                }
+                Err(e) => {
+                    eprintln!("Invalid bans: {}", e);
+                    Err(rocket)
+                }
+            }
+        }))
+        .attach(AdHoc::on_attach(|rocket| {
+            match EventStreams::from_config(rocket.config()) {
+                Ok(streams) => Ok(rocket.manage(streams)),
+                Err(e) => {
+                    eprintln!("Invalid event stream limit: {}", e);
+                    Err(rocket)
+                }
+            }
+        }))
+        .attach(AdHoc::on_attach(|rocket| {
+            match account::configure(rocket.config()) {
+                Ok(()) => Ok(rocket),
+                Err(e) => {
+                    eprintln!("Invalid deletion log: {}", e);
+                    Err(rocket)
+                }
+            }
+        }))
+        .attach(AdHoc::on_attach(|rocket| {
+            match escrow::configure(rocket.config()) {
+                Ok(()) => Ok(rocket),
+                Err(e) => {
+                    eprintln!("Invalid escrow: {}", e);
+                    Err(rocket)
+                }
+            }
+        }))
+        .attach(AdHoc::on_attach(|rocket| {
+            match ExportColumns::from_config(rocket.config()) {
+                Ok(columns) => Ok(rocket.manage(columns)),
+                Err(e) => {
+                    eprintln!("Invalid leaderboard export: {}", e);
+                    Err(rocket)
+                }
+            }
+        }))
+        .attach(AdHoc::on_attach(|rocket| {
+            match tournament::configure(rocket.config()) {
+                Ok(()) => Ok(rocket),
+                Err(e) => {
+                    eprintln!("Invalid tournament schedule: {}", e);
+                    Err(rocket)
+                }
+            }
+        }))
+        .attach(AdHoc::on_attach(|rocket| {
+            match relay::configure(rocket.config()) {
+                Ok(()) => Ok(rocket),
+                Err(e) => {
+                    eprintln!("Invalid relay schedule: {}", e);
+                    Err(rocket)
+                }
+            }
+        }))
+        .attach(AdHoc::on_attach(|rocket| {
+            match maintenance::configure(rocket.config()) {
+                Ok(()) => Ok(rocket),
+                Err(e) => {
+                    eprintln!("Invalid maintenance snapshot: {}", e);
+                    Err(rocket)
+                }
+            }
+        }))
+        .attach(AdHoc::on_attach(|rocket| {
+            match commitment::configure(rocket.config()) {
+                Ok(()) => Ok(rocket),
+                Err(e) => {
+                    eprintln!("Invalid reveal delay: {}", e);
+                    Err(rocket)
+                }
+            }
+        }))
+        .attach(AdHoc::on_attach(|rocket| {
+            match pow::configure(rocket.config()) {
+                Ok(()) => Ok(rocket),
+                Err(e) => {
+                    eprintln!("Invalid proof of work difficulty: {}", e);
+                    Err(rocket)
+                }
+            }
+        }))
+        .attach(AdHoc::on_attach(|rocket| {
+            match cookie_policy::configure(rocket.config()) {
+                Ok(()) => Ok(rocket),
+                Err(e) => {
+                    eprintln!("Invalid cookie attributes: {}", e);
+                    Err(rocket)
+                }
+            }
+        }))
+        .attach(AdHoc::on_attach(|rocket| {
+            match rating::configure(rocket.config()) {
+                Ok(()) => Ok(rocket),
+                Err(e) => {
+                    eprintln!("Invalid rating system: {}", e);
+                    Err(rocket)
+                }
+            }
+        }))
+        .attach(AdHoc::on_attach(|rocket| {
+            match profile::configure(rocket.config()) {
+                Ok(()) => Ok(rocket),
+                Err(e) => {
+                    eprintln!("Invalid profiles: {}", e);
+                    Err(rocket)
+                }
+            }
+        }))
+        .attach(AdHoc::on_attach(|rocket| {
+            match strategy::configure(rocket.config()) {
+                Ok(()) => Ok(rocket),
+                Err(e) => {
+                    eprintln!("Invalid fun mode: {}", e);
+                    Err(rocket)
+                }
+            }
+        }))
+        .attach(AdHoc::on_attach(|rocket| {
+            match backup::configure(rocket.config()) {
+                Ok(()) => Ok(rocket),
+                Err(e) => {
+                    eprintln!("Invalid backup settings: {}", e);
+                    Err(rocket)
+                }
+            }
+        }))
+        .attach(AdHoc::on_attach(|rocket| {
+            match experiment::configure(rocket.config()) {
+                Ok(()) => Ok(rocket),
+                Err(e) => {
+                    eprintln!("Invalid experiments: {}", e);
+                    Err(rocket)
+                }
+            }
+        }))
+        .attach(quota::RateLimitHeaders)
+        .attach(AdHoc::on_attach(|rocket| {
+            let policy = SecurityPolicy::from_config(rocket.config());
+            Ok(rocket.manage(policy))
+        }))
+        .attach(security::SecurityHeaders)
+        .manage(Outbox::<Notification>::new())
+        .attach(AdHoc::on_attach(|rocket| {
+            match Revocations::from_config(rocket.config()) {
+                Ok(revocations) => Ok(rocket.manage(revocations)),
+                Err(e) => {
+                    eprintln!("Invalid revocations: {}", e);
+                    Err(rocket)
+                }
+            }
+        }))
+        .mount("/",
+            routes![logout, feed::commitments, feed::commitments_page, export::leaderboard_csv,
+                    export::leaderboard_json, tournament::standings, relay::standings])
+        .catch(api::catchers())
+        .mount("/api/v1",
+            routes![
+                api::create_key, api::round, api::play, api::receipt, api::receipt_proof,
+                api::stats, api::sparkline, api::opponent, api::opponent_scripts, api::export,
+                api::history, api::history_search, api::model, api::profile,
+                api::activity, api::simulate, api::register_bot, api::withdraw_bot, api::bot_turn,
+                api::play_bot_turn, api::register_team, api::join_team, api::leave_team,
+                api::drop_team_member, api::team, api::play_leg, api::openapi,
+            ])
+        .mount("/admin",
+            routes![admin::bans, admin::ban, admin::unban, admin::drain_outbox, admin::revoke_sessions,
+                    admin::open_escrow, admin::flags, admin::dismiss_flag,
+                    admin::strategies, admin::experiments, admin::maintenance,
+                    admin::maintenance_status, admin::maintenance_snapshot, admin::metrics,
+                    admin::drain_webhooks, admin::backups, admin::take_backup, admin::restore_backup])
+        .mount("/inbound", routes![email::inbound])
+        .mount("/widget", routes![widget::leaderboard_json])
+        .mount("/events", routes![widget::leaderboard_events]);
+
+    #[cfg(debug_assertions)]
+    let rocket = rocket
+        .attach(AdHoc::on_attach(|rocket| {
+            match faults::FaultConfig::from_config(rocket.config()) {
+                Ok(Some(faults)) => Ok(rocket.manage(faults)),
+                Ok(None) => Ok(rocket),
+                Err(e) => {
+                    eprintln!("Invalid fault injection settings: {}", e);
+                    Err(rocket)
+                }
+            }
+        }))
+        .attach(faults::FaultInjection);
+
+    #[cfg(feature = "scripting")]
+    let rocket = rocket
+        .attach(AdHoc::on_attach(|rocket| {
+            match scripting::configure(rocket.config()) {
+                Ok(()) => Ok(rocket),
+                Err(e) => {
+                    eprintln!("Invalid strategy script: {}", e);
+                    Err(rocket)
+                }
+            }
+        }));
+
+    #[cfg(feature = "templates")]
+    let rocket = pages::mount(rocket);
+    #[cfg(not(feature = "templates"))]
+    let rocket = embedded::mount(rocket);
+    rocket
+}
@@ -0,0 +1,198 @@
+//! How pages write times and numbers for a player
+//!
+//! A player picks a language and a UTC offset on `/profile`; both are kept in their profile as a
+//! `Locale`, English and UTC until they do. Pages format timestamps and large counts with it before
+//! handing them to templates, e.g. `15.10.2026, 14:05` and `12.345` in German, where English has
+//! `Oct 15, 2026, 14:05` and `12,345`. There is no time zone database: an offset is fixed, so it
+//! has to be changed by hand when daylight saving time starts or ends.
+
+use std::str::FromStr;
+
+use util::format_date;
+
+/// Lowest and highest offsets in use, in minutes east of UTC: from UTC−12:00 to UTC+14:00.
+pub const MIN_UTC_OFFSET: i32 = -12 * 60;
+pub const MAX_UTC_OFFSET: i32 = 14 * 60;
+
+const MONTHS: [&str; 12] =
+    ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Language {
+    En,
+    De,
+    Fr,
+    Es,
+}
+
+impl Language {
+    pub const ALL: [Language; 4] = [Language::En, Language::De, Language::Fr, Language::Es];
+
+    /// The ISO 639-1 code, as in `lang` attributes.
+    pub fn code(&self) -> &'static str {
+        match *self {
+            Language::En => "en",
+            Language::De => "de",
+            Language::Fr => "fr",
+            Language::Es => "es",
+        }
+    }
+
+    /// The name of the language, in that language.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Language::En => "English",
+            Language::De => "Deutsch",
+            Language::Fr => "Français",
+            Language::Es => "Español",
+        }
+    }
+
+    /// Between groups of three digits.
+    fn group_separator(&self) -> &'static str {
+        match *self {
+            Language::En => ",",
+            Language::De | Language::Es => ".",
+            // A narrow no-break space.
+            Language::Fr => "\u{202f}",
+        }
+    }
+
+    /// The fewest digits a number has for its digits to be grouped. Spanish leaves 4-digit
+    /// numbers be.
+    fn min_grouped_digits(&self) -> usize {
+        match *self {
+            Language::Es => 5,
+            _ => 4,
+        }
+    }
+}
+
+impl Default for Language {
+    fn default() -> Language {
+        Language::En
+    }
+}
+
+pub struct ParseLanguageError;
+
+impl FromStr for Language {
+    type Err = ParseLanguageError;
+
+    /// Takes a code, or a language tag such as `de-AT`.
+    fn from_str(s: &str) -> Result<Language, ParseLanguageError> {
+        let code = s.split(|c| c == '-' || c == '_').next().unwrap_or("").to_lowercase();
+        Language::ALL.iter()
+            .find(|language| language.code() == code)
+            .cloned()
+            .ok_or(ParseLanguageError)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Locale {
+    pub language: Language,
+    /// Minutes east of UTC, from `MIN_UTC_OFFSET` to `MAX_UTC_OFFSET`.
+    #[serde(default)]
+    pub utc_offset: i32,
+}
+
+impl Locale {
+    /// Writes a count with its digits grouped by thousands.
+    pub fn format_count(&self, count: u64) -> String {
+        let digits = count.to_string();
+        if digits.len() < self.language.min_grouped_digits() {
+            return digits;
+        }
+        let mut grouped = String::new();
+        for (i, digit) in digits.chars().enumerate() {
+            if i > 0 && (digits.len() - i) % 3 == 0 {
+                grouped.push_str(self.language.group_separator());
+            }
+            grouped.push(digit);
+        }
+        grouped
+    }
+
+    /// The Unix time that reads as the wall clock at the locale's offset at `time`: formatted or
+    /// bucketed as if in UTC, it gives the player's date and hour.
+    pub fn local_time(&self, time: u64) -> u64 {
+        (time as i64 + i64::from(self.utc_offset) * 60).max(0) as u64
+    }
+
+    /// The reverse of `local_time`: the Unix time at which the wall clock at the locale's offset
+    /// reads `local`, e.g. when the player's day starts.
+    pub fn from_local_time(&self, local: u64) -> u64 {
+        (local as i64 - i64::from(self.utc_offset) * 60).max(0) as u64
+    }
+
+    /// Writes the date and the time of day of a Unix time, at the locale's offset.
+    pub fn format_time(&self, time: u64) -> String {
+        let local = self.local_time(time);
+        let date = format_date(local);
+        let (year, month, day) = (&date[..4], &date[5..7], &date[8..]);
+        let secs = local % 86_400;
+        let clock = format!("{:02}:{:02}", secs / 3600, secs / 60 % 60);
+        match self.language {
+            Language::En => {
+                let month: usize = month.parse().unwrap();
+                let day: u32 = day.parse().unwrap();
+                format!("{} {}, {}, {}", MONTHS[month - 1], day, year, clock)
+            }
+            Language::De => format!("{}.{}.{}, {}", day, month, year, clock),
+            Language::Fr => format!("{}/{}/{} {}", day, month, year, clock),
+            Language::Es => format!("{}/{}/{}, {}", day, month, year, clock),
+        }
+    }
+
+    /// The offset, e.g. `UTC+05:30`, or `UTC` for none.
+    pub fn time_zone(&self) -> String {
+        if self.utc_offset == 0 {
+            return "UTC".to_owned();
+        }
+        format!("UTC{}", format_utc_offset(self.utc_offset))
+    }
+}
+
+/// Writes an offset in minutes east of UTC as `+HH:MM` or `-HH:MM`.
+pub fn format_utc_offset(offset: i32) -> String {
+    let sign = if offset < 0 { '-' } else { '+' };
+    format!("{}{:02}:{:02}", sign, offset.abs() / 60, offset.abs() % 60)
+}
+
+/// Reads an offset from UTC such as `+02:00`, `-0530`, `+9` or `UTC+1`, into minutes east of UTC.
+/// `UTC`, `Z` and an empty string are no offset.
+pub fn parse_utc_offset(offset: &str) -> Option<i32> {
+    let offset = offset.trim();
+    let offset = match offset.get(..3) {
+        Some(prefix) if prefix.eq_ignore_ascii_case("utc") => &offset[3..],
+        _ => offset,
+    };
+    if offset.is_empty() || offset == "Z" || offset == "z" {
+        return Some(0);
+    }
+    let sign = match offset.chars().next()? {
+        '+' => 1,
+        '-' | '−' => -1,
+        _ => return None,
+    };
+    let digits: String = offset.chars().skip(1).filter(|&c| c != ':').collect();
+    if digits.is_empty() || digits.len() > 4 || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let (hours, minutes) = if digits.len() <= 2 {
+        (digits.parse::<i32>().ok()?, 0)
+    } else {
+        let split = digits.len() - 2;
+        (digits[..split].parse::<i32>().ok()?, digits[split..].parse::<i32>().ok()?)
+    };
+    if minutes >= 60 {
+        return None;
+    }
+    let offset = sign * (hours * 60 + minutes);
+    if offset < MIN_UTC_OFFSET || offset > MAX_UTC_OFFSET {
+        return None;
+    }
+    Some(offset)
+}
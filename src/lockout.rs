@@ -0,0 +1,138 @@
+//! Lockout after repeated failed logins
+//!
+//! The only secrets a login checks are recovery codes, see `recovery`, which the changes that need
+//! the owner to prove themselves again, e.g. deleting the account, check too. Failed attempts are
+//! counted per account and client address together, and per address, and once either has failed
+//! `FREE_ATTEMPTS` times in a row, it is locked out for `BASE_LOCKOUT_SECS`, doubling with every
+//! further failure up to `MAX_LOCKOUT_SECS`. An account is never locked out as a whole, or anyone
+//! could keep its owner from recovering it by guessing wrong on purpose. A successful login clears
+//! the counts; so does a day without failures.
+//!
+//! The counts are kept in `LOGIN_ATTEMPTS`, a sharded map like the sessions', so that they live
+//! wherever the sessions do. Forgotten counts are evicted whenever they are looked at, and every
+//! hour by `sweep`.
+
+use std::net::IpAddr;
+
+use util::unix_time;
+use LOGIN_ATTEMPTS;
+
+/// Failures in a row before the first lockout.
+const FREE_ATTEMPTS: u32 = 3;
+const BASE_LOCKOUT_SECS: u64 = 30;
+const MAX_LOCKOUT_SECS: u64 = 3600;
+/// Time after the last failure after which the failures are forgotten.
+const FORGET_AFTER_SECS: u64 = 24 * 3600;
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Attempts {
+    failures: u32,
+    /// Unix times.
+    last_failure: u64,
+    locked_until: u64,
+}
+
+impl Attempts {
+    /// Whether the failures are forgotten by `now`, a day after the last one.
+    fn is_forgotten(&self, now: u64) -> bool {
+        now >= self.last_failure + FORGET_AFTER_SECS
+    }
+
+    fn fail(&mut self, now: u64) {
+        if self.is_forgotten(now) {
+            self.failures = 0;
+        }
+        self.failures += 1;
+        self.last_failure = now;
+        if self.failures >= FREE_ATTEMPTS {
+            let doublings = (self.failures - FREE_ATTEMPTS).min(16);
+            let lockout = (BASE_LOCKOUT_SECS << doublings).min(MAX_LOCKOUT_SECS);
+            self.locked_until = now + lockout;
+        }
+    }
+}
+
+/// The keys counting the failures of `user_name` from `ip`: `ip:<address> name:<user name>` and
+/// `ip:<address>`, or only `name:<user name>` when the address is unknown. An address holds no
+/// space, so the name is whatever follows the first ` name:`.
+fn keys(user_name: &str, ip: Option<IpAddr>) -> Vec<String> {
+    match ip {
+        Some(ip) => vec![format!("ip:{} name:{}", ip, user_name), format!("ip:{}", ip)],
+        None => vec![format!("name:{}", user_name)],
+    }
+}
+
+/// The user name `key` counts failures of, if any.
+fn name_of(key: &str) -> Option<&str> {
+    if key.starts_with("name:") {
+        return Some(&key["name:".len()..]);
+    }
+    key.splitn(2, " name:").nth(1)
+}
+
+/// Seconds until `user_name` may try to log in again from `ip`, if they are locked out.
+pub fn wait(user_name: &str, ip: Option<IpAddr>) -> Option<u64> {
+    let now = unix_time();
+    keys(user_name, ip).iter()
+        .filter_map(|key| {
+            let mut attempts = LOGIN_ATTEMPTS.write(key);
+            let (locked_until, forgotten) = attempts.get(key)
+                .map(|attempts| (attempts.locked_until, attempts.is_forgotten(now)))?;
+            if forgotten {
+                attempts.remove(key);
+            }
+            Some(locked_until)
+        })
+        .max()
+        .filter(|&until| until > now)
+        .map(|until| until - now)
+}
+
+/// Counts a failed login of `user_name` from `ip`.
+pub fn fail(user_name: &str, ip: Option<IpAddr>) {
+    let now = unix_time();
+    for key in keys(user_name, ip) {
+        let mut attempts = LOGIN_ATTEMPTS.write(&key);
+        attempts.retain(|_, attempts| !attempts.is_forgotten(now));
+        attempts.entry(key.clone()).or_insert_with(Attempts::default).fail(now);
+    }
+}
+
+/// Clears the failures of `user_name` and `ip` after a successful login.
+pub fn succeed(user_name: &str, ip: Option<IpAddr>) {
+    for key in keys(user_name, ip) {
+        LOGIN_ATTEMPTS.write(&key).remove(&key);
+    }
+}
+
+/// Forgets the failures of `user_name`, from every address, e.g. when their account is deleted.
+/// Those of addresses alone stay.
+pub fn forget(user_name: &str) {
+    let mut attempts = LOGIN_ATTEMPTS.write_all();
+    let forgotten: Vec<String> = attempts.iter()
+        .filter(|&(key, _)| name_of(key) == Some(user_name))
+        .map(|(key, _)| key.clone())
+        .collect();
+    for key in forgotten {
+        attempts.remove(&key);
+    }
+}
+
+/// Drops the failures that are forgotten by now, every hour, see `scheduler`. Failures are also
+/// dropped as others of the same shard come in, but shards nobody fails in would keep theirs.
+pub fn sweep() {
+    let now = unix_time();
+    let mut attempts = LOGIN_ATTEMPTS.write_all();
+    let forgotten: Vec<String> = attempts.iter()
+        .filter(|&(_, attempts)| attempts.is_forgotten(now))
+        .map(|(key, _)| key.clone())
+        .collect();
+    for key in forgotten {
+        attempts.remove(&key);
+    }
+}
+
+/// A wait in whole minutes, rounded up.
+pub fn wait_minutes(secs: u64) -> u64 {
+    (secs + 59) / 60
+}
@@ -0,0 +1,171 @@
+//! Maintenance mode, for draining the server before it is redeployed
+//!
+//! `POST /admin/maintenance` with `{"on": true}` stops the server from starting anything that would
+//! not survive a restart: logins, PvP matches, seeded matches and tournaments are refused, and so
+//! are PvP rounds that neither player has picked a hand for yet. Rounds already in flight, where
+//! one player picked, can still be finished, and so can a running bot or relay tournament.
+//!
+//! `GET /admin/maintenance` reports what is left. Once nothing is, it pauses the `scheduler`'s
+//! periodic jobs and the `workers` queues, waits for both to be done, and reports `ready`. Then
+//! `POST /admin/maintenance/snapshot` saves the running PvP matches to the `maintenance_snapshot`
+//! file, a `store::Format`, if that config extra is set: the server can then be shut down. The
+//! next launch restores the matches from the file and deletes it, or warns and leaves it there if
+//! it cannot be read. Turning maintenance off deletes it too, and resumes the jobs and queues.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering, ATOMIC_BOOL_INIT};
+use std::sync::RwLock;
+
+use rocket::Config;
+
+use poison::Recover;
+#[cfg(feature = "templates")]
+use pvp::{self, SavedMatch};
+use relay;
+use scheduler;
+#[cfg(feature = "templates")]
+use store::{self, Format, Options};
+use tournament;
+use workers;
+
+#[cfg(feature = "templates")]
+const FORMAT: Format = Format {
+    name: "maintenance-snapshot",
+    migrations: &[],
+};
+
+static ON: AtomicBool = ATOMIC_BOOL_INIT;
+static SAVED: AtomicBool = ATOMIC_BOOL_INIT;
+
+lazy_static! {
+    /// The `maintenance_snapshot` config extra.
+    static ref SNAPSHOT_FILE: RwLock<Option<PathBuf>> = RwLock::new(None);
+}
+
+/// How far draining got.
+#[derive(Serialize)]
+pub struct Status {
+    on: bool,
+    /// PvP matches going on, which are kept in the snapshot.
+    matches: usize,
+    /// PvP rounds one player picked a hand for, waiting for the other.
+    rounds_in_flight: usize,
+    /// Whether a bot or relay tournament is being played.
+    tournament_running: bool,
+    /// Whether a periodic job is still running, see `scheduler::pause`.
+    job_running: bool,
+    /// Whether a worker queue still has items queued or being handled, see `workers::pause`.
+    work_queued: bool,
+    /// Whether maintenance is on and nothing is left in flight, so that the snapshot can be saved.
+    ready: bool,
+    /// Whether the snapshot was saved since maintenance was turned on: the server can be shut down.
+    saved: bool,
+}
+
+/// Whether maintenance mode is on, so that nothing new may start.
+pub fn is_on() -> bool {
+    ON.load(Ordering::SeqCst)
+}
+
+#[cfg(feature = "templates")]
+fn matches() -> (usize, usize) {
+    pvp::in_flight()
+}
+
+#[cfg(not(feature = "templates"))]
+fn matches() -> (usize, usize) {
+    (0, 0)
+}
+
+/// Turns maintenance mode on or off, and reports the status as `status` does.
+pub fn set(on: bool) -> io::Result<Status> {
+    ON.store(on, Ordering::SeqCst);
+    SAVED.store(false, Ordering::SeqCst);
+    if !on {
+        scheduler::resume();
+        workers::resume();
+        if let Some(ref path) = *SNAPSHOT_FILE.read().recover() {
+            if path.exists() {
+                fs::remove_file(path)?;
+            }
+        }
+    }
+    Ok(status())
+}
+
+/// What is left before the snapshot can be saved.
+pub fn status() -> Status {
+    let on = is_on();
+    let (matches, rounds_in_flight) = matches();
+    let tournament_running = tournament::is_running() || relay::is_running();
+    let drained = on && rounds_in_flight == 0 && !tournament_running;
+    let job_running = drained && !scheduler::pause();
+    let work_queued = drained && !workers::pause();
+    Status {
+        on: on,
+        matches: matches,
+        rounds_in_flight: rounds_in_flight,
+        tournament_running: tournament_running,
+        job_running: job_running,
+        work_queued: work_queued,
+        ready: drained && !job_running && !work_queued,
+        saved: SAVED.load(Ordering::SeqCst),
+    }
+}
+
+/// Saves the snapshot, once `status` is `ready`. Returns the status, or `None` if not ready.
+pub fn snapshot() -> io::Result<Option<Status>> {
+    if !status().ready {
+        return Ok(None);
+    }
+    save()?;
+    SAVED.store(true, Ordering::SeqCst);
+    Ok(Some(status()))
+}
+
+#[cfg(feature = "templates")]
+fn save() -> io::Result<()> {
+    match *SNAPSHOT_FILE.read().recover() {
+        Some(ref path) => store::save(path, &FORMAT, &pvp::saved_matches()),
+        None => Ok(()),
+    }
+}
+
+#[cfg(not(feature = "templates"))]
+fn save() -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(feature = "templates")]
+fn restore(file: &Path) -> Result<(), String> {
+    let saved = store::load::<Vec<SavedMatch>>(file, &FORMAT, Options::default())?;
+    pvp::restore(saved.map_or_else(Vec::new, |loaded| loaded.data));
+    Ok(())
+}
+
+#[cfg(not(feature = "templates"))]
+fn restore(_file: &Path) -> Result<(), String> {
+    Ok(())
+}
+
+/// Reads the `maintenance_snapshot` config extra, and restores the matches saved there, if any. A
+/// snapshot that cannot be read is left where it is, for the operator to look into.
+pub fn configure(config: &Config) -> Result<(), String> {
+    let file = match config.get_str("maintenance_snapshot") {
+        Ok(path) => PathBuf::from(path),
+        Err(_) => return Ok(()),
+    };
+    if file.exists() {
+        match restore(&file) {
+            Ok(()) => {
+                fs::remove_file(&file)
+                    .map_err(|e| format!("unable to remove {}: {}", file.display(), e))?;
+            }
+            Err(e) => eprintln!("Warning: not restoring the PvP matches: {}", e),
+        }
+    }
+    *SNAPSHOT_FILE.write().recover() = Some(file);
+    Ok(())
+}
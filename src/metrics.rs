@@ -0,0 +1,136 @@
+//! Prometheus metrics of the computer's strategies
+//!
+//! Every time a strategy decides on a hand, how long it took is observed into a histogram labelled
+//! with the strategy: the computer's own ones in classic rounds, the player model behind the
+//! practice HUD, and the house bots of the tournaments, scripted ones included. `GET
+//! /admin/metrics` serves them in Prometheus' text format, so that a strategy growing slower shows
+//! up before it adds to the players' latency. Alongside them are the counters of `poison`, which
+//! should stay at 0: alert on them, how the `scheduler`'s jobs have been doing, and how backed up
+//! the `workers` queues are.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use poison::{self, Recover};
+use scheduler;
+use workers;
+
+/// Upper bounds of the buckets, in seconds.
+const BUCKETS: [f64; 10] = [1e-6, 5e-6, 1e-5, 5e-5, 1e-4, 5e-4, 1e-3, 5e-3, 1e-2, 5e-2];
+
+lazy_static! {
+    /// By strategy.
+    static ref DECISION_TIMES: Mutex<BTreeMap<String, Histogram>> = Mutex::new(BTreeMap::new());
+}
+
+#[derive(Default)]
+struct Histogram {
+    /// Observations up to each bound of `BUCKETS`, not cumulative.
+    buckets: [u64; 10],
+    count: u64,
+    sum: f64,
+}
+
+impl Histogram {
+    fn observe(&mut self, secs: f64) {
+        if let Some(i) = BUCKETS.iter().position(|&bound| secs <= bound) {
+            self.buckets[i] += 1;
+        }
+        self.count += 1;
+        self.sum += secs;
+    }
+}
+
+/// Runs `decide`, the decision of `strategy`, observing how long it took.
+pub fn time_decision<T, F: FnOnce() -> T>(strategy: &str, decide: F) -> T {
+    let started = Instant::now();
+    let decision = decide();
+    let elapsed = started.elapsed();
+    let secs = elapsed.as_secs() as f64 + f64::from(elapsed.subsec_nanos()) / 1e9;
+    DECISION_TIMES.lock().recover()
+        .entry(strategy.to_owned())
+        .or_insert_with(Histogram::default)
+        .observe(secs);
+    decision
+}
+
+/// All metrics, in Prometheus' text exposition format.
+pub fn render() -> String {
+    let histograms = DECISION_TIMES.lock().recover();
+    let mut text = String::new();
+    text.push_str("# HELP rps_strategy_decision_seconds Time taken to decide on a hand.\n");
+    text.push_str("# TYPE rps_strategy_decision_seconds histogram\n");
+    for (strategy, histogram) in histograms.iter() {
+        let mut cumulative = 0;
+        for (bound, &n) in BUCKETS.iter().zip(histogram.buckets.iter()) {
+            cumulative += n;
+            writeln!(text, "rps_strategy_decision_seconds_bucket{{strategy=\"{}\",le=\"{}\"}} {}",
+                     strategy, bound, cumulative).unwrap();
+        }
+        writeln!(text, "rps_strategy_decision_seconds_bucket{{strategy=\"{}\",le=\"+Inf\"}} {}",
+                 strategy, histogram.count).unwrap();
+        writeln!(text, "rps_strategy_decision_seconds_sum{{strategy=\"{}\"}} {}",
+                 strategy, histogram.sum).unwrap();
+        writeln!(text, "rps_strategy_decision_seconds_count{{strategy=\"{}\"}} {}",
+                 strategy, histogram.count).unwrap();
+    }
+    text.push_str("# HELP rps_poisoned_lock_recoveries_total Poisoned locks taken.\n");
+    text.push_str("# TYPE rps_poisoned_lock_recoveries_total counter\n");
+    writeln!(text, "rps_poisoned_lock_recoveries_total {}", poison::recoveries()).unwrap();
+    text.push_str("# HELP rps_poisoned_entries_dropped_total Entries dropped after a panic.\n");
+    text.push_str("# TYPE rps_poisoned_entries_dropped_total counter\n");
+    writeln!(text, "rps_poisoned_entries_dropped_total {}", poison::dropped_entries()).unwrap();
+    let jobs = scheduler::stats();
+    text.push_str("# HELP rps_job_runs_total Runs of each periodic job.\n");
+    text.push_str("# TYPE rps_job_runs_total counter\n");
+    for job in &jobs {
+        writeln!(text, "rps_job_runs_total{{job=\"{}\"}} {}", job.name, job.runs).unwrap();
+    }
+    text.push_str("# HELP rps_job_panics_total Runs of each periodic job that panicked.\n");
+    text.push_str("# TYPE rps_job_panics_total counter\n");
+    for job in &jobs {
+        writeln!(text, "rps_job_panics_total{{job=\"{}\"}} {}", job.name, job.panics).unwrap();
+    }
+    text.push_str("# HELP rps_job_last_duration_seconds Duration of the latest run of a job.\n");
+    text.push_str("# TYPE rps_job_last_duration_seconds gauge\n");
+    for job in &jobs {
+        if let Some(duration) = job.last_duration {
+            let secs = duration.as_secs() as f64 + f64::from(duration.subsec_nanos()) / 1e9;
+            writeln!(text, "rps_job_last_duration_seconds{{job=\"{}\"}} {}", job.name, secs)
+                .unwrap();
+        }
+    }
+    let queues = workers::stats();
+    text.push_str("# HELP rps_queue_items Items waiting in each worker queue.\n");
+    text.push_str("# TYPE rps_queue_items gauge\n");
+    for queue in &queues {
+        writeln!(text, "rps_queue_items{{queue=\"{}\"}} {}", queue.name, queue.queued).unwrap();
+    }
+    text.push_str("# HELP rps_queue_capacity Most items each worker queue holds.\n");
+    text.push_str("# TYPE rps_queue_capacity gauge\n");
+    for queue in &queues {
+        writeln!(text, "rps_queue_capacity{{queue=\"{}\"}} {}", queue.name, queue.capacity)
+            .unwrap();
+    }
+    text.push_str("# HELP rps_queue_handled_total Items each worker queue handled.\n");
+    text.push_str("# TYPE rps_queue_handled_total counter\n");
+    for queue in &queues {
+        writeln!(text, "rps_queue_handled_total{{queue=\"{}\"}} {}", queue.name, queue.handled)
+            .unwrap();
+    }
+    text.push_str("# HELP rps_queue_rejected_total Items refused by a full or paused queue.\n");
+    text.push_str("# TYPE rps_queue_rejected_total counter\n");
+    for queue in &queues {
+        writeln!(text, "rps_queue_rejected_total{{queue=\"{}\"}} {}", queue.name,
+                 queue.rejected).unwrap();
+    }
+    text.push_str("# HELP rps_queue_panics_total Items whose handler panicked.\n");
+    text.push_str("# TYPE rps_queue_panics_total counter\n");
+    for queue in &queues {
+        writeln!(text, "rps_queue_panics_total{{queue=\"{}\"}} {}", queue.name, queue.panics)
+            .unwrap();
+    }
+    text
+}
@@ -0,0 +1,141 @@
+//! Bans of user names and IP addresses
+//!
+//! Bans are kept in memory and, if the `ban_file` config extra is set, saved to that JSON file on
+//! every change so that they outlive the process. Timed bans simply stop matching once expired.
+//! The file is a `store::Format`, with no migrations yet.
+
+use std::io;
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::sync::RwLock;
+
+use rocket::http::RawStr;
+use rocket::request::FromParam;
+use rocket::Config;
+
+use poison::Recover;
+use store::{self, Format, Options};
+use util::unix_time;
+
+const FORMAT: Format = Format {
+    name: "bans",
+    migrations: &[],
+};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BanKind {
+    Name,
+    Ip,
+}
+
+impl<'a> FromParam<'a> for BanKind {
+    type Error = &'a RawStr;
+
+    fn from_param(param: &'a RawStr) -> Result<BanKind, &'a RawStr> {
+        match param.as_str() {
+            "name" => Ok(BanKind::Name),
+            "ip" => Ok(BanKind::Ip),
+            _ => Err(param),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Ban {
+    pub kind: BanKind,
+    /// The user name, or the IP address in its canonical text form.
+    pub value: String,
+    #[serde(default)]
+    pub reason: Option<String>,
+    /// Unix time at which the ban expires. Permanent if absent.
+    #[serde(default)]
+    pub until: Option<u64>,
+}
+
+impl Ban {
+    fn active(&self, now: u64) -> bool {
+        self.until.map_or(true, |until| now < until)
+    }
+
+    fn matches(&self, name: Option<&str>, ip: Option<IpAddr>) -> bool {
+        match self.kind {
+            BanKind::Name => name == Some(self.value.as_str()),
+            BanKind::Ip => ip.map_or(false, |ip| ip.to_string() == self.value),
+        }
+    }
+
+    /// Whole minutes left, rounded up, for timed bans.
+    #[cfg(feature = "templates")]
+    pub fn minutes_left(&self) -> Option<u64> {
+        self.until.map(|until| (until.saturating_sub(unix_time()) + 59) / 60)
+    }
+}
+
+pub struct Bans {
+    file: Option<PathBuf>,
+    bans: RwLock<Vec<Ban>>,
+}
+
+impl Bans {
+    pub fn from_config(config: &Config) -> Result<Bans, String> {
+        let mut file = config.get_str("ban_file").ok().map(PathBuf::from);
+        let bans = match file {
+            Some(ref path) => store::load(path, &FORMAT, Options::from_config(config))?,
+            None => None,
+        };
+        let bans = match bans {
+            Some(loaded) => {
+                if loaded.read_only {
+                    file = None;
+                }
+                loaded.data
+            }
+            None => Vec::new(),
+        };
+        Ok(Bans {
+            file: file,
+            bans: RwLock::new(bans),
+        })
+    }
+
+    /// The active ban matching a user name or an IP address, if any.
+    pub fn find(&self, name: Option<&str>, ip: Option<IpAddr>) -> Option<Ban> {
+        let now = unix_time();
+        let bans = self.bans.read().recover();
+        bans.iter().find(|ban| ban.active(now) && ban.matches(name, ip)).cloned()
+    }
+
+    /// The active bans.
+    pub fn list(&self) -> Vec<Ban> {
+        let now = unix_time();
+        self.bans.read().recover().iter().filter(|ban| ban.active(now)).cloned().collect()
+    }
+
+    /// Adds a ban, replacing any earlier one of the same target.
+    pub fn add(&self, ban: Ban) -> io::Result<()> {
+        let now = unix_time();
+        let mut bans = self.bans.write().recover();
+        bans.retain(|b| b.active(now) && !(b.kind == ban.kind && b.value == ban.value));
+        bans.push(ban);
+        self.save(&bans)
+    }
+
+    /// Lifts a ban. Returns whether there was one.
+    pub fn remove(&self, kind: BanKind, value: &str) -> io::Result<bool> {
+        let mut bans = self.bans.write().recover();
+        let count = bans.len();
+        bans.retain(|b| !(b.kind == kind && b.value == value));
+        if bans.len() == count {
+            return Ok(false);
+        }
+        self.save(&bans).map(|_| true)
+    }
+
+    fn save(&self, bans: &[Ban]) -> io::Result<()> {
+        match self.file {
+            Some(ref path) => store::save(path, &FORMAT, bans),
+            None => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,46 @@
+//! Outgoing notifications
+//!
+//! Messages are queued in an outbox until a gateway (e.g. the email gateway feeding
+//! `/inbound/email`) collects them with `POST /admin/outbox/drain`. Other kinds of messages, e.g.
+//! the posts of `webhook`, have queues of their own.
+
+use std::sync::Mutex;
+
+use poison::Recover;
+
+/// Messages kept at most; the oldest are dropped first if nobody collects them.
+const OUTBOX_CAPACITY: usize = 1000;
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Notification {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+pub struct Outbox<T = Notification>(Mutex<Vec<T>>);
+
+impl<T> Outbox<T> {
+    pub fn new() -> Outbox<T> {
+        Outbox(Mutex::new(Vec::new()))
+    }
+
+    pub fn send(&self, message: T) {
+        let mut queue = self.0.lock().recover();
+        if queue.len() >= OUTBOX_CAPACITY {
+            queue.remove(0);
+        }
+        queue.push(message);
+    }
+
+    /// Takes every queued message.
+    pub fn drain(&self) -> Vec<T> {
+        let mut queue = self.0.lock().recover();
+        ::std::mem::replace(&mut *queue, Vec::new())
+    }
+
+    /// Drops the queued messages for which `keep` is false.
+    pub fn retain<F: FnMut(&T) -> bool>(&self, keep: F) {
+        self.0.lock().recover().retain(keep);
+    }
+}
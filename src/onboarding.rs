@@ -0,0 +1,64 @@
+//! The guided first session
+//!
+//! The first `TUTORIAL_ROUNDS` classic rounds of a player who never finished them are against a
+//! scripted opponent, which plays `SCRIPT` in order, and the index page explains one step of the
+//! commitment scheme with each. Once they are played, the player's profile records the onboarding
+//! as done, so that it only shows once. Seeded matches pause the tutorial, and sudden throws don't
+//! count as tutorial rounds. Being practice, tutorial rounds are not ranked: they count toward
+//! neither the profile's stats and rating nor the leaderboard, see `Strategy::is_ranked`.
+
+use game::Hand;
+
+pub const TUTORIAL_ROUNDS: usize = 3;
+
+/// The scripted opponent's hands.
+const SCRIPT: [Hand; TUTORIAL_ROUNDS] = [Hand::Rock, Hand::Paper, Hand::Scissors];
+
+/// What each tutorial round explains, shown while it is pending.
+#[cfg(feature = "templates")]
+const STEPS: [&str; TUTORIAL_ROUNDS] = [
+    "The computer has picked its hand for this round already, but only shows you its digest: the \
+     SHA-256 hash of a random nonce followed by the hand. Pick yours; the computer can no longer \
+     change its own without the digest giving it away.",
+    "The last round revealed the nonce and the computer's hand. Hash them as shown under the \
+     scoreboard: you get the digest you saw before you played, so the computer played the hand it \
+     had committed to.",
+    "Every revealed round is chained into your history: each link hashes the previous one with the \
+     nonce and the hand of the round, so no past round can change without the chain head changing \
+     too. This is the last tutorial round; from the next one on, the computer plays for real.",
+];
+
+#[derive(Clone, Debug, Default)]
+pub struct Tutorial {
+    /// Tutorial rounds played so far.
+    played: usize,
+}
+
+impl Tutorial {
+    pub fn new() -> Tutorial {
+        Tutorial::default()
+    }
+
+    /// The scripted hand of the next tutorial round.
+    pub fn hand(&self) -> Hand {
+        SCRIPT[self.played]
+    }
+
+    /// The explanation that goes with the pending tutorial round.
+    #[cfg(feature = "templates")]
+    pub fn step(&self) -> &'static str {
+        STEPS[self.played]
+    }
+
+    /// The pending tutorial round, counting from 1.
+    #[cfg(feature = "templates")]
+    pub fn round(&self) -> usize {
+        self.played + 1
+    }
+
+    /// Counts a tutorial round as played. Returns whether that was the last one.
+    pub fn advance(&mut self) -> bool {
+        self.played += 1;
+        self.played >= TUTORIAL_ROUNDS
+    }
+}
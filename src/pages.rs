@@ -0,0 +1,1773 @@
+//! HTML pages, rendered from the templates
+//!
+//! Only built with the `templates` feature, which is on by default.
+
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+
+use rocket::fairing::AdHoc;
+use rocket::http::uri::Segments;
+use rocket::http::{Cookies, Status};
+use rocket::request::{Form, FlashMessage, Request, State};
+use rocket::response::{status, Flash, Redirect};
+use rocket::Rocket;
+use rocket_contrib::{Json, Template, Value};
+
+use about;
+use account::{self, Proof};
+use admin;
+use alerts::{self, Device};
+use api::{self, ApiError};
+use arcade::{Arcade, Item};
+use assets::{self, RangeHeader, StaticDir, StaticFile};
+use commitment::{self, MatchSeed, RevealPadding};
+use cookie_policy;
+use credentials;
+use csrf;
+use entropy::Entropy;
+use experiment;
+use flash::{self, Level, Notice};
+use game::{Hand, Round, Scoreboard, Strategy, Verdict};
+use handoff::{self, Claim, SiteUrl};
+use history::{self, Filter, MAX_NOTE_LEN};
+use ids::UserId;
+use locale::{self, Language, Locale};
+use lockout;
+use maintenance;
+use moderation::{Ban, Bans};
+use notification::{Notification, Outbox};
+use onboarding::TUTORIAL_ROUNDS;
+use params::{HandParam, HistoryQuery, QueryError, UserHands};
+use passkey::{self, Assertion, PasskeyError, Registration, RelyingParty};
+use profile::{self, UserProfile};
+use rating;
+use pvp;
+use qr;
+use recovery;
+use render::{self, render, Renderer};
+use revocation::Revocations;
+use rules::RuleKind;
+use snapshot;
+use strategy::{self, Adaptive, Opponent, Rotation};
+use team::{self, Member};
+use util::{self, unix_time};
+use webhook::{self, Webhook};
+use widget;
+use {
+    open_session, Login, LoginRefusal, Session, SessionGuard, SessionLimits,
+    Transaction, User, REPEAT_WINDOW,
+};
+
+fn banned_page(ban: &Ban) -> status::Custom<Template> {
+    let context = json!({
+        "reason": ban.reason,
+        "minutes_left": ban.minutes_left(),
+    });
+    status::Custom(Status::Forbidden, render("banned", &context))
+}
+
+/// Warns of the latest alert of the player until they have seen it, see `alerts`.
+fn insert_alert_view(context: &mut HashMap<&'static str, String>, session: &Session) {
+    if let Some(notice) = alerts::unseen(&session.user_name) {
+        context.insert("flash", notice.text(profile::locale(&session.user_name).language));
+        context.insert("flash_level", notice.level.name().to_owned());
+    }
+}
+
+/// Sets the `flash` and `flash_level` keys of a page, see `flash`.
+fn insert_flash(context: &mut HashMap<&'static str, String>, flash: Option<&FlashMessage>,
+                language: Language) {
+    if let Some((level, text)) = flash.map(|flash| flash::read(flash, language)) {
+        context.insert("flash", text);
+        context.insert("flash_level", level.name().to_owned());
+    }
+}
+
+fn insert_score_view(context: &mut HashMap<&'static str, String>, score: &Scoreboard) {
+    context.insert("win_count", format!("{}", score.wins));
+    context.insert("tie_count", format!("{}", score.ties));
+    context.insert("loss_count", format!("{}", score.losses));
+}
+
+fn insert_chain_view(context: &mut HashMap<&'static str, String>, session: &Session) {
+    context.insert("chain_head", session.history.head().to_owned());
+}
+
+fn icons(hands: &[Hand]) -> String {
+    hands.iter().map(|hand| hand.as_icon()).collect::<Vec<_>>().join(" ")
+}
+
+fn reset_last_view(context: &mut HashMap<&'static str, String>) {
+    const NA: &str = " ∅ ";
+
+    context.insert("win_count", "0".to_owned());
+    context.insert("tie_count", "0".to_owned());
+    context.insert("loss_count", "0".to_owned());
+
+    context.insert("last_human", NA.to_owned());
+    context.insert("last_computer", NA.to_owned());
+    context.insert("last_result", NA.to_owned());
+    context.insert("last_random", NA.to_owned());
+    context.insert("last_hand", NA.to_owned());
+    context.insert("last_digest", NA.to_owned());
+}
+
+fn insert_match_view(context: &mut HashMap<&'static str, String>, session: &Session) {
+    if let Some(ref seed) = session.seeded_match {
+        context.insert("match_commitment", seed.commitment());
+    }
+}
+
+fn insert_trainer_view(context: &mut HashMap<&'static str, String>, session: &Session,
+                       human: Hand, computer: Hand) {
+    let (best, regret) = strategy::advise(human, computer);
+    context.insert("best_response", best.as_icon().to_owned());
+    context.insert("round_regret", format!("{}", regret));
+    context.insert("regret_total", format!("{}", session.regret.total));
+    context.insert("regret_average", format!("{:.2}", session.regret.average()));
+}
+
+/// A QR code of the proof of the round just played, to check it on a phone.
+fn insert_proof_view(context: &mut HashMap<&'static str, String>, session: &Session) {
+    let proof = session.history.last()
+        .and_then(|record| session.history.proof(&record.link));
+    if let Some(qr) = proof.and_then(|proof| qr::svg(&proof.to_canonical())) {
+        context.insert("proof_qr", qr);
+    }
+}
+
+fn insert_arcade_view(context: &mut HashMap<&'static str, String>, session: &Session) {
+    let arcade = match session.arcade {
+        Some(ref arcade) => arcade,
+        None => return,
+    };
+    context.insert("arcade", "true".to_owned());
+    context.insert("streak", format!("{}", arcade.streak));
+    context.insert("peeks", format!("{}", arcade.peeks));
+    context.insert("redos", format!("{}", arcade.redos));
+    if arcade.peeks > 0 && arcade.peeked.is_none() {
+        context.insert("can_peek", "true".to_owned());
+    }
+    if arcade.can_redo() {
+        context.insert("can_redo", "true".to_owned());
+    }
+    if let Some(hand) = arcade.peeked {
+        context.insert("peeked", hand.as_icon().to_owned());
+    }
+    context.insert("points", format!("{}", arcade.points));
+    if arcade.insured {
+        context.insert("insured", "true".to_owned());
+    }
+    if arcade.can_buy(Item::StreakInsurance) {
+        context.insert("can_insure", "true".to_owned());
+    }
+    context.insert("insurance_price", format!("{}", Item::StreakInsurance.price()));
+    let ledger: Vec<String> = arcade.ledger().iter()
+        .map(|entry| format!("{:+} {}", entry.change, entry.reason))
+        .collect();
+    if !ledger.is_empty() {
+        context.insert("ledger", ledger.join(", "));
+    }
+}
+
+fn insert_opponent_view(context: &mut HashMap<&'static str, String>, session: &Session) {
+    let selected = match session.opponent {
+        Opponent::Random => "opponent_random",
+        Opponent::Mirror => "opponent_mirror",
+        Opponent::CounterMirror => "opponent_counter_mirror",
+    };
+    context.insert(selected, "selected".to_owned());
+    let rotation = session.rotation.unwrap_or_default();
+    context.insert("rotation_random", format!("{}", rotation.random));
+    context.insert("rotation_mirror", format!("{}", rotation.mirror));
+    context.insert("rotation_counter_mirror", format!("{}", rotation.counter_mirror));
+    if session.rotation.is_some() {
+        if let Some(opponent) = session.round_opponent {
+            context.insert("round_opponent", opponent.name().to_owned());
+        }
+        let scores: Vec<_> = Opponent::ALL.iter()
+            .filter(|&&opponent| rotation.weight(opponent) > 0)
+            .map(|&opponent| {
+                let score = &session.by_opponent[opponent.index()];
+                format!("{} {}-{}-{}", opponent.name(), score.wins, score.ties, score.losses)
+            })
+            .collect();
+        context.insert("rotation_scores", scores.join(", "));
+    }
+    if let Some((opponent, score)) = session.toughest_opponent() {
+        context.insert("toughest_opponent", opponent.name().to_owned());
+        context.insert("toughest_losses", format!("{}", score.losses));
+    }
+}
+
+fn insert_profile_view(context: &mut HashMap<&'static str, String>, session: &Session) {
+    let profile = match profile::get(&session.user_name) {
+        Some(profile) => profile,
+        None => return,
+    };
+    let lifetime = &profile.classic;
+    context.insert("lifetime", format!("Wins = {}, Ties = {}, Losses = {}",
+                                       lifetime.wins, lifetime.ties, lifetime.losses));
+    context.insert("rating", describe_rating(&profile));
+    context.insert("best_streak", format!("{}", profile.best_streak));
+    let achievements: Vec<_> = profile.achievements.iter()
+        .map(|achievement| achievement.describe())
+        .collect();
+    if !achievements.is_empty() {
+        context.insert("achievements", achievements.join("; "));
+    }
+}
+
+fn insert_settings_view(context: &mut HashMap<&'static str, String>, session: &Session) {
+    if session.house_rules.is_on(RuleKind::RepeatedHand) {
+        context.insert("rule_repeated_hand", "true".to_owned());
+        context.insert("penalties", format!("{}", session.house_rules.penalties));
+    }
+    if profile::opted_out_of_analytics(&session.user_name) {
+        context.insert("analytics_opt_out", "true".to_owned());
+    }
+    if session.verified {
+        context.insert("verified", "true".to_owned());
+        context.insert("recovery_codes_left",
+                       format!("{}", recovery::remaining(&session.user_name)));
+        if let Some(codes) = recovery::take_unseen(&session.user_name) {
+            context.insert("recovery_codes", codes.join("\n"));
+        }
+    }
+}
+
+/// The variants of the experiments the player is in, but the controls, see `experiment`.
+fn insert_experiment_view(context: &mut HashMap<&'static str, String>, session: &Session) {
+    for (experiment, variant) in experiment::assignments(&session.user_name) {
+        if variant != experiment.control() {
+            context.insert(experiment.context_key, variant.to_owned());
+        }
+    }
+}
+
+/// How long the player took over the round just played, and the server after them, e.g. "2.3s"
+/// and "0.15 ms".
+fn insert_latency_view(context: &mut HashMap<&'static str, String>, session: &Session) {
+    if let Some(latency) = session.history.last().and_then(|record| record.latency) {
+        context.insert("last_decided", format!("{:.1}s", latency.decided_ms as f64 / 1000.0));
+        context.insert("last_server", format!("{:.2} ms", latency.server_us as f64 / 1000.0));
+    }
+}
+
+fn insert_practice_view(context: &mut HashMap<&'static str, String>, session: &Session) {
+    if !session.practice {
+        return;
+    }
+    let snapshot = session.model.snapshot();
+    context.insert("practice", "true".to_owned());
+    context.insert("model_rock", format!("{:.0}", snapshot.rock * 100.0));
+    context.insert("model_paper", format!("{:.0}", snapshot.paper * 100.0));
+    context.insert("model_scissors", format!("{:.0}", snapshot.scissors * 100.0));
+    context.insert("model_likely", snapshot.likely.as_icon().to_owned());
+    context.insert("model_counter", snapshot.counter.as_icon().to_owned());
+    if let Some(ref adaptive) = session.adaptive {
+        let state = adaptive.snapshot();
+        context.insert("fun_target", format!("{:.0}", state.target * 100.0));
+        context.insert("fun_exploitation", format!("{:+.0}", state.exploitation * 100.0));
+        if let Some(win_rate) = state.win_rate {
+            context.insert("fun_win_rate", format!("{:.0}", win_rate * 100.0));
+        }
+    }
+}
+
+/// Everything the index page shows besides the scoreboard and the last round.
+fn insert_session_view(context: &mut HashMap<&'static str, String>, session: &Session) {
+    if let Some(ref round) = session.last_round {
+        context.insert("digest", round.digest.clone());
+        if let Some(ref backup) = round.backup {
+            context.insert("backup_digest", backup.digest.clone());
+        }
+    }
+    if session.sudden_throws {
+        context.insert("sudden_throws", "true".to_owned());
+    }
+    if session.adaptive.is_some() {
+        context.insert("fun_mode", "true".to_owned());
+    }
+    if session.in_sudden_throw {
+        context.insert("sudden_throw", "true".to_owned());
+    }
+    if let Some(decoy) = session.decoy {
+        context.insert("decoy", decoy.as_ref().to_owned());
+    }
+    let scripted = session.last_round.as_ref()
+        .map_or(false, |round| round.strategy == Strategy::Scripted);
+    if let Some(tutorial) = session.tutorial.as_ref().filter(|_| scripted) {
+        context.insert("tutorial_round", format!("{}", tutorial.round()));
+        context.insert("tutorial_rounds", format!("{}", TUTORIAL_ROUNDS));
+        context.insert("tutorial_step", tutorial.step().to_owned());
+    }
+    insert_chain_view(context, session);
+    insert_match_view(context, session);
+    insert_practice_view(context, session);
+    insert_arcade_view(context, session);
+    insert_opponent_view(context, session);
+    insert_profile_view(context, session);
+    insert_settings_view(context, session);
+    insert_experiment_view(context, session);
+    insert_alert_view(context, session);
+}
+
+// ===== Routers =====
+
+#[post("/login", data = "<login>")]
+fn login(mut cookies: Cookies, login: Form<Login>, limits: State<SessionLimits>,
+         bans: State<Bans>, entropy: State<Entropy>, remote: Option<SocketAddr>, device: Device)
+    -> Result<Redirect, status::Custom<Template>>
+{
+    let user_name = &login.get().user_name;
+    log_in(&mut cookies, user_name, &limits, &bans, &entropy, remote, &device)?;
+    match cookies.get("invite").map(|cookie| cookie.value().to_owned()) {
+        Some(token) => {
+            cookies.remove(cookie_policy::named("invite"));
+            Ok(Redirect::to(&format!("/invite/{}", token)))
+        }
+        None => Ok(Redirect::to("/")),
+    }
+}
+
+/// Starts a session for `user_name` on a login form, `/login` or `/lite/login`.
+fn log_in(cookies: &mut Cookies, user_name: &str, limits: &SessionLimits, bans: &Bans,
+          entropy: &Entropy, remote: Option<SocketAddr>, device: &Device)
+    -> Result<(), status::Custom<Template>>
+{
+    let (user_id, verified) = open_session(cookies, user_name, false, limits, bans, entropy,
+                                           remote).map_err(refusal_page)?;
+    alerts::logged_in(user_name, device);
+    if verified {
+        take_correspondence_seat(user_id, user_name);
+        recovery::issue_if_missing(user_name);
+    }
+    Ok(())
+}
+
+/// Seats a player who proved they own their name in their correspondence match, if any. Logins by
+/// name only do not, or anyone could take the match over.
+fn take_correspondence_seat(user_id: UserId, user_name: &str) {
+    pvp::rebind(&Member { id: user_id, name: user_name.to_owned() });
+}
+
+fn refusal_page(refusal: LoginRefusal) -> status::Custom<Template> {
+    match refusal {
+        LoginRefusal::Banned(ban) => banned_page(&ban),
+        LoginRefusal::Full(max_sessions) => {
+            let mut context = HashMap::new();
+            context.insert("max_sessions", max_sessions);
+            status::Custom(Status::ServiceUnavailable, render("server_full", &context))
+        }
+        LoginRefusal::Maintenance => {
+            status::Custom(Status::ServiceUnavailable, render("maintenance", &json!({})))
+        }
+        LoginRefusal::PasskeyRequired => {
+            let notice = Notice::error("passkey.required");
+            let mut context = HashMap::new();
+            context.insert("flash", notice.text(Language::default()));
+            context.insert("flash_level", notice.level.name().to_owned());
+            status::Custom(Status::Forbidden, render("login", &context))
+        }
+    }
+}
+
+#[get("/login")]
+fn login_user(_user: User) -> Redirect {
+    Redirect::to("/")
+}
+
+#[get("/login", rank = 2)]
+fn login_page(flash: Option<FlashMessage>, mut cookies: Cookies, bans: State<Bans>,
+              remote: Option<SocketAddr>, site: Option<SiteUrl>) -> status::Custom<Template> {
+    let credentials = credentials::load(&mut cookies);
+    let user_name = credentials.as_ref().map(|credentials| credentials.user_name.as_str());
+    if let Some(ban) = bans.find(user_name, remote.map(|addr| addr.ip())) {
+        return banned_page(&ban);
+    }
+
+    let mut context = HashMap::new();
+    insert_flash(&mut context, flash.as_ref(), Language::default());
+    if let Some(SiteUrl(site)) = site {
+        // A reload shows the token the browser has already, while it can be approved.
+        let token = cookies.get_private("handoff")
+            .map(|cookie| cookie.value().to_owned())
+            .filter(|token| handoff::is_pending(token))
+            .or_else(|| handoff::issue(remote.map(|addr| addr.ip())));
+        let handoff = token
+            .and_then(|token| Some((qr::svg(&format!("{}/handoff/{}", site, token))?, token)));
+        if let Some((qr, token)) = handoff {
+            cookies.add_private(cookie_policy::build("handoff", token));
+            context.insert("handoff_qr", qr);
+            context.insert("handoff_minutes", format!("{}", handoff::HANDOFF_TTL / 60));
+        }
+    }
+
+    status::Custom(Status::Ok, render("login", &context))
+}
+
+/// Polled by the login page: logs the browser in as soon as its handoff token is approved.
+#[get("/login/handoff")]
+fn poll_handoff(mut cookies: Cookies, limits: State<SessionLimits>, bans: State<Bans>,
+                entropy: State<Entropy>, remote: Option<SocketAddr>, device: Device)
+    -> Json<Value>
+{
+    let token = match cookies.get_private("handoff") {
+        Some(cookie) => cookie.value().to_owned(),
+        None => return Json(json!({ "status": "gone" })),
+    };
+    let status = match handoff::claim(&token) {
+        Claim::Pending => "pending",
+        Claim::Gone => {
+            cookies.remove_private(cookie_policy::named("handoff"));
+            "gone"
+        }
+        Claim::Approved { user_name, verified } => {
+            cookies.remove_private(cookie_policy::named("handoff"));
+            match open_session(&mut cookies, &user_name, verified, &limits, &bans, &entropy,
+                               remote) {
+                Ok((user_id, verified)) => {
+                    if verified {
+                        take_correspondence_seat(user_id, &user_name);
+                    }
+                    alerts::logged_in(&user_name, &device);
+                    "approved"
+                }
+                Err(_) => "refused",
+            }
+        }
+    };
+    Json(json!({ "status": status }))
+}
+
+/// Asks the player to confirm logging the browser that shows the QR code in as them.
+#[get("/handoff/<token>", rank = 1)]
+fn handoff_page(user: User, token: String) -> Template {
+    let context = json!({
+        "user_name": user.name,
+        "token": token,
+        "pending": handoff::is_pending(&token),
+    });
+    render("handoff", &context)
+}
+
+/// Only a device that is logged in can approve a handoff.
+#[get("/handoff/<_token>", rank = 2)]
+fn handoff_login(_token: String) -> Flash<Redirect> {
+    Notice::warning("handoff.login-first").to(Redirect::to("/login"))
+}
+
+#[post("/handoff/<token>")]
+fn approve_handoff(user: User, token: String) -> Template {
+    let approved = handoff::approve(&token, &user.name, user.verified);
+    let context = json!({
+        "user_name": user.name,
+        "approved": approved,
+    });
+    render("handoff", &context)
+}
+
+
+#[derive(FromForm)]
+struct Recovery {
+    user_name: String,
+    code: String,
+}
+
+#[get("/recover")]
+fn recover_page() -> Template {
+    render("recover", &json!({}))
+}
+
+/// The recovery page again, with why the code was not taken.
+fn refused_recovery(status: Status, notice: Notice) -> status::Custom<Template> {
+    let mut context = HashMap::new();
+    context.insert("flash", notice.text(Language::default()));
+    context.insert("flash_level", notice.level.name().to_owned());
+    status::Custom(status, render("recover", &context))
+}
+
+/// Redeems a recovery code into a new, verified session: whoever holds the old one may not be
+/// the owner. Repeated failures lock the account and the client out for a while, see `lockout`.
+#[post("/recover", data = "<recovery>")]
+fn recover(mut cookies: Cookies, recovery: Form<Recovery>, limits: State<SessionLimits>,
+           bans: State<Bans>, entropy: State<Entropy>, remote: Option<SocketAddr>, device: Device)
+    -> Result<Redirect, status::Custom<Template>>
+{
+    let recovery = recovery.get();
+    let ip = remote.map(|addr| addr.ip());
+    if let Some(ban) = bans.find(Some(&recovery.user_name), ip) {
+        return Err(banned_page(&ban));
+    }
+    if let Some(wait) = lockout::wait(&recovery.user_name, ip) {
+        let notice = Notice::error("recovery.locked-out")
+            .with(&lockout::wait_minutes(wait).to_string());
+        return Err(refused_recovery(Status::TooManyRequests, notice));
+    }
+    if !recovery::redeem(&recovery.user_name, &recovery.code) {
+        lockout::fail(&recovery.user_name, ip);
+        return Err(refused_recovery(Status::Forbidden, Notice::error("recovery.bad-code")));
+    }
+    lockout::succeed(&recovery.user_name, ip);
+    alerts::recovery_code_used(&recovery.user_name, &device);
+    let (user_id, _) = open_session(&mut cookies, &recovery.user_name, true, &limits, &bans,
+                                    &entropy, remote).map_err(refusal_page)?;
+    take_correspondence_seat(user_id, &recovery.user_name);
+    recovery::issue_if_missing(&recovery.user_name);
+    Ok(Redirect::to("/"))
+}
+
+/// Issues new recovery codes, shown once on the index page, voiding the old ones. Only a verified
+/// session may, or logging in under someone's name would hand out codes to their account.
+#[post("/recovery/codes")]
+fn regenerate_recovery_codes(user: User) -> Result<Redirect, Flash<Redirect>> {
+    if !user.verified {
+        return Err(Notice::error("recovery.unverified").to(Redirect::to("/")));
+    }
+    recovery::issue(&user.name);
+    Ok(Redirect::to("/"))
+}
+
+/// Why a passkey ceremony failed, for the page to show before falling back on the name form.
+fn passkey_refusal(status: Status, reason: &str) -> status::Custom<Json<Value>> {
+    status::Custom(status, Json(json!({ "status": "refused", "reason": reason })))
+}
+
+fn passkey_failure(e: PasskeyError) -> status::Custom<Json<Value>> {
+    passkey_refusal(e.status(), &e.describe())
+}
+
+fn passkey_login_refusal(refusal: LoginRefusal) -> status::Custom<Json<Value>> {
+    match refusal {
+        LoginRefusal::Banned(_) => passkey_refusal(Status::Forbidden, "This account is banned."),
+        LoginRefusal::Full(_) => {
+            passkey_refusal(Status::ServiceUnavailable, "The server is full; try again later.")
+        }
+        LoginRefusal::Maintenance => {
+            passkey_refusal(Status::ServiceUnavailable, "The server is down for maintenance.")
+        }
+        // Passkey logins are verified.
+        LoginRefusal::PasskeyRequired => unreachable!(),
+    }
+}
+
+/// Only a verified session adds passkeys, or whoever logged in under a name could then log in
+/// with their own passkey as its owner.
+fn unverified_passkey_refusal() -> status::Custom<Json<Value>> {
+    passkey_refusal(Status::Forbidden, "Log in with a recovery code or a passkey to add one.")
+}
+
+/// Starts adding a passkey: the options of `navigator.credentials.create()`.
+#[post("/passkey/register/begin")]
+fn begin_passkey_registration(user: User, site: SiteUrl)
+    -> Result<Json<Value>, status::Custom<Json<Value>>>
+{
+    if !user.verified {
+        return Err(unverified_passkey_refusal());
+    }
+    passkey::registration_options(&RelyingParty::of(&site.0), &user.name)
+        .map(Json)
+        .map_err(passkey_failure)
+}
+
+#[post("/passkey/register", format = "application/json", data = "<registration>")]
+fn register_passkey(user: User, site: SiteUrl, registration: Json<Registration>)
+    -> Result<Json<Value>, status::Custom<Json<Value>>>
+{
+    if !user.verified {
+        return Err(unverified_passkey_refusal());
+    }
+    passkey::register(&RelyingParty::of(&site.0), &user.name, &registration)
+        .map_err(passkey_failure)?;
+    Ok(Json(json!({ "status": "registered" })))
+}
+
+/// Starts logging in with a passkey: the options of `navigator.credentials.get()`.
+#[post("/passkey/login/begin")]
+fn begin_passkey_login(site: SiteUrl) -> Result<Json<Value>, status::Custom<Json<Value>>> {
+    passkey::login_options(&RelyingParty::of(&site.0)).map(Json).map_err(passkey_failure)
+}
+
+/// Logs in as the owner of the passkey, into a new, verified session, as a recovery code would.
+#[post("/passkey/login", format = "application/json", data = "<assertion>")]
+fn passkey_login(mut cookies: Cookies, site: SiteUrl, assertion: Json<Assertion>,
+                 limits: State<SessionLimits>, bans: State<Bans>, entropy: State<Entropy>,
+                 remote: Option<SocketAddr>, device: Device)
+    -> Result<Json<Value>, status::Custom<Json<Value>>>
+{
+    let user_name = passkey::authenticate(&RelyingParty::of(&site.0), &assertion)
+        .map_err(passkey_failure)?;
+    if bans.find(Some(&user_name), remote.map(|addr| addr.ip())).is_some() {
+        return Err(passkey_refusal(Status::Forbidden, "This account is banned."));
+    }
+    let (user_id, _) = open_session(&mut cookies, &user_name, true, &limits, &bans, &entropy,
+                                    remote).map_err(passkey_login_refusal)?;
+    ::note_passkey_login(&user_id);
+    take_correspondence_seat(user_id, &user_name);
+    recovery::issue_if_missing(&user_name);
+    alerts::logged_in(&user_name, &device);
+    Ok(Json(json!({ "status": "logged-in" })))
+}
+
+#[derive(FromForm)]
+struct PasskeyRemoval {
+    id: String,
+    /// A recovery code, unless the session logged in with a passkey lately.
+    code: String,
+    csrf: String,
+}
+
+/// Only a verified session removes passkeys, once the player proved themselves again as for
+/// deleting the account: without its passkeys, the account logs in by name again.
+#[post("/passkey/remove", data = "<removal>")]
+fn remove_passkey(user: User, removal: Form<PasskeyRemoval>, remote: Option<SocketAddr>)
+    -> Flash<Redirect>
+{
+    let removal = removal.get();
+    if !csrf::verify(&user.id, &removal.csrf) {
+        return Notice::error("csrf.invalid").to(Redirect::to("/profile"));
+    }
+    if !user.verified {
+        return Notice::error("passkey.unverified").to(Redirect::to("/profile"));
+    }
+    if let Err(notice) = prove_owner(&user, &removal.code, remote.map(|addr| addr.ip())) {
+        return notice.to(Redirect::to("/profile"));
+    }
+    if profile::remove_passkey(&user.name, &removal.id) {
+        Notice::success("passkey.removed").to(Redirect::to("/profile"))
+    } else {
+        Notice::error("passkey.unknown").to(Redirect::to("/profile"))
+    }
+}
+
+/// The look of the game page: the full one at `/`, or the minimal one at `/lite`, off the same
+/// context.
+#[derive(Clone, Copy)]
+enum Skin {
+    Full,
+    Lite,
+}
+
+impl Skin {
+    fn home(self) -> &'static str {
+        match self {
+            Skin::Full => "/",
+            Skin::Lite => "/lite",
+        }
+    }
+
+    fn template(self) -> &'static str {
+        match self {
+            Skin::Full => "index",
+            Skin::Lite => "lite/index",
+        }
+    }
+}
+
+/// Starts a new round and shows the game page.
+fn index_page(mut session: SessionGuard, skin: Skin) -> Transaction<Template> {
+    let mut context = HashMap::new();
+    context.insert("user_name", session.user.name.clone());
+    reset_last_view(&mut context);
+
+    session.start_round();
+    session.plant_decoy();
+    insert_session_view(&mut context, &session);
+    let page = render(skin.template(), &context);
+    Transaction::new(session, page)
+}
+
+#[get("/", rank = 1)]
+fn user_index(session: SessionGuard) -> Transaction<Template> {
+    index_page(session, Skin::Full)
+}
+
+/// Whether logged-out visitors of `/` get the landing page rather than a redirect to `/login`:
+/// the `landing_page` config extra, true by default. An API-only deployment turns it off.
+struct LandingPage(bool);
+
+/// The rules, a teaser of the global stats and the login form, for visitors not logged in.
+#[get("/", rank = 2)]
+fn index(landing: State<LandingPage>, flash: Option<FlashMessage>) -> Result<Template, Redirect> {
+    if !landing.0 {
+        return Err(Redirect::to("/login"));
+    }
+    let flash = flash.as_ref().map(|flash| flash::read(flash, Language::default()));
+    let context = json!({
+        "stats": about::teaser(),
+        "flash": flash.as_ref().map(|&(_, ref text)| text),
+        "flash_level": flash.as_ref().map(|&(level, _)| level.name()),
+    });
+    Ok(render("landing", &context))
+}
+
+#[derive(FromForm)]
+struct PlayQuery {
+    hand: HandParam,
+    /// The digest of the round the page showed; the latest round if left out.
+    round: Option<String>,
+}
+
+/// Reports the result of the round the page showed, then starts a new one. Without that round
+/// pending, e.g. when following an old link, goes back to `GET /` for one.
+#[get("/?<query>")]
+fn user_play_index(padding: RevealPadding, session: SessionGuard, query: PlayQuery)
+    -> Result<Transaction<Template>, Redirect>
+{
+    play_page(padding, session, query, Skin::Full)
+}
+
+/// Plays the round of `query` and shows the game page with its result.
+fn play_page(padding: RevealPadding, mut session: SessionGuard, query: PlayQuery, skin: Skin)
+    -> Result<Transaction<Template>, Redirect>
+{
+    let mut context = HashMap::new();
+    context.insert("user_id", session.user.name.clone());
+
+    let hand = query.hand;
+    let repeated = match query.round {
+        // The round is no longer pending: show its result again if this is the same click twice.
+        Some(ref digest) if !session.resume_round(digest) => {
+            let record = session.repeated_play(digest, hand.0, REPEAT_WINDOW);
+            Some(record.ok_or_else(|| Redirect::to(skin.home()))?)
+        }
+        _ => None,
+    };
+    let (computer, random, digest, verdict) = match repeated {
+        Some(ref record) => {
+            let computer = record.computer.parse().map_err(|_| Redirect::to(skin.home()))?;
+            (computer, record.random.clone(), record.digest.clone(), record.verdict)
+        }
+        None => {
+            let (round, verdict) = session.play(hand.0)
+                .ok_or_else(|| Redirect::to(skin.home()))?;
+            (round.computer, round.random_bytes, round.digest, verdict)
+        }
+    };
+    insert_score_view(&mut context, &session.score);
+    context.insert("last_human", hand.0.as_icon().to_owned());
+    context.insert("last_human_hand", hand.0.as_ref().to_owned());
+    context.insert("last_computer", computer.as_icon().to_owned());
+    context.insert("last_result", verdict.describe().to_owned());
+    context.insert("last_random", random);
+    context.insert("last_hand", computer.as_ref().to_owned());
+    context.insert("last_digest", digest);
+    insert_latency_view(&mut context, &session);
+    insert_trainer_view(&mut context, &session, hand.0, computer);
+    if let Skin::Full = skin {
+        insert_proof_view(&mut context, &session);
+    }
+    // The page of the first submission already planted the decoy of the pending round.
+    if repeated.is_none() {
+        session.plant_decoy();
+    }
+    insert_session_view(&mut context, &session);
+    let page = render(skin.template(), &context);
+    Ok(Transaction::new(session, page).padded(padding))
+}
+
+// ===== Lite =====
+//
+// Mounted at `/lite`: the game page again, off the same context, but under 2 KB of plain HTML
+// without scripts, styles or emoji, for terminal browsers and slow links. Every action is a link
+// or a form with an access key.
+
+#[get("/", rank = 1)]
+fn lite_index(session: SessionGuard) -> Transaction<Template> {
+    index_page(session, Skin::Lite)
+}
+
+#[get("/", rank = 2)]
+fn lite_login_page(flash: Option<FlashMessage>) -> Template {
+    let mut context = HashMap::new();
+    insert_flash(&mut context, flash.as_ref(), Language::default());
+    render("lite/login", &context)
+}
+
+#[get("/?<query>")]
+fn lite_play(padding: RevealPadding, session: SessionGuard, query: PlayQuery)
+    -> Result<Transaction<Template>, Redirect>
+{
+    play_page(padding, session, query, Skin::Lite)
+}
+
+#[post("/login", data = "<login>")]
+fn lite_login(mut cookies: Cookies, login: Form<Login>, limits: State<SessionLimits>,
+              bans: State<Bans>, entropy: State<Entropy>, remote: Option<SocketAddr>,
+              device: Device) -> Result<Redirect, status::Custom<Template>> {
+    log_in(&mut cookies, &login.get().user_name, &limits, &bans, &entropy, remote, &device)?;
+    Ok(Redirect::to(Skin::Lite.home()))
+}
+
+#[get("/chaos", rank = 1)]
+fn user_chaos(mut session: SessionGuard) -> Transaction<Template> {
+    let mut context = HashMap::new();
+    context.insert("user_name", session.user.name.clone());
+    reset_last_view(&mut context);
+
+    insert_score_view(&mut context, &session.chaos_score);
+    insert_chain_view(&mut context, &session);
+    context.insert("digest", session.start_chaos_round().digest.clone());
+    let page = render("chaos", &context);
+    Transaction::new(session, page)
+}
+
+#[get("/chaos", rank = 2)]
+fn chaos() -> Redirect {
+    Redirect::to("/login")
+}
+
+#[get("/chaos?<hands>")]
+fn user_play_chaos(padding: RevealPadding, mut session: SessionGuard, hands: UserHands)
+    -> Option<Transaction<Template>>
+{
+    let mut context = HashMap::new();
+    context.insert("user_name", session.user.name.clone());
+
+    let (last_round, bouts, verdict) = session.play_chaos(&hands.0)?;
+
+    insert_score_view(&mut context, &session.chaos_score);
+    context.insert("last_human", icons(&hands.0));
+    context.insert("last_computer", icons(&last_round.computer));
+    context.insert("last_bouts", bouts.iter().map(|v| v.describe()).collect::<Vec<_>>().join(" / "));
+    context.insert("last_result", verdict.describe().to_owned());
+    context.insert("last_random", last_round.random_bytes.to_owned());
+    context.insert("last_hand", commitment::join_hands(&last_round.computer));
+    context.insert("last_digest", last_round.digest.to_owned());
+    insert_latency_view(&mut context, &session);
+
+    context.insert("digest", session.last_multi_round.as_ref().unwrap().digest.clone());
+    insert_chain_view(&mut context, &session);
+    let page = render("chaos", &context);
+    Some(Transaction::new(session, page).padded(padding))
+}
+
+/// Starts a seeded match, unless the server is in maintenance mode.
+#[post("/match/seeded")]
+fn start_seeded_match(mut session: SessionGuard) -> Redirect {
+    if !maintenance::is_on() {
+        session.seeded_match = Some(MatchSeed::random());
+        session.last_round = None;
+    }
+    Redirect::to("/")
+}
+
+/// Ends the seeded match and reveals its seed along with every round derived from it.
+#[post("/match/end")]
+fn end_seeded_match(mut session: SessionGuard) -> Option<Transaction<Template>> {
+    let seed = session.end_match()?;
+    session.start_round();
+
+    let rounds: Vec<_> = (0..seed.round_count())
+        .map(|index| {
+            let (nonce, hand) = seed.derive(index);
+            let round = Round::new(hand, &nonce, Strategy::Seeded);
+            json!({
+                "index": index,
+                "hand": round.computer.as_ref(),
+                "random": round.random_bytes,
+                "digest": round.digest,
+            })
+        })
+        .collect();
+
+    let context = json!({
+        "user_name": session.user.name,
+        "seed": seed.seed_hex(),
+        "commitment": seed.commitment(),
+        "rounds": rounds,
+    });
+    let page = render("match_end", &context);
+    Some(Transaction::new(session, page))
+}
+
+#[post("/practice")]
+fn toggle_practice(mut session: SessionGuard) -> Redirect {
+    session.practice = !session.practice;
+    Redirect::to("/")
+}
+
+/// Turns fun mode on or off, from the next round on.
+#[post("/fun-mode")]
+fn toggle_fun_mode(mut session: SessionGuard) -> Redirect {
+    session.adaptive = match session.adaptive {
+        Some(_) => None,
+        None => Some(Adaptive::new()),
+    };
+    Redirect::to("/")
+}
+
+/// Turns sudden throws on or off, from the next round on.
+#[post("/sudden-throw")]
+fn toggle_sudden_throws(mut session: SessionGuard) -> Redirect {
+    session.sudden_throws = !session.sudden_throws;
+    Redirect::to("/")
+}
+
+#[post("/arcade")]
+fn toggle_arcade(mut session: SessionGuard) -> Redirect {
+    session.arcade = match session.arcade {
+        Some(_) => None,
+        None => Some(Arcade::new()),
+    };
+    Redirect::to("/")
+}
+
+/// Spends a peek on the pending round. Renders the page in place, since `GET /` would start a new
+/// round.
+#[post("/arcade/peek")]
+fn arcade_peek(mut session: SessionGuard) -> Option<Transaction<Template>> {
+    let mut context = HashMap::new();
+    context.insert("user_name", session.user.name.clone());
+    reset_last_view(&mut context);
+
+    let computer = session.last_round.as_ref()?.computer;
+    session.arcade.as_mut()?.peek(computer);
+    insert_score_view(&mut context, &session.score);
+    insert_session_view(&mut context, &session);
+    let page = render("index", &context);
+    Some(Transaction::new(session, page))
+}
+
+#[derive(FromForm)]
+struct Purchase {
+    item: String,
+}
+
+/// Buys an item of the arcade shop with points.
+#[post("/arcade/shop", data = "<purchase>")]
+fn arcade_shop(mut session: SessionGuard, purchase: Form<Purchase>) -> Redirect {
+    let item = Item::parse(&purchase.get().item);
+    if let (Some(item), Some(arcade)) = (item, session.arcade.as_mut()) {
+        arcade.buy(item);
+    }
+    Redirect::to("/")
+}
+
+/// Spends a redo to void the round just lost.
+#[post("/arcade/redo")]
+fn arcade_redo(mut session: SessionGuard) -> Redirect {
+    session.redo();
+    Redirect::to("/")
+}
+
+/// A page of the session's revealed rounds matching the query, latest first, each with a form to
+/// edit its note. Only the chunks of rounds that may match are decoded, see `History::search`.
+fn history_page(user: &User, session: &Session, flash: Option<(Level, String)>,
+                query: HistoryQuery) -> Template {
+    let skip = query.page.saturating_mul(history::PAGE_LEN);
+    let locale = profile::locale(&user.name);
+    // The dates searched are the player's days, which start at midnight at their offset.
+    let days = Filter {
+        since: query.filter.since.map(|since| locale.from_local_time(since)),
+        until: query.filter.until.map(|until| locale.from_local_time(until)),
+        ..query.filter
+    };
+    let mut found = session.history.search(&days, skip, history::PAGE_LEN + 1);
+    let older = found.len() > history::PAGE_LEN;
+    found.truncate(history::PAGE_LEN);
+    let rounds: Vec<_> = found.into_iter()
+        .map(|(index, record)| {
+            json!({
+                "index": index + 1,
+                "played_at": locale.format_time(record.played_at),
+                "kind": record.kind,
+                "human": record.human,
+                "computer": record.computer,
+                "verdict": record.verdict,
+                "link": record.link,
+                "note": record.note,
+            })
+        })
+        .collect();
+
+    let link = |page| {
+        let query = HistoryQuery { page: page, filter: query.filter, ..HistoryQuery::default() };
+        let query = query.to_query();
+        if query.is_empty() { "/history".to_owned() } else { format!("/history?{}", query) }
+    };
+    let filter = &query.filter;
+    let hands = Hand::CHOICES;
+    let context = json!({
+        "user_name": user.name,
+        "chain_head": session.history.head(),
+        "rounds": rounds,
+        "time_zone": locale.time_zone(),
+        "filtered": *filter != Filter::default(),
+        "verdicts": search_options(Verdict::ALL.iter().map(|verdict| verdict.name()),
+                                   filter.verdict.map(|verdict| verdict.name())),
+        "hands": search_options(hands.iter().map(|hand| hand.as_ref()),
+                                filter.hand.as_ref().map(|hand| hand.as_ref())),
+        "opponents": search_options(Strategy::ALL.iter().map(|strategy| strategy.name()),
+                                    filter.opponent.map(|strategy| strategy.name())),
+        "since": filter.since.map(util::format_date),
+        "until": filter.until.map(|until| util::format_date(until - 1)),
+        "newer": if query.page > 0 { Some(link(query.page - 1)) } else { None },
+        "older": if older { Some(link(query.page + 1)) } else { None },
+        "max_note_len": MAX_NOTE_LEN,
+        "flash": flash.as_ref().map(|&(_, ref text)| text),
+        "flash_level": flash.as_ref().map(|&(level, _)| level.name()),
+    });
+    render("history", &context)
+}
+
+/// The options of a select of the history search, with `selected` selected.
+fn search_options<'a, I: Iterator<Item = &'a str>>(names: I, selected: Option<&str>) -> Vec<Value> {
+    names.map(|name| json!({ "name": name, "selected": selected == Some(name) })).collect()
+}
+
+/// The latest page of the history. The history pages only read the session, and take the flash
+/// message before they lock it.
+#[get("/history")]
+fn history(user: User, flash: Option<FlashMessage>) -> Option<Template> {
+    let language = profile::locale(&user.name).language;
+    let flash = flash.as_ref().map(|flash| flash::read(flash, language));
+    ::with_session(&user.id, |session| {
+        history_page(&user, session, flash, HistoryQuery::default())
+    })
+}
+
+/// A page of the history, searched by verdict, hand, opponent and date, see `HistoryQuery`.
+#[get("/history?<query>")]
+fn history_at(user: User, flash: Option<FlashMessage>, query: Result<HistoryQuery, QueryError>)
+    -> Option<Result<Template, Flash<Redirect>>>
+{
+    let language = profile::locale(&user.name).language;
+    let flash = flash.as_ref().map(|flash| flash::read(flash, language));
+    let query = match query {
+        Ok(query) => query,
+        Err(e) => {
+            return Some(Err(Notice::error("history.bad-search").with(&e.to_string())
+                                .to(Redirect::to("/history"))));
+        }
+    };
+    ::with_session(&user.id, |session| Ok(history_page(&user, session, flash, query)))
+}
+
+#[derive(FromForm)]
+struct Note {
+    link: String,
+    note: String,
+}
+
+/// Sets the note on a round of the history; a blank note removes it. The form is read before the
+/// session is locked.
+#[post("/history/note", data = "<note>")]
+fn annotate_round(user: User, note: Form<Note>) -> Flash<Redirect> {
+    let note = note.get();
+    if ::annotate(&user.id, &note.link, &note.note) {
+        Notice::success("history.note-saved").to(Redirect::to("/history"))
+    } else {
+        Notice::error("history.no-such-round").to(Redirect::to("/history"))
+    }
+}
+
+/// Lifetime stats, and a heatmap of when the player plays.
+#[get("/profile")]
+fn profile_page(user: User, flash: Option<FlashMessage>) -> Template {
+    let profile = profile::get(&user.name);
+    let locale = profile.as_ref().and_then(|profile| profile.locale).unwrap_or_default();
+    let activity = profile.as_ref().map_or([[0; 24]; 7], |profile| profile.activity);
+    let busiest = activity.iter().flat_map(|hours| hours.iter()).cloned().max().unwrap_or(0);
+    let days: Vec<_> = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"].iter()
+        .zip(activity.iter())
+        .map(|(day, hours)| {
+            let cells: Vec<_> = hours.iter()
+                .enumerate()
+                .map(|(hour, &rounds)| json!({
+                    "hour": hour,
+                    "rounds": rounds,
+                    // Five shades, the darkest for the busiest hour.
+                    "level": if rounds == 0 { 0 } else { 1 + rounds * 3 / busiest.max(1) },
+                }))
+                .collect();
+            json!({ "day": day, "hours": cells })
+        })
+        .collect();
+
+    // Only the owner sees where the alerts came from, see `alerts`.
+    let alerts: Vec<_> = profile.as_ref().filter(|_| user.verified).map_or(Vec::new(), |profile| {
+        profile.alerts.iter().rev()
+            .map(|alert| json!({
+                "what": alert.kind.describe(),
+                "at": locale.format_time(alert.at),
+                "ip": alert.ip,
+                "user_agent": alert.user_agent,
+                "new": !alert.seen,
+            }))
+            .collect()
+    });
+    let unseen = profile.as_ref().map_or(false, |profile| {
+        profile.alerts.iter().any(|alert| !alert.seen)
+    });
+    if unseen && user.verified {
+        profile::see_alerts(&user.name);
+    }
+
+    let flash = flash.as_ref().map(|flash| flash::read(flash, locale.language));
+    let context = json!({
+        "user_name": user.name,
+        "profile": profile.as_ref().map(|profile| public_profile_view(profile, &locale)),
+        "alerts": alerts,
+        "alerts_hidden": unseen && !user.verified,
+        "languages": Language::ALL.iter()
+            .map(|language| json!({
+                "code": language.code(),
+                "name": language.name(),
+                "selected": *language == locale.language,
+            }))
+            .collect::<Vec<_>>(),
+        "utc_offset": locale::format_utc_offset(locale.utc_offset),
+        "time_zone": locale.time_zone(),
+        "hidden": profile.as_ref().map_or(false, |profile| profile.hidden),
+        // The secret signs the posts, and the URL receives them.
+        "webhook": profile.as_ref()
+            .filter(|_| user.verified)
+            .and_then(|profile| profile.webhook.as_ref()),
+        "can_set_webhook": user.verified,
+        "passkeys": profile.as_ref().map_or(Vec::new(), |profile| {
+            profile.passkeys.iter()
+                .map(|passkey| json!({
+                    "id": passkey.id,
+                    "added": locale.format_time(passkey.added_at),
+                    "last_used": passkey.last_used.map(|at| locale.format_time(at)),
+                }))
+                .collect()
+        }),
+        "can_remove_passkeys": user.verified,
+        "can_add_passkey": user.verified && profile.as_ref()
+            .map_or(true, |profile| profile.passkeys.len() < passkey::MAX_PASSKEYS),
+        "other_sessions": ::session_count(&user.name).saturating_sub(1),
+        "csrf": csrf::token(&user.id),
+        "days": days,
+        "hours": (0..24).collect::<Vec<_>>(),
+        "flash": flash.as_ref().map(|&(_, ref text)| text),
+        "flash_level": flash.as_ref().map(|&(level, _)| level.name()),
+    });
+    render("profile", &context)
+}
+
+#[derive(FromForm)]
+struct LogoutEverywhere {
+    csrf: String,
+}
+
+/// Ends every session of the player, this one included, and revokes their API keys, e.g. after
+/// logging in on a device they no longer have. Only a verified session does, or whoever logged in
+/// under the name could log its owner out.
+#[post("/account/logout-everywhere", data = "<logout>")]
+fn logout_everywhere(user: User, logout: Form<LogoutEverywhere>, mut cookies: Cookies,
+                     revocations: State<Revocations>) -> Flash<Redirect> {
+    if !csrf::verify(&user.id, &logout.get().csrf) {
+        return Notice::error("csrf.invalid").to(Redirect::to("/profile"));
+    }
+    if !user.verified {
+        return Notice::error("account.unverified").to(Redirect::to("/profile"));
+    }
+    let ended = ::end_sessions_of(&user.name, &revocations).len();
+    credentials::remove(&mut cookies);
+    snapshot::remove(&mut cookies);
+    Notice::success("account.logged-out-everywhere").with(&ended.to_string())
+        .to(Redirect::to("/login"))
+}
+
+#[derive(FromForm)]
+struct Deletion {
+    /// The player's name, typed again.
+    confirm: String,
+    /// A recovery code, unless the session logged in with a passkey lately.
+    code: String,
+    csrf: String,
+}
+
+/// How the player proves they own the account before a change they could not take back: a
+/// passkey login within `account::REAUTHENTICATION_WINDOW`, or else the recovery `code`, whose
+/// failures count towards the `lockout` of the account and `ip` as on `/recover`.
+fn prove_owner(user: &User, code: &str, ip: Option<IpAddr>) -> Result<Proof, Notice> {
+    if ::passkey_login_within(&user.id, account::REAUTHENTICATION_WINDOW) {
+        return Ok(Proof::Passkey);
+    }
+    if code.trim().is_empty() {
+        return Err(Notice::error("account.unproven"));
+    }
+    if let Some(wait) = lockout::wait(&user.name, ip) {
+        return Err(Notice::error("recovery.locked-out")
+            .with(&lockout::wait_minutes(wait).to_string()));
+    }
+    if !recovery::redeem(&user.name, code) {
+        lockout::fail(&user.name, ip);
+        return Err(Notice::error("account.unproven"));
+    }
+    lockout::succeed(&user.name, ip);
+    Ok(Proof::RecoveryCode)
+}
+
+/// Deletes the player's account and everything kept under it, see `account`, then logs them out.
+#[post("/account/delete", data = "<deletion>")]
+fn delete_account(user: User, deletion: Form<Deletion>, mut cookies: Cookies,
+                  revocations: State<Revocations>, remote: Option<SocketAddr>)
+    -> Flash<Redirect>
+{
+    let deletion = deletion.get();
+    if !csrf::verify(&user.id, &deletion.csrf) {
+        return Notice::error("csrf.invalid").to(Redirect::to("/profile"));
+    }
+    if deletion.confirm != user.name {
+        return Notice::error("account.unconfirmed").to(Redirect::to("/profile"));
+    }
+    let proof = match prove_owner(&user, &deletion.code, remote.map(|addr| addr.ip())) {
+        Ok(proof) => proof,
+        Err(notice) => return notice.to(Redirect::to("/profile")),
+    };
+    if let Err(e) = account::delete(&user.name, proof, &revocations) {
+        eprintln!("Unable to log the deletion of an account: {}", e);
+        return Notice::error("account.not-deleted").to(Redirect::to("/profile"));
+    }
+    credentials::remove(&mut cookies);
+    snapshot::remove(&mut cookies);
+    Notice::success("account.deleted").to(Redirect::to("/login"))
+}
+
+#[derive(FromForm)]
+struct WebhookUrl {
+    /// Empty to remove the webhook.
+    url: String,
+}
+
+/// Sets where the player's rounds are posted, under a new secret, or stops posting them. Only a
+/// verified session does, or whoever logged in under a name could have the rounds posted to them.
+#[post("/profile/webhook", data = "<webhook>")]
+fn profile_webhook(user: User, webhook: Form<WebhookUrl>) -> Flash<Redirect> {
+    if !user.verified {
+        return Notice::error("webhook.unverified").to(Redirect::to("/profile"));
+    }
+    let url = webhook.get().url.trim();
+    if url.is_empty() {
+        profile::set_webhook(&user.name, None);
+        return Notice::success("webhook.removed").to(Redirect::to("/profile"));
+    }
+    match Webhook::new(url) {
+        Ok(webhook) => {
+            profile::set_webhook(&user.name, Some(webhook));
+            Notice::success("webhook.saved").to(Redirect::to("/profile"))
+        }
+        Err(e) => Notice::error(e.code()).to(Redirect::to("/profile")),
+    }
+}
+
+/// Queues a test post to the player's webhook.
+#[post("/profile/webhook/test")]
+fn test_webhook(user: User) -> Flash<Redirect> {
+    if !user.verified {
+        return Notice::error("webhook.unverified").to(Redirect::to("/profile"));
+    }
+    match webhook::test(&user.name) {
+        Ok(()) => Notice::success("webhook.test-queued").to(Redirect::to("/profile")),
+        Err(e) => Notice::error(e.code()).to(Redirect::to("/profile")),
+    }
+}
+
+/// The rating of a profile, e.g. `1523`, with its deviation if Glicko-2 tracks it: `1523 ± 87`.
+/// A deviation left from before the server switched to Elo is not shown.
+fn describe_rating(profile: &UserProfile) -> String {
+    match profile.rating_deviation.filter(|_| rating::system().tracks_deviation()) {
+        Some(deviation) => format!("{:.0} ± {:.0}", profile.rating, deviation),
+        None => format!("{:.0}", profile.rating),
+    }
+}
+
+/// The counts of a scoreboard, written for `locale`.
+fn scoreboard_view(score: &Scoreboard, locale: &Locale) -> Value {
+    json!({
+        "wins": locale.format_count(score.wins as u64),
+        "ties": locale.format_count(score.ties as u64),
+        "losses": locale.format_count(score.losses as u64),
+    })
+}
+
+/// What anyone may see of a profile, on `/u/<name>` and atop the player's own `/profile`, with
+/// counts written for `locale`, the viewer's.
+fn public_profile_view(profile: &UserProfile, locale: &Locale) -> Value {
+    let matches: Vec<_> = profile.recent_matches.iter().rev()
+        .map(|summary| json!({
+            "commitment": summary.commitment,
+            "rounds": locale.format_count(summary.rounds),
+            "finished_at": summary.finished_at,
+        }))
+        .collect();
+    json!({
+        "classic": scoreboard_view(&profile.classic, locale),
+        "chaos": scoreboard_view(&profile.chaos, locale),
+        "rating": describe_rating(profile),
+        "best_streak": locale.format_count(u64::from(profile.best_streak)),
+        "achievements": profile.achievements.iter()
+            .map(|achievement| achievement.describe())
+            .collect::<Vec<_>>(),
+        "recent_matches": matches,
+    })
+}
+
+/// The public profile of `name`. Not found if they never logged in, or hid it.
+#[get("/u/<name>")]
+fn public_profile(name: String, viewer: Option<User>) -> Option<Template> {
+    let profile = profile::get_public(&name)?;
+    let locale = viewer.map_or_else(Locale::default, |viewer| profile::locale(&viewer.name));
+    let context = json!({
+        "user_name": name,
+        "profile": public_profile_view(&profile, &locale),
+    });
+    Some(render("public_profile", &context))
+}
+
+#[derive(FromForm)]
+struct Visibility {
+    hidden: bool,
+}
+
+#[derive(FromForm)]
+struct LocaleChoice {
+    language: String,
+    /// E.g. `+02:00`.
+    utc_offset: String,
+}
+
+/// Sets how pages write times and numbers for the player.
+#[post("/profile/locale", data = "<choice>")]
+fn profile_locale(user: User, choice: Form<LocaleChoice>) -> Flash<Redirect> {
+    let choice = choice.get();
+    let language = match choice.language.parse() {
+        Ok(language) => language,
+        Err(_) => return Notice::error("locale.invalid-language").to(Redirect::to("/profile")),
+    };
+    let utc_offset = match locale::parse_utc_offset(&choice.utc_offset) {
+        Some(offset) => offset,
+        None => return Notice::error("locale.invalid-offset").to(Redirect::to("/profile")),
+    };
+    profile::set_locale(&user.name, Locale { language: language, utc_offset: utc_offset });
+    Notice::success("locale.saved").to(Redirect::to("/profile"))
+}
+
+/// Hides the player's public profile, or shows it again.
+#[post("/profile/visibility", data = "<visibility>")]
+fn profile_visibility(user: User, visibility: Form<Visibility>) -> Redirect {
+    profile::set_hidden(&user.name, visibility.get().hidden);
+    Redirect::to("/profile")
+}
+
+#[derive(FromForm)]
+struct OpponentChoice {
+    opponent: String,
+}
+
+/// Picks the computer's strategy family and commits it to a new round, unless a seeded match is
+/// on, as `api::opponent` does.
+#[post("/opponent", data = "<choice>")]
+fn choose_opponent(mut session: SessionGuard, choice: Form<OpponentChoice>) -> Redirect {
+    if let Ok(opponent) = choice.get().opponent.parse() {
+        session.opponent = opponent;
+        session.rotation = None;
+        session.script = None;
+        if session.seeded_match.is_none() {
+            session.start_round();
+        }
+    }
+    Redirect::to("/")
+}
+
+#[derive(FromForm)]
+struct RotationWeights {
+    random: u32,
+    mirror: u32,
+    counter_mirror: u32,
+}
+
+/// Has the computer rotate among the opponents with these weights, from the next classic round
+/// on. All weights 0 stops the rotation, leaving the last opponent drawn.
+#[post("/opponent/rotation", data = "<weights>")]
+fn rotate_opponents(mut session: SessionGuard, weights: Form<RotationWeights>) -> Redirect {
+    let weights = weights.get();
+    session.rotation = Rotation::new(weights.random, weights.mirror, weights.counter_mirror);
+    session.script = None;
+    Redirect::to("/")
+}
+
+/// Turns a house rule on or off.
+#[post("/rules/<name>")]
+fn toggle_rule(mut session: SessionGuard, name: String) -> Option<Redirect> {
+    let kind = RuleKind::parse(&name)?;
+    session.toggle_rule(kind);
+    Some(Redirect::to("/"))
+}
+
+/// Opts the player out of (or back into) anonymized analytics, in every session of theirs.
+#[post("/analytics/opt-out")]
+fn toggle_analytics(user: User) -> Redirect {
+    profile::set_analytics_opt_out(&user.name, !profile::opted_out_of_analytics(&user.name));
+    Redirect::to("/")
+}
+
+fn member(user: User) -> Member {
+    Member {
+        id: user.id,
+        name: user.name,
+    }
+}
+
+/// The co-op page: the team's shared score and, on its member's turn, the hands to pick from.
+fn team_page(user: &User, flash: Option<&FlashMessage>,
+             last: Option<HashMap<&'static str, String>>) -> Template
+{
+    let mut context = last.unwrap_or_else(HashMap::new);
+    context.insert("user_name", user.name.clone());
+    insert_flash(&mut context, flash, profile::locale(&user.name).language);
+    team::with_team(&user.id, |team| {
+        context.insert("team_name", team.name.clone());
+        context.insert("team_code", team.code.clone());
+        context.insert("team_members", team.member_names());
+        insert_score_view(&mut context, &team.score);
+        context.insert("chain_head", team.history.head().to_owned());
+        match team.up_next() {
+            Some(member) if member.id == user.id => {
+                context.insert("your_turn", "true".to_owned());
+            }
+            Some(member) => {
+                context.insert("up_next", member.name.clone());
+            }
+            None => {}
+        }
+        if let Some(ref round) = team.last_round {
+            context.insert("digest", round.digest.clone());
+        }
+    });
+    render("team", &context)
+}
+
+#[get("/team", rank = 1)]
+fn user_team(user: User, flash: Option<FlashMessage>) -> Template {
+    team_page(&user, flash.as_ref(), None)
+}
+
+#[get("/team", rank = 2)]
+fn team_login() -> Redirect {
+    Redirect::to("/login")
+}
+
+#[get("/team?<hand>")]
+fn user_play_team(user: User, hand: HandParam) -> Result<Template, Flash<Redirect>> {
+    let (round, verdict) = team::play(&user.id, hand.0)
+        .map_err(|e| Notice::error(e.code()).to(Redirect::to("/team")))?;
+    let mut last = HashMap::new();
+    last.insert("last_human", hand.0.as_icon().to_owned());
+    last.insert("last_computer", round.computer.as_icon().to_owned());
+    last.insert("last_result", verdict.describe().to_owned());
+    last.insert("last_random", round.random_bytes.clone());
+    last.insert("last_hand", round.computer.as_ref().to_owned());
+    last.insert("last_digest", round.digest.clone());
+    Ok(team_page(&user, None, Some(last)))
+}
+
+#[derive(FromForm)]
+struct TeamForm {
+    name: String,
+}
+
+#[post("/team", data = "<form>")]
+fn form_team(user: User, form: Form<TeamForm>) -> Flash<Redirect> {
+    match team::form(member(user), &form.get().name) {
+        Ok(_) => Notice::success("team.formed").to(Redirect::to("/team")),
+        Err(e) => Notice::error(e.code()).to(Redirect::to("/team")),
+    }
+}
+
+#[derive(FromForm)]
+struct Invite {
+    code: String,
+}
+
+#[post("/team/join", data = "<invite>")]
+fn join_team(user: User, invite: Form<Invite>) -> Flash<Redirect> {
+    match team::join(member(user), &invite.get().code) {
+        Ok(()) => Notice::success("team.joined").to(Redirect::to("/team")),
+        Err(e) => Notice::error(e.code()).to(Redirect::to("/team")),
+    }
+}
+
+/// Leaving disbands the team, for the teammate too.
+#[post("/team/leave")]
+fn leave_team(user: User) -> Redirect {
+    team::leave(&user.id);
+    Redirect::to("/team")
+}
+
+/// The PvP page: the match in progress or, outside of one, the pending invite link.
+fn pvp_page(user: &User, flash: Option<&FlashMessage>) -> Template {
+    let mut context = HashMap::new();
+    context.insert("user_name", user.name.clone());
+    insert_flash(&mut context, flash, profile::locale(&user.name).language);
+    let in_match = pvp::with_match(&user.id, |m| {
+        let opponent = m.opponent(&user.id).name.clone();
+        insert_score_view(&mut context, &m.score(&user.id));
+        if let Some((own, other, verdict)) = m.last_round(&user.id) {
+            context.insert("last_human", own.as_icon().to_owned());
+            context.insert("last_opponent", other.as_icon().to_owned());
+            let result = match verdict {
+                Verdict::Loss => format!("{} won", opponent),
+                verdict => verdict.describe().to_owned(),
+            };
+            context.insert("last_result", result);
+        }
+        let reactions = reactions_view(&m.crowd);
+        if !reactions.is_empty() {
+            context.insert("crowd", reactions);
+        }
+        context.insert("opponent", opponent);
+        if m.has_picked(&user.id) {
+            context.insert("picked", "true".to_owned());
+        }
+        if m.is_correspondence() {
+            let days = pvp::CORRESPONDENCE_TTL / (24 * 3600);
+            context.insert("correspondence_days", days.to_string());
+        }
+    });
+    if in_match.is_none() {
+        if let Some(invite) = pvp::pending_invite(&user.id) {
+            context.insert("invite_token", invite.token);
+            let minutes_left = invite.expires_at.saturating_sub(unix_time()) / 60;
+            context.insert("invite_minutes", minutes_left.to_string());
+        }
+    }
+    let mut context = json!(context);
+    if in_match.is_none() {
+        let emailed: Vec<_> = pvp::email_invites_of(&user.name).into_iter()
+            .map(|invite| {
+                let days_left = invite.expires_at.saturating_sub(unix_time()) / (24 * 3600);
+                json!({ "email": invite.email, "days": days_left })
+            })
+            .collect();
+        context["emailed_invites"] = json!(emailed);
+        let live: Vec<_> = pvp::live_matches().into_iter()
+            .map(|(id, host, guest)| json!({ "id": id, "host": host.name, "guest": guest.name }))
+            .collect();
+        context["live_matches"] = json!(live);
+    }
+    render("pvp", &context)
+}
+
+/// The spectators' reactions to the latest round, e.g. `👏 3 😮 1`; empty if there were none.
+fn reactions_view(crowd: &pvp::Crowd) -> String {
+    crowd.last_reactions().iter()
+        .map(|&(reaction, count)| format!("{} {}", reaction.as_icon(), count))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Watching someone else's match: the score, the latest round, and the spectators' corner.
+#[get("/pvp/watch/<id>")]
+fn watch_pvp(user: User, id: String, flash: Option<FlashMessage>)
+    -> Result<Template, Flash<Redirect>>
+{
+    let language = profile::locale(&user.name).language;
+    let flash = flash.as_ref().map(|flash| flash::read(flash, language));
+    let context = pvp::watch(&id, &user.id, |m| {
+        let (host, guest) = (&m.players[0], &m.players[1]);
+        let score = m.score(&host.id);
+        let chat: Vec<_> = m.crowd.chat().iter()
+            .map(|message| json!({ "user_name": message.user_name, "text": message.text }))
+            .collect();
+        json!({
+            "id": m.id,
+            "user_name": user.name,
+            "flash": flash.as_ref().map(|&(_, ref text)| text),
+            "flash_level": flash.as_ref().map(|&(level, _)| level.name()),
+            "host": host.name,
+            "guest": guest.name,
+            "host_wins": score.wins,
+            "ties": score.ties,
+            "guest_wins": score.losses,
+            "last_hands": m.last_hands().map(|hands| icons(&hands)),
+            "crowd": Some(reactions_view(&m.crowd)).filter(|view| !view.is_empty()),
+            "reacted": m.crowd.has_reacted(&user.id),
+            "reactions": pvp::Reaction::ALL.iter()
+                .map(|reaction| json!({ "name": reaction.name(), "icon": reaction.as_icon() }))
+                .collect::<Vec<_>>(),
+            "chat": chat,
+            "max_chat_len": pvp::MAX_CHAT_LEN,
+        })
+    });
+    match context {
+        Ok(context) => Ok(render("pvp_watch", &context)),
+        Err(e) => Err(Notice::error(e.code()).to(Redirect::to("/pvp"))),
+    }
+}
+
+#[derive(FromForm)]
+struct ReactionForm {
+    reaction: String,
+}
+
+/// Reacts to the pending round of a watched match.
+#[post("/pvp/watch/<id>/react", data = "<form>")]
+fn react_pvp(user: User, id: String, form: Form<ReactionForm>) -> Option<Flash<Redirect>> {
+    let reaction = pvp::Reaction::parse(&form.get().reaction)?;
+    let back = Redirect::to(&format!("/pvp/watch/{}", id));
+    Some(match pvp::react(&id, &user.id, reaction) {
+        Ok(()) => Notice::success("pvp.reacted").to(back),
+        Err(e) => Notice::error(e.code()).to(back),
+    })
+}
+
+#[derive(FromForm)]
+struct ChatForm {
+    text: String,
+}
+
+/// Posts to the spectator chat of a watched match, which the players don't see.
+#[post("/pvp/watch/<id>/chat", data = "<form>")]
+fn chat_pvp(user: User, id: String, form: Form<ChatForm>) -> Flash<Redirect> {
+    let back = Redirect::to(&format!("/pvp/watch/{}", id));
+    match pvp::say(&id, &member(user), &form.get().text) {
+        Ok(()) => Notice::success("pvp.sent").to(back),
+        Err(e) => Notice::error(e.code()).to(back),
+    }
+}
+
+#[get("/pvp", rank = 1)]
+fn user_pvp(user: User, flash: Option<FlashMessage>) -> Template {
+    pvp_page(&user, flash.as_ref())
+}
+
+#[get("/pvp", rank = 2)]
+fn pvp_login() -> Redirect {
+    Redirect::to("/login")
+}
+
+#[get("/pvp?<hand>")]
+fn user_play_pvp(user: User, hand: HandParam) -> Flash<Redirect> {
+    match pvp::play(&user.id, hand.0) {
+        Ok(Some(_)) => Notice::success("pvp.both-picked").to(Redirect::to("/pvp")),
+        Ok(None) => Notice::success("pvp.picked").to(Redirect::to("/pvp")),
+        Err(e) => Notice::error(e.code()).to(Redirect::to("/pvp")),
+    }
+}
+
+/// Issues a new invite link, revoking any previous one.
+#[post("/pvp/invite")]
+fn invite_pvp(user: User) -> Flash<Redirect> {
+    match pvp::invite(member(user)) {
+        Ok(_) => Notice::success("pvp.invite-ready").to(Redirect::to("/pvp")),
+        Err(e) => Notice::error(e.code()).to(Redirect::to("/pvp")),
+    }
+}
+
+#[derive(FromForm)]
+struct EmailInviteForm {
+    email: String,
+}
+
+/// Emails an invite link for a correspondence match, see `pvp`.
+#[post("/pvp/invite/email", data = "<form>")]
+fn email_invite_pvp(user: User, form: Form<EmailInviteForm>, site: SiteUrl,
+                    outbox: State<Outbox<Notification>>, remote: Option<SocketAddr>)
+    -> Flash<Redirect>
+{
+    if !user.verified {
+        return Notice::error("pvp.unverified").to(Redirect::to("/pvp"));
+    }
+    let remote = remote.map(|addr| addr.ip());
+    let invite = match pvp::invite_by_email(&user.name, form.get().email.trim(), remote) {
+        Ok(invite) => invite,
+        Err(e) => return Notice::error(e.code()).to(Redirect::to("/pvp")),
+    };
+    let days = pvp::EMAIL_INVITE_TTL / (24 * 3600);
+    let body = format!(
+        "{} challenges you to a match of rock-paper-scissors, by correspondence: pick your \
+         hand whenever you like, and come back for the result.\n\n\
+         Accept within {} days at {}/invite/{}\n\n\
+         You will be asked to log in first, under your usual name or a new one.\n",
+        user.name, days, site.0, invite.token);
+    outbox.send(Notification {
+        to: invite.email.clone(),
+        subject: format!("Rock-Paper-Scissors: {} challenges you", user.name),
+        body: body,
+    });
+    Notice::success("pvp.invite-emailed").with(&invite.email).with(&days.to_string())
+        .to(Redirect::to("/pvp"))
+}
+
+/// Opening an invite link, emailed or not, starts the match against its host.
+#[get("/invite/<token>", rank = 1)]
+fn accept_invite(user: User, token: String) -> Flash<Redirect> {
+    match pvp::accept(member(user), &token) {
+        Ok(host) => Notice::success("pvp.match-on").with(&host.name).to(Redirect::to("/pvp")),
+        Err(e) => Notice::error(e.code()).to(Redirect::to("/pvp")),
+    }
+}
+
+/// Remembers the invite link while the visitor logs in, which then takes them back to it.
+#[get("/invite/<token>", rank = 2)]
+fn invite_login(mut cookies: Cookies, token: String) -> Flash<Redirect> {
+    cookies.add(cookie_policy::build("invite", token));
+    Notice::warning("pvp.login-to-accept").to(Redirect::to("/login"))
+}
+
+/// Leaving ends the match, for the opponent too.
+#[post("/pvp/leave")]
+fn leave_pvp(user: User) -> Redirect {
+    pvp::leave(&user.id);
+    Redirect::to("/pvp")
+}
+
+/// Suggests the game or the login page, depending on whether the visitor is logged in. The API
+/// answers with an `ApiError` instead.
+#[catch(404)]
+fn not_found(request: &Request) -> Result<status::NotFound<Template>, ApiError> {
+    if api::is_api_request(request) {
+        return Err(api::not_found());
+    }
+    let user = request.guard::<User>().succeeded();
+    let context = json!({
+        "path": request.uri().path(),
+        "user_name": user.map(|user| user.name),
+    });
+    Ok(status::NotFound(render("not_found", &context)))
+}
+
+#[get("/<file..>")]
+fn files(file: Segments, range: RangeHeader, root: State<StaticDir>) -> Option<StaticFile> {
+    assets::resolve(&root.0, file).and_then(|path| StaticFile::open(path, range.0).ok())
+}
+
+pub fn mount(rocket: Rocket) -> Rocket {
+    render::Engine::attach(rocket)
+        .attach(AdHoc::on_attach(|rocket| {
+            let landing = rocket.config().get_bool("landing_page").unwrap_or(true);
+            let static_dir = StaticDir::from_config(rocket.config());
+            Ok(rocket.manage(LandingPage(landing)).manage(static_dir))
+        }))
+        .catch(catchers![not_found])
+        .mount("/",
+            routes![
+                index, user_index, user_play_index,
+                chaos, user_chaos, user_play_chaos,
+                login, login_user, login_page, recover_page, recover, regenerate_recovery_codes,
+                begin_passkey_registration, register_passkey, begin_passkey_login, passkey_login,
+                remove_passkey,
+                poll_handoff, handoff_page, handoff_login, approve_handoff,
+                start_seeded_match, end_seeded_match, toggle_practice, toggle_rule, toggle_analytics,
+                toggle_sudden_throws, toggle_fun_mode, toggle_arcade, arcade_peek, arcade_redo,
+                arcade_shop, choose_opponent, rotate_opponents,
+                history, history_at, annotate_round, profile_page, public_profile, profile_visibility,
+                profile_locale, profile_webhook, test_webhook, logout_everywhere,
+                delete_account,
+                team_login, user_team, user_play_team, form_team, join_team, leave_team,
+                pvp_login, user_pvp, user_play_pvp, invite_pvp, accept_invite, invite_login,
+                leave_pvp, watch_pvp, react_pvp, chat_pvp, email_invite_pvp,
+            ])
+        .mount("/lite", routes![lite_index, lite_login_page, lite_play, lite_login])
+        .mount("/about", routes![about::stats])
+        .mount("/static", routes![files])
+        .mount("/api/v1", routes![api::docs])
+        .mount("/admin", routes![admin::quotas, admin::analytics])
+        .mount("/widget", routes![widget::leaderboard_html, widget::leaderboard_html_in])
+}
@@ -0,0 +1,220 @@
+//! Request parameters shared by the pages, the JSON API and the webhooks
+
+use std::fmt;
+use std::str::FromStr;
+
+use rocket::http::RawStr;
+use rocket::request::{FormItems, FromForm, FromFormValue, FromParam};
+use serde::de::{self, Deserialize, Deserializer};
+
+use game::{Hand, Strategy, Verdict, MULTI_HANDS};
+use history::Filter;
+use util::{format_date, parse_date};
+
+/// A hand given by the player, parsed the same way, case-insensitively, wherever it comes from:
+///
+/// - a whole query or form of one `hand` field (`FromForm`), e.g. `GET /?hand=rock`;
+/// - a field of a derived form (`FromFormValue`);
+/// - a path segment (`FromParam`);
+/// - a JSON string (`Deserialize`), e.g. `{"hand": "rock"}`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct HandParam(pub Hand);
+
+#[derive(Debug)]
+pub enum HandError {
+    /// No `hand` field.
+    Missing,
+    /// Fields other than `hand`, in a strict form.
+    Unexpected(String),
+    Invalid(String),
+}
+
+impl fmt::Display for HandError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            HandError::Missing => write!(f, "missing `hand`"),
+            HandError::Unexpected(ref key) => write!(f, "unexpected field `{}`", key),
+            HandError::Invalid(ref hand) => {
+                write!(f, "`hand` must be rock, paper or scissors, got {:?}", hand)
+            }
+        }
+    }
+}
+
+impl FromStr for HandParam {
+    type Err = HandError;
+
+    fn from_str(s: &str) -> Result<HandParam, HandError> {
+        s.parse().map(HandParam).map_err(|_| HandError::Invalid(s.to_owned()))
+    }
+}
+
+fn decode(raw: &RawStr) -> Result<HandParam, HandError> {
+    let decoded = raw.url_decode().map_err(|_| HandError::Invalid(raw.as_str().to_owned()))?;
+    decoded.parse()
+}
+
+impl<'f> FromForm<'f> for HandParam {
+    type Error = HandError;
+
+    fn from_form(items: &mut FormItems<'f>, strict: bool) -> Result<HandParam, HandError> {
+        let mut hand = Err(HandError::Missing);
+        for (key, value) in items {
+            if key == "hand" {
+                hand = Ok(decode(value)?);
+                if !strict {
+                    return hand;
+                }
+            } else if strict {
+                return Err(HandError::Unexpected(key.as_str().to_owned()));
+            }
+        }
+        hand
+    }
+}
+
+impl<'v> FromFormValue<'v> for HandParam {
+    type Error = HandError;
+
+    fn from_form_value(value: &'v RawStr) -> Result<HandParam, HandError> {
+        decode(value)
+    }
+}
+
+impl<'a> FromParam<'a> for HandParam {
+    type Error = HandError;
+
+    fn from_param(param: &'a RawStr) -> Result<HandParam, HandError> {
+        decode(param)
+    }
+}
+
+/// The hands of a chaos round, given as `MULTI_HANDS` repeated `hand` fields.
+pub struct UserHands(pub [Hand; MULTI_HANDS]);
+
+impl<'f> FromForm<'f> for UserHands {
+    type Error = HandError;
+
+    fn from_form(items: &mut FormItems<'f>, strict: bool) -> Result<UserHands, HandError> {
+        let mut hands = Vec::with_capacity(MULTI_HANDS);
+        for (key, value) in items {
+            if key == "hand" {
+                hands.push(HandParam::from_form_value(value)?.0);
+            } else if strict {
+                return Err(HandError::Unexpected(key.as_str().to_owned()));
+            }
+        }
+        if hands.len() != MULTI_HANDS {
+            return Err(HandError::Missing);
+        }
+        Ok(UserHands([hands[0], hands[1], hands[2]]))
+    }
+}
+
+impl<'de> Deserialize<'de> for HandParam {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<HandParam, D::Error> {
+        let hand = String::deserialize(deserializer)?;
+        hand.parse().map_err(de::Error::custom)
+    }
+}
+
+/// A page of the history and what to search it for, e.g.
+/// `?verdict=loss&hand=rock&opponent=mirror&since=2018-06-01&until=2018-06-30&page=1`. Every
+/// field may be left out or empty; `until` is the last day searched, in UTC.
+///
+/// The API also pages by cursor: `after` is the index of the last round of the previous page,
+/// and `limit` how many rounds a page holds, up to `history::PAGE_LEN`.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct HistoryQuery {
+    pub page: usize,
+    pub after: Option<usize>,
+    pub limit: Option<usize>,
+    pub filter: Filter,
+}
+
+#[derive(Debug)]
+pub enum QueryError {
+    /// Fields other than those of the query, in a strict form.
+    Unexpected(String),
+    /// A field and its value.
+    Invalid(String, String),
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            QueryError::Unexpected(ref key) => write!(f, "unexpected field `{}`", key),
+            QueryError::Invalid(ref key, ref value) => write!(f, "invalid `{}`: {:?}", key, value),
+        }
+    }
+}
+
+impl<'f> FromForm<'f> for HistoryQuery {
+    type Error = QueryError;
+
+    fn from_form(items: &mut FormItems<'f>, strict: bool) -> Result<HistoryQuery, QueryError> {
+        let mut query = HistoryQuery::default();
+        for (key, raw) in items {
+            let invalid = || QueryError::Invalid(key.as_str().to_owned(), raw.as_str().to_owned());
+            let value = raw.url_decode().map_err(|_| invalid())?;
+            let value = value.trim();
+            if value.is_empty() && key != "page" {
+                continue;
+            }
+            let filter = &mut query.filter;
+            match key.as_str() {
+                "page" => query.page = value.parse().map_err(|_| invalid())?,
+                "after" => query.after = Some(value.parse().map_err(|_| invalid())?),
+                "limit" => query.limit = Some(value.parse().map_err(|_| invalid())?),
+                "verdict" => {
+                    let verdict = Verdict::ALL.iter().find(|verdict| verdict.name() == value);
+                    filter.verdict = Some(*verdict.ok_or_else(&invalid)?);
+                }
+                "hand" => filter.hand = Some(value.parse::<HandParam>().map_err(|_| invalid())?.0),
+                "opponent" => {
+                    let strategy = Strategy::ALL.iter().find(|strategy| strategy.name() == value);
+                    filter.opponent = Some(*strategy.ok_or_else(&invalid)?);
+                }
+                "since" => filter.since = Some(parse_date(value).ok_or_else(&invalid)?),
+                // Up to the end of the day.
+                "until" => filter.until = Some(parse_date(value).ok_or_else(&invalid)? + 86_400),
+                _ if strict => return Err(QueryError::Unexpected(key.as_str().to_owned())),
+                _ => {}
+            }
+        }
+        Ok(query)
+    }
+}
+
+impl HistoryQuery {
+    /// The query string of this search, without the leading `?`, as `from_form` reads it back.
+    pub fn to_query(&self) -> String {
+        let filter = &self.filter;
+        let mut fields = Vec::new();
+        if let Some(verdict) = filter.verdict {
+            fields.push(format!("verdict={}", verdict.name()));
+        }
+        if let Some(hand) = filter.hand {
+            fields.push(format!("hand={}", hand.as_ref()));
+        }
+        if let Some(strategy) = filter.opponent {
+            fields.push(format!("opponent={}", strategy.name()));
+        }
+        if let Some(since) = filter.since {
+            fields.push(format!("since={}", format_date(since)));
+        }
+        if let Some(until) = filter.until {
+            fields.push(format!("until={}", format_date(until - 1)));
+        }
+        if self.page > 0 {
+            fields.push(format!("page={}", self.page));
+        }
+        if let Some(after) = self.after {
+            fields.push(format!("after={}", after));
+        }
+        if let Some(limit) = self.limit {
+            fields.push(format!("limit={}", limit));
+        }
+        fields.join("&")
+    }
+}
@@ -0,0 +1,381 @@
+//! Logging in with a passkey
+//!
+//! Besides typing their name, a player can log in with a passkey (WebAuthn): a key pair kept by
+//! their device or password manager and unlocked with a fingerprint, a face or a PIN. They add one
+//! on `/profile`, and the login page then offers it; browsers without passkeys keep the name form.
+//! Both ceremonies take two requests: the first issues a one-time challenge, valid for
+//! `CEREMONY_TTL` seconds, which the browser hands to the authenticator, and the second checks
+//! what the authenticator signed. Only ES256 keys (ECDSA on P-256 with SHA-256), which every
+//! authenticator supports, are taken, and the page sends the public key as DER, from
+//! `getPublicKey()`, so that no CBOR has to be read.
+//!
+//! The relying party is the host the site is served from, and the origin the browser signs must be
+//! the site's, so passkeys only work over HTTPS, or on `localhost`. Credentials are kept in the
+//! player's profile, at most `MAX_PASSKEYS` of them, with their signature counters: a counter that
+//! does not go up means the key was cloned, and the login is refused.
+//!
+//! A passkey login is verified, as a recovery code login is, so only a verified session can add
+//! one. Once an account has a passkey, its name alone no longer logs in.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use base64;
+use ring::signature;
+use rocket::http::Status;
+use serde_json::{self, Value};
+use sha2::{Digest, Sha256};
+use untrusted::Input;
+
+use poison::Recover;
+use profile;
+use util::{bytes_to_hex, gen_random_bytes, hex_to_bytes, unix_time};
+
+/// How long a challenge can be answered, in seconds.
+pub const CEREMONY_TTL: u64 = 300;
+/// Passkeys a player can have at once.
+pub const MAX_PASSKEYS: usize = 10;
+/// Challenges waiting at once, beyond which new ceremonies are refused.
+const MAX_PENDING: usize = 10_000;
+/// The COSE algorithm identifier of ES256.
+const ES256: i64 = -7;
+/// How DER encodes a P-256 public key up to the point itself, which is 65 bytes long.
+const P256_SPKI_PREFIX: [u8; 26] = [
+    0x30, 0x59, 0x30, 0x13, 0x06, 0x07, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01, 0x06, 0x08, 0x2a,
+    0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07, 0x03, 0x42, 0x00,
+];
+const P256_POINT_LEN: usize = 65;
+/// Flags of the authenticator data.
+const USER_PRESENT: u8 = 0x01;
+const ATTESTED_CREDENTIAL: u8 = 0x40;
+/// The authenticator data up to the flags, then the signature counter.
+const FLAGS_AT: usize = 32;
+const COUNTER_AT: usize = 33;
+/// The attested credential data, past the AAGUID: the length of the credential id, then the id.
+const CREDENTIAL_ID_LEN_AT: usize = 53;
+
+lazy_static! {
+    static ref CEREMONIES: Mutex<HashMap<String, Ceremony>> = Mutex::new(HashMap::new());
+}
+
+struct Ceremony {
+    /// Unix time after which the challenge no longer works.
+    expires_at: u64,
+    /// Who is adding a passkey, or `None` for a login.
+    user_name: Option<String>,
+}
+
+/// A passkey, as kept in a profile.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Passkey {
+    /// The credential id, base64url-encoded.
+    pub id: String,
+    /// The uncompressed P-256 point, in hex.
+    pub public_key: String,
+    pub sign_count: u32,
+    /// Unix times.
+    pub added_at: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_used: Option<u64>,
+}
+
+/// What `navigator.credentials.create()` gave the page, its buffers base64url-encoded.
+#[derive(Deserialize)]
+pub struct Registration {
+    id: String,
+    #[serde(rename = "clientDataJSON")]
+    client_data: String,
+    #[serde(rename = "authenticatorData")]
+    authenticator_data: String,
+    /// DER, from `getPublicKey()`.
+    #[serde(rename = "publicKey")]
+    public_key: String,
+    #[serde(rename = "publicKeyAlgorithm")]
+    public_key_algorithm: i64,
+}
+
+/// What `navigator.credentials.get()` gave the page, its buffers base64url-encoded.
+#[derive(Deserialize)]
+pub struct Assertion {
+    id: String,
+    #[serde(rename = "clientDataJSON")]
+    client_data: String,
+    #[serde(rename = "authenticatorData")]
+    authenticator_data: String,
+    signature: String,
+}
+
+#[derive(Deserialize)]
+struct ClientData {
+    #[serde(rename = "type")]
+    kind: String,
+    challenge: String,
+    origin: String,
+}
+
+/// The site passkeys are bound to.
+pub struct RelyingParty {
+    /// The host, without the port.
+    id: String,
+    /// The scheme, the host and the port, if any.
+    origin: String,
+}
+
+impl RelyingParty {
+    /// The relying party of the site at `site_url`, e.g. `https://rps.example.com`.
+    pub fn of(site_url: &str) -> RelyingParty {
+        let host = site_url.splitn(2, "://").nth(1).unwrap_or(site_url);
+        RelyingParty {
+            id: host.split(':').next().unwrap_or(host).to_lowercase(),
+            origin: site_url.trim_right_matches('/').to_owned(),
+        }
+    }
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub enum PasskeyError {
+    /// Too many ceremonies are under way.
+    Busy,
+    /// The player has `MAX_PASSKEYS` already.
+    TooMany,
+    AlreadyAdded,
+    /// The challenge expired, was answered already or is someone else's.
+    Expired,
+    Malformed(&'static str),
+    WrongOrigin,
+    /// The authenticator did not check that the player was there.
+    NotPresent,
+    UnsupportedKey,
+    UnknownPasskey,
+    BadSignature,
+    /// The signature counter did not go up.
+    Cloned,
+}
+
+impl PasskeyError {
+    pub fn status(&self) -> Status {
+        match *self {
+            PasskeyError::Busy => Status::ServiceUnavailable,
+            PasskeyError::TooMany | PasskeyError::AlreadyAdded => Status::Conflict,
+            PasskeyError::Expired | PasskeyError::Malformed(_) => Status::BadRequest,
+            PasskeyError::UnsupportedKey => Status::UnprocessableEntity,
+            PasskeyError::WrongOrigin |
+            PasskeyError::NotPresent |
+            PasskeyError::UnknownPasskey |
+            PasskeyError::BadSignature |
+            PasskeyError::Cloned => Status::Forbidden,
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match *self {
+            PasskeyError::Busy => "Too many logins are under way; try again shortly.".to_owned(),
+            PasskeyError::TooMany => format!("You have {} passkeys already.", MAX_PASSKEYS),
+            PasskeyError::AlreadyAdded => "This passkey is added already.".to_owned(),
+            PasskeyError::Expired => "That took too long; please try again.".to_owned(),
+            PasskeyError::Malformed(what) => format!("The browser sent an invalid {}.", what),
+            PasskeyError::WrongOrigin => "The passkey was used for another site.".to_owned(),
+            PasskeyError::NotPresent => "Your presence was not checked.".to_owned(),
+            PasskeyError::UnsupportedKey => "Only ES256 passkeys are supported.".to_owned(),
+            PasskeyError::UnknownPasskey => "No account has this passkey.".to_owned(),
+            PasskeyError::BadSignature => "The passkey's signature does not check out.".to_owned(),
+            PasskeyError::Cloned => "This passkey seems to have been copied; remove it.".to_owned(),
+        }
+    }
+}
+
+fn encode(bytes: &[u8]) -> String {
+    base64::encode_config(bytes, base64::URL_SAFE_NO_PAD)
+}
+
+fn decode(encoded: &str, what: &'static str) -> Result<Vec<u8>, PasskeyError> {
+    base64::decode_config(encoded, base64::URL_SAFE_NO_PAD)
+        .map_err(|_| PasskeyError::Malformed(what))
+}
+
+fn prune(ceremonies: &mut HashMap<String, Ceremony>) {
+    let now = unix_time();
+    ceremonies.retain(|_, ceremony| ceremony.expires_at > now);
+}
+
+/// Issues a challenge, for `user_name` to add a passkey or, without one, to log in.
+fn begin(user_name: Option<&str>) -> Result<String, PasskeyError> {
+    let mut ceremonies = CEREMONIES.lock().recover();
+    prune(&mut ceremonies);
+    if ceremonies.len() >= MAX_PENDING {
+        return Err(PasskeyError::Busy);
+    }
+    let challenge = encode(&gen_random_bytes(32));
+    ceremonies.insert(challenge.clone(), Ceremony {
+        expires_at: unix_time() + CEREMONY_TTL,
+        user_name: user_name.map(str::to_owned),
+    });
+    Ok(challenge)
+}
+
+/// Uses up `challenge`, if it was issued for `user_name` (`None` for a login) and is still valid.
+fn answer(challenge: &str, user_name: Option<&str>) -> Result<(), PasskeyError> {
+    let mut ceremonies = CEREMONIES.lock().recover();
+    prune(&mut ceremonies);
+    let ceremony = ceremonies.remove(challenge).ok_or(PasskeyError::Expired)?;
+    if ceremony.user_name.as_ref().map(String::as_str) != user_name {
+        return Err(PasskeyError::Expired);
+    }
+    Ok(())
+}
+
+/// The options of `navigator.credentials.create()` for `user_name` to add a passkey.
+pub fn registration_options(party: &RelyingParty, user_name: &str) -> Result<Value, PasskeyError> {
+    let passkeys = profile::passkeys(user_name);
+    if passkeys.len() >= MAX_PASSKEYS {
+        return Err(PasskeyError::TooMany);
+    }
+    let challenge = begin(Some(user_name))?;
+    let exclude: Vec<_> = passkeys.iter()
+        .map(|passkey| json!({ "type": "public-key", "id": passkey.id }))
+        .collect();
+    Ok(json!({
+        "challenge": challenge,
+        "rp": { "id": party.id, "name": "Rock-Paper-Scissors" },
+        // The user handle is not meant to tell who the player is.
+        "user": {
+            "id": encode(&Sha256::digest(user_name.as_bytes())),
+            "name": user_name,
+            "displayName": user_name,
+        },
+        "pubKeyCredParams": [{ "type": "public-key", "alg": ES256 }],
+        "excludeCredentials": exclude,
+        "authenticatorSelection": { "residentKey": "required", "userVerification": "preferred" },
+        "attestation": "none",
+        "timeout": CEREMONY_TTL * 1000,
+    }))
+}
+
+/// The options of `navigator.credentials.get()` to log in. The authenticator offers the passkeys
+/// it has for the site, so no name is asked for.
+pub fn login_options(party: &RelyingParty) -> Result<Value, PasskeyError> {
+    Ok(json!({
+        "challenge": begin(None)?,
+        "rpId": party.id,
+        "userVerification": "preferred",
+        "timeout": CEREMONY_TTL * 1000,
+    }))
+}
+
+/// Checks the client data of a ceremony of `kind` and uses up its challenge.
+pub fn check_client_data(party: &RelyingParty, client_data: &[u8], kind: &str,
+                     user_name: Option<&str>) -> Result<(), PasskeyError> {
+    let client_data: ClientData = serde_json::from_slice(client_data)
+        .map_err(|_| PasskeyError::Malformed("client data"))?;
+    if client_data.kind != kind {
+        return Err(PasskeyError::Malformed("client data"));
+    }
+    answer(&client_data.challenge, user_name)?;
+    if client_data.origin != party.origin {
+        return Err(PasskeyError::WrongOrigin);
+    }
+    Ok(())
+}
+
+/// Checks the authenticator data is for the site and the player was there.
+pub fn check_authenticator_data(party: &RelyingParty, data: &[u8]) -> Result<(), PasskeyError> {
+    if data.len() < COUNTER_AT + 4 {
+        return Err(PasskeyError::Malformed("authenticator data"));
+    }
+    if data[..FLAGS_AT] != Sha256::digest(party.id.as_bytes())[..] {
+        return Err(PasskeyError::WrongOrigin);
+    }
+    if data[FLAGS_AT] & USER_PRESENT == 0 {
+        return Err(PasskeyError::NotPresent);
+    }
+    Ok(())
+}
+
+/// The signature counter of authenticator data that passed `check_authenticator_data`.
+pub fn sign_count(data: &[u8]) -> u32 {
+    data[COUNTER_AT..COUNTER_AT + 4].iter().fold(0, |count, &byte| count << 8 | u32::from(byte))
+}
+
+/// Checks the signature counter went up from `stored` to `count`. Authenticators that keep no
+/// counter always send 0.
+pub fn check_sign_count(stored: u32, count: u32) -> Result<(), PasskeyError> {
+    if (count != 0 || stored != 0) && count <= stored {
+        return Err(PasskeyError::Cloned);
+    }
+    Ok(())
+}
+
+/// The credential id attested in authenticator data that passed `check_authenticator_data`.
+pub fn credential_id(data: &[u8]) -> Result<&[u8], PasskeyError> {
+    let id_at = CREDENTIAL_ID_LEN_AT + 2;
+    if data[FLAGS_AT] & ATTESTED_CREDENTIAL == 0 || data.len() < id_at {
+        return Err(PasskeyError::Malformed("authenticator data"));
+    }
+    let id_len = usize::from(data[CREDENTIAL_ID_LEN_AT]) << 8 |
+        usize::from(data[CREDENTIAL_ID_LEN_AT + 1]);
+    data.get(id_at..id_at + id_len).ok_or(PasskeyError::Malformed("authenticator data"))
+}
+
+/// Checks what the authenticator made of a challenge from `registration_options`, and adds the
+/// passkey to the profile of `user_name`.
+pub fn register(party: &RelyingParty, user_name: &str, registration: &Registration)
+    -> Result<Passkey, PasskeyError>
+{
+    let client_data = decode(&registration.client_data, "client data")?;
+    check_client_data(party, &client_data, "webauthn.create", Some(user_name))?;
+    let data = decode(&registration.authenticator_data, "authenticator data")?;
+    check_authenticator_data(party, &data)?;
+
+    let id = decode(&registration.id, "credential id")?;
+    if credential_id(&data)? != &id[..] {
+        return Err(PasskeyError::Malformed("credential id"));
+    }
+
+    let public_key = decode(&registration.public_key, "public key")?;
+    let prefix = P256_SPKI_PREFIX.len();
+    if registration.public_key_algorithm != ES256 ||
+        public_key.len() != prefix + P256_POINT_LEN ||
+        public_key[..prefix] != P256_SPKI_PREFIX[..]
+    {
+        return Err(PasskeyError::UnsupportedKey);
+    }
+
+    let passkey = Passkey {
+        id: encode(&id),
+        public_key: bytes_to_hex(&public_key[prefix..]),
+        sign_count: sign_count(&data),
+        added_at: unix_time(),
+        last_used: None,
+    };
+    if profile::find_passkey(&passkey.id).is_some() {
+        return Err(PasskeyError::AlreadyAdded);
+    }
+    if !profile::add_passkey(user_name, passkey.clone(), MAX_PASSKEYS) {
+        return Err(PasskeyError::TooMany);
+    }
+    Ok(passkey)
+}
+
+/// Checks what the authenticator signed for a challenge from `login_options`, and returns whose
+/// passkey it was.
+pub fn authenticate(party: &RelyingParty, assertion: &Assertion) -> Result<String, PasskeyError> {
+    let client_data = decode(&assertion.client_data, "client data")?;
+    check_client_data(party, &client_data, "webauthn.get", None)?;
+    let data = decode(&assertion.authenticator_data, "authenticator data")?;
+    check_authenticator_data(party, &data)?;
+
+    let id = encode(&decode(&assertion.id, "credential id")?);
+    let (user_name, passkey) = profile::find_passkey(&id).ok_or(PasskeyError::UnknownPasskey)?;
+    let public_key = hex_to_bytes(&passkey.public_key).ok_or(PasskeyError::UnknownPasskey)?;
+    let signature = decode(&assertion.signature, "signature")?;
+    let mut signed = data.clone();
+    signed.extend_from_slice(&Sha256::digest(&client_data));
+    signature::verify(&signature::ECDSA_P256_SHA256_ASN1, Input::from(&public_key[..]),
+                      Input::from(&signed[..]), Input::from(&signature[..]))
+        .map_err(|_| PasskeyError::BadSignature)?;
+
+    let count = sign_count(&data);
+    check_sign_count(passkey.sign_count, count)?;
+    profile::use_passkey(&user_name, &id, count, unix_time());
+    Ok(user_name)
+}
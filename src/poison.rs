@@ -0,0 +1,52 @@
+//! Recovery from poisoned locks
+//!
+//! A lock is poisoned when a thread panics while holding it, e.g. a handler hitting a bug halfway
+//! through a request, and unwrapping it from then on panics in every request that takes it: one
+//! bad request would take down every session of a shard, or every feature behind a global lock.
+//! Locks are taken with `recover` instead, which carries on with the data as the panicking thread
+//! left it. Where that data may be half-updated, the entry being changed is dropped as the thread
+//! panics, see `shard::WriteGuard`; a dropped session is rebuilt from its snapshot cookie.
+//!
+//! Both are counted for `/admin/metrics`, as `rps_poisoned_lock_recoveries_total` and
+//! `rps_poisoned_entries_dropped_total`: either going up is a bug to look into. The poison itself
+//! never wears off, so recoveries are only logged the 1st, 2nd, 4th, 8th... time.
+
+use std::fmt::Debug;
+use std::sync::atomic::{AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
+use std::sync::LockResult;
+
+static RECOVERIES: AtomicUsize = ATOMIC_USIZE_INIT;
+static DROPPED_ENTRIES: AtomicUsize = ATOMIC_USIZE_INIT;
+
+pub trait Recover<G> {
+    /// The guard of the lock, poisoned or not.
+    fn recover(self) -> G;
+}
+
+impl<G> Recover<G> for LockResult<G> {
+    fn recover(self) -> G {
+        self.unwrap_or_else(|poisoned| {
+            let recoveries = RECOVERIES.fetch_add(1, Ordering::SeqCst) + 1;
+            if recoveries.is_power_of_two() {
+                eprintln!("Recovered a poisoned lock ({} time(s) so far)", recoveries);
+            }
+            poisoned.into_inner()
+        })
+    }
+}
+
+/// Counts an entry dropped because a thread panicked while changing it.
+pub fn entry_dropped(key: &Debug) {
+    DROPPED_ENTRIES.fetch_add(1, Ordering::SeqCst);
+    eprintln!("Dropped entry {:?}, left behind by a panic", key);
+}
+
+/// Poisoned locks taken so far.
+pub fn recoveries() -> usize {
+    RECOVERIES.load(Ordering::SeqCst)
+}
+
+/// Entries dropped so far, see `entry_dropped`.
+pub fn dropped_entries() -> usize {
+    DROPPED_ENTRIES.load(Ordering::SeqCst)
+}
@@ -0,0 +1,67 @@
+//! Optional proof of work on `/api/v1/play`
+//!
+//! To make scripted play through the API costly, the `pow_difficulty` config extra (0, i.e. off,
+//! by default) asks every play for a `nonce` such that `SHA-256(challenge || nonce)` starts with
+//! that many zero bits. The challenge is the digest of the round being played: it is only known
+//! once the computer committed to the round, and changes with every round, so work can neither be
+//! done ahead nor reused. A nonce is any string of up to `MAX_NONCE_LEN` bytes, e.g. a counter.
+
+use std::sync::atomic::{AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
+
+use rocket::Config;
+use sha2::{Digest, Sha256};
+
+/// Beyond which no client could play at all.
+pub const MAX_DIFFICULTY: u32 = 32;
+pub const MAX_NONCE_LEN: usize = 64;
+
+/// The `pow_difficulty` config extra.
+static DIFFICULTY: AtomicUsize = ATOMIC_USIZE_INIT;
+
+/// Reads the `pow_difficulty` config extra.
+pub fn configure(config: &Config) -> Result<(), String> {
+    let difficulty = match config.get_int("pow_difficulty") {
+        Ok(bits) if bits >= 0 && bits <= MAX_DIFFICULTY as i64 => bits as usize,
+        Ok(bits) => {
+            return Err(format!("`pow_difficulty` must be 0 to {}, got {}", MAX_DIFFICULTY, bits));
+        }
+        Err(_) => 0,
+    };
+    DIFFICULTY.store(difficulty, Ordering::SeqCst);
+    Ok(())
+}
+
+/// The zero bits asked of every play, 0 if no work is.
+pub fn difficulty() -> u32 {
+    DIFFICULTY.load(Ordering::SeqCst) as u32
+}
+
+fn leading_zero_bits(bytes: &[u8]) -> u32 {
+    let mut bits = 0;
+    for &byte in bytes {
+        bits += byte.leading_zeros();
+        if byte != 0 {
+            break;
+        }
+    }
+    bits
+}
+
+/// Whether `nonce` solves `challenge` at `difficulty`.
+pub fn solves(challenge: &str, nonce: &str, difficulty: u32) -> bool {
+    if nonce.len() > MAX_NONCE_LEN {
+        return false;
+    }
+    let mut hasher = Sha256::new();
+    hasher.input(challenge.as_bytes());
+    hasher.input(nonce.as_bytes());
+    leading_zero_bits(&hasher.result()) >= difficulty
+}
+
+/// Finds a nonce solving `challenge` at `difficulty`, the way a client would: by counting.
+pub fn solve(challenge: &str, difficulty: u32) -> String {
+    (0u64..)
+        .map(|counter| counter.to_string())
+        .find(|nonce| solves(challenge, nonce, difficulty))
+        .unwrap()
+}
@@ -0,0 +1,609 @@
+//! Persistent user profiles
+//!
+//! A `Session` holds what only matters while logged in: the pending rounds, this visit's
+//! scoreboards and history. What a player earns over time lives in their `UserProfile` instead,
+//! keyed by user name and kept across sessions: lifetime scoreboards, win streaks, a rating,
+//! achievements and when they usually play. Profiles are kept in memory and, if the
+//! `profile_file` config extra is set, saved to that JSON file after every change so that they
+//! outlive the process. The file is a `store::Format`, upgraded by `MIGRATIONS` as it is loaded.
+//! Saves go through a `workers::Queue`, so that the requests and events updating profiles do not
+//! wait on the disk, some of them with a session locked; changes made while a save waits on the
+//! queue are written by that save.
+//!
+//! Profiles are public, at `/u/<name>`, unless their player hides them. Only the stats, the
+//! achievements and the recent seeded matches are shown there; the activity heatmap stays private.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering, ATOMIC_BOOL_INIT};
+use std::sync::{Mutex, RwLock};
+
+use rocket::Config;
+use serde_json::Value;
+
+use alerts::{self, Alert};
+use events::{self, Event, Subscriber};
+use game::{Hand, RoundKind, Scoreboard, Verdict};
+use locale::Locale;
+use passkey::Passkey;
+use poison::Recover;
+use rating::{self, Rating};
+use store::{self, Format, Migration, Options};
+use util::unix_time;
+use webhook::Webhook;
+use workers::Queue;
+
+/// Seeded matches kept in a profile, the latest ones.
+pub const RECENT_MATCHES: usize = 10;
+
+const FORMAT: Format = Format {
+    name: "profiles",
+    migrations: MIGRATIONS,
+};
+
+/// The upgrades of the profile file, see `store`.
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "profiles from before the tutorial skip it",
+        upgrade: skip_onboarding,
+    },
+];
+
+lazy_static! {
+    static ref PROFILES: RwLock<Profiles> = RwLock::new(Profiles::default());
+    /// The names whose accounts are being deleted, see `hold_for_deletion`.
+    static ref DELETING: Mutex<HashSet<String>> = Mutex::new(HashSet::new());
+    static ref SAVES: Queue<()> = Queue::new("profile-saves", 1, 1, save_queued);
+}
+
+/// Whether a save is queued that has yet to read the profiles, see `Profiles::changed`.
+static SAVE_QUEUED: AtomicBool = ATOMIC_BOOL_INIT;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Achievement {
+    FirstWin,
+    HatTrick,
+    Unstoppable,
+    Centurion,
+    ChaosTamer,
+}
+
+impl Achievement {
+    pub fn describe(&self) -> &'static str {
+        match *self {
+            Achievement::FirstWin => "First win",
+            Achievement::HatTrick => "Hat trick: three classic wins in a row",
+            Achievement::Unstoppable => "Unstoppable: ten classic wins in a row",
+            Achievement::Centurion => "Centurion: a hundred classic rounds",
+            Achievement::ChaosTamer => "Chaos tamer: a chaos round won",
+        }
+    }
+}
+
+/// A finished seeded match, whose seed was revealed.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MatchSummary {
+    pub commitment: String,
+    pub rounds: u64,
+    /// Unix time at which it finished.
+    pub finished_at: u64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct UserProfile {
+    pub classic: Scoreboard,
+    pub chaos: Scoreboard,
+    /// Classic wins in a row, ties aside.
+    pub streak: u32,
+    pub best_streak: u32,
+    /// A rating against the computer, from classic rounds, see `rating`.
+    pub rating: f64,
+    /// The Glicko-2 deviation and volatility of the rating, while the server rates with Glicko-2:
+    /// a round rated with Elo clears them, since they no longer describe the rating.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rating_deviation: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rating_volatility: Option<f64>,
+    /// In the order they were earned.
+    pub achievements: Vec<Achievement>,
+    /// Hands played, indexed like `Hand::CHOICES`.
+    #[serde(default)]
+    pub hands: [u64; 3],
+    /// Rounds played, by day of the week (Monday first) and hour of the day, at the UTC offset the
+    /// player had set when they played each, see `locale`.
+    #[serde(default)]
+    pub activity: [[u32; 24]; 7],
+    /// Unix times of the first and the latest login.
+    pub first_seen: u64,
+    pub last_seen: u64,
+    /// At most `RECENT_MATCHES`, the latest last.
+    #[serde(default)]
+    pub recent_matches: Vec<MatchSummary>,
+    /// Whether the player hid their public profile.
+    #[serde(default)]
+    pub hidden: bool,
+    /// Whether the player opted out of anonymized analytics, see `analytics`, from any session.
+    #[serde(default)]
+    pub analytics_opt_out: bool,
+    /// Whether the player went through the tutorial, see `onboarding`. Profiles from before it
+    /// existed skip it, see `MIGRATIONS`.
+    pub onboarded: bool,
+    /// Where the player's rounds are posted, if anywhere.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub webhook: Option<Webhook>,
+    /// The variant the player was assigned of each experiment, by experiment, see `experiment`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub experiments: BTreeMap<String, String>,
+    /// Ranked classic rounds played in the variant of each experiment, by experiment, unless the
+    /// player opted out of analytics.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub experiment_rounds: BTreeMap<String, Scoreboard>,
+    /// Fingerprints of the devices the player logged in from, the latest last, see `alerts`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub devices: Vec<String>,
+    /// At most `alerts::RECENT_ALERTS`, the latest last.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub alerts: Vec<Alert>,
+    /// How pages write times and numbers for the player, once they picked, see `locale`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub locale: Option<Locale>,
+    /// The passkeys the player logs in with, the oldest first, see `passkey`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub passkeys: Vec<Passkey>,
+}
+
+impl UserProfile {
+    fn new() -> UserProfile {
+        let now = unix_time();
+        UserProfile {
+            classic: Scoreboard::default(),
+            chaos: Scoreboard::default(),
+            streak: 0,
+            best_streak: 0,
+            rating: rating::INITIAL_RATING,
+            rating_deviation: None,
+            rating_volatility: None,
+            achievements: Vec::new(),
+            hands: [0; 3],
+            activity: [[0; 24]; 7],
+            first_seen: now,
+            last_seen: now,
+            recent_matches: Vec::new(),
+            hidden: false,
+            analytics_opt_out: false,
+            onboarded: false,
+            webhook: None,
+            experiments: BTreeMap::new(),
+            experiment_rounds: BTreeMap::new(),
+            devices: Vec::new(),
+            alerts: Vec::new(),
+            locale: None,
+            passkeys: Vec::new(),
+        }
+    }
+
+    fn record_match(&mut self, commitment: &str, rounds: u64, time: u64) {
+        if self.recent_matches.len() >= RECENT_MATCHES {
+            self.recent_matches.remove(0);
+        }
+        self.recent_matches.push(MatchSummary {
+            commitment: commitment.to_owned(),
+            rounds: rounds,
+            finished_at: time,
+        });
+    }
+
+    fn record(&mut self, kind: RoundKind, hands: &[Hand], verdict: Verdict, time: u64) {
+        for hand in hands {
+            self.hands[hand.index()] += 1;
+        }
+        let (day, hour) = weekday_and_hour(self.locale.unwrap_or_default().local_time(time));
+        self.activity[day][hour] += 1;
+        match kind {
+            RoundKind::Classic => {
+                self.rate(verdict);
+                self.classic.record(verdict);
+                match verdict {
+                    Verdict::Win => self.streak += 1,
+                    Verdict::Tie => {}
+                    Verdict::Loss => self.streak = 0,
+                }
+                self.best_streak = self.best_streak.max(self.streak);
+            }
+            RoundKind::Chaos => self.chaos.record(verdict),
+        }
+        self.award();
+    }
+
+    /// Tallies a classic round in every experiment the player is in.
+    fn record_experiments(&mut self, verdict: Verdict) {
+        for experiment in self.experiments.keys() {
+            self.experiment_rounds.entry(experiment.clone())
+                .or_insert_with(Scoreboard::default)
+                .record(verdict);
+        }
+    }
+
+    /// The rating of the player, migrated if the deviation is not tracked yet.
+    fn rating(&self) -> Rating {
+        match (self.rating_deviation, self.rating_volatility) {
+            (Some(deviation), Some(volatility)) => Rating {
+                rating: self.rating,
+                deviation: deviation,
+                volatility: volatility,
+            },
+            _ => {
+                let rounds = self.classic.wins + self.classic.ties + self.classic.losses;
+                Rating::migrated(self.rating, rounds)
+            }
+        }
+    }
+
+    /// Rates a classic round against the computer with the server's system.
+    fn rate(&mut self, verdict: Verdict) {
+        let system = rating::system();
+        let mut rating = self.rating();
+        let score = f64::from(verdict.payoff() + 1) / 2.0;
+        system.rate(&mut rating, &[(rating::COMPUTER, score)]);
+        self.rating = rating.rating;
+        if system.tracks_deviation() {
+            self.rating_deviation = Some(rating.deviation);
+            self.rating_volatility = Some(rating.volatility);
+        } else {
+            self.rating_deviation = None;
+            self.rating_volatility = None;
+        }
+    }
+
+    /// Takes back a classic loss voided by an arcade redo. The rating stays: it was earned.
+    fn void_loss(&mut self) {
+        self.classic.losses = self.classic.losses.saturating_sub(1);
+    }
+
+    fn award(&mut self) {
+        let classic_rounds = self.classic.wins + self.classic.ties + self.classic.losses;
+        let earned = [
+            (Achievement::FirstWin, self.classic.wins + self.chaos.wins > 0),
+            (Achievement::HatTrick, self.best_streak >= 3),
+            (Achievement::Unstoppable, self.best_streak >= 10),
+            (Achievement::Centurion, classic_rounds >= 100),
+            (Achievement::ChaosTamer, self.chaos.wins > 0),
+        ];
+        for &(achievement, earned) in &earned {
+            if earned && !self.achievements.contains(&achievement) {
+                self.achievements.push(achievement);
+            }
+        }
+    }
+}
+
+fn skip_onboarding(profiles: &mut Value) -> Result<(), String> {
+    let profiles = profiles.as_object_mut().ok_or("profiles are not an object")?;
+    for profile in profiles.values_mut() {
+        let profile = profile.as_object_mut().ok_or("a profile is not an object")?;
+        profile.entry("onboarded").or_insert(Value::Bool(true));
+    }
+    Ok(())
+}
+
+/// The day of the week, from 0 for Monday, and the hour of a Unix time, in UTC. Shifted by
+/// `Locale::local_time`, it is the player's.
+fn weekday_and_hour(time: u64) -> (usize, usize) {
+    let days = time / (24 * 3600);
+    // 1 January 1970 was a Thursday.
+    (((days + 3) % 7) as usize, (time / 3600 % 24) as usize)
+}
+
+#[derive(Default)]
+struct Profiles {
+    file: Option<PathBuf>,
+    profiles: HashMap<String, UserProfile>,
+}
+
+impl Profiles {
+    fn save(&self) -> io::Result<()> {
+        match self.file {
+            Some(ref path) => store::save(path, &FORMAT, &self.profiles),
+            None => Ok(()),
+        }
+    }
+
+    /// Queues a save after a change, unless one is queued already. Saves right away, on this
+    /// thread, if the queue refuses it.
+    fn changed(&self) {
+        if self.file.is_none() || SAVE_QUEUED.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        if SAVES.submit(()).is_err() {
+            SAVE_QUEUED.store(false, Ordering::SeqCst);
+            if let Err(e) = self.save() {
+                eprintln!("Unable to save profiles: {}", e);
+            }
+        }
+    }
+}
+
+fn save_queued(_: ()) {
+    let profiles = PROFILES.read().recover();
+    SAVE_QUEUED.store(false, Ordering::SeqCst);
+    if let Err(e) = profiles.save() {
+        eprintln!("Unable to save profiles: {}", e);
+    }
+}
+
+/// Loads the profiles saved to `profile_file`, if it is set, and keeps saving them there.
+pub fn configure(config: &Config) -> Result<(), String> {
+    let file = match config.get_str("profile_file") {
+        Ok(path) => PathBuf::from(path),
+        Err(_) => return Ok(()),
+    };
+    let (profiles, read_only) = match store::load(&file, &FORMAT, Options::from_config(config))? {
+        Some(loaded) => (loaded.data, loaded.read_only),
+        None => (HashMap::new(), false),
+    };
+    *PROFILES.write().recover() = Profiles {
+        file: if read_only { None } else { Some(file) },
+        profiles: profiles,
+    };
+    Ok(())
+}
+
+/// The profile of `user_name`, if they ever logged in. The events a batch open on this thread
+/// holds back are applied to it, so that pages show the lifetime stats of the round they report.
+pub fn get(user_name: &str) -> Option<UserProfile> {
+    let mut profile = PROFILES.read().recover().profiles.get(user_name).cloned();
+    for event in events::held() {
+        if subject(&event) == Some(user_name) {
+            apply(profile.get_or_insert_with(UserProfile::new), &event);
+        }
+    }
+    profile
+}
+
+/// What `f` reads of the profile of `user_name`, if they ever logged in, without copying it.
+/// Unlike `get`, the events a batch holds back are not applied.
+pub fn with<T, F: FnOnce(&UserProfile) -> T>(user_name: &str, f: F) -> Option<T> {
+    PROFILES.read().recover().profiles.get(user_name).map(f)
+}
+
+/// The profile of `user_name`, unless they never logged in or hid it.
+pub fn get_public(user_name: &str) -> Option<UserProfile> {
+    get(user_name).filter(|profile| !profile.hidden)
+}
+
+/// Whether `user_name` opted out of anonymized analytics.
+pub fn opted_out_of_analytics(user_name: &str) -> bool {
+    with(user_name, |profile| profile.analytics_opt_out).unwrap_or(false)
+}
+
+/// Opts `user_name` out of anonymized analytics, or back in.
+pub fn set_analytics_opt_out(user_name: &str, opt_out: bool) {
+    update(user_name, |profile| profile.analytics_opt_out = opt_out);
+}
+
+/// Hides the public profile of `user_name`, or shows it again.
+pub fn set_hidden(user_name: &str, hidden: bool) {
+    update(user_name, |profile| profile.hidden = hidden);
+}
+
+/// Sets the webhook of `user_name`, or removes it.
+pub fn set_webhook(user_name: &str, webhook: Option<Webhook>) {
+    update(user_name, |profile| profile.webhook = webhook);
+}
+
+/// Sets how pages write times and numbers for `user_name`.
+pub fn set_locale(user_name: &str, locale: Locale) {
+    update(user_name, |profile| profile.locale = Some(locale));
+}
+
+/// How pages write times and numbers for `user_name`: English and UTC unless they picked.
+pub fn locale(user_name: &str) -> Locale {
+    get(user_name).and_then(|profile| profile.locale).unwrap_or_default()
+}
+
+/// Records that `user_name` went through the tutorial.
+pub fn complete_onboarding(user_name: &str) {
+    update(user_name, |profile| profile.onboarded = true);
+}
+
+/// Assigns `user_name` to a variant of each experiment, as `(experiment, variant)` pairs. The
+/// rounds tallied in another variant before are dropped.
+pub fn join_experiments(user_name: &str, variants: &[(&str, &str)]) {
+    update(user_name, |profile| {
+        for &(experiment, variant) in variants {
+            profile.experiments.insert(experiment.to_owned(), variant.to_owned());
+            profile.experiment_rounds.remove(experiment);
+        }
+    });
+}
+
+/// Records that `user_name` logged in from the device of `fingerprint`. Returns whether it is new
+/// to an account already logged in from others.
+pub fn add_device(user_name: &str, fingerprint: &str) -> bool {
+    let mut new = false;
+    update(user_name, |profile| {
+        match profile.devices.iter().position(|device| device == fingerprint) {
+            Some(index) => {
+                profile.devices.remove(index);
+            }
+            None => new = !profile.devices.is_empty(),
+        }
+        if profile.devices.len() >= alerts::MAX_DEVICES {
+            profile.devices.remove(0);
+        }
+        profile.devices.push(fingerprint.to_owned());
+    });
+    new
+}
+
+/// Keeps `alert` among the latest alerts of `user_name`.
+pub fn add_alert(user_name: &str, alert: Alert) {
+    update(user_name, |profile| {
+        let cutoff = alert.at.saturating_sub(alerts::ALERT_RETENTION);
+        profile.alerts.retain(|alert| alert.at >= cutoff);
+        if profile.alerts.len() >= alerts::RECENT_ALERTS {
+            profile.alerts.remove(0);
+        }
+        profile.alerts.push(alert);
+    });
+}
+
+/// Drops the alerts older than `alerts::ALERT_RETENTION` from every profile.
+pub fn expire_alerts() {
+    let cutoff = unix_time().saturating_sub(alerts::ALERT_RETENTION);
+    let mut profiles = PROFILES.write().recover();
+    let mut expired = false;
+    for profile in profiles.profiles.values_mut() {
+        let before = profile.alerts.len();
+        profile.alerts.retain(|alert| alert.at >= cutoff);
+        expired |= profile.alerts.len() < before;
+    }
+    if expired {
+        profiles.changed();
+    }
+}
+
+/// Marks every alert of `user_name` seen.
+pub fn see_alerts(user_name: &str) {
+    update(user_name, |profile| {
+        for alert in &mut profile.alerts {
+            alert.seen = true;
+        }
+    });
+}
+
+/// The passkeys of `user_name`, the oldest first.
+pub fn passkeys(user_name: &str) -> Vec<Passkey> {
+    get(user_name).map_or(Vec::new(), |profile| profile.passkeys)
+}
+
+/// The player with the passkey `id`, and the passkey.
+pub fn find_passkey(id: &str) -> Option<(String, Passkey)> {
+    PROFILES.read().recover().profiles.iter()
+        .filter_map(|(user_name, profile)| {
+            let passkey = profile.passkeys.iter().find(|passkey| passkey.id == id)?;
+            Some((user_name.clone(), passkey.clone()))
+        })
+        .next()
+}
+
+/// Adds a passkey to the profile of `user_name`, unless they have `max` already.
+pub fn add_passkey(user_name: &str, passkey: Passkey, max: usize) -> bool {
+    let mut added = false;
+    update(user_name, |profile| {
+        if profile.passkeys.len() < max {
+            profile.passkeys.push(passkey);
+            added = true;
+        }
+    });
+    added
+}
+
+/// Records a login with the passkey `id` of `user_name`, and its new signature counter.
+pub fn use_passkey(user_name: &str, id: &str, sign_count: u32, at: u64) {
+    update(user_name, |profile| {
+        if let Some(passkey) = profile.passkeys.iter_mut().find(|passkey| passkey.id == id) {
+            passkey.sign_count = sign_count;
+            passkey.last_used = Some(at);
+        }
+    });
+}
+
+/// Removes the passkey `id` of `user_name`. Returns whether they had it.
+pub fn remove_passkey(user_name: &str, id: &str) -> bool {
+    let mut removed = false;
+    update(user_name, |profile| {
+        let before = profile.passkeys.len();
+        profile.passkeys.retain(|passkey| passkey.id != id);
+        removed = profile.passkeys.len() < before;
+    });
+    removed
+}
+
+/// Folds every profile into `init` with `f`, in no particular order.
+pub fn fold<T, F: FnMut(T, &UserProfile) -> T>(init: T, f: F) -> T {
+    PROFILES.read().recover().profiles.values().fold(init, f)
+}
+
+/// Every profile, by user name, e.g. for a backup.
+pub fn all() -> HashMap<String, UserProfile> {
+    PROFILES.read().recover().profiles.clone()
+}
+
+/// Replaces every profile with `profiles`, e.g. from a backup.
+pub fn replace_all(profiles: HashMap<String, UserProfile>) {
+    let mut current = PROFILES.write().recover();
+    current.profiles = profiles;
+    current.changed();
+}
+
+/// Stops events from updating, or recreating, the profile of `user_name` until its account is
+/// deleted, so that none in flight brings it back.
+pub fn hold_for_deletion(user_name: &str) {
+    DELETING.lock().recover().insert(user_name.to_owned());
+}
+
+fn remove(user_name: &str) {
+    let mut profiles = PROFILES.write().recover();
+    DELETING.lock().recover().remove(user_name);
+    if profiles.profiles.remove(user_name).is_some() {
+        profiles.changed();
+    }
+}
+
+fn update<F: FnOnce(&mut UserProfile)>(user_name: &str, f: F) {
+    let mut profiles = PROFILES.write().recover();
+    if DELETING.lock().recover().contains(user_name) {
+        return;
+    }
+    f(profiles.profiles.entry(user_name.to_owned()).or_insert_with(UserProfile::new));
+    profiles.changed();
+}
+
+/// The player whose profile `event` updates, if it updates one.
+fn subject(event: &Event) -> Option<&str> {
+    match *event {
+        Event::SessionStarted { ref user_name }
+        | Event::RoundPlayed { ref user_name, ranked: true, .. }
+        | Event::RoundVoided { ref user_name }
+        | Event::MatchFinished { ref user_name, .. } => Some(user_name),
+        _ => None,
+    }
+}
+
+/// Updates `profile`, that of the `subject` of `event`.
+fn apply(profile: &mut UserProfile, event: &Event) {
+    match *event {
+        Event::SessionStarted { .. } => profile.last_seen = unix_time(),
+        Event::RoundPlayed { kind, ref human, verdict, ranked: true, analytics_opt_out, .. } => {
+            profile.record(kind, human, verdict, unix_time());
+            if kind == RoundKind::Classic && !analytics_opt_out {
+                profile.record_experiments(verdict);
+            }
+        }
+        Event::RoundVoided { .. } => profile.void_loss(),
+        Event::MatchFinished { ref commitment, rounds, .. } => {
+            profile.record_match(commitment, rounds, unix_time());
+        }
+        _ => {}
+    }
+}
+
+/// Keeps profiles up to date with game events.
+pub struct Keeper;
+
+impl Subscriber for Keeper {
+    fn notify(&self, event: &Event) {
+        match *event {
+            Event::AccountDeleted { ref user_name } => remove(user_name),
+            _ => {
+                if let Some(user_name) = subject(event) {
+                    update(user_name, |profile| apply(profile, event));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,139 @@
+//! Canonical round proofs
+//!
+//! A round proof is everything needed to check a revealed round on its own: the committed hand(s),
+//! the nonce, the commitment, the human's hand(s) and the verdict, and the chain links before and
+//! after the round. Its canonical JSON
+//! encoding has no whitespace, sorts the keys bytewise, writes every hash and the nonce in lower
+//! hex and names the hash function in `algorithm`, so that an external verifier hashing or signing
+//! a proof gets the same bytes, and the same `fingerprint`, as the server.
+
+use std::collections::BTreeMap;
+
+use serde_json;
+use sha2::{Digest, Sha256};
+
+use commitment;
+use game::{RoundKind, Verdict};
+use history::{self, RoundRecord};
+
+/// The hash function behind commitments and chain links, the only one there is.
+pub const ALGORITHM: &str = "sha256";
+/// Lower hex digits of a SHA-256 hash.
+const HASH_HEX_LEN: usize = 64;
+
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RoundProof {
+    pub algorithm: String,
+    pub kind: RoundKind,
+    /// The computer's hand(s), in the committed format.
+    pub computer: String,
+    /// The human's hand(s), formatted like the computer's.
+    pub human: String,
+    pub verdict: Verdict,
+    /// The nonce, in lower hex.
+    pub random: String,
+    pub digest: String,
+    /// The link preceding the round's, `history::GENESIS` for the first round.
+    pub prev_link: String,
+    pub link: String,
+}
+
+fn is_lower_hex(s: &str) -> bool {
+    !s.is_empty() && s.len() % 2 == 0 &&
+        s.bytes().all(|b| b.is_ascii_digit() || b'a' <= b && b <= b'f')
+}
+
+impl RoundProof {
+    /// The proof of a recorded round following `prev_link`. `None` if the record's nonce is not
+    /// lower hex, as with some imported rounds, since changing its case would break the commitment.
+    pub fn from_record(record: &RoundRecord, prev_link: &str) -> Option<RoundProof> {
+        if !is_lower_hex(&record.random) {
+            return None;
+        }
+        Some(RoundProof {
+            algorithm: ALGORITHM.to_owned(),
+            kind: record.kind,
+            computer: record.computer.clone(),
+            human: record.human.clone(),
+            verdict: record.verdict,
+            random: record.random.clone(),
+            digest: record.digest.clone(),
+            prev_link: prev_link.to_owned(),
+            link: record.link.clone(),
+        })
+    }
+
+    /// Parses a proof, which must be in canonical form already.
+    pub fn parse(text: &str) -> Result<RoundProof, String> {
+        let proof: RoundProof = serde_json::from_str(text).map_err(|e| e.to_string())?;
+        if proof.algorithm != ALGORITHM {
+            return Err(format!("unsupported algorithm {:?}", proof.algorithm));
+        }
+        if !is_lower_hex(&proof.random) {
+            return Err("`random` is not lower hex".to_owned());
+        }
+        for &(name, hash) in &[("digest", &proof.digest), ("prev_link", &proof.prev_link),
+                               ("link", &proof.link)] {
+            if hash.len() != HASH_HEX_LEN || !is_lower_hex(hash) {
+                return Err(format!("`{}` is not a lower hex SHA-256 hash", name));
+            }
+        }
+        if proof.to_canonical() != text {
+            return Err("not in canonical form".to_owned());
+        }
+        Ok(proof)
+    }
+
+    /// The canonical encoding.
+    pub fn to_canonical(&self) -> String {
+        let kind = serde_json::to_value(self.kind).expect("Unable to serialize the round kind");
+        let kind = kind.as_str().expect("Round kinds serialize as strings").to_owned();
+        let verdict = self.verdict.name().to_owned();
+
+        let mut fields = BTreeMap::new();
+        fields.insert("algorithm", &self.algorithm);
+        fields.insert("computer", &self.computer);
+        fields.insert("human", &self.human);
+        fields.insert("verdict", &verdict);
+        fields.insert("random", &self.random);
+        fields.insert("digest", &self.digest);
+        fields.insert("prev_link", &self.prev_link);
+        fields.insert("link", &self.link);
+        fields.insert("kind", &kind);
+
+        let fields: Vec<String> = fields.iter()
+            .map(|(key, value)| {
+                let value = serde_json::to_string(value).expect("Unable to serialize a string");
+                format!("\"{}\":{}", key, value)
+            })
+            .collect();
+        format!("{{{}}}", fields.join(","))
+    }
+
+    /// Lower hex SHA-256 of the canonical encoding.
+    pub fn fingerprint(&self) -> String {
+        format!("{:x}", Sha256::digest(self.to_canonical().as_bytes()))
+    }
+
+    /// Checks the commitment, the verdict and the chain link. Returns what does not match.
+    pub fn verify(&self) -> Result<(), String> {
+        if commitment::digest_str(&self.random, &self.computer) != self.digest {
+            return Err("the revealed hand does not match its commitment".to_owned());
+        }
+        let (human, computer) = match (history::parse_hands(&self.human, self.kind),
+                                       history::parse_hands(&self.computer, self.kind)) {
+            (Some(human), Some(computer)) => (human, computer),
+            _ => return Err("unexpected hands".to_owned()),
+        };
+        if Verdict::overall(&human, &computer) != self.verdict {
+            return Err("the verdict does not follow from the hands".to_owned());
+        }
+        let link = commitment::chain_link(&self.prev_link, &self.random, &self.computer,
+                                          &self.human, self.verdict);
+        if link != self.link {
+            return Err("broken chain link".to_owned());
+        }
+        Ok(())
+    }
+}
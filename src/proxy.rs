@@ -0,0 +1,112 @@
+//! Running behind a reverse proxy
+//!
+//! Behind a proxy such as nginx, every connection comes from the proxy. For peers listed in the
+//! `trusted_proxies` config extra, the client address is taken from `X-Forwarded-For` instead, so
+//! that bans, login and the access log see real client IPs. From anyone else the `X-Forwarded-*`
+//! headers could be forged, so they are overwritten with what the connection itself shows.
+
+use std::net::{IpAddr, SocketAddr};
+
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::Header;
+use rocket::{Config, Data, Request, Response, State};
+
+pub const FORWARDED_FOR: &str = "X-Forwarded-For";
+pub const FORWARDED_PROTO: &str = "X-Forwarded-Proto";
+
+/// The `trusted_proxies` (addresses) and `access_log` (whether to print one line per request)
+/// config extras.
+pub struct ProxyConfig {
+    trusted: Vec<IpAddr>,
+    access_log: bool,
+}
+
+impl ProxyConfig {
+    pub fn from_config(config: &Config) -> Result<ProxyConfig, String> {
+        let trusted = match config.get_slice("trusted_proxies") {
+            Ok(values) => values.iter()
+                .map(|value| {
+                    value.as_str()
+                        .and_then(|addr| addr.parse().ok())
+                        .ok_or_else(|| format!("invalid proxy address {}", value))
+                })
+                .collect::<Result<_, _>>()?,
+            Err(_) => Vec::new(),
+        };
+        Ok(ProxyConfig {
+            trusted: trusted,
+            access_log: config.get_bool("access_log").unwrap_or(false),
+        })
+    }
+
+    fn trusts(&self, ip: IpAddr) -> bool {
+        self.trusted.contains(&ip)
+    }
+
+    /// The client behind `X-Forwarded-For`: the last address not added by a trusted proxy, since
+    /// everything before it came from the client and may be forged.
+    fn client<'a, I: Iterator<Item = &'a str>>(&self, forwarded_for: I) -> Option<IpAddr> {
+        let hops: Vec<IpAddr> = forwarded_for
+            .flat_map(|value| value.split(','))
+            .map(|hop| hop.trim().parse().ok())
+            .collect::<Option<_>>()?;
+        hops.into_iter().rev().find(|&ip| !self.trusts(ip))
+    }
+}
+
+/// The scheme the client used, as reported by a trusted proxy.
+pub fn forwarded_proto<'a>(request: &'a Request) -> Option<&'a str> {
+    request.headers().get_one(FORWARDED_PROTO).filter(|proto| !proto.is_empty())
+}
+
+pub struct ForwardedHeaders;
+
+impl Fairing for ForwardedHeaders {
+    fn info(&self) -> Info {
+        Info {
+            name: "Reverse proxy",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    fn on_request(&self, request: &mut Request, _: &Data) {
+        let peer = match request.remote() {
+            Some(peer) => peer,
+            None => return,
+        };
+        let client = {
+            let config = match request.guard::<State<ProxyConfig>>().succeeded() {
+                Some(config) => config,
+                None => return,
+            };
+            if config.trusts(peer.ip()) {
+                Some(config.client(request.headers().get(FORWARDED_FOR)))
+            } else {
+                None
+            }
+        };
+        match client {
+            Some(Some(ip)) => request.set_remote(SocketAddr::new(ip, peer.port())),
+            // Every hop was a trusted proxy, or the header is garbled: keep the peer's address.
+            Some(None) => {}
+            None => {
+                request.replace_header(Header::new(FORWARDED_FOR, peer.ip().to_string()));
+                request.replace_header(Header::new(FORWARDED_PROTO, ""));
+            }
+        }
+    }
+
+    fn on_response(&self, request: &Request, response: &mut Response) {
+        let access_log = request.guard::<State<ProxyConfig>>().succeeded()
+            .map_or(false, |config| config.access_log);
+        if !access_log {
+            return;
+        }
+        println!("{} {} \"{} {}\" {}",
+                 request.remote().map_or("-".to_owned(), |addr| addr.ip().to_string()),
+                 forwarded_proto(request).unwrap_or("-"),
+                 request.method(),
+                 request.uri(),
+                 response.status().code);
+    }
+}
@@ -0,0 +1,677 @@
+//! Player-versus-player matches, started from invite links
+//!
+//! A player asks for an invite link, `/invite/<token>`, and passes it on; whoever opens it while
+//! logged in, or right after logging in, starts a match against them at once. A link works only
+//! once and expires after `INVITE_TTL` seconds; asking for a new one revokes the previous one.
+//! In a match, both players pick a hand every round, in either order, and neither sees the other's
+//! pick before having picked too. A match ends as soon as one of the players leaves or their
+//! session ends, except for a match restored after a restart, see `maintenance`, which waits
+//! `RESTORE_GRACE` seconds for its players to come back.
+//!
+//! A player can also have an invite link emailed to a friend, through the outbox of
+//! `notification`. It works for `EMAIL_INVITE_TTL` seconds, whether or not its host is still
+//! logged in, and starts a correspondence match: one that outlives the players' sessions and waits
+//! up to `CORRESPONDENCE_TTL` seconds for every pick. Whoever opens the link logs in first if they
+//! need to, under a new name or their own, and a player who logs in again with a recovery code or
+//! a passkey takes their seat back in the new session. Emails go out at most
+//! `EMAILS_PER_ADDRESS` an hour from an IP address, and `EMAILS_PER_HOUR` overall, and only for
+//! hosts whose names are fit for a subject line.
+//!
+//! Any other logged-in player can watch a match at `/pvp/watch/<id>`. Spectators react to the
+//! pending round with 👏 or 😮, once each per round, and the counts go to the players along with
+//! the round's result. They also chat among themselves, apart from the players, in a buffer that
+//! only keeps the latest `CHAT_BUFFER` messages of every match.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::net::IpAddr;
+use std::sync::Mutex;
+
+use game::{Hand, Scoreboard, Verdict};
+use ids::UserId;
+use maintenance;
+use poison::Recover;
+pub use team::Member;
+use throttle::Throttle;
+use util::{bytes_to_hex, gen_random_bytes, unix_time};
+use SESSIONS;
+
+/// How long an invite link can be used, in seconds.
+pub const INVITE_TTL: u64 = 3600;
+/// How long a restored match is kept even though its players have no session yet, in seconds.
+const RESTORE_GRACE: u64 = 600;
+/// How long an emailed invite link can be used, in seconds.
+pub const EMAIL_INVITE_TTL: u64 = 7 * 24 * 3600;
+/// Emailed invite links a player may have waiting at once.
+pub const MAX_EMAIL_INVITES: usize = 5;
+/// How long a correspondence match waits for a pick, in seconds.
+pub const CORRESPONDENCE_TTL: u64 = 7 * 24 * 3600;
+/// Longest email address, in bytes.
+const MAX_EMAIL_LEN: usize = 254;
+/// Invites emailed from an IP address in an hour, at most.
+pub const EMAILS_PER_ADDRESS: u32 = 10;
+/// Invites emailed in an hour, at most.
+pub const EMAILS_PER_HOUR: u32 = 200;
+/// Spectator chat messages kept per match.
+const CHAT_BUFFER: usize = 50;
+/// Longest spectator chat message, in characters.
+pub const MAX_CHAT_LEN: usize = 200;
+
+lazy_static! {
+    static ref PVP: Mutex<Registry> = Mutex::new(Registry::default());
+    static ref EMAILS_BY_ADDRESS: Throttle = Throttle::new(EMAILS_PER_ADDRESS, 3600);
+    static ref EMAILS: Throttle = Throttle::new(EMAILS_PER_HOUR, 3600);
+}
+
+#[derive(Clone, Debug)]
+pub struct Invite {
+    pub token: String,
+    pub host: Member,
+    /// Unix time after which the link no longer works.
+    pub expires_at: u64,
+}
+
+#[derive(Clone, Debug)]
+pub struct EmailInvite {
+    pub token: String,
+    pub host_name: String,
+    pub email: String,
+    /// Unix time after which the link no longer works.
+    pub expires_at: u64,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Reaction {
+    Clap,
+    Wow,
+}
+
+impl Reaction {
+    pub const ALL: [Reaction; 2] = [Reaction::Clap, Reaction::Wow];
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Reaction::Clap => "clap",
+            Reaction::Wow => "wow",
+        }
+    }
+
+    pub fn as_icon(&self) -> &'static str {
+        match *self {
+            Reaction::Clap => "👏",
+            Reaction::Wow => "😮",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Reaction> {
+        Reaction::ALL.iter().find(|reaction| reaction.name() == name).cloned()
+    }
+
+    /// Position in `ALL`.
+    fn index(&self) -> usize {
+        match *self {
+            Reaction::Clap => 0,
+            Reaction::Wow => 1,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ChatMessage {
+    pub user_name: String,
+    pub text: String,
+}
+
+/// What the spectators of a match do.
+#[derive(Default)]
+pub struct Crowd {
+    /// Reactions to the pending round, indexed like `Reaction::ALL`.
+    reactions: [usize; 2],
+    /// Who reacted to the pending round already.
+    reacted: HashSet<UserId>,
+    /// Reactions to the latest round.
+    last_reactions: Option<[usize; 2]>,
+    /// Oldest first.
+    chat: VecDeque<ChatMessage>,
+}
+
+impl Crowd {
+    /// How many spectators reacted to the latest round with each reaction, if it got any.
+    pub fn last_reactions(&self) -> Vec<(Reaction, usize)> {
+        self.last_reactions
+            .map(|counts| Reaction::ALL.iter().cloned().zip(counts.iter().cloned()).collect())
+            .unwrap_or_default()
+    }
+
+    pub fn has_reacted(&self, user_id: &UserId) -> bool {
+        self.reacted.contains(user_id)
+    }
+
+    pub fn chat(&self) -> &VecDeque<ChatMessage> {
+        &self.chat
+    }
+
+    fn react(&mut self, user_id: &UserId, reaction: Reaction) -> bool {
+        if !self.reacted.insert(user_id.clone()) {
+            return false;
+        }
+        self.reactions[reaction.index()] += 1;
+        true
+    }
+
+    fn say(&mut self, message: ChatMessage) {
+        if self.chat.len() == CHAT_BUFFER {
+            self.chat.pop_front();
+        }
+        self.chat.push_back(message);
+    }
+
+    /// Hands the reactions to the round just over to the players.
+    fn end_round(&mut self) {
+        self.last_reactions = if self.reacted.is_empty() {
+            None
+        } else {
+            Some(self.reactions)
+        };
+        self.reactions = [0; 2];
+        self.reacted.clear();
+    }
+}
+
+pub struct Match {
+    /// Names the match to spectators.
+    pub id: String,
+    /// The host first.
+    pub players: [Member; 2],
+    /// What each player picked for the pending round, until both have.
+    picks: [Option<Hand>; 2],
+    /// From the host's side.
+    score: Scoreboard,
+    /// The hands of the latest round, the host's first.
+    last_round: Option<[Hand; 2]>,
+    /// Unix time until which a restored match is kept without its players' sessions; 0 for others.
+    restored_until: u64,
+    /// Unix time until which a correspondence match waits for the next pick; 0 for others.
+    correspondence_until: u64,
+    pub crowd: Crowd,
+}
+
+/// A match as saved by `maintenance` for the next launch.
+#[derive(Serialize, Deserialize)]
+pub struct SavedMatch {
+    players: [Member; 2],
+    score: Scoreboard,
+    /// Only correspondence matches keep their picks, which may wait for days.
+    #[serde(default)]
+    picks: [Option<Hand>; 2],
+    #[serde(default)]
+    correspondence_until: u64,
+}
+
+impl Match {
+    fn seat(&self, user_id: &UserId) -> Option<usize> {
+        self.players.iter().position(|player| player.id == *user_id)
+    }
+
+    fn has(&self, user_id: &UserId) -> bool {
+        self.seat(user_id).is_some()
+    }
+
+    /// The other player than `user_id`, who must be in the match.
+    pub fn opponent(&self, user_id: &UserId) -> &Member {
+        &self.players[1 - self.seat(user_id).unwrap()]
+    }
+
+    /// The score from `user_id`'s side.
+    pub fn score(&self, user_id: &UserId) -> Scoreboard {
+        match self.seat(user_id) {
+            Some(0) => self.score,
+            _ => Scoreboard {
+                wins: self.score.losses,
+                ties: self.score.ties,
+                losses: self.score.wins,
+            },
+        }
+    }
+
+    /// Whether `user_id` has picked a hand for the pending round.
+    pub fn has_picked(&self, user_id: &UserId) -> bool {
+        self.seat(user_id).map_or(false, |seat| self.picks[seat].is_some())
+    }
+
+    pub fn is_correspondence(&self) -> bool {
+        self.correspondence_until > 0
+    }
+
+    /// The hands of the latest round, the host's first.
+    pub fn last_hands(&self) -> Option<[Hand; 2]> {
+        self.last_round
+    }
+
+    /// `(own hand, opponent's hand, verdict)` of the latest round, from `user_id`'s side.
+    pub fn last_round(&self, user_id: &UserId) -> Option<(Hand, Hand, Verdict)> {
+        let seat = self.seat(user_id)?;
+        self.last_round.map(|hands| {
+            let (own, other) = (hands[seat], hands[1 - seat]);
+            (own, other, Verdict::of(own, other))
+        })
+    }
+}
+
+#[derive(Debug)]
+pub enum PvpError {
+    /// Unknown, used up or expired.
+    NoSuchInvite,
+    OwnInvite,
+    AlreadyInMatch,
+    /// The host started another match in the meantime.
+    HostBusy,
+    NotInMatch,
+    AlreadyPicked,
+    /// The server is in maintenance mode.
+    Maintenance,
+    /// Unknown, or over.
+    NoSuchMatch,
+    /// Players don't spectate their own match.
+    OwnMatch,
+    AlreadyReacted,
+    EmptyMessage,
+    InvalidEmail,
+    /// `MAX_EMAIL_INVITES` emailed invites are waiting already.
+    TooManyInvites,
+    /// Too many invites were emailed lately, from the address or overall.
+    TooManyEmails,
+    /// The host's name has characters that do not belong in a subject line.
+    UnfitName,
+}
+
+impl PvpError {
+    /// The code of the message in `flash`.
+    pub fn code(&self) -> &'static str {
+        match *self {
+            PvpError::NoSuchInvite => "pvp.no-such-invite",
+            PvpError::OwnInvite => "pvp.own-invite",
+            PvpError::AlreadyInMatch => "pvp.already-in-match",
+            PvpError::HostBusy => "pvp.host-busy",
+            PvpError::NotInMatch => "pvp.not-in-match",
+            PvpError::AlreadyPicked => "pvp.already-picked",
+            PvpError::Maintenance => "pvp.maintenance",
+            PvpError::NoSuchMatch => "pvp.no-such-match",
+            PvpError::OwnMatch => "pvp.own-match",
+            PvpError::AlreadyReacted => "pvp.already-reacted",
+            PvpError::EmptyMessage => "pvp.empty-message",
+            PvpError::InvalidEmail => "pvp.invalid-email",
+            PvpError::TooManyInvites => "pvp.too-many-invites",
+            PvpError::TooManyEmails => "pvp.too-many-emails",
+            PvpError::UnfitName => "pvp.unfit-name",
+        }
+    }
+}
+
+#[derive(Default)]
+struct Registry {
+    // Token -> Invite
+    invites: HashMap<String, Invite>,
+    // Token -> EmailInvite
+    email_invites: HashMap<String, EmailInvite>,
+    matches: Vec<Match>,
+}
+
+impl Registry {
+    /// Drops the expired invites, the invite links of players who no longer have a session, and
+    /// their matches unless they are by correspondence.
+    ///
+    /// Takes the sessions' read lock under `PVP`, so never call anything here with `SESSIONS`
+    /// held.
+    fn prune(&mut self) {
+        let sessions = SESSIONS.read_all();
+        let now = unix_time();
+        self.invites.retain(|_, invite| {
+            invite.expires_at > now && sessions.contains_key(&invite.host.id)
+        });
+        self.email_invites.retain(|_, invite| invite.expires_at > now);
+        self.matches.retain(|m| {
+            m.restored_until > now
+                || m.correspondence_until > now
+                || m.players.iter().all(|player| sessions.contains_key(&player.id))
+        });
+    }
+
+    fn match_of(&mut self, user_id: &UserId) -> Option<&mut Match> {
+        self.matches.iter_mut().find(|m| m.has(user_id))
+    }
+
+    /// Whether `user_name` plays a correspondence match, under any session.
+    fn in_correspondence(&self, user_name: &str) -> bool {
+        self.matches.iter()
+            .filter(|m| m.is_correspondence())
+            .any(|m| m.players.iter().any(|player| player.name == user_name))
+    }
+
+    fn revoke_invites_of(&mut self, user_id: &UserId) {
+        self.invites.retain(|_, invite| invite.host.id != *user_id);
+    }
+}
+
+/// Calls `f` with the match of `user_id`, if any.
+pub fn with_match<T, F: FnOnce(&Match) -> T>(user_id: &UserId, f: F) -> Option<T> {
+    let mut pvp = PVP.lock().recover();
+    pvp.prune();
+    pvp.match_of(user_id).map(|m| f(m))
+}
+
+/// The invite link of `user_id` still waiting to be used, if any.
+pub fn pending_invite(user_id: &UserId) -> Option<Invite> {
+    let mut pvp = PVP.lock().recover();
+    pvp.prune();
+    pvp.invites.values().find(|invite| invite.host.id == *user_id).cloned()
+}
+
+/// Issues a new invite link for `host`, revoking the previous one.
+pub fn invite(host: Member) -> Result<Invite, PvpError> {
+    if maintenance::is_on() {
+        return Err(PvpError::Maintenance);
+    }
+    let mut pvp = PVP.lock().recover();
+    pvp.prune();
+    if pvp.match_of(&host.id).is_some() {
+        return Err(PvpError::AlreadyInMatch);
+    }
+    pvp.revoke_invites_of(&host.id);
+    let invite = Invite {
+        token: bytes_to_hex(&gen_random_bytes(16)),
+        host: host,
+        expires_at: unix_time() + INVITE_TTL,
+    };
+    pvp.invites.insert(invite.token.clone(), invite.clone());
+    Ok(invite)
+}
+
+/// Issues an invite link for `host_name` to email to `email`, for a correspondence match, asked
+/// for from `remote`.
+pub fn invite_by_email(host_name: &str, email: &str, remote: Option<IpAddr>)
+    -> Result<EmailInvite, PvpError>
+{
+    if maintenance::is_on() {
+        return Err(PvpError::Maintenance);
+    }
+    if !is_valid_email(email) {
+        return Err(PvpError::InvalidEmail);
+    }
+    if host_name.chars().any(char::is_control) {
+        return Err(PvpError::UnfitName);
+    }
+    let mut pvp = PVP.lock().recover();
+    pvp.prune();
+    let waiting = pvp.email_invites.values().filter(|invite| invite.host_name == host_name).count();
+    if waiting >= MAX_EMAIL_INVITES {
+        return Err(PvpError::TooManyInvites);
+    }
+    let address = remote.map_or_else(String::new, |ip| ip.to_string());
+    if !EMAILS_BY_ADDRESS.admit(&address) || !EMAILS.admit("") {
+        return Err(PvpError::TooManyEmails);
+    }
+    let invite = EmailInvite {
+        token: bytes_to_hex(&gen_random_bytes(16)),
+        host_name: host_name.to_owned(),
+        email: email.to_owned(),
+        expires_at: unix_time() + EMAIL_INVITE_TTL,
+    };
+    pvp.email_invites.insert(invite.token.clone(), invite.clone());
+    Ok(invite)
+}
+
+/// The emailed invite links of `host_name` still waiting to be used, the latest last.
+pub fn email_invites_of(host_name: &str) -> Vec<EmailInvite> {
+    let mut pvp = PVP.lock().recover();
+    pvp.prune();
+    let mut invites: Vec<_> = pvp.email_invites.values()
+        .filter(|invite| invite.host_name == host_name)
+        .cloned()
+        .collect();
+    invites.sort_by_key(|invite| invite.expires_at);
+    invites
+}
+
+/// A loose check: one `@`, something before it, a dot in the domain and no spaces. The gateway
+/// finds out the rest.
+fn is_valid_email(email: &str) -> bool {
+    let mut parts = email.split('@');
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(local), Some(domain), None) => {
+            email.len() <= MAX_EMAIL_LEN
+                && !local.is_empty()
+                && domain.contains('.')
+                && !domain.starts_with('.')
+                && !domain.ends_with('.')
+                && !email.chars().any(|c| c.is_whitespace() || c.is_control())
+        }
+        _ => false,
+    }
+}
+
+/// The seat of `user_name` in a match: the id of their latest verified session or, without one, an
+/// id no session has, until `rebind` seats them.
+fn seat_of(user_name: &str, token: &str) -> Member {
+    let sessions = SESSIONS.read_all();
+    let id = sessions.iter()
+        .filter(|&(_, session)| session.user_name == user_name && session.verified)
+        .max_by_key(|&(_, session)| session.last_active())
+        .map(|(id, _)| id.clone())
+        .unwrap_or_else(|| UserId::from_string(format!("offline-{}", token)));
+    Member { id: id, name: user_name.to_owned() }
+}
+
+/// Uses up the invite link `token`, or the emailed one, to start a match between its host and
+/// `guest`, by correspondence if emailed. Returns the host.
+pub fn accept(guest: Member, token: &str) -> Result<Member, PvpError> {
+    accept_at(guest, token, unix_time())
+}
+
+/// `accept`, as of Unix time `now`: an invite that expires by then is refused.
+pub fn accept_at(guest: Member, token: &str, now: u64) -> Result<Member, PvpError> {
+    if maintenance::is_on() {
+        return Err(PvpError::Maintenance);
+    }
+    let mut pvp = PVP.lock().recover();
+    pvp.prune();
+    let (host, correspondence) = {
+        let invite = pvp.invites.get(token).filter(|invite| invite.expires_at > now);
+        let email_invite = pvp.email_invites.get(token).filter(|invite| invite.expires_at > now);
+        match (invite, email_invite) {
+            (Some(invite), _) if invite.host.id == guest.id => return Err(PvpError::OwnInvite),
+            (Some(invite), _) => (invite.host.clone(), false),
+            (None, Some(invite)) if invite.host_name == guest.name => {
+                return Err(PvpError::OwnInvite)
+            }
+            (None, Some(invite)) => (seat_of(&invite.host_name, token), true),
+            (None, None) => return Err(PvpError::NoSuchInvite),
+        }
+    };
+    if pvp.match_of(&guest.id).is_some() || pvp.in_correspondence(&guest.name) {
+        return Err(PvpError::AlreadyInMatch);
+    }
+    if pvp.match_of(&host.id).is_some() || pvp.in_correspondence(&host.name) {
+        return Err(PvpError::HostBusy);
+    }
+    pvp.revoke_invites_of(&host.id);
+    pvp.revoke_invites_of(&guest.id);
+    pvp.email_invites.remove(token);
+    pvp.matches.push(Match {
+        id: bytes_to_hex(&gen_random_bytes(8)),
+        players: [host.clone(), guest],
+        picks: [None, None],
+        score: Scoreboard::default(),
+        last_round: None,
+        restored_until: 0,
+        correspondence_until: if correspondence { now + CORRESPONDENCE_TTL } else { 0 },
+        crowd: Crowd::default(),
+    });
+    Ok(host)
+}
+
+/// Seats `player`, who just logged in and proved they own their name, in their correspondence
+/// match, if any, in place of the session they had.
+pub fn rebind(player: &Member) {
+    let mut pvp = PVP.lock().recover();
+    let seats = pvp.matches.iter_mut()
+        .filter(|m| m.is_correspondence())
+        .flat_map(|m| m.players.iter_mut())
+        .filter(|seat| seat.name == player.name);
+    for seat in seats {
+        seat.id = player.id.clone();
+    }
+}
+
+/// Picks `user_id`'s hand for the pending round. In maintenance mode, only the second hand of a
+/// round is taken. Returns the round, from their side, if it is
+/// complete: `(own hand, opponent's hand, verdict)`.
+pub fn play(user_id: &UserId, hand: Hand) -> Result<Option<(Hand, Hand, Verdict)>, PvpError> {
+    let mut pvp = PVP.lock().recover();
+    pvp.prune();
+    let m = pvp.match_of(user_id).ok_or(PvpError::NotInMatch)?;
+    let seat = m.seat(user_id).unwrap();
+    if m.picks[seat].is_some() {
+        return Err(PvpError::AlreadyPicked);
+    }
+    if maintenance::is_on() && m.picks.iter().all(Option::is_none) {
+        return Err(PvpError::Maintenance);
+    }
+    m.picks[seat] = Some(hand);
+    if m.is_correspondence() {
+        m.correspondence_until = unix_time() + CORRESPONDENCE_TTL;
+    }
+    if let [Some(host), Some(guest)] = m.picks {
+        m.score.record(Verdict::of(host, guest));
+        m.last_round = Some([host, guest]);
+        m.picks = [None, None];
+        m.crowd.end_round();
+        return Ok(m.last_round(user_id));
+    }
+    Ok(None)
+}
+
+/// The matches going on, as `(id, host, guest)`.
+pub fn live_matches() -> Vec<(String, Member, Member)> {
+    let mut pvp = PVP.lock().recover();
+    pvp.prune();
+    pvp.matches.iter()
+        .map(|m| (m.id.clone(), m.players[0].clone(), m.players[1].clone()))
+        .collect()
+}
+
+/// Calls `f` with the match `match_id` for `spectator_id` to watch, who must not play in it.
+pub fn watch<T, F: FnOnce(&Match) -> T>(match_id: &str, spectator_id: &UserId, f: F)
+    -> Result<T, PvpError>
+{
+    let mut pvp = PVP.lock().recover();
+    pvp.prune();
+    let m = pvp.matches.iter().find(|m| m.id == match_id).ok_or(PvpError::NoSuchMatch)?;
+    if m.has(spectator_id) {
+        return Err(PvpError::OwnMatch);
+    }
+    Ok(f(m))
+}
+
+/// Applies `f` to the crowd of the match `match_id`, which `spectator_id` must not play in.
+fn with_crowd<T, F: FnOnce(&mut Crowd) -> T>(match_id: &str, spectator_id: &UserId, f: F)
+    -> Result<T, PvpError>
+{
+    let mut pvp = PVP.lock().recover();
+    pvp.prune();
+    let m = pvp.matches.iter_mut().find(|m| m.id == match_id).ok_or(PvpError::NoSuchMatch)?;
+    if m.has(spectator_id) {
+        return Err(PvpError::OwnMatch);
+    }
+    Ok(f(&mut m.crowd))
+}
+
+/// Reacts to the pending round of the match `match_id` on behalf of `spectator_id`.
+pub fn react(match_id: &str, spectator_id: &UserId, reaction: Reaction) -> Result<(), PvpError> {
+    if with_crowd(match_id, spectator_id, |crowd| crowd.react(spectator_id, reaction))? {
+        Ok(())
+    } else {
+        Err(PvpError::AlreadyReacted)
+    }
+}
+
+/// Posts to the spectator chat of the match `match_id`. Messages longer than `MAX_CHAT_LEN` are
+/// cut short.
+pub fn say(match_id: &str, spectator: &Member, text: &str) -> Result<(), PvpError> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Err(PvpError::EmptyMessage);
+    }
+    with_crowd(match_id, &spectator.id, |crowd| {
+        crowd.say(ChatMessage {
+            user_name: spectator.name.clone(),
+            text: text.chars().take(MAX_CHAT_LEN).collect(),
+        })
+    })
+}
+
+/// Ends the match of `user_id`, for the opponent too. Returns whether there was one.
+pub fn leave(user_id: &UserId) -> bool {
+    let mut pvp = PVP.lock().recover();
+    let before = pvp.matches.len();
+    pvp.matches.retain(|m| !m.has(user_id));
+    pvp.matches.len() < before
+}
+
+/// Ends the match of `user_id`, if any, and revokes their invite links, e.g. when their account is
+/// deleted.
+pub fn forget(user_id: &UserId) {
+    let mut pvp = PVP.lock().recover();
+    pvp.matches.retain(|m| !m.has(user_id));
+    pvp.revoke_invites_of(user_id);
+}
+
+/// Ends the correspondence match of `user_name`, if any, and revokes their emailed invite links,
+/// which outlive their sessions.
+pub fn forget_player(user_name: &str) {
+    let mut pvp = PVP.lock().recover();
+    pvp.matches.retain(|m| {
+        !m.is_correspondence() || m.players.iter().all(|player| player.name != user_name)
+    });
+    pvp.email_invites.retain(|_, invite| invite.host_name != user_name);
+}
+
+/// How many matches are going on, and how many of their rounds one player picked a hand for.
+/// Picks of correspondence matches, which are saved, are not in flight.
+pub fn in_flight() -> (usize, usize) {
+    let mut pvp = PVP.lock().recover();
+    pvp.prune();
+    let rounds = pvp.matches.iter()
+        .filter(|m| !m.is_correspondence() && m.picks.iter().any(Option::is_some))
+        .count();
+    (pvp.matches.len(), rounds)
+}
+
+/// The matches going on, without any round in flight but those of correspondence matches.
+pub fn saved_matches() -> Vec<SavedMatch> {
+    let mut pvp = PVP.lock().recover();
+    pvp.prune();
+    pvp.matches.iter()
+        .map(|m| SavedMatch {
+            players: m.players.clone(),
+            score: m.score,
+            picks: if m.is_correspondence() { m.picks } else { [None, None] },
+            correspondence_until: m.correspondence_until,
+        })
+        .collect()
+}
+
+/// Takes the matches saved before a restart up again.
+pub fn restore(saved: Vec<SavedMatch>) {
+    let restored_until = unix_time() + RESTORE_GRACE;
+    let mut pvp = PVP.lock().recover();
+    pvp.matches.extend(saved.into_iter().map(|saved| Match {
+        id: bytes_to_hex(&gen_random_bytes(8)),
+        players: saved.players,
+        picks: saved.picks,
+        score: saved.score,
+        last_round: None,
+        restored_until: restored_until,
+        correspondence_until: saved.correspondence_until,
+        crowd: Crowd::default(),
+    }));
+}
@@ -0,0 +1,13 @@
+//! QR codes, rendered server-side as SVG
+//!
+//! The login page shows one for logging in from a phone, see `handoff`, and the result page one of
+//! the proof of the round just played, see `proof`, for the player to check it on their phone.
+
+use qrcode::render::svg;
+use qrcode::QrCode;
+
+/// The QR code of `text`, as an SVG image. `None` if `text` is too long for one.
+pub fn svg(text: &str) -> Option<String> {
+    let code = QrCode::new(text.as_bytes()).ok()?;
+    Some(code.render::<svg::Color>().min_dimensions(200, 200).build())
+}
@@ -0,0 +1,157 @@
+//! Daily quotas of the JSON API
+//!
+//! Requests under `/api/v1` are counted per consumer, i.e. per API key or, for cookie clients, per
+//! session. Counters reset at midnight UTC.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::{Config, Data, Request, Response, State};
+
+use api;
+use ids::SessionToken;
+use poison::Recover;
+use util::unix_time;
+use User;
+
+pub const DEFAULT_DAILY_QUOTA: u64 = 1000;
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+const API_PREFIX: &str = "/api/v1/";
+
+/// Who a request is counted against.
+pub struct Consumer {
+    pub id: String,
+    /// What the admin view shows instead of the (secret) id.
+    pub label: String,
+}
+
+/// Identifies the consumer of an API request, if it is authenticated.
+pub fn consumer(request: &Request) -> Option<Consumer> {
+    if let Some(key) = request.headers().get_one(api::API_KEY_HEADER) {
+        let user = api::key_user(&SessionToken::from_string(key.to_owned()))?;
+        return Some(Consumer {
+            id: key.to_owned(),
+            label: format!("{} (key {}…)", user.name, &key[..8.min(key.len())]),
+        });
+    }
+    let user = request.guard::<User>().succeeded()?;
+    Some(Consumer {
+        label: format!("{} (session)", user.name),
+        id: user.id.as_str().to_owned(),
+    })
+}
+
+struct Usage {
+    #[cfg_attr(not(feature = "templates"), allow(dead_code))]
+    label: String,
+    day: u64,
+    count: u64,
+}
+
+/// Per-consumer request counters, with the daily limit from the `api_daily_quota` config extra.
+pub struct Quotas {
+    daily_limit: u64,
+    usage: Mutex<HashMap<String, Usage>>,
+}
+
+impl Quotas {
+    pub fn from_config(config: &Config) -> Result<Quotas, String> {
+        let daily_limit = match config.get_int("api_daily_quota") {
+            Ok(n) if n > 0 => n as u64,
+            Ok(n) => return Err(format!("`api_daily_quota` must be positive, got {}", n)),
+            Err(_) => DEFAULT_DAILY_QUOTA,
+        };
+        Ok(Quotas {
+            daily_limit: daily_limit,
+            usage: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub fn daily_limit(&self) -> u64 {
+        self.daily_limit
+    }
+
+    /// Counts one request of `consumer`.
+    fn count(&self, consumer: &Consumer) {
+        let today = today();
+        let mut usage = self.usage.lock().recover();
+        // Forgets yesterday's consumers so that the table doesn't grow forever.
+        usage.retain(|_, u| u.day == today);
+        let entry = usage.entry(consumer.id.clone()).or_insert_with(|| Usage {
+            label: consumer.label.clone(),
+            day: today,
+            count: 0,
+        });
+        entry.count += 1;
+    }
+
+    /// Requests made by the consumer `id` today.
+    pub fn used(&self, id: &str) -> u64 {
+        let usage = self.usage.lock().recover();
+        match usage.get(id) {
+            Some(u) if u.day == today() => u.count,
+            _ => 0,
+        }
+    }
+
+    pub fn exceeded(&self, id: &str) -> bool {
+        self.used(id) > self.daily_limit
+    }
+
+    /// The `n` consumers with the most requests today, as `(label, count)`.
+    #[cfg(feature = "templates")]
+    pub fn top(&self, n: usize) -> Vec<(String, u64)> {
+        let today = today();
+        let usage = self.usage.lock().recover();
+        let mut top: Vec<_> = usage.values()
+            .filter(|u| u.day == today)
+            .map(|u| (u.label.clone(), u.count))
+            .collect();
+        top.sort_by(|a, b| b.1.cmp(&a.1));
+        top.truncate(n);
+        top
+    }
+}
+
+fn today() -> u64 {
+    unix_time() / SECONDS_PER_DAY
+}
+
+/// Counts API requests and reports the consumer's quota in `X-RateLimit-*` headers. Enforcement
+/// is left to `ApiUser`, since fairings cannot answer requests themselves.
+pub struct RateLimitHeaders;
+
+impl Fairing for RateLimitHeaders {
+    fn info(&self) -> Info {
+        Info {
+            name: "API quotas",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    fn on_request(&self, request: &mut Request, _: &Data) {
+        if !request.uri().path().starts_with(API_PREFIX) {
+            return;
+        }
+        if let (Some(quotas), Some(consumer)) = (request.guard::<State<Quotas>>().succeeded(),
+                                                consumer(request)) {
+            quotas.count(&consumer);
+        }
+    }
+
+    fn on_response(&self, request: &Request, response: &mut Response) {
+        if !request.uri().path().starts_with(API_PREFIX) {
+            return;
+        }
+        if let (Some(quotas), Some(consumer)) = (request.guard::<State<Quotas>>().succeeded(),
+                                                consumer(request)) {
+            let used = quotas.used(&consumer.id);
+            let reset = (today() + 1) * SECONDS_PER_DAY;
+            response.set_raw_header("X-RateLimit-Limit", quotas.daily_limit().to_string());
+            response.set_raw_header("X-RateLimit-Remaining",
+                                    quotas.daily_limit().saturating_sub(used).to_string());
+            response.set_raw_header("X-RateLimit-Reset", reset.to_string());
+        }
+    }
+}
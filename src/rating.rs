@@ -0,0 +1,202 @@
+//! Rating systems
+//!
+//! Players are rated against the computer from their classic rounds. Two systems implement
+//! `RatingSystem`, and the `rating_system` config extra picks one for the whole server:
+//!
+//! - `elo`, the default: a single number, moved by up to `ELO_K` a round;
+//! - `glicko-2`: the number comes with a deviation, how unsure it is, and a volatility, how much
+//!   the player's strength swings. A player the system knows little about moves fast, and one it
+//!   knows well moves slowly. Every round is a rating period of its own.
+//!
+//! Both share the Elo scale, centered on 1500, so a rating carries over when the system changes.
+//! A rating Glicko-2 has not tracked yet, i.e. one from Elo, gets the deviation of a player who
+//! played that many rounds against an even opponent from scratch, see `Rating::migrated`, and
+//! the initial volatility.
+
+use std::f64::consts::PI;
+use std::sync::RwLock;
+
+use rocket::Config;
+
+use poison::Recover;
+
+pub const INITIAL_RATING: f64 = 1500.0;
+/// The deviation of a player never rated.
+pub const INITIAL_DEVIATION: f64 = 350.0;
+/// The lowest deviation a migrated rating gets.
+const MIN_MIGRATED_DEVIATION: f64 = 50.0;
+pub const INITIAL_VOLATILITY: f64 = 0.06;
+/// How much a single round moves an Elo rating, at most.
+const ELO_K: f64 = 16.0;
+/// Glicko-2's system constant, which constrains how fast the volatility changes.
+const TAU: f64 = 0.5;
+/// Between the Glicko and Glicko-2 scales.
+const GLICKO2_SCALE: f64 = 173.7178;
+const CONVERGENCE: f64 = 1e-6;
+
+/// The computer's fixed rating, which players are rated against. It always plays the same way,
+/// so Glicko-2 is sure of it.
+pub const COMPUTER: Rating = Rating {
+    rating: INITIAL_RATING,
+    deviation: 30.0,
+    volatility: INITIAL_VOLATILITY,
+};
+
+lazy_static! {
+    static ref SYSTEM: RwLock<&'static RatingSystem> = RwLock::new(&Elo);
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rating {
+    pub rating: f64,
+    pub deviation: f64,
+    pub volatility: f64,
+}
+
+impl Rating {
+    /// A rating of `rating` after `rounds` rounds, without a deviation of its own, e.g. from Elo:
+    /// it gets the one that many rounds against an even opponent leave a new player with.
+    pub fn migrated(rating: f64, rounds: usize) -> Rating {
+        let q = 10f64.ln() / 400.0;
+        let information = 1.0 / INITIAL_DEVIATION.powi(2) + rounds as f64 * q * q / 4.0;
+        Rating {
+            rating: rating,
+            deviation: (1.0 / information.sqrt()).max(MIN_MIGRATED_DEVIATION),
+            volatility: INITIAL_VOLATILITY,
+        }
+    }
+}
+
+pub trait RatingSystem: Sync {
+    /// As in the `rating_system` config extra.
+    fn name(&self) -> &'static str;
+
+    /// Whether the system updates the deviation and the volatility, rather than only the rating.
+    fn tracks_deviation(&self) -> bool;
+
+    /// Rates `rating` after one rating period of `games`: the ratings of the opponents, and the
+    /// player's score against each, 1 for a win, 0.5 for a tie and 0 for a loss.
+    fn rate(&self, rating: &mut Rating, games: &[(Rating, f64)]);
+}
+
+pub struct Elo;
+
+impl RatingSystem for Elo {
+    fn name(&self) -> &'static str {
+        "elo"
+    }
+
+    fn tracks_deviation(&self) -> bool {
+        false
+    }
+
+    fn rate(&self, rating: &mut Rating, games: &[(Rating, f64)]) {
+        let change: f64 = games.iter()
+            .map(|&(opponent, score)| {
+                let expected = 1.0 / (1.0 + 10f64.powf((opponent.rating - rating.rating) / 400.0));
+                ELO_K * (score - expected)
+            })
+            .sum();
+        rating.rating += change;
+    }
+}
+
+pub struct Glicko2;
+
+impl Glicko2 {
+    /// The new volatility, found with the Illinois algorithm as in Glickman's paper.
+    fn volatility(phi: f64, sigma: f64, v: f64, delta: f64) -> f64 {
+        let a = (sigma * sigma).ln();
+        let f = |x: f64| {
+            let ex = x.exp();
+            ex * (delta * delta - phi * phi - v - ex) / (2.0 * (phi * phi + v + ex).powi(2))
+                - (x - a) / (TAU * TAU)
+        };
+        let mut lower = a;
+        let mut upper = if delta * delta > phi * phi + v {
+            (delta * delta - phi * phi - v).ln()
+        } else {
+            let mut k = 1.0;
+            while f(a - k * TAU) < 0.0 {
+                k += 1.0;
+            }
+            a - k * TAU
+        };
+        let (mut f_lower, mut f_upper) = (f(lower), f(upper));
+        while (upper - lower).abs() > CONVERGENCE {
+            let c = lower + (lower - upper) * f_lower / (f_upper - f_lower);
+            let f_c = f(c);
+            if f_c * f_upper <= 0.0 {
+                lower = upper;
+                f_lower = f_upper;
+            } else {
+                f_lower /= 2.0;
+            }
+            upper = c;
+            f_upper = f_c;
+        }
+        (lower / 2.0).exp()
+    }
+}
+
+impl RatingSystem for Glicko2 {
+    fn name(&self) -> &'static str {
+        "glicko-2"
+    }
+
+    fn tracks_deviation(&self) -> bool {
+        true
+    }
+
+    fn rate(&self, rating: &mut Rating, games: &[(Rating, f64)]) {
+        let mu = (rating.rating - INITIAL_RATING) / GLICKO2_SCALE;
+        let phi = rating.deviation / GLICKO2_SCALE;
+        if games.is_empty() {
+            let phi = (phi * phi + rating.volatility * rating.volatility).sqrt();
+            rating.deviation = (phi * GLICKO2_SCALE).min(INITIAL_DEVIATION);
+            return;
+        }
+
+        // g(φ_j), E(μ, μ_j, φ_j) and the score of every game.
+        let outcomes: Vec<_> = games.iter()
+            .map(|&(opponent, score)| {
+                let mu_j = (opponent.rating - INITIAL_RATING) / GLICKO2_SCALE;
+                let phi_j = opponent.deviation / GLICKO2_SCALE;
+                let g = 1.0 / (1.0 + 3.0 * phi_j * phi_j / (PI * PI)).sqrt();
+                let expected = 1.0 / (1.0 + (-g * (mu - mu_j)).exp());
+                (g, expected, score)
+            })
+            .collect();
+        let v = 1.0 / outcomes.iter()
+            .map(|&(g, expected, _)| g * g * expected * (1.0 - expected))
+            .sum::<f64>();
+        let improvement: f64 = outcomes.iter()
+            .map(|&(g, expected, score)| g * (score - expected))
+            .sum();
+
+        let sigma = Glicko2::volatility(phi, rating.volatility, v, v * improvement);
+        let phi_star = (phi * phi + sigma * sigma).sqrt();
+        let phi = 1.0 / (1.0 / (phi_star * phi_star) + 1.0 / v).sqrt();
+        let mu = mu + phi * phi * improvement;
+
+        rating.rating = mu * GLICKO2_SCALE + INITIAL_RATING;
+        rating.deviation = (phi * GLICKO2_SCALE).min(INITIAL_DEVIATION);
+        rating.volatility = sigma;
+    }
+}
+
+/// Picks the system named in the `rating_system` config extra, `elo` or `glicko-2`.
+pub fn configure(config: &Config) -> Result<(), String> {
+    let system: &'static RatingSystem = match config.get_str("rating_system") {
+        Ok("elo") | Err(_) => &Elo,
+        Ok("glicko-2") => &Glicko2,
+        Ok(name) => return Err(format!("unknown rating system {}", name)),
+    };
+    *SYSTEM.write().recover() = system;
+    Ok(())
+}
+
+/// The system the server rates players with.
+pub fn system() -> &'static RatingSystem {
+    *SYSTEM.read().recover()
+}
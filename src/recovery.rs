@@ -0,0 +1,92 @@
+//! One-time recovery codes
+//!
+//! There are no emails or passwords to fall back on, so whoever opens an account, or logs in with
+//! a code or a passkey and has no codes left, is issued `RECOVERY_CODES` of them. They are shown
+//! once, on the next index page of a verified session, and only their SHA-256 digests are kept.
+//! After losing their cookies, e.g. by clearing the browser, a player redeems one at `/recover` to
+//! get into a new, verified session of the same name. A code works only once; issuing new codes
+//! voids the old ones.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use sha2::{Digest, Sha256};
+
+use poison::Recover;
+use util::{bytes_to_hex, gen_random_bytes};
+
+/// How many codes are issued at a time.
+pub const RECOVERY_CODES: usize = 8;
+
+lazy_static! {
+    static ref CODES: Mutex<Codes> = Mutex::new(Codes::default());
+}
+
+#[derive(Default)]
+struct Codes {
+    // User name -> digests of the codes not redeemed yet
+    digests: HashMap<String, Vec<String>>,
+    // User name -> codes issued but not shown yet
+    unseen: HashMap<String, Vec<String>>,
+}
+
+fn digest(code: &str) -> String {
+    format!("{:x}", Sha256::digest(code.trim().to_lowercase().as_bytes()))
+}
+
+/// A fresh code, e.g. `3f9a-07c2-b1d4-8e65`.
+fn new_code() -> String {
+    let hex = bytes_to_hex(&gen_random_bytes(8));
+    let groups: Vec<_> = (0..4).map(|i| &hex[i * 4..i * 4 + 4]).collect();
+    groups.join("-")
+}
+
+/// Issues new codes for `user_name`, voiding the old ones. They are kept for `take_unseen`.
+pub fn issue(user_name: &str) {
+    let codes: Vec<_> = (0..RECOVERY_CODES).map(|_| new_code()).collect();
+    let mut registry = CODES.lock().recover();
+    registry.digests.insert(user_name.to_owned(), codes.iter().map(|code| digest(code)).collect());
+    registry.unseen.insert(user_name.to_owned(), codes);
+}
+
+/// Issues codes for `user_name` if they have none left. Returns whether it did.
+pub fn issue_if_missing(user_name: &str) -> bool {
+    if remaining(user_name) > 0 {
+        return false;
+    }
+    issue(user_name);
+    true
+}
+
+/// How many codes of `user_name` can still be redeemed.
+pub fn remaining(user_name: &str) -> usize {
+    CODES.lock().recover().digests.get(user_name).map_or(0, Vec::len)
+}
+
+/// The codes issued to `user_name` that were not shown yet, if any. They are not kept after that.
+pub fn take_unseen(user_name: &str) -> Option<Vec<String>> {
+    CODES.lock().recover().unseen.remove(user_name)
+}
+
+/// Drops the codes of `user_name`, shown or not, e.g. when their account is deleted.
+pub fn forget(user_name: &str) {
+    let mut registry = CODES.lock().recover();
+    registry.digests.remove(user_name);
+    registry.unseen.remove(user_name);
+}
+
+/// Uses up `code` of `user_name`. Returns whether it was one of theirs not redeemed yet.
+pub fn redeem(user_name: &str, code: &str) -> bool {
+    let code = digest(code);
+    let mut registry = CODES.lock().recover();
+    match registry.digests.get_mut(user_name) {
+        Some(digests) => match digests.iter().position(|digest| *digest == code) {
+            Some(i) => {
+                digests.remove(i);
+                true
+            }
+            None => false,
+        },
+        None => false,
+    }
+}
@@ -0,0 +1,677 @@
+//! Relay tournaments between teams of three
+//!
+//! Players form relay teams through the API: a captain registers one, `POST /api/v1/relay/teams`,
+//! and passes its invite code on to teammates, who join with `POST /api/v1/relay/teams/join`.
+//! Members can leave and the captain can drop members, but not while the team plays. A team is
+//! full at `RELAY_TEAM_SIZE`; teams are kept by their members' names, so they outlive sessions,
+//! and only sessions that proved they own their name, see `Session::verified`, may use them.
+//!
+//! Every `relay_interval_secs` seconds (a day by default), a tournament starts between the full
+//! teams, if there are two or more. Each team runs a relay against the computer: its members, in
+//! the order they joined, play a leg of `LEG_ROUNDS` classic rounds each, through
+//! `POST /api/v1/relay/play`, and pass the baton on once their leg is over. A round won scores 1,
+//! a round lost -1, and the team's score is the sum of its legs. Relays have
+//! `relay_duration_secs` seconds (an hour by default); the rounds not played by then are lost, as
+//! are the legs left of a member whose account is deleted mid-relay. Legs count for the team
+//! only: their rounds are unranked, so they stay out of the players' stats and the leaderboard.
+//!
+//! Once every relay is over, every two teams meet in a relay match, won by the higher score. A
+//! match won scores 3 points, a drawn one 1, on the team leaderboard of `/relay/standings.json`,
+//! kept for the life of the process. A job of the `scheduler` starts the tournaments and closes
+//! them, every second.
+//!
+//! The rounds of the relays are drawn from the `feed`, and their events published, once `RELAYS`
+//! is unlocked: a relay waits on its next round, without a `digest`, for the moment in between.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use rocket::http::Status;
+use rocket::Config;
+use rocket_contrib::Json;
+
+use events::{self, Event};
+use feed;
+use game::{Hand, Round, RoundKind, Scoreboard, Verdict};
+use maintenance;
+use poison::Recover;
+use scheduler;
+use tournament;
+use util::{bytes_to_hex, gen_random_bytes, unix_time};
+
+pub const RELAY_TEAM_SIZE: usize = 3;
+/// Rounds each member plays in a relay.
+pub const LEG_ROUNDS: usize = 5;
+const DEFAULT_INTERVAL_SECS: u64 = 24 * 3600;
+const DEFAULT_DURATION_SECS: u64 = 3600;
+
+lazy_static! {
+    static ref RELAYS: Mutex<Relays> = Mutex::new(Relays::default());
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum RelayError {
+    /// Names are 1 to `tournament::MAX_NAME_LEN` lowercase letters, digits and dashes.
+    InvalidName,
+    NameTaken,
+    AlreadyInTeam,
+    /// No team has that invite code.
+    NoSuchTeam,
+    TeamFull,
+    NotInTeam,
+    /// Only the captain may drop members.
+    NotCaptain,
+    NoSuchMember,
+    /// The team plays in the running tournament.
+    RosterLocked,
+    /// The team of the player has no relay going on.
+    NotPlaying,
+    /// Another member runs the current leg.
+    NotYourLeg,
+    /// The session did not prove it owns the name it plays under.
+    Unverified,
+}
+
+impl RelayError {
+    pub fn status(&self) -> Status {
+        match *self {
+            RelayError::InvalidName => Status::BadRequest,
+            RelayError::NotCaptain | RelayError::Unverified => Status::Forbidden,
+            RelayError::NoSuchTeam | RelayError::NotInTeam | RelayError::NoSuchMember => {
+                Status::NotFound
+            }
+            RelayError::NameTaken | RelayError::AlreadyInTeam | RelayError::TeamFull
+            | RelayError::RosterLocked | RelayError::NotPlaying | RelayError::NotYourLeg => {
+                Status::Conflict
+            }
+        }
+    }
+}
+
+struct RelayTeam {
+    name: String,
+    code: String,
+    /// User names, the captain first.
+    members: Vec<String>,
+}
+
+/// A team's run through its legs.
+struct Relay {
+    team: String,
+    /// The roster as the relay started, in leg order.
+    runners: Vec<String>,
+    /// Index into `runners` of the current leg; the relay is over once it reaches their count.
+    leg: usize,
+    legs: Vec<Scoreboard>,
+    /// The round committed to for the current leg, once dealt.
+    pending: Option<Round>,
+}
+
+impl Relay {
+    fn new(team: &RelayTeam) -> Relay {
+        Relay {
+            team: team.name.clone(),
+            runners: team.members.clone(),
+            leg: 0,
+            legs: vec![Scoreboard::default(); team.members.len()],
+            pending: None,
+        }
+    }
+
+    fn is_over(&self) -> bool {
+        self.leg == self.runners.len()
+    }
+
+    fn runner(&self) -> Option<&str> {
+        self.runners.get(self.leg).map(String::as_str)
+    }
+
+    /// Rounds won minus rounds lost, over every leg.
+    fn score(&self) -> i64 {
+        self.legs.iter().map(|leg| leg.wins as i64 - leg.losses as i64).sum()
+    }
+
+    fn is_waiting(&self) -> bool {
+        !self.is_over() && self.pending.is_none()
+    }
+
+    /// Commits to `round` for the current leg. Returns the event to publish.
+    fn deal(&mut self, round: Round) -> Event {
+        let event = Event::RoundCommitted {
+            user_name: self.runner().expect("Only running relays are dealt rounds").to_owned(),
+            digest: round.digest.clone(),
+            nonce: round.random_bytes.clone(),
+            committed: round.computer.as_ref().to_owned(),
+        };
+        self.pending = Some(round);
+        event
+    }
+
+    /// Passes the baton on past the legs played in full.
+    fn advance(&mut self) {
+        while self.legs.get(self.leg)
+            .map_or(false, |leg| leg.wins + leg.ties + leg.losses == LEG_ROUNDS)
+        {
+            self.leg += 1;
+        }
+    }
+
+    /// Plays `hand` against the pending round, passing the baton on once the leg is over.
+    fn play(&mut self, round: &Round, hand: Hand) -> Verdict {
+        let verdict = Verdict::of(hand, round.computer);
+        self.legs[self.leg].record(verdict);
+        self.advance();
+        verdict
+    }
+
+    /// Loses the legs left of `runner`, who is gone, and passes the baton on if theirs is the
+    /// current one.
+    fn drop_runner(&mut self, runner: &str) {
+        for i in self.leg..self.runners.len() {
+            if self.runners[i] == runner {
+                let leg = &mut self.legs[i];
+                leg.losses = LEG_ROUNDS - leg.wins - leg.ties;
+            }
+        }
+        let leg = self.leg;
+        self.advance();
+        if self.leg != leg {
+            self.pending = None;
+        }
+    }
+
+    /// Loses the rounds left, once time is up.
+    fn forfeit(&mut self) {
+        for leg in &mut self.legs[self.leg..] {
+            leg.losses = LEG_ROUNDS - leg.wins - leg.ties;
+        }
+        self.leg = self.runners.len();
+        self.pending = None;
+    }
+
+    fn view(&self, tournament: u64, deadline: u64) -> RelayStatus {
+        RelayStatus {
+            tournament: tournament,
+            team: self.team.clone(),
+            leg: if self.is_over() { None } else { Some(self.leg + 1) },
+            runner: self.runner().map(str::to_owned),
+            digest: self.pending.as_ref().map(|round| round.digest.clone()),
+            legs: self.runners.iter().zip(&self.legs)
+                .map(|(runner, &score)| Leg {
+                    runner: runner.clone(),
+                    score: score,
+                })
+                .collect(),
+            score: self.score(),
+            deadline: deadline,
+        }
+    }
+}
+
+struct RelayTournament {
+    id: u64,
+    relays: Vec<Relay>,
+    /// Unix time at which the relays are cut short.
+    deadline: u64,
+}
+
+#[derive(Clone, Default, Serialize)]
+pub struct TeamStanding {
+    pub team: String,
+    pub tournaments: u32,
+    pub points: u32,
+    pub won: u32,
+    pub drawn: u32,
+    pub lost: u32,
+    /// The best relay score of the team.
+    pub best_score: i64,
+}
+
+#[derive(Clone, Serialize)]
+pub struct MatchResult {
+    pub teams: (String, String),
+    /// The relay scores, in the order of the teams.
+    pub scores: (i64, i64),
+}
+
+#[derive(Serialize)]
+pub struct RelayStandings {
+    /// Unix time at which the next tournament starts, unless one is running.
+    pub next_start: Option<u64>,
+    pub tournaments: u64,
+    /// Most points first.
+    pub standings: Vec<TeamStanding>,
+    /// The matches of the latest tournament.
+    pub latest: Vec<MatchResult>,
+}
+
+/// A relay team, as its members see it.
+#[derive(Serialize)]
+pub struct TeamView {
+    pub name: String,
+    /// To pass on to teammates.
+    pub code: String,
+    pub captain: String,
+    /// In leg order.
+    pub members: Vec<String>,
+    pub full: bool,
+    /// The team's relay in the running tournament, if it plays.
+    pub relay: Option<RelayStatus>,
+}
+
+#[derive(Serialize)]
+pub struct Leg {
+    pub runner: String,
+    pub score: Scoreboard,
+}
+
+#[derive(Serialize)]
+pub struct RelayStatus {
+    pub tournament: u64,
+    pub team: String,
+    /// From 1; `None` once the relay is over.
+    pub leg: Option<usize>,
+    /// Whoever runs the current leg.
+    pub runner: Option<String>,
+    /// The commitment of the round to play.
+    pub digest: Option<String>,
+    pub legs: Vec<Leg>,
+    pub score: i64,
+    /// Unix time at which the rounds left are lost.
+    pub deadline: u64,
+}
+
+/// A round of a leg, revealed.
+#[derive(Serialize)]
+pub struct LegRound {
+    pub human: Hand,
+    pub computer: Hand,
+    pub verdict: Verdict,
+    /// The nonce, to check the commitment with.
+    pub random: String,
+    pub digest: String,
+    pub relay: RelayStatus,
+}
+
+struct Relays {
+    interval_secs: u64,
+    duration_secs: u64,
+    teams: Vec<RelayTeam>,
+    running: Option<RelayTournament>,
+    /// Unix time at which the next tournament starts, once none is running.
+    next_start: u64,
+    held: u64,
+    standings: HashMap<String, TeamStanding>,
+    latest: Vec<MatchResult>,
+}
+
+impl Default for Relays {
+    fn default() -> Relays {
+        Relays {
+            interval_secs: DEFAULT_INTERVAL_SECS,
+            duration_secs: DEFAULT_DURATION_SECS,
+            teams: Vec::new(),
+            running: None,
+            next_start: unix_time() + DEFAULT_INTERVAL_SECS,
+            held: 0,
+            standings: HashMap::new(),
+            latest: Vec::new(),
+        }
+    }
+}
+
+impl Relays {
+    /// Starts the tournament when it is due, and finishes it once every relay is over, as of Unix
+    /// time `now`.
+    fn tick(&mut self, now: u64) {
+        // Held back in maintenance mode, while the server drains; see `maintenance`.
+        if self.running.is_none() && now >= self.next_start && !maintenance::is_on() {
+            self.start(now);
+        }
+        let over = match self.running {
+            Some(ref mut tournament) => {
+                if now >= tournament.deadline {
+                    for relay in &mut tournament.relays {
+                        relay.forfeit();
+                    }
+                }
+                tournament.relays.iter().all(Relay::is_over)
+            }
+            None => false,
+        };
+        if over {
+            let tournament = self.running.take().unwrap();
+            self.finish(tournament, now);
+        }
+    }
+
+    fn start(&mut self, now: u64) {
+        let relays: Vec<_> = self.teams.iter()
+            .filter(|team| team.members.len() == RELAY_TEAM_SIZE)
+            .map(Relay::new)
+            .collect();
+        if relays.len() < 2 {
+            self.next_start = now + self.interval_secs;
+            return;
+        }
+        self.held += 1;
+        self.running = Some(RelayTournament {
+            id: self.held,
+            relays: relays,
+            deadline: now + self.duration_secs,
+        });
+    }
+
+    fn finish(&mut self, tournament: RelayTournament, now: u64) {
+        for relay in &tournament.relays {
+            let standing = self.standings.entry(relay.team.clone())
+                .or_insert_with(|| TeamStanding {
+                    team: relay.team.clone(),
+                    best_score: i64::min_value(),
+                    ..TeamStanding::default()
+                });
+            standing.tournaments += 1;
+            standing.best_score = standing.best_score.max(relay.score());
+        }
+        self.latest.clear();
+        for (i, first) in tournament.relays.iter().enumerate() {
+            for second in &tournament.relays[i + 1..] {
+                let scores = (first.score(), second.score());
+                let (first_result, second_result) = if scores.0 > scores.1 {
+                    ((3, 1, 0, 0), (0, 0, 0, 1))
+                } else if scores.0 < scores.1 {
+                    ((0, 0, 0, 1), (3, 1, 0, 0))
+                } else {
+                    ((1, 0, 1, 0), (1, 0, 1, 0))
+                };
+                for &(team, (points, won, drawn, lost)) in
+                    &[(&first.team, first_result), (&second.team, second_result)]
+                {
+                    let standing = self.standings.get_mut(team).unwrap();
+                    standing.points += points;
+                    standing.won += won;
+                    standing.drawn += drawn;
+                    standing.lost += lost;
+                }
+                self.latest.push(MatchResult {
+                    teams: (first.team.clone(), second.team.clone()),
+                    scores: scores,
+                });
+            }
+        }
+        self.next_start = now + self.interval_secs;
+    }
+
+    fn next_start(&self) -> Option<u64> {
+        if self.running.is_some() { None } else { Some(self.next_start) }
+    }
+
+    fn team_of(&self, user_name: &str) -> Option<usize> {
+        self.teams.iter().position(|team| team.members.iter().any(|member| member == user_name))
+    }
+
+    fn relay_of(&mut self, team: &str) -> Option<(&mut Relay, u64, u64)> {
+        let tournament = self.running.as_mut()?;
+        let (id, deadline) = (tournament.id, tournament.deadline);
+        let relay = tournament.relays.iter_mut().find(|relay| relay.team == team)?;
+        Some((relay, id, deadline))
+    }
+
+    fn waiting(&self) -> usize {
+        self.running.as_ref()
+            .map_or(0, |tournament| {
+                tournament.relays.iter().filter(|relay| relay.is_waiting()).count()
+            })
+    }
+
+    /// Commits to `rounds` for the relays waiting on one, while they last. Returns the events to
+    /// publish.
+    fn deal(&mut self, rounds: Vec<Round>) -> Vec<Event> {
+        let relays = match self.running {
+            Some(ref mut tournament) => {
+                tournament.relays.iter_mut().filter(|relay| relay.is_waiting())
+            }
+            None => return Vec::new(),
+        };
+        relays.zip(rounds).map(|(relay, round)| relay.deal(round)).collect()
+    }
+
+    fn is_playing(&self, team: &str) -> bool {
+        self.running.as_ref()
+            .map_or(false, |tournament| tournament.relays.iter().any(|relay| relay.team == team))
+    }
+
+    fn view(&mut self, index: usize) -> TeamView {
+        let name = self.teams[index].name.clone();
+        let relay = self.relay_of(&name).map(|(relay, id, deadline)| relay.view(id, deadline));
+        let team = &self.teams[index];
+        TeamView {
+            name: team.name.clone(),
+            code: team.code.clone(),
+            captain: team.members[0].clone(),
+            members: team.members.clone(),
+            full: team.members.len() == RELAY_TEAM_SIZE,
+            relay: relay,
+        }
+    }
+
+    /// Takes `user_name` off the roster of their team, which is dropped once empty.
+    fn remove_member(&mut self, index: usize, user_name: &str) {
+        self.teams[index].members.retain(|member| member != user_name);
+        if self.teams[index].members.is_empty() {
+            self.teams.remove(index);
+        }
+    }
+}
+
+/// Reads the schedule from the `relay_interval_secs` and `relay_duration_secs` config extras, and
+/// schedules the ticks.
+pub fn configure(config: &Config) -> Result<(), String> {
+    let secs = |key: &str, default: u64| match config.get_int(key) {
+        Ok(secs) if secs > 0 => Ok(secs as u64),
+        Ok(secs) => Err(format!("`{}` must be positive, got {}", key, secs)),
+        Err(_) => Ok(default),
+    };
+    let interval_secs = secs("relay_interval_secs", DEFAULT_INTERVAL_SECS)?;
+    let duration_secs = secs("relay_duration_secs", DEFAULT_DURATION_SECS)?;
+    {
+        let mut relays = RELAYS.lock().recover();
+        relays.interval_secs = interval_secs;
+        relays.duration_secs = duration_secs;
+        if relays.running.is_none() {
+            relays.next_start = unix_time() + interval_secs;
+        }
+    }
+
+    scheduler::every("relay-tick", Duration::from_secs(1), || tick_at(unix_time()));
+    Ok(())
+}
+
+/// Runs `f` on the relays, ticked as of Unix time `now` before and after, then deals the rounds
+/// they wait on.
+fn update<T, F: FnOnce(&mut Relays) -> T>(now: u64, f: F) -> T {
+    let (result, waiting) = {
+        let mut relays = RELAYS.lock().recover();
+        relays.tick(now);
+        let result = f(&mut relays);
+        relays.tick(now);
+        (result, relays.waiting())
+    };
+    if waiting > 0 {
+        let rounds = (0..waiting).map(|_| feed::take()).collect();
+        let committed = RELAYS.lock().recover().deal(rounds);
+        for event in committed {
+            events::publish(event);
+        }
+    }
+    result
+}
+
+/// Starts and finishes the tournaments due as of Unix time `now`, as the scheduler does every
+/// second.
+pub fn tick_at(now: u64) {
+    update(now, |_| ())
+}
+
+/// Whether a relay tournament is being played.
+pub fn is_running() -> bool {
+    RELAYS.lock().recover().running.is_some()
+}
+
+/// Whether some relay has yet to play the round committed to as `digest`.
+pub fn is_pending(digest: &str) -> bool {
+    let relays = RELAYS.lock().recover();
+    relays.running.as_ref().map_or(false, |tournament| {
+        tournament.relays.iter()
+            .any(|relay| relay.pending.as_ref().map_or(false, |round| round.digest == digest))
+    })
+}
+
+/// Registers the team `name` with `captain` as its only member. Names stay taken by the team that
+/// registered them first, as long as it has standings.
+pub fn register(name: &str, captain: &str) -> Result<TeamView, RelayError> {
+    if !tournament::is_valid_name(name) {
+        return Err(RelayError::InvalidName);
+    }
+    let mut relays = RELAYS.lock().recover();
+    if relays.team_of(captain).is_some() {
+        return Err(RelayError::AlreadyInTeam);
+    }
+    if relays.teams.iter().any(|team| team.name == name) || relays.standings.contains_key(name) {
+        return Err(RelayError::NameTaken);
+    }
+    relays.teams.push(RelayTeam {
+        name: name.to_owned(),
+        code: bytes_to_hex(&gen_random_bytes(4)),
+        members: vec![captain.to_owned()],
+    });
+    let index = relays.teams.len() - 1;
+    Ok(relays.view(index))
+}
+
+/// Adds `user_name` to the team invited to with `code`.
+pub fn join(user_name: &str, code: &str) -> Result<TeamView, RelayError> {
+    let mut relays = RELAYS.lock().recover();
+    if relays.team_of(user_name).is_some() {
+        return Err(RelayError::AlreadyInTeam);
+    }
+    let index = relays.teams.iter().position(|team| team.code == code.trim())
+        .ok_or(RelayError::NoSuchTeam)?;
+    if relays.teams[index].members.len() == RELAY_TEAM_SIZE {
+        return Err(RelayError::TeamFull);
+    }
+    if relays.is_playing(&relays.teams[index].name) {
+        return Err(RelayError::RosterLocked);
+    }
+    relays.teams[index].members.push(user_name.to_owned());
+    Ok(relays.view(index))
+}
+
+/// The team of `user_name`.
+pub fn team(user_name: &str) -> Result<TeamView, RelayError> {
+    update(unix_time(), |relays| {
+        let index = relays.team_of(user_name).ok_or(RelayError::NotInTeam)?;
+        Ok(relays.view(index))
+    })
+}
+
+/// Takes `user_name` off their team. The next member becomes captain if they were.
+pub fn leave(user_name: &str) -> Result<(), RelayError> {
+    let mut relays = RELAYS.lock().recover();
+    let index = relays.team_of(user_name).ok_or(RelayError::NotInTeam)?;
+    if relays.is_playing(&relays.teams[index].name) {
+        return Err(RelayError::RosterLocked);
+    }
+    relays.remove_member(index, user_name);
+    Ok(())
+}
+
+/// Drops `member` from the team captained by `captain`.
+pub fn drop_member(captain: &str, member: &str) -> Result<TeamView, RelayError> {
+    let mut relays = RELAYS.lock().recover();
+    let index = relays.team_of(captain).ok_or(RelayError::NotInTeam)?;
+    if relays.teams[index].members[0] != captain {
+        return Err(RelayError::NotCaptain);
+    }
+    if member == captain || !relays.teams[index].members.iter().any(|name| name == member) {
+        return Err(RelayError::NoSuchMember);
+    }
+    if relays.is_playing(&relays.teams[index].name) {
+        return Err(RelayError::RosterLocked);
+    }
+    relays.remove_member(index, member);
+    Ok(relays.view(index))
+}
+
+/// Takes `user_name` off their team, e.g. when their account is deleted, even mid-relay: their
+/// legs left are lost, and the baton passes on to the next runner if they had it.
+pub fn forget_member(user_name: &str) {
+    update(unix_time(), |relays| {
+        if let Some(index) = relays.team_of(user_name) {
+            let team = relays.teams[index].name.clone();
+            if let Some((relay, _, _)) = relays.relay_of(&team) {
+                relay.drop_runner(user_name);
+            }
+            relays.remove_member(index, user_name);
+        }
+    })
+}
+
+/// Plays `hand` in the current leg of the relay of `user_name`.
+pub fn play(user_name: &str, hand: Hand, analytics_opt_out: bool)
+    -> Result<LegRound, RelayError>
+{
+    let (round, verdict, status) = update(unix_time(), |relays| {
+        let index = relays.team_of(user_name).ok_or(RelayError::NotInTeam)?;
+        let team = relays.teams[index].name.clone();
+        let (relay, id, deadline) = relays.relay_of(&team).ok_or(RelayError::NotPlaying)?;
+        if relay.is_over() {
+            return Err(RelayError::NotPlaying);
+        }
+        if relay.runner() != Some(user_name) {
+            return Err(RelayError::NotYourLeg);
+        }
+        // Not dealt yet, a moment after the start or a handover.
+        let round = relay.pending.take().ok_or(RelayError::NotPlaying)?;
+        let verdict = relay.play(&round, hand);
+        Ok((round, verdict, relay.view(id, deadline)))
+    })?;
+
+    events::publish(Event::RoundPlayed {
+        user_name: user_name.to_owned(),
+        kind: RoundKind::Classic,
+        human: vec![hand],
+        computer: vec![round.computer],
+        verdict: verdict,
+        analytics_opt_out: analytics_opt_out,
+        unpredictable: true,
+        decoy: None,
+        strategy: Some(round.strategy),
+        ranked: false,
+    });
+    Ok(LegRound {
+        human: hand,
+        computer: round.computer,
+        verdict: verdict,
+        random: round.random_bytes,
+        digest: round.digest,
+        relay: status,
+    })
+}
+
+// ===== Routers =====
+
+#[get("/relay/standings.json")]
+pub fn standings() -> Json<RelayStandings> {
+    Json(update(unix_time(), |relays| {
+        let mut standings: Vec<_> = relays.standings.values().cloned().collect();
+        standings.sort_by(|a, b| b.points.cmp(&a.points).then_with(|| a.team.cmp(&b.team)));
+        RelayStandings {
+            next_start: relays.next_start(),
+            tournaments: relays.held,
+            standings: standings,
+            latest: relays.latest.clone(),
+        }
+    }))
+}
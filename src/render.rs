@@ -0,0 +1,39 @@
+//! Template rendering
+//!
+//! Pages only name a template and hand over a serializable context, and every page is rendered
+//! through `render`, so that the engine is set up and called in this module alone. Handlebars,
+//! through the `templates` feature, is the only engine the crate can be built with: there is no
+//! Tera build. Adding one would take a `Renderer` behind a feature forwarding to
+//! `rocket_contrib/tera_templates`, and a Tera port of every template in `templates/`.
+
+use rocket::Rocket;
+use rocket_contrib::Template;
+use serde::Serialize;
+
+pub trait Renderer {
+    /// Renders the template `name`, e.g. `"index"` for `templates/index.html.hbs`, with `context`.
+    fn render<C: Serialize>(name: &'static str, context: &C) -> Template;
+
+    /// Loads the templates and attaches whatever the engine needs to `rocket`.
+    fn attach(rocket: Rocket) -> Rocket;
+}
+
+pub struct Handlebars;
+
+impl Renderer for Handlebars {
+    fn render<C: Serialize>(name: &'static str, context: &C) -> Template {
+        Template::render(name, context)
+    }
+
+    fn attach(rocket: Rocket) -> Rocket {
+        rocket.attach(Template::fairing())
+    }
+}
+
+/// The engine the crate is built with.
+pub type Engine = Handlebars;
+
+/// Renders a template with the `Engine`. Every page is rendered through here.
+pub fn render<C: Serialize>(name: &'static str, context: &C) -> Template {
+    Engine::render(name, context)
+}
@@ -0,0 +1,113 @@
+//! Server-side revocation of session ids
+//!
+//! Logging out deletes the session cookies, but a copy of them (e.g. a stolen one) would otherwise
+//! still be accepted: the `User` guard rebuilds lost sessions from their snapshot cookie. Revoked
+//! ids are therefore remembered and refused by the guard, whatever happens to the cookies.
+//!
+//! Since sessions are rebuilt after a restart too, revocations must outlive the process: if the
+//! `revocation_file` config extra is set, they are saved to that JSON file on every change. The
+//! file is a `store::Format`, with no migrations yet. An id is remembered for `REVOCATION_TTL`,
+//! until no login cookie of its session can be accepted anymore, see `credentials`.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use rocket::Config;
+
+use credentials::{CLOCK_SKEW_SECS, MAX_AGE_SECS};
+use ids::UserId;
+use poison::Recover;
+use store::{self, Format, Options};
+use util::unix_time;
+
+const FORMAT: Format = Format {
+    name: "revocations",
+    migrations: &[],
+};
+
+/// How long a revoked id is remembered, in seconds: the login cookie of its session, issued at
+/// most `CLOCK_SKEW_SECS` after the revocation by the clock of another server, is refused by then.
+pub const REVOCATION_TTL: u64 = MAX_AGE_SECS + CLOCK_SKEW_SECS;
+
+/// A line of the `revocation_file`.
+#[derive(Serialize, Deserialize)]
+struct Revoked {
+    user_id: UserId,
+    /// Unix time.
+    at: u64,
+}
+
+pub struct Revocations {
+    file: Option<PathBuf>,
+    /// Id -> Unix time of the revocation.
+    revoked: Mutex<HashMap<UserId, u64>>,
+}
+
+impl Revocations {
+    /// Revocations kept in memory only.
+    pub fn new() -> Revocations {
+        Revocations {
+            file: None,
+            revoked: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Loads the revocations saved to `revocation_file`, if it is set, and keeps saving them there.
+    pub fn from_config(config: &Config) -> Result<Revocations, String> {
+        let mut file = config.get_str("revocation_file").ok().map(PathBuf::from);
+        let loaded: Option<store::Loaded<Vec<Revoked>>> = match file {
+            Some(ref path) => store::load(path, &FORMAT, Options::from_config(config))?,
+            None => None,
+        };
+        let now = unix_time();
+        let revoked = match loaded {
+            Some(loaded) => {
+                if loaded.read_only {
+                    file = None;
+                }
+                loaded.data.into_iter()
+                    .filter(|revoked| is_remembered(revoked.at, now))
+                    .map(|revoked| (revoked.user_id, revoked.at))
+                    .collect()
+            }
+            None => HashMap::new(),
+        };
+        Ok(Revocations {
+            file: file,
+            revoked: Mutex::new(revoked),
+        })
+    }
+
+    pub fn revoke(&self, user_id: &UserId) {
+        let now = unix_time();
+        let mut revoked = self.revoked.lock().recover();
+        revoked.retain(|_, &mut at| is_remembered(at, now));
+        revoked.insert(user_id.clone(), now);
+        self.save(&revoked);
+    }
+
+    pub fn is_revoked(&self, user_id: &UserId) -> bool {
+        self.revoked.lock().recover().get(user_id)
+            .map_or(false, |&at| is_remembered(at, unix_time()))
+    }
+
+    /// Saves `revoked` to the `revocation_file`, if it is set. A failure is only logged: the
+    /// revocation holds until the server stops.
+    fn save(&self, revoked: &HashMap<UserId, u64>) {
+        let path = match self.file {
+            Some(ref path) => path,
+            None => return,
+        };
+        let lines: Vec<_> = revoked.iter()
+            .map(|(user_id, &at)| Revoked { user_id: user_id.clone(), at: at })
+            .collect();
+        if let Err(e) = store::save(path, &FORMAT, &lines) {
+            eprintln!("Unable to save revocations: {}", e);
+        }
+    }
+}
+
+fn is_remembered(revoked_at: u64, now: u64) -> bool {
+    now < revoked_at.saturating_add(REVOCATION_TTL)
+}
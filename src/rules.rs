@@ -0,0 +1,132 @@
+//! Optional house rules a player can turn on for their classic rounds
+//!
+//! A rule judges every hand the human plays and may charge penalty points for it, which are
+//! counted next to the scoreboard. Rules can keep state from hand to hand; when turned on, a rule
+//! catches up on the hands already in the session's history, so that turning it on mid-game judges
+//! the next hand like any other.
+
+use game::Hand;
+
+/// Same hands in a row from which every further one costs a point under `RepeatedHand`.
+const REPEAT_LIMIT: usize = 3;
+
+/// The house rules there are.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum RuleKind {
+    /// A penalty point for every hand that makes it three of the same in a row.
+    RepeatedHand,
+}
+
+impl RuleKind {
+    pub const ALL: [RuleKind; 1] = [RuleKind::RepeatedHand];
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            RuleKind::RepeatedHand => "repeated-hand",
+        }
+    }
+
+    /// How many of the latest hands the rule needs to catch up on, see `HouseRules::turn_on`.
+    pub fn memory(&self) -> usize {
+        match *self {
+            RuleKind::RepeatedHand => REPEAT_LIMIT - 1,
+        }
+    }
+
+    fn build(&self) -> Box<Rule> {
+        match *self {
+            RuleKind::RepeatedHand => Box::new(RepeatedHand::default()),
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<RuleKind> {
+        RuleKind::ALL.iter().find(|kind| kind.name() == name).cloned()
+    }
+}
+
+/// A house rule, fed the human's hands in the order they are played.
+trait Rule: Send + Sync {
+    /// Penalty points for playing `hand`.
+    fn judge(&mut self, hand: Hand) -> usize;
+
+    /// A copy of the rule, in the state it is in.
+    fn boxed_clone(&self) -> Box<Rule>;
+}
+
+#[derive(Clone, Default)]
+struct RepeatedHand {
+    last: Option<Hand>,
+    run: usize,
+}
+
+impl Rule for RepeatedHand {
+    fn judge(&mut self, hand: Hand) -> usize {
+        if self.last == Some(hand) {
+            self.run += 1;
+        } else {
+            self.last = Some(hand);
+            self.run = 1;
+        }
+        if self.run >= REPEAT_LIMIT { 1 } else { 0 }
+    }
+
+    fn boxed_clone(&self) -> Box<Rule> {
+        Box::new(self.clone())
+    }
+}
+
+/// The house rules a session plays by, and the penalty points they charged.
+#[derive(Default)]
+pub struct HouseRules {
+    rules: Vec<(RuleKind, Box<Rule>)>,
+    pub penalties: usize,
+    /// The points charged for the latest hand, see `void_last`.
+    last_charge: usize,
+}
+
+impl Clone for HouseRules {
+    fn clone(&self) -> HouseRules {
+        HouseRules {
+            rules: self.rules.iter().map(|&(kind, ref rule)| (kind, rule.boxed_clone())).collect(),
+            penalties: self.penalties,
+            last_charge: self.last_charge,
+        }
+    }
+}
+
+impl HouseRules {
+    pub fn is_on(&self, kind: RuleKind) -> bool {
+        self.rules.iter().any(|&(on, _)| on == kind)
+    }
+
+    /// Turns `kind` on, after the human played `recent` (oldest first), which is not charged for.
+    pub fn turn_on(&mut self, kind: RuleKind, recent: &[Hand]) {
+        if self.is_on(kind) {
+            return;
+        }
+        let mut rule = kind.build();
+        for &hand in recent {
+            rule.judge(hand);
+        }
+        self.rules.push((kind, rule));
+    }
+
+    pub fn turn_off(&mut self, kind: RuleKind) {
+        self.rules.retain(|&(on, _)| on != kind);
+    }
+
+    /// Judges a hand by every rule that is on. Returns the penalty points it cost.
+    pub fn judge(&mut self, hand: Hand) -> usize {
+        let points = self.rules.iter_mut().map(|&mut (_, ref mut rule)| rule.judge(hand)).sum();
+        self.penalties += points;
+        self.last_charge = points;
+        points
+    }
+
+    /// Takes back the points charged for the latest hand, whose round was voided. The rules still
+    /// count the hand as played.
+    pub fn void_last(&mut self) {
+        self.penalties -= self.last_charge;
+        self.last_charge = 0;
+    }
+}
@@ -0,0 +1,163 @@
+//! In-process scheduler of periodic jobs
+//!
+//! Work that happens on a clock rather than on a request, e.g. ticking tournaments or sweeping
+//! stale login failures, is registered as a job with `every`: a name, an interval and a function.
+//! A single scheduler thread runs the jobs as they fall due, one at a time. Every interval is
+//! stretched or shrunk by up to `JITTER` of itself, drawn anew for every run, so that jobs of the
+//! same interval, and servers started together, drift apart instead of running in lockstep.
+//!
+//! A job that panics is counted and runs again at its next time. Runs, panics and how long the
+//! latest run took are reported per job on `/admin/metrics`.
+//!
+//! Once maintenance mode reports the server ready to shut down, see `maintenance`, the scheduler
+//! is paused: no job is running and none starts, so stopping the process cuts nothing short. It
+//! resumes when maintenance mode is turned off.
+
+use std::panic;
+use std::sync::{Condvar, Mutex, Once, ONCE_INIT};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rand::Rng;
+
+use entropy;
+use poison::Recover;
+
+/// Largest share of its interval by which a run of a job is moved.
+const JITTER: f64 = 0.1;
+
+lazy_static! {
+    static ref SCHEDULER: Mutex<Scheduler> = Mutex::new(Scheduler::default());
+    /// Signalled when a job is added or the scheduler resumes.
+    static ref WAKE: Condvar = Condvar::new();
+}
+
+#[derive(Default)]
+struct Scheduler {
+    jobs: Vec<Job>,
+    paused: bool,
+    /// Whether a job is running right now, outside the lock.
+    running: bool,
+}
+
+struct Job {
+    name: &'static str,
+    interval: Duration,
+    task: fn(),
+    next_run: Instant,
+    runs: u64,
+    panics: u64,
+    last_duration: Option<Duration>,
+}
+
+/// How a job has been doing, for the metrics.
+pub struct JobStats {
+    pub name: &'static str,
+    pub runs: u64,
+    pub panics: u64,
+    pub last_duration: Option<Duration>,
+}
+
+/// `interval`, moved by up to `JITTER` of itself either way.
+fn jittered(interval: Duration) -> Duration {
+    let secs = interval.as_secs() as f64 + f64::from(interval.subsec_nanos()) / 1e9;
+    let factor = 1.0 + entropy::get().gen_range(-JITTER, JITTER);
+    Duration::from_millis((secs * factor * 1000.0) as u64)
+}
+
+/// Runs `task` every `interval`, give or take the jitter, from one interval from now on. A job of
+/// the same name, e.g. from an earlier configuration, is rescheduled with the new interval.
+pub fn every(name: &'static str, interval: Duration, task: fn()) {
+    static START: Once = ONCE_INIT;
+    START.call_once(|| {
+        thread::spawn(run);
+    });
+    let next_run = Instant::now() + jittered(interval);
+    let mut scheduler = SCHEDULER.lock().recover();
+    match scheduler.jobs.iter_mut().find(|job| job.name == name) {
+        Some(job) => {
+            job.interval = interval;
+            job.task = task;
+            job.next_run = next_run;
+        }
+        None => scheduler.jobs.push(Job {
+            name: name,
+            interval: interval,
+            task: task,
+            next_run: next_run,
+            runs: 0,
+            panics: 0,
+            last_duration: None,
+        }),
+    }
+    WAKE.notify_all();
+}
+
+/// The scheduler thread.
+fn run() {
+    let mut scheduler = SCHEDULER.lock().recover();
+    loop {
+        let due = scheduler.jobs.iter().enumerate()
+            .min_by_key(|&(_, job)| job.next_run)
+            .map(|(index, job)| (index, job.next_run));
+        let now = Instant::now();
+        let index = match due {
+            _ if scheduler.paused => {
+                scheduler = WAKE.wait(scheduler).recover();
+                continue;
+            }
+            Some((index, next_run)) if next_run <= now => index,
+            Some((_, next_run)) => {
+                scheduler = WAKE.wait_timeout(scheduler, next_run - now).recover().0;
+                continue;
+            }
+            None => {
+                scheduler = WAKE.wait(scheduler).recover();
+                continue;
+            }
+        };
+        let task = scheduler.jobs[index].task;
+        scheduler.running = true;
+        drop(scheduler);
+
+        let started = Instant::now();
+        let panicked = panic::catch_unwind(task).is_err();
+        let duration = started.elapsed();
+
+        scheduler = SCHEDULER.lock().recover();
+        scheduler.running = false;
+        let job = &mut scheduler.jobs[index];
+        job.runs += 1;
+        if panicked {
+            job.panics += 1;
+            eprintln!("Job {} panicked", job.name);
+        }
+        job.last_duration = Some(duration);
+        job.next_run = Instant::now() + jittered(job.interval);
+    }
+}
+
+/// Holds jobs back from starting. Returns whether none is running either, which, once it is true,
+/// stays true until `resume`.
+pub fn pause() -> bool {
+    let mut scheduler = SCHEDULER.lock().recover();
+    scheduler.paused = true;
+    !scheduler.running
+}
+
+pub fn resume() {
+    SCHEDULER.lock().recover().paused = false;
+    WAKE.notify_all();
+}
+
+/// Every job, in the order they were registered.
+pub fn stats() -> Vec<JobStats> {
+    SCHEDULER.lock().recover().jobs.iter()
+        .map(|job| JobStats {
+            name: job.name,
+            runs: job.runs,
+            panics: job.panics,
+            last_duration: job.last_duration,
+        })
+        .collect()
+}
@@ -0,0 +1,173 @@
+//! House bots and opponents scripted in Rhai
+//!
+//! Only built with the `scripting` feature. Every `*.rhai` file in the `scripts_dir` config extra
+//! (`scripts/` by default) is loaded at launch as a strategy of the computer, named
+//! `house-<file stem>`: it enters the tournaments as a house bot next to the built-in ones, and
+//! players can pick it as their opponent, see `GET /api/v1/opponent/scripts`. A script sees the
+//! past rounds against its current opponent as two arrays of hand names, oldest first, `mine` and
+//! `theirs` (an empty string for a forfeited hand), and evaluates to the name of the hand to play:
+//!
+//! ```text
+//! if theirs.len() == 0 { "rock" } else { theirs[theirs.len() - 1] }
+//! ```
+//!
+//! Only admins may add scripts, and every run is cut short after `MAX_OPERATIONS` operations of
+//! the engine. One that fails or names no hand forfeits the round in a tournament, and leaves the
+//! round to the uniform strategy against a player; failures are logged, up to
+//! `FAILURES_LOGGED_PER_HOUR` per script.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::RwLock;
+
+use rhai::Engine;
+use rocket::Config;
+
+use game::Hand;
+use metrics;
+use poison::Recover;
+use strategy::Opponent;
+use throttle::Throttle;
+use tournament::{Bot, Throw, HOUSE_PREFIX, MAX_NAME_LEN};
+
+const DEFAULT_SCRIPTS_DIR: &str = "scripts/";
+const EXTENSION: &str = "rhai";
+/// Operations of the engine a run may take, far more than deciding on a hand needs.
+const MAX_OPERATIONS: u64 = 100_000;
+const FAILURES_LOGGED_PER_HOUR: u32 = 10;
+
+lazy_static! {
+    static ref SCRIPTS: RwLock<Vec<Script>> = RwLock::new(Vec::new());
+    static ref FAILURE_LOG: Throttle = Throttle::new(FAILURES_LOGGED_PER_HOUR, 3600);
+}
+
+#[derive(Clone)]
+struct Script {
+    /// The bot name, with `HOUSE_PREFIX`.
+    name: String,
+    source: String,
+}
+
+impl Script {
+    /// Loads the script at `path`, checking that it names a hand before any round was played.
+    fn load(path: &Path) -> Result<Script, String> {
+        let stem = path.file_stem().and_then(|stem| stem.to_str()).unwrap_or("");
+        let valid = !stem.is_empty()
+            && HOUSE_PREFIX.len() + stem.len() <= MAX_NAME_LEN
+            && stem.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+            && Opponent::ALL.iter().all(|opponent| opponent.name() != stem);
+        if !valid {
+            return Err(format!("{}: not a valid bot name", path.display()));
+        }
+        let source = fs::read_to_string(path)
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        let script = Script {
+            name: format!("{}{}", HOUSE_PREFIX, stem),
+            source: source,
+        };
+        script.run(&[]).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Ok(script)
+    }
+
+    /// Evaluates the script after the rounds `past`, own throw first.
+    fn run(&self, past: &[(Throw, Throw)]) -> Result<Hand, String> {
+        let array = |throws: Vec<Throw>| -> String {
+            let names: Vec<_> = throws.iter()
+                .map(|throw| format!("\"{}\"", throw.as_ref().map_or("", |hand| hand.as_ref())))
+                .collect();
+            format!("[{}]", names.join(", "))
+        };
+        let program = format!("let mine = {};\nlet theirs = {};\n{}",
+                              array(past.iter().map(|&(mine, _)| mine).collect()),
+                              array(past.iter().map(|&(_, theirs)| theirs).collect()),
+                              self.source);
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+        let name = engine.eval::<String>(&program).map_err(|e| format!("{:?}", e))?;
+        name.parse().map_err(|_| format!("{:?} is not a hand", name))
+    }
+}
+
+/// A script in a tournament. It decides outside `TOURNAMENTS`, see `tournament`, and is handed
+/// its picks like a registered bot.
+struct ScriptBot {
+    name: String,
+    /// By opponent, for the current round.
+    picks: HashMap<String, Hand>,
+}
+
+impl Bot for ScriptBot {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn hand(&self, opponent: &str, _: &[(Throw, Throw)]) -> Option<Hand> {
+        self.picks.get(opponent).cloned()
+    }
+
+    fn submit(&mut self, opponent: &str, hand: Hand) -> bool {
+        self.picks.insert(opponent.to_owned(), hand);
+        true
+    }
+
+    fn end_round(&mut self) {
+        self.picks.clear();
+    }
+
+    fn is_scripted(&self) -> bool {
+        true
+    }
+}
+
+/// Loads the scripts of the `scripts_dir` config extra, replacing any loaded before. A missing
+/// directory holds no scripts.
+pub fn configure(config: &Config) -> Result<(), String> {
+    let dir = Path::new(config.get_str("scripts_dir").unwrap_or(DEFAULT_SCRIPTS_DIR));
+    let mut scripts = Vec::new();
+    if dir.is_dir() {
+        let mut paths: Vec<_> = fs::read_dir(dir)
+            .map_err(|e| format!("{}: {}", dir.display(), e))?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().map_or(false, |ext| ext == EXTENSION))
+            .collect();
+        paths.sort();
+        for path in paths {
+            scripts.push(Script::load(&path)?);
+        }
+    }
+    *SCRIPTS.write().recover() = scripts;
+    Ok(())
+}
+
+/// A fresh house bot for every loaded script.
+pub fn bots() -> Vec<Box<Bot>> {
+    SCRIPTS.read().recover().iter()
+        .map(|script| {
+            Box::new(ScriptBot {
+                name: script.name.clone(),
+                picks: HashMap::new(),
+            }) as Box<Bot>
+        })
+        .collect()
+}
+
+/// The names of the loaded scripts, with `HOUSE_PREFIX`.
+pub fn names() -> Vec<String> {
+    SCRIPTS.read().recover().iter().map(|script| script.name.clone()).collect()
+}
+
+/// The hand the script `name` plays after the rounds `past`, own throw first. `None` if there is
+/// no such script or it failed.
+pub fn pick(name: &str, past: &[(Throw, Throw)]) -> Option<Hand> {
+    let script = SCRIPTS.read().recover().iter().find(|script| script.name == name).cloned()?;
+    match metrics::time_decision(name, || script.run(past)) {
+        Ok(hand) => Some(hand),
+        Err(e) => {
+            if FAILURE_LOG.admit(name) {
+                eprintln!("Script {} failed: {}", name, e);
+            }
+            None
+        }
+    }
+}
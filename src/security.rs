@@ -0,0 +1,72 @@
+//! Security headers of HTML responses
+//!
+//! Every header value comes from a config extra and an empty value leaves the header out. Widgets
+//! are meant to be framed by other sites, so they get their own policy and no `X-Frame-Options`.
+
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::ContentType;
+use rocket::{Config, Request, Response, State};
+
+const WIDGET_PREFIX: &str = "/widget/";
+
+pub struct SecurityPolicy {
+    content_security_policy: String,
+    widget_content_security_policy: String,
+    x_frame_options: String,
+    referrer_policy: String,
+}
+
+fn extra(config: &Config, key: &str, default: &str) -> String {
+    config.get_str(key).unwrap_or(default).to_owned()
+}
+
+impl SecurityPolicy {
+    pub fn from_config(config: &Config) -> SecurityPolicy {
+        SecurityPolicy {
+            content_security_policy: extra(config, "content_security_policy",
+                                           "default-src 'self'; frame-ancestors 'none'"),
+            widget_content_security_policy: extra(config, "widget_content_security_policy",
+                                                  "default-src 'none'; script-src 'self'; \
+                                                   connect-src 'self'; style-src 'unsafe-inline'; \
+                                                   frame-ancestors *"),
+            x_frame_options: extra(config, "x_frame_options", "DENY"),
+            referrer_policy: extra(config, "referrer_policy", "same-origin"),
+        }
+    }
+}
+
+fn set(response: &mut Response, name: &'static str, value: &str) {
+    if !value.is_empty() {
+        response.set_raw_header(name, value.to_owned());
+    }
+}
+
+pub struct SecurityHeaders;
+
+impl Fairing for SecurityHeaders {
+    fn info(&self) -> Info {
+        Info {
+            name: "Security headers",
+            kind: Kind::Response,
+        }
+    }
+
+    fn on_response(&self, request: &Request, response: &mut Response) {
+        if response.content_type() != Some(ContentType::HTML) {
+            return;
+        }
+        let policy = match request.guard::<State<SecurityPolicy>>().succeeded() {
+            Some(policy) => policy,
+            None => return,
+        };
+
+        if request.uri().path().starts_with(WIDGET_PREFIX) {
+            set(response, "Content-Security-Policy", &policy.widget_content_security_policy);
+        } else {
+            set(response, "Content-Security-Policy", &policy.content_security_policy);
+            set(response, "X-Frame-Options", &policy.x_frame_options);
+        }
+        set(response, "Referrer-Policy", &policy.referrer_policy);
+        response.set_raw_header("X-Content-Type-Options", "nosniff");
+    }
+}
@@ -0,0 +1,198 @@
+//! A map split into independently locked shards
+//!
+//! Every request locks the session of its user, and with a single lock over all sessions, players
+//! wait for each other even though they never touch the same session. A `ShardedMap` spreads its
+//! keys over a fixed number of shards, by hash, each behind its own lock, so that only players
+//! whose sessions share a shard contend. What needs every entry at once, e.g. counting sessions or
+//! ranking them, locks all shards, always in the same order.
+//!
+//! A shard poisoned by a panic stays usable, see `poison`: the entry of the key whose shard was
+//! write-locked is dropped as the thread panics, since it may have been left half-changed.
+
+use std::borrow::Borrow;
+use std::collections::hash_map::{self, DefaultHasher};
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::hash::{Hash, Hasher};
+use std::ops::{Deref, DerefMut};
+use std::slice;
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::thread;
+
+use poison::{self, Recover};
+
+/// How many shards there are unless configured otherwise.
+pub const DEFAULT_SHARDS: usize = 16;
+
+pub type Shard<V, K = String> = HashMap<K, V>;
+
+/// Keys are strings unless given, e.g. `ShardedMap<Session, UserId>`.
+pub struct ShardedMap<V, K = String> {
+    shards: Vec<RwLock<Shard<V, K>>>,
+}
+
+impl<V, K: Hash + Eq + Debug> ShardedMap<V, K> {
+    /// An empty map of `shard_count` shards, at least one.
+    pub fn new(shard_count: usize) -> ShardedMap<V, K> {
+        ShardedMap {
+            shards: (0..shard_count.max(1)).map(|_| RwLock::new(HashMap::new())).collect(),
+        }
+    }
+
+    pub fn shard_count(&self) -> usize {
+        self.shards.len()
+    }
+
+    fn index<Q: ?Sized + Hash>(&self, key: &Q) -> usize {
+        // Unkeyed, so that a key stays in the same shard for the life of the map.
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        (hasher.finish() % self.shards.len() as u64) as usize
+    }
+
+    /// Read-locks the shard of `key`.
+    pub fn read<Q: ?Sized + Hash>(&self, key: &Q) -> RwLockReadGuard<Shard<V, K>>
+        where K: Borrow<Q>
+    {
+        self.shards[self.index(key)].read().recover()
+    }
+
+    /// Write-locks the shard of `key`.
+    pub fn write<Q: ?Sized + Hash + ToOwned<Owned = K>>(&self, key: &Q) -> WriteGuard<V, K>
+        where K: Borrow<Q>
+    {
+        WriteGuard {
+            guard: self.shards[self.index(key)].write().recover(),
+            key: key.to_owned(),
+        }
+    }
+
+    /// Read-locks every shard. Blocks while any of them is write-locked, so never call it with a
+    /// shard locked.
+    pub fn read_all(&self) -> All<RwLockReadGuard<Shard<V, K>>, V, K> {
+        All {
+            map: self,
+            guards: self.shards.iter().map(|shard| shard.read().recover()).collect(),
+        }
+    }
+
+    /// Write-locks every shard. Never call it with a shard locked.
+    pub fn write_all(&self) -> All<RwLockWriteGuard<Shard<V, K>>, V, K> {
+        All {
+            map: self,
+            guards: self.shards.iter().map(|shard| shard.write().recover()).collect(),
+        }
+    }
+}
+
+/// The shard of a key, write-locked. Should the thread panic while holding it, the key's entry is
+/// dropped along with the lock.
+pub struct WriteGuard<'a, V: 'a, K: 'a + Hash + Eq + Debug = String> {
+    guard: RwLockWriteGuard<'a, Shard<V, K>>,
+    key: K,
+}
+
+impl<'a, V, K: Hash + Eq + Debug> Deref for WriteGuard<'a, V, K> {
+    type Target = Shard<V, K>;
+
+    fn deref(&self) -> &Shard<V, K> {
+        &self.guard
+    }
+}
+
+impl<'a, V, K: Hash + Eq + Debug> DerefMut for WriteGuard<'a, V, K> {
+    fn deref_mut(&mut self) -> &mut Shard<V, K> {
+        &mut self.guard
+    }
+}
+
+impl<'a, V, K: Hash + Eq + Debug> Drop for WriteGuard<'a, V, K> {
+    fn drop(&mut self) {
+        if thread::panicking() && self.guard.remove(&self.key).is_some() {
+            poison::entry_dropped(&self.key);
+        }
+    }
+}
+
+/// Every shard of a `ShardedMap`, locked, to be used like a single map.
+pub struct All<'a, G, V: 'a, K: 'a = String> {
+    map: &'a ShardedMap<V, K>,
+    guards: Vec<G>,
+}
+
+impl<'a, G: Deref<Target = Shard<V, K>>, V, K: Hash + Eq + Debug> All<'a, G, V, K> {
+    pub fn len(&self) -> usize {
+        self.guards.iter().map(|shard| shard.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.guards.iter().all(|shard| shard.is_empty())
+    }
+
+    pub fn get<Q: ?Sized + Hash + Eq>(&self, key: &Q) -> Option<&V> where K: Borrow<Q> {
+        self.guards[self.map.index(key)].get(key)
+    }
+
+    pub fn contains_key<Q: ?Sized + Hash + Eq>(&self, key: &Q) -> bool where K: Borrow<Q> {
+        self.guards[self.map.index(key)].contains_key(key)
+    }
+
+    pub fn iter(&self) -> Iter<G, V, K> {
+        Iter {
+            shards: self.guards.iter(),
+            entries: None,
+        }
+    }
+
+    pub fn values(&self) -> Values<G, V, K> {
+        Values(self.iter())
+    }
+}
+
+impl<'a, G: DerefMut<Target = Shard<V, K>>, V, K: Hash + Eq + Debug> All<'a, G, V, K> {
+    pub fn get_mut<Q: ?Sized + Hash + Eq>(&mut self, key: &Q) -> Option<&mut V>
+        where K: Borrow<Q>
+    {
+        let index = self.map.index(key);
+        self.guards[index].get_mut(key)
+    }
+
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let index = self.map.index(&key);
+        self.guards[index].insert(key, value)
+    }
+
+    pub fn remove<Q: ?Sized + Hash + Eq>(&mut self, key: &Q) -> Option<V> where K: Borrow<Q> {
+        let index = self.map.index(key);
+        self.guards[index].remove(key)
+    }
+}
+
+/// The entries of every shard, shard by shard.
+pub struct Iter<'b, G: 'b, V: 'b, K: 'b = String> {
+    shards: slice::Iter<'b, G>,
+    entries: Option<hash_map::Iter<'b, K, V>>,
+}
+
+impl<'b, G: Deref<Target = Shard<V, K>>, V, K> Iterator for Iter<'b, G, V, K> {
+    type Item = (&'b K, &'b V);
+
+    fn next(&mut self) -> Option<(&'b K, &'b V)> {
+        loop {
+            if let Some(entry) = self.entries.as_mut().and_then(|entries| entries.next()) {
+                return Some(entry);
+            }
+            self.entries = Some(self.shards.next()?.iter());
+        }
+    }
+}
+
+pub struct Values<'b, G: 'b, V: 'b, K: 'b = String>(Iter<'b, G, V, K>);
+
+impl<'b, G: Deref<Target = Shard<V, K>>, V, K> Iterator for Values<'b, G, V, K> {
+    type Item = &'b V;
+
+    fn next(&mut self) -> Option<&'b V> {
+        self.0.next().map(|(_, value)| value)
+    }
+}
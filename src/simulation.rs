@@ -0,0 +1,138 @@
+//! Simulated matches of the computer against a scripted player
+//!
+//! `GET /api/v1/simulate` plays one of the computer's strategies against a player policy for up
+//! to `MAX_ROUNDS` classic rounds, server-side, and reports how they went from the player's side:
+//! e.g. to show in the docs that the mirroring opponents lose to a player who keeps their hand,
+//! or to check how a strategy fares before offering it. Nothing is recorded anywhere, not even
+//! the time the computer's decisions take, see `metrics`.
+//!
+//! Besides the opponents players choose from, the computer can play `markov` here: the hand
+//! beating the one its `PlayerModel` of the player finds most likely next.
+
+use rand::Rng;
+
+use entropy;
+use game::{Hand, Scoreboard, Verdict};
+use strategy::{Opponent, PlayerModel};
+
+/// Rounds simulated unless asked otherwise.
+pub const DEFAULT_ROUNDS: usize = 1000;
+/// The most rounds a simulation plays.
+pub const MAX_ROUNDS: usize = 10_000;
+
+/// The computer's side of a simulation.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Computer {
+    Opponent(Opponent),
+    Markov,
+}
+
+impl Computer {
+    pub fn from_name(name: &str) -> Option<Computer> {
+        match name {
+            "markov" => Some(Computer::Markov),
+            name => name.parse().ok().map(Computer::Opponent),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Computer::Opponent(opponent) => opponent.name(),
+            Computer::Markov => "markov",
+        }
+    }
+
+    fn pick(&self, model: &PlayerModel) -> Hand {
+        match *self {
+            Computer::Opponent(opponent) => opponent.pick(model.last()),
+            Computer::Markov => model.likely_untimed().counter(),
+        }
+    }
+}
+
+/// The player's side of a simulation.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Policy {
+    Uniform,
+    /// Plays a random hand, then that hand over and over.
+    LastMoveRepeat,
+    /// Draws every hand with a probability in proportion to its weight, indexed like
+    /// `Hand::CHOICES`. The weights are summed as `u64`, so that no total overflows.
+    Custom([u32; 3]),
+}
+
+impl Policy {
+    /// The policy called `name`; `custom` takes `weights`, which must not all be 0.
+    pub fn from_name(name: &str, weights: [u32; 3]) -> Option<Policy> {
+        match name {
+            "uniform" => Some(Policy::Uniform),
+            "last-move-repeat" => Some(Policy::LastMoveRepeat),
+            "custom" if weights.iter().any(|&weight| weight > 0) => Some(Policy::Custom(weights)),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Policy::Uniform => "uniform",
+            Policy::LastMoveRepeat => "last-move-repeat",
+            Policy::Custom(_) => "custom",
+        }
+    }
+
+    fn pick<R: Rng>(&self, last: Option<Hand>, rng: &mut R) -> Hand {
+        match (*self, last) {
+            (Policy::LastMoveRepeat, Some(last)) => last,
+            (Policy::Custom(weights), _) => {
+                let total = weights.iter().map(|&weight| u64::from(weight)).sum::<u64>();
+                let mut ticket = rng.gen_range(0, total);
+                for (&hand, &weight) in Hand::CHOICES.iter().zip(weights.iter()) {
+                    if ticket < u64::from(weight) {
+                        return hand;
+                    }
+                    ticket -= u64::from(weight);
+                }
+                unreachable!()
+            }
+            _ => *rng.choose(&Hand::CHOICES).unwrap(),
+        }
+    }
+}
+
+/// How a simulation went, from the player's side.
+#[derive(Serialize)]
+pub struct Simulation {
+    pub strategy: &'static str,
+    pub policy: &'static str,
+    pub rounds: usize,
+    #[serde(flatten)]
+    pub score: Scoreboard,
+    /// Shares of the rounds the player won, tied and lost.
+    pub win_rate: f64,
+    pub tie_rate: f64,
+    pub loss_rate: f64,
+}
+
+/// Plays `rounds` classic rounds of `computer` against `policy`, at most `MAX_ROUNDS`.
+pub fn run(computer: Computer, policy: Policy, rounds: usize) -> Simulation {
+    let rounds = rounds.min(MAX_ROUNDS);
+    let mut rng = entropy::get();
+    let mut model = PlayerModel::new();
+    let mut score = Scoreboard::default();
+    for _ in 0..rounds {
+        let computer_hand = computer.pick(&model);
+        let human = policy.pick(model.last(), &mut rng);
+        score.record(Verdict::of(human, computer_hand));
+        model.observe(human);
+    }
+    let share = |n: usize| if rounds == 0 { 0.0 } else { n as f64 / rounds as f64 };
+    Simulation {
+        strategy: computer.name(),
+        policy: policy.name(),
+        rounds: rounds,
+        win_rate: share(score.wins),
+        tie_rate: share(score.ties),
+        loss_rate: share(score.losses),
+        score: score,
+    }
+}
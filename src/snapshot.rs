@@ -0,0 +1,112 @@
+//! Session snapshots kept client-side
+//!
+//! The essential part of a session (name, scoreboards, opponent, pending round and whether it
+//! is verified) is mirrored into a private, i.e. encrypted and authenticated, cookie. If the
+//! server loses its sessions, e.g. on a restart, the `User` guard rebuilds the session from it
+//! instead of sending the player back to the login page. This only survives restarts if Rocket's
+//! `secret_key` is configured, otherwise a new key is generated on every launch. The house script
+//! picked as the opponent is not kept: the rebuilt session plays the opponent it had before.
+
+use rocket::http::Cookies;
+use serde_json;
+
+use commitment;
+use cookie_policy;
+use game::{Hand, Round, Scoreboard, Strategy};
+use strategy::{Opponent, Rotation};
+use Session;
+
+const COOKIE_NAME: &str = "session";
+
+/// Field names are kept short, since the snapshot travels with every request.
+#[derive(Serialize, Deserialize)]
+pub struct Snapshot {
+    #[serde(rename = "n")]
+    pub user_name: String,
+    /// Wins, ties and losses of classic rounds.
+    #[serde(rename = "s")]
+    score: [usize; 3],
+    #[serde(rename = "c")]
+    chaos_score: [usize; 3],
+    /// The computer's hand and the hex nonce of the pending classic round.
+    #[serde(rename = "r")]
+    pending: Option<(Hand, String)>,
+    /// See `Session::verified`.
+    #[serde(rename = "v", default)]
+    verified: bool,
+    #[serde(rename = "p", default)]
+    opponent: Opponent,
+    /// What picked the computer's hand of the pending round.
+    #[serde(rename = "g", default)]
+    strategy: Strategy,
+    #[serde(rename = "w", default, skip_serializing_if = "Option::is_none")]
+    rotation: Option<Rotation>,
+}
+
+fn counts(score: &Scoreboard) -> [usize; 3] {
+    [score.wins, score.ties, score.losses]
+}
+
+fn scoreboard(counts: [usize; 3]) -> Scoreboard {
+    Scoreboard {
+        wins: counts[0],
+        ties: counts[1],
+        losses: counts[2],
+    }
+}
+
+impl Snapshot {
+    pub(crate) fn of(session: &Session) -> Snapshot {
+        Snapshot {
+            user_name: session.user_name.clone(),
+            score: counts(&session.score),
+            chaos_score: counts(&session.chaos_score),
+            pending: session.last_round.as_ref()
+                .map(|round| (round.computer, round.random_bytes.clone())),
+            verified: session.verified,
+            opponent: session.opponent,
+            strategy: session.last_round.as_ref().map_or(Strategy::Uniform, |round| round.strategy),
+            rotation: session.rotation,
+        }
+    }
+
+    /// Rebuilds a session. Everything not in the snapshot (history, player model, seeded match,
+    /// practice and fun modes, house rules, sudden throws and the per-opponent breakdown) starts
+    /// afresh.
+    pub(crate) fn restore(self) -> Session {
+        let mut session = Session::new(self.user_name);
+        session.score = scoreboard(self.score);
+        session.chaos_score = scoreboard(self.chaos_score);
+        session.verified = self.verified;
+        let opponent = self.opponent;
+        let strategy = self.strategy;
+        session.opponent = opponent;
+        session.rotation = self.rotation;
+        session.round_opponent = self.pending.as_ref()
+            .filter(|_| strategy != Strategy::Adaptive && strategy != Strategy::House)
+            .map(|_| opponent);
+        session.last_round = self.pending.map(|(hand, nonce_hex)| Round {
+            computer: hand,
+            digest: commitment::digest(&nonce_hex, hand),
+            random_bytes: nonce_hex,
+            strategy: strategy,
+            backup: None,
+        });
+        session
+    }
+}
+
+/// Mirrors `session` into the snapshot cookie. Call it after every change worth recovering.
+pub(crate) fn save(cookies: &mut Cookies, session: &Session) {
+    let value = serde_json::to_string(&Snapshot::of(session)).unwrap();
+    cookies.add_private(cookie_policy::build(COOKIE_NAME, value));
+}
+
+pub fn load(cookies: &mut Cookies) -> Option<Snapshot> {
+    let cookie = cookies.get_private(COOKIE_NAME)?;
+    serde_json::from_str(cookie.value()).ok()
+}
+
+pub fn remove(cookies: &mut Cookies) {
+    cookies.remove_private(cookie_policy::named(COOKIE_NAME));
+}
@@ -0,0 +1,204 @@
+//! Versioned data files, and their migrations
+//!
+//! What the server keeps on disk, e.g. the `profile_file`, is saved as a `Format`: a JSON envelope
+//! `{"format": ..., "version": ..., "data": ...}` around the data. When a release changes the
+//! shape of the data in a way serde defaults can't absorb, it adds a `Migration` to the format,
+//! which rewrites the JSON of the version before into that of its own version. Files are upgraded
+//! as they are loaded, at startup, one migration after another; files from before the envelope
+//! are version 0.
+//!
+//! Before an upgraded file is written back, the old one is copied to `<file>.v<version>.bak`,
+//! unless the `migration_backup` config extra is `false`. With `migration_dry_run`, the migrations
+//! run in memory and are reported, but the file is left as it is and not saved to while the server
+//! runs. A file of a version newer than the release knows of is refused, rather than clobbered by
+//! a downgrade. Files are saved whole, to a temporary file synced to disk, then renamed over the
+//! old one, so that a crash mid-write loses nothing. On Unix, they are created readable by the
+//! server's user only.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+
+use rocket::Config;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{self, Map, Value};
+
+pub struct Migration {
+    /// The version it upgrades to, from the one before.
+    pub version: u32,
+    pub description: &'static str,
+    pub upgrade: fn(&mut Value) -> Result<(), String>,
+}
+
+pub struct Format {
+    /// E.g. `profiles`.
+    pub name: &'static str,
+    /// In order, from version 1.
+    pub migrations: &'static [Migration],
+}
+
+impl Format {
+    /// The version the release saves.
+    pub fn version(&self) -> u32 {
+        self.migrations.len() as u32
+    }
+
+    /// Upgrades `data`, of version `version`, to the current version. Returns the migrations run.
+    pub fn upgrade(&self, version: u32, data: &mut Value) -> Result<Vec<&Migration>, String> {
+        if version > self.version() {
+            return Err(format!("{} version {} is newer than this release's, {}",
+                               self.name, version, self.version()));
+        }
+        let pending = &self.migrations[version as usize..];
+        for (migration, expected) in pending.iter().zip(version + 1..) {
+            debug_assert_eq!(migration.version, expected, "{} migrations out of order", self.name);
+            (migration.upgrade)(data)
+                .map_err(|e| format!("migrating {} to version {}: {}", self.name, expected, e))?;
+        }
+        Ok(pending.iter().collect())
+    }
+
+    /// Splits the JSON of a file into its version and its data.
+    pub fn unwrap(&self, mut json: Value) -> (u32, Value) {
+        let version = {
+            let is_envelope = json.as_object().map_or(false, |object| {
+                object.len() == 3
+                    && object.get("format").and_then(Value::as_str) == Some(self.name)
+                    && object.contains_key("data")
+            });
+            match json.get("version").and_then(Value::as_u64) {
+                Some(version) if is_envelope => version as u32,
+                _ => return (0, json),
+            }
+        };
+        (version, json["data"].take())
+    }
+
+    /// The JSON of a file holding `data`, at the current version.
+    pub fn wrap(&self, data: Value) -> Value {
+        let mut envelope = Map::new();
+        envelope.insert("format".to_owned(), Value::from(self.name));
+        envelope.insert("version".to_owned(), Value::from(self.version()));
+        envelope.insert("data".to_owned(), data);
+        Value::Object(envelope)
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Options {
+    /// Whether to only report the migrations.
+    pub dry_run: bool,
+    /// Whether to keep a copy of a file before migrating it.
+    pub backup: bool,
+}
+
+impl Options {
+    pub fn from_config(config: &Config) -> Options {
+        Options {
+            dry_run: config.get_bool("migration_dry_run").unwrap_or(false),
+            backup: config.get_bool("migration_backup").unwrap_or(true),
+        }
+    }
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            dry_run: false,
+            backup: true,
+        }
+    }
+}
+
+pub struct Loaded<T> {
+    pub data: T,
+    /// Whether the file was left at an older version by a dry run, and must not be saved to.
+    pub read_only: bool,
+}
+
+/// Loads the file at `path`, of `format`, upgrading it if it is older than the current version.
+/// `None` if there is no such file.
+pub fn load<T: DeserializeOwned>(path: &Path, format: &Format, options: Options)
+    -> Result<Option<Loaded<T>>, String>
+{
+    if !path.exists() {
+        return Ok(None);
+    }
+    let reader = File::open(path).map(BufReader::new)
+        .map_err(|e| format!("unable to read {}: {}", path.display(), e))?;
+    let json = serde_json::from_reader(reader)
+        .map_err(|e| format!("invalid {} file {}: {}", format.name, path.display(), e))?;
+    let (version, mut data) = format.unwrap(json);
+    let migrations = format.upgrade(version, &mut data)?;
+    let loaded = T::deserialize(&data)
+        .map_err(|e| format!("invalid {} file {}: {}", format.name, path.display(), e))?;
+    if migrations.is_empty() {
+        return Ok(Some(Loaded { data: loaded, read_only: false }));
+    }
+
+    let steps: Vec<_> = migrations.iter()
+        .map(|migration| format!("{} ({})", migration.version, migration.description))
+        .collect();
+    if options.dry_run {
+        eprintln!("Dry run: {} would be migrated to {}; leaving it as it is",
+                  path.display(), steps.join(", "));
+        return Ok(Some(Loaded { data: loaded, read_only: true }));
+    }
+    if options.backup {
+        let backup = backup_path(path, version);
+        fs::copy(path, &backup)
+            .map_err(|e| format!("unable to back {} up: {}", path.display(), e))?;
+    }
+    write(path, &format.wrap(data))
+        .map_err(|e| format!("unable to write {}: {}", path.display(), e))?;
+    eprintln!("Migrated {} to {}", path.display(), steps.join(", "));
+    Ok(Some(Loaded { data: loaded, read_only: false }))
+}
+
+/// Saves `data` to the file at `path`, at the current version of `format`.
+pub fn save<T: ?Sized + Serialize>(path: &Path, format: &Format, data: &T) -> io::Result<()> {
+    let json = serde_json::to_value(data)
+        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+    write(path, &format.wrap(json))
+}
+
+/// Creates the file at `path`, or truncates it, readable and writable by the server's user only.
+fn create(path: &Path) -> io::Result<File> {
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path)
+}
+
+/// Writes `json` to a temporary file, then renames it to `path`. The file at `path` is left as it
+/// was if that fails, and the temporary one removed.
+pub fn write(path: &Path, json: &Value) -> io::Result<()> {
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".tmp");
+    let temporary = PathBuf::from(temporary);
+    let written = write_synced(&temporary, json).and_then(|()| fs::rename(&temporary, path));
+    if written.is_err() {
+        let _ = fs::remove_file(&temporary);
+    }
+    written
+}
+
+/// Writes `json` to the file at `path`, and waits until it is on disk.
+fn write_synced(path: &Path, json: &Value) -> io::Result<()> {
+    let mut writer = BufWriter::new(create(path)?);
+    serde_json::to_writer_pretty(&mut writer, json)
+        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+    writer.into_inner()?.sync_all()
+}
+
+/// Where the file at `path` is copied before being migrated from `version`.
+pub fn backup_path(path: &Path, version: u32) -> PathBuf {
+    let mut backup = path.as_os_str().to_owned();
+    backup.push(format!(".v{}.bak", version));
+    PathBuf::from(backup)
+}
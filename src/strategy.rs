@@ -0,0 +1,446 @@
+//! Models of the players' behaviour
+
+use std::str::FromStr;
+use std::sync::RwLock;
+
+use rand::Rng;
+use rocket::Config;
+
+use entropy;
+use game::{Hand, Strategy, Verdict};
+use metrics;
+use poison::Recover;
+
+/// The share of decided rounds fun mode lets the player win, unless configured otherwise.
+pub const DEFAULT_TARGET_WIN_RATE: f64 = 0.45;
+/// How much the latest decided round weighs in the win rate fun mode measures.
+const WIN_RATE_SMOOTHING: f64 = 0.05;
+/// How fast fun mode moves its exploitation level to close the gap to the target.
+const EXPLOITATION_GAIN: f64 = 0.1;
+
+lazy_static! {
+    static ref TARGET_WIN_RATE: RwLock<f64> = RwLock::new(DEFAULT_TARGET_WIN_RATE);
+}
+
+/// The computer's default strategy: every hand with the same probability, indexed like
+/// `Hand::CHOICES`. Seeded matches derive their hands uniformly too.
+pub fn computer_distribution() -> [f64; 3] {
+    [1.0 / 3.0; 3]
+}
+
+/// The strategy families the computer can play classic rounds with, selectable by the player.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Opponent {
+    Random,
+    /// Plays the human's previous hand.
+    Mirror,
+    /// Plays the hand beating the human's previous hand.
+    CounterMirror,
+}
+
+impl Default for Opponent {
+    fn default() -> Opponent {
+        Opponent::Random
+    }
+}
+
+impl Opponent {
+    pub const ALL: [Opponent; 3] = [Opponent::Random, Opponent::Mirror, Opponent::CounterMirror];
+
+    /// Position in `ALL`.
+    pub fn index(&self) -> usize {
+        match *self {
+            Opponent::Random => 0,
+            Opponent::Mirror => 1,
+            Opponent::CounterMirror => 2,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Opponent::Random => "random",
+            Opponent::Mirror => "mirror",
+            Opponent::CounterMirror => "counter-mirror",
+        }
+    }
+
+    /// The hand this opponent would play given the human's previous hand. Without one, every
+    /// opponent plays at random.
+    fn respond(&self, last_human: Hand) -> Option<Hand> {
+        match *self {
+            Opponent::Random => None,
+            Opponent::Mirror => Some(last_human),
+            Opponent::CounterMirror => Some(last_human.counter()),
+        }
+    }
+
+    /// Picks the computer's next hand.
+    pub fn pick(&self, last_human: Option<Hand>) -> Hand {
+        match last_human.and_then(|hand| self.respond(hand)) {
+            Some(hand) => hand,
+            None => Hand::random(),
+        }
+    }
+
+    /// What `pick` picks with.
+    pub fn strategy(&self, last_human: Option<Hand>) -> Strategy {
+        match (*self, last_human) {
+            (Opponent::Mirror, Some(_)) => Strategy::Mirror,
+            (Opponent::CounterMirror, Some(_)) => Strategy::CounterMirror,
+            _ => Strategy::Uniform,
+        }
+    }
+
+    /// The distribution `pick` draws from, indexed like `Hand::CHOICES`.
+    pub fn distribution(&self, last_human: Option<Hand>) -> [f64; 3] {
+        match last_human.and_then(|hand| self.respond(hand)) {
+            Some(hand) => pure(hand),
+            None => computer_distribution(),
+        }
+    }
+}
+
+pub struct ParseOpponentError;
+
+impl FromStr for Opponent {
+    type Err = ParseOpponentError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Opponent::ALL.iter()
+            .find(|opponent| opponent.name() == s.to_lowercase())
+            .cloned()
+            .ok_or(ParseOpponentError)
+    }
+}
+
+/// Highest weight of an opponent in a `Rotation`.
+pub const MAX_ROTATION_WEIGHT: u32 = 100;
+
+/// A pool of opponents the computer rotates among, drawing the opponent of every classic round
+/// with a probability in proportion to its weight. Opponents of weight 0 are left out.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct Rotation {
+    pub random: u32,
+    pub mirror: u32,
+    pub counter_mirror: u32,
+}
+
+impl Rotation {
+    /// A rotation with these weights, capped to `MAX_ROTATION_WEIGHT`, unless every one is 0.
+    pub fn new(random: u32, mirror: u32, counter_mirror: u32) -> Option<Rotation> {
+        let rotation = Rotation {
+            random: random.min(MAX_ROTATION_WEIGHT),
+            mirror: mirror.min(MAX_ROTATION_WEIGHT),
+            counter_mirror: counter_mirror.min(MAX_ROTATION_WEIGHT),
+        };
+        if rotation.total() == 0 {
+            None
+        } else {
+            Some(rotation)
+        }
+    }
+
+    pub fn weight(&self, opponent: Opponent) -> u32 {
+        match opponent {
+            Opponent::Random => self.random,
+            Opponent::Mirror => self.mirror,
+            Opponent::CounterMirror => self.counter_mirror,
+        }
+    }
+
+    fn total(&self) -> u32 {
+        Opponent::ALL.iter().map(|&opponent| self.weight(opponent)).sum()
+    }
+
+    /// Draws the opponent of the next round.
+    pub fn draw(&self) -> Opponent {
+        let mut ticket = entropy::get().gen_range(0, self.total().max(1));
+        for &opponent in &Opponent::ALL {
+            if ticket < self.weight(opponent) {
+                return opponent;
+            }
+            ticket -= self.weight(opponent);
+        }
+        Opponent::Random
+    }
+}
+
+/// The distribution of a strategy that always plays `hand`, e.g. the computer's revealed hand.
+pub fn pure(hand: Hand) -> [f64; 3] {
+    let mut p = [0.0; 3];
+    p[hand.index()] = 1.0;
+    p
+}
+
+/// The most likely hand of the distribution `p`. Ties go to the earlier hand in `CHOICES`.
+fn most_likely(p: [f64; 3]) -> Hand {
+    let mut best = 0;
+    for i in 1..3 {
+        if p[i] > p[best] {
+            best = i;
+        }
+    }
+    Hand::CHOICES[best]
+}
+
+/// Expected payoff of playing `hand` against an opponent's distribution.
+pub fn expected_payoff(hand: Hand, opponent: &[f64; 3]) -> f64 {
+    Hand::CHOICES.iter()
+        .zip(opponent.iter())
+        .map(|(&other, &p)| p * f64::from(Verdict::of(hand, other).payoff()))
+        .sum()
+}
+
+/// The hand with the highest expected payoff against `opponent`. Ties go to the earlier hand in
+/// `CHOICES`, so against the uniform computer every hand is as good as rock.
+pub fn best_response(opponent: &[f64; 3]) -> Hand {
+    let mut best = Hand::CHOICES[0];
+    for &hand in &Hand::CHOICES[1..] {
+        if expected_payoff(hand, opponent) > expected_payoff(best, opponent) {
+            best = hand;
+        }
+    }
+    best
+}
+
+/// What the human should have played against the computer's revealed hand, and the regret of
+/// having played `human` instead.
+pub fn advise(human: Hand, computer: Hand) -> (Hand, u32) {
+    let best = best_response(&pure(computer));
+    let regret = Verdict::of(best, computer).payoff() - Verdict::of(human, computer).payoff();
+    (best, regret as u32)
+}
+
+/// Regret of the human over a session: per round, how much more the best response to the
+/// computer's revealed hand would have earned than the hand actually played.
+#[derive(Clone, Copy, Debug, Default, Serialize)]
+pub struct Regret {
+    pub rounds: u32,
+    pub total: u32,
+}
+
+impl Regret {
+    pub fn record(&mut self, human: Hand, computer: Hand) {
+        self.rounds += 1;
+        self.total += advise(human, computer).1;
+    }
+
+    /// Takes back a round recorded before, e.g. voided by an arcade redo.
+    pub fn void(&mut self, human: Hand, computer: Hand) {
+        self.rounds -= 1;
+        self.total -= advise(human, computer).1;
+    }
+
+    pub fn average(&self) -> f64 {
+        if self.rounds == 0 {
+            0.0
+        } else {
+            f64::from(self.total) / f64::from(self.rounds)
+        }
+    }
+}
+
+/// First-order Markov model of the human's hands: how often each hand followed the previous one.
+#[derive(Clone, Debug, Default)]
+pub struct PlayerModel {
+    last: Option<Hand>,
+    transitions: [[u32; 3]; 3],
+    totals: [u32; 3],
+}
+
+/// A point-in-time view of a `PlayerModel`, as exposed to the player in practice mode.
+#[derive(Clone, Debug, Serialize)]
+pub struct ModelSnapshot {
+    pub observations: u32,
+    pub last: Option<Hand>,
+    pub rock: f64,
+    pub paper: f64,
+    pub scissors: f64,
+    pub likely: Hand,
+    pub counter: Hand,
+    /// Fun mode's state, while it is on.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub adaptive: Option<AdaptiveSnapshot>,
+}
+
+impl PlayerModel {
+    pub fn new() -> PlayerModel {
+        PlayerModel::default()
+    }
+
+    /// The human's previous hand.
+    pub fn last(&self) -> Option<Hand> {
+        self.last
+    }
+
+    pub fn observe(&mut self, hand: Hand) {
+        if let Some(last) = self.last {
+            self.transitions[last.index()][hand.index()] += 1;
+        }
+        self.totals[hand.index()] += 1;
+        self.last = Some(hand);
+    }
+
+    /// Predicted probabilities of the next hand, indexed like `Hand::CHOICES`.
+    ///
+    /// Uses the transitions out of the last hand once there are any, the overall frequencies
+    /// otherwise. Counts are Laplace-smoothed, so an empty model predicts a uniform distribution.
+    pub fn predict(&self) -> [f64; 3] {
+        metrics::time_decision("markov", || self.estimate())
+    }
+
+    /// `predict`, without timing it as a decision of the computer, e.g. in a simulation.
+    pub fn estimate(&self) -> [f64; 3] {
+        let from_last = self.last.map(|last| self.transitions[last.index()]);
+        let counts = match from_last {
+            Some(counts) if counts != [0; 3] => counts,
+            _ => self.totals,
+        };
+        let total = counts.iter().sum::<u32>() as f64 + 3.0;
+        let mut p = [0.0; 3];
+        for (p, &n) in p.iter_mut().zip(counts.iter()) {
+            *p = (n as f64 + 1.0) / total;
+        }
+        p
+    }
+
+    /// The hand the model considers most likely next. Ties go to the earlier hand in `CHOICES`.
+    pub fn likely(&self) -> Hand {
+        most_likely(self.predict())
+    }
+
+    /// `likely`, without timing it, see `estimate`.
+    pub fn likely_untimed(&self) -> Hand {
+        most_likely(self.estimate())
+    }
+
+    pub fn snapshot(&self) -> ModelSnapshot {
+        let p = self.predict();
+        let likely = self.likely();
+        ModelSnapshot {
+            observations: self.totals.iter().sum(),
+            last: self.last,
+            rock: p[0],
+            paper: p[1],
+            scissors: p[2],
+            likely: likely,
+            counter: likely.counter(),
+            adaptive: None,
+        }
+    }
+}
+
+/// The meta-strategy of fun mode, which holds the player's long-run win rate, over the rounds not
+/// tied, to a target.
+///
+/// Every round, it exploits the player's `PlayerModel` with a probability of the exploitation
+/// level, and plays uniformly at random otherwise. A positive level plays the best response to
+/// the model's prediction, a negative one throws the round, playing the hand the player's likely
+/// hand beats. After each decided round, the level moves against the gap between the measured
+/// win rate, an exponential moving average, and the target: integral control, so a player the
+/// model reads well meets a computer that exploits them less, and a hard one to read a computer
+/// that gives rounds away.
+#[derive(Clone, Copy, Debug)]
+pub struct Adaptive {
+    target: f64,
+    /// From -1, always throwing the round, to 1, always exploiting the model.
+    exploitation: f64,
+    /// `None` until a round is decided.
+    win_rate: Option<f64>,
+    decided: u32,
+}
+
+/// What fun mode is up to, as exposed to the player in practice mode.
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct AdaptiveSnapshot {
+    pub target: f64,
+    pub exploitation: f64,
+    pub win_rate: Option<f64>,
+    pub decided: u32,
+}
+
+impl Adaptive {
+    /// Aims at the configured target win rate, starting out as the uniform computer.
+    pub fn new() -> Adaptive {
+        Adaptive {
+            target: target_win_rate(),
+            exploitation: 0.0,
+            win_rate: None,
+            decided: 0,
+        }
+    }
+
+    /// The hand the exploitation level calls for against `model`.
+    fn exploit(&self, model: &PlayerModel) -> Hand {
+        if self.exploitation >= 0.0 {
+            best_response(&model.predict())
+        } else {
+            // The hand that loses to the one the player most likely plays.
+            model.likely().counter().counter()
+        }
+    }
+
+    /// Picks the computer's next hand.
+    pub fn pick(&self, model: &PlayerModel) -> Hand {
+        if entropy::get().gen::<f64>() < self.exploitation.abs() {
+            self.exploit(model)
+        } else {
+            Hand::random()
+        }
+    }
+
+    /// The distribution `pick` draws from, indexed like `Hand::CHOICES`.
+    pub fn distribution(&self, model: &PlayerModel) -> [f64; 3] {
+        let level = self.exploitation.abs();
+        let exploited = self.exploit(model);
+        let mut p = [(1.0 - level) / 3.0; 3];
+        p[exploited.index()] += level;
+        p
+    }
+
+    /// Feeds the verdict of a round fun mode played back into the exploitation level.
+    pub fn record(&mut self, verdict: Verdict) {
+        let won = match verdict {
+            Verdict::Win => 1.0,
+            Verdict::Loss => 0.0,
+            Verdict::Tie => return,
+        };
+        self.decided += 1;
+        let win_rate = match self.win_rate {
+            Some(rate) => rate + WIN_RATE_SMOOTHING * (won - rate),
+            None => won,
+        };
+        self.win_rate = Some(win_rate);
+        let exploitation = self.exploitation + EXPLOITATION_GAIN * (win_rate - self.target);
+        self.exploitation = exploitation.max(-1.0).min(1.0);
+    }
+
+    pub fn snapshot(&self) -> AdaptiveSnapshot {
+        AdaptiveSnapshot {
+            target: self.target,
+            exploitation: self.exploitation,
+            win_rate: self.win_rate,
+            decided: self.decided,
+        }
+    }
+}
+
+/// Reads the win rate fun mode aims at from the `fun_mode_win_rate` config extra, e.g. `0.45`,
+/// between 0 and 1.
+pub fn configure(config: &Config) -> Result<(), String> {
+    let target = match config.get_float("fun_mode_win_rate") {
+        Ok(target) if target > 0.0 && target < 1.0 => target,
+        Ok(target) => return Err(format!("`fun_mode_win_rate` must be between 0 and 1, got {}",
+                                         target)),
+        Err(_) => DEFAULT_TARGET_WIN_RATE,
+    };
+    *TARGET_WIN_RATE.write().recover() = target;
+    Ok(())
+}
+
+/// The share of decided rounds fun mode lets players win.
+pub fn target_win_rate() -> f64 {
+    *TARGET_WIN_RATE.read().recover()
+}
@@ -0,0 +1,222 @@
+//! Co-op mode: two players take turns against one computer, sharing a team score
+//!
+//! A player forms a team and passes its invite code on; the teammate joins with it. Members then
+//! alternate classic rounds, which are recorded on the team rather than on either session. A team
+//! is disbanded as soon as one of its members leaves or their session ends.
+
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+use events::{self, Event};
+use feed;
+use game::{Hand, Round, RoundKind, Scoreboard, Verdict};
+use history::History;
+use ids::UserId;
+use poison::Recover;
+use profile;
+use util::{bytes_to_hex, gen_random_bytes};
+use SESSIONS;
+
+pub const TEAM_SIZE: usize = 2;
+
+lazy_static! {
+    // Invite code -> Team
+    static ref TEAMS: RwLock<HashMap<String, Team>> = RwLock::new(HashMap::new());
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Member {
+    pub id: UserId,
+    pub name: String,
+}
+
+pub struct Team {
+    pub code: String,
+    pub name: String,
+    /// The founder first.
+    pub members: Vec<Member>,
+    pub score: Scoreboard,
+    /// Index into `members` of whoever plays the pending round.
+    turn: usize,
+    /// Committed to once the team is complete.
+    pub last_round: Option<Round>,
+    pub history: History,
+}
+
+impl Team {
+    pub fn is_complete(&self) -> bool {
+        self.members.len() == TEAM_SIZE
+    }
+
+    /// The member whose turn it is, once the team is complete.
+    pub fn up_next(&self) -> Option<&Member> {
+        if self.is_complete() { self.members.get(self.turn) } else { None }
+    }
+
+    /// "alice & bob".
+    pub fn member_names(&self) -> String {
+        self.members.iter().map(|m| m.name.as_str()).collect::<Vec<_>>().join(" & ")
+    }
+
+    fn start_round(&mut self) {
+        let round = feed::take();
+        events::publish(Event::RoundCommitted {
+            user_name: self.members[self.turn].name.clone(),
+            digest: round.digest.clone(),
+            nonce: round.random_bytes.clone(),
+            committed: round.computer.as_ref().to_owned(),
+        });
+        self.last_round = Some(round);
+    }
+}
+
+#[derive(Debug)]
+pub enum TeamError {
+    NoSuchTeam,
+    AlreadyInTeam,
+    TeamFull,
+    NotInTeam,
+    /// Still waiting for the teammate to join.
+    Incomplete,
+    NotYourTurn,
+}
+
+impl TeamError {
+    /// The code of the message in `flash`.
+    pub fn code(&self) -> &'static str {
+        match *self {
+            TeamError::NoSuchTeam => "team.no-such-team",
+            TeamError::AlreadyInTeam => "team.already-in-team",
+            TeamError::TeamFull => "team.full",
+            TeamError::NotInTeam => "team.not-in-team",
+            TeamError::Incomplete => "team.incomplete",
+            TeamError::NotYourTurn => "team.not-your-turn",
+        }
+    }
+}
+
+/// Disbands the teams some member of which no longer has a session.
+///
+/// Takes the sessions' read lock under the teams' lock, so never call it, or anything else here,
+/// with `SESSIONS` held.
+fn disband_orphans(teams: &mut HashMap<String, Team>) {
+    let sessions = SESSIONS.read_all();
+    teams.retain(|_, team| team.members.iter().all(|m| sessions.contains_key(&m.id)));
+}
+
+fn code_of(teams: &HashMap<String, Team>, user_id: &UserId) -> Option<String> {
+    teams.values()
+        .find(|team| team.members.iter().any(|m| m.id == *user_id))
+        .map(|team| team.code.clone())
+}
+
+/// Calls `f` with the team of `user_id`, if any.
+pub fn with_team<T, F: FnOnce(&Team) -> T>(user_id: &UserId, f: F) -> Option<T> {
+    let mut teams = TEAMS.write().recover();
+    disband_orphans(&mut teams);
+    let code = code_of(&teams, user_id)?;
+    Some(f(&teams[&code]))
+}
+
+/// Forms a team with `founder` as its only member. Returns the invite code.
+pub fn form(founder: Member, name: &str) -> Result<String, TeamError> {
+    let mut teams = TEAMS.write().recover();
+    disband_orphans(&mut teams);
+    if code_of(&teams, &founder.id).is_some() {
+        return Err(TeamError::AlreadyInTeam);
+    }
+    let code = bytes_to_hex(&gen_random_bytes(4));
+    let name = match name.trim() {
+        "" => format!("{}'s team", founder.name),
+        name => name.to_owned(),
+    };
+    teams.insert(code.clone(), Team {
+        code: code.clone(),
+        name: name,
+        members: vec![founder],
+        score: Scoreboard::default(),
+        turn: 0,
+        last_round: None,
+        history: History::new(),
+    });
+    Ok(code)
+}
+
+/// Adds `member` to the team invited to with `code`, which then commits to its first round.
+pub fn join(member: Member, code: &str) -> Result<(), TeamError> {
+    let mut teams = TEAMS.write().recover();
+    disband_orphans(&mut teams);
+    if code_of(&teams, &member.id).is_some() {
+        return Err(TeamError::AlreadyInTeam);
+    }
+    let team = teams.get_mut(code.trim()).ok_or(TeamError::NoSuchTeam)?;
+    if team.is_complete() {
+        return Err(TeamError::TeamFull);
+    }
+    team.members.push(member);
+    team.start_round();
+    events::publish(Event::TeamFormed { name: team.name.clone() });
+    Ok(())
+}
+
+/// Disbands the team of `user_id`. Returns whether there was one.
+pub fn leave(user_id: &UserId) -> bool {
+    let mut teams = TEAMS.write().recover();
+    let team = match code_of(&teams, user_id) {
+        Some(code) => teams.remove(&code).unwrap(),
+        None => return false,
+    };
+    events::publish(Event::TeamDisbanded { name: team.name });
+    true
+}
+
+/// Plays `user_id`'s hand against the team's pending round, passes the turn on and commits to the
+/// next round.
+pub fn play(user_id: &UserId, hand: Hand) -> Result<(Round, Verdict), TeamError> {
+    let mut teams = TEAMS.write().recover();
+    disband_orphans(&mut teams);
+    let code = code_of(&teams, user_id).ok_or(TeamError::NotInTeam)?;
+    let team = teams.get_mut(&code).unwrap();
+    let player = team.up_next().cloned().ok_or(TeamError::Incomplete)?;
+    if player.id != *user_id {
+        return Err(TeamError::NotYourTurn);
+    }
+
+    let round = team.last_round.take().unwrap();
+    let verdict = Verdict::of(hand, round.computer);
+    team.score.record(verdict);
+    team.history.push(RoundKind::Classic, &[hand], &[round.computer], verdict,
+                      &round.random_bytes, &round.digest, None, Some(round.strategy));
+    let analytics_opt_out = profile::opted_out_of_analytics(&player.name);
+    events::publish(Event::RoundPlayed {
+        user_name: player.name,
+        kind: RoundKind::Classic,
+        human: vec![hand],
+        computer: vec![round.computer],
+        verdict: verdict,
+        analytics_opt_out: analytics_opt_out,
+        unpredictable: true,
+        decoy: None,
+        strategy: Some(round.strategy),
+        ranked: round.strategy.is_ranked(),
+    });
+    team.turn = (team.turn + 1) % TEAM_SIZE;
+    team.start_round();
+    Ok((round, verdict))
+}
+
+/// `(name, members, score)` of every complete team.
+pub fn scores() -> Vec<(String, String, Scoreboard)> {
+    let mut teams = TEAMS.write().recover();
+    disband_orphans(&mut teams);
+    teams.values()
+        .filter(|team| team.is_complete())
+        .map(|team| (team.name.clone(), team.member_names(), team.score))
+        .collect()
+}
+
+/// Whether some team has yet to play the round committed to as `digest`.
+pub fn is_pending(digest: &str) -> bool {
+    let teams = TEAMS.read().recover();
+    teams.values().any(|team| team.last_round.as_ref().map_or(false, |round| round.digest == digest))
+}
@@ -0,0 +1,42 @@
+//! Rate limits over fixed windows
+//!
+//! A `Throttle` lets at most `limit` events through per key in every window of `window` seconds,
+//! the windows aligned on Unix time. It forgets the keys of past windows as it goes, so its table
+//! only holds the keys seen in the current one.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use poison::Recover;
+use util::unix_time;
+
+pub struct Throttle {
+    limit: u32,
+    window: u64,
+    // Key -> (window, events in it)
+    counts: Mutex<HashMap<String, (u64, u32)>>,
+}
+
+impl Throttle {
+    pub fn new(limit: u32, window: u64) -> Throttle {
+        Throttle {
+            limit: limit,
+            window: window,
+            counts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Counts an event of `key`, unless `limit` of them went through in this window already.
+    /// Returns whether it was let through.
+    pub fn admit(&self, key: &str) -> bool {
+        let window = unix_time() / self.window;
+        let mut counts = self.counts.lock().recover();
+        counts.retain(|_, &mut (w, _)| w == window);
+        let count = counts.entry(key.to_owned()).or_insert((window, 0));
+        if count.1 >= self.limit {
+            return false;
+        }
+        count.1 += 1;
+        true
+    }
+}
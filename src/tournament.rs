@@ -0,0 +1,701 @@
+//! Round-robin tournaments between bots
+//!
+//! Players register bots with the API, `POST /api/v1/bots`. Every `tournament_interval_secs`
+//! seconds (an hour by default), a tournament starts between those bots whose owner still has a
+//! session and the house bots, one per `Opponent`: every entrant meets every other one over
+//! `TOURNAMENT_ROUNDS` rounds, all pairings playing their rounds at the same pace. House bots pick
+//! at once; registered bots have `tournament_turn_secs` seconds (30 by default) to post their hands
+//! for the round, `POST /api/v1/bots/<name>/turn`, and forfeit those they miss. A job of the
+//! `scheduler` starts the tournaments and closes overdue rounds, every second.
+//!
+//! A pairing won scores 3 points, a drawn one 1, on the standings of `/tournament/standings.json`,
+//! kept for the life of the process. Bots are held to one API call per `MIN_BOT_INTERVAL_MS`, on
+//! top of their owner's daily quota.
+//!
+//! Other kinds of bots only have to implement `Bot`, like the scripted house bots of `scripting`.
+//! Scripts are run by the job outside `TOURNAMENTS`, which they would hold for as long as they
+//! take otherwise, and their hands submitted once they are done.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use rocket::http::Status;
+use rocket::Config;
+use rocket_contrib::Json;
+
+use game::{Hand, Scoreboard, Verdict};
+use ids::UserId;
+use maintenance;
+use metrics;
+use poison::Recover;
+use scheduler;
+#[cfg(feature = "scripting")]
+use scripting;
+use strategy::Opponent;
+use util::unix_time;
+use SESSIONS;
+
+pub const TOURNAMENT_ROUNDS: usize = 10;
+const DEFAULT_INTERVAL_SECS: u64 = 3600;
+const DEFAULT_TURN_SECS: u64 = 30;
+const MIN_BOT_INTERVAL_MS: u64 = 500;
+pub(crate) const MAX_NAME_LEN: usize = 32;
+pub(crate) const HOUSE_PREFIX: &str = "house-";
+
+lazy_static! {
+    static ref TOURNAMENTS: Mutex<Tournaments> = Mutex::new(Tournaments::default());
+}
+
+/// A hand of a past round, `None` if it was forfeited.
+pub type Throw = Option<Hand>;
+
+pub trait Bot: Send {
+    fn name(&self) -> &str;
+
+    /// The hand to play against `opponent`, given the past rounds against them, own throw first;
+    /// `None` while the bot has not picked one.
+    fn hand(&self, opponent: &str, past: &[(Throw, Throw)]) -> Option<Hand>;
+
+    /// Takes the hand picked against `opponent` from outside, e.g. through the API. Returns
+    /// whether the bot takes hands that way.
+    fn submit(&mut self, _opponent: &str, _hand: Hand) -> bool {
+        false
+    }
+
+    /// Called once a round is over.
+    fn end_round(&mut self) {}
+
+    /// Whether the hands are decided by a script, and submitted once it ran.
+    fn is_scripted(&self) -> bool {
+        false
+    }
+}
+
+/// One of the computer's strategy families.
+struct HouseBot {
+    name: String,
+    opponent: Opponent,
+}
+
+impl Bot for HouseBot {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn hand(&self, _: &str, past: &[(Throw, Throw)]) -> Option<Hand> {
+        let last = past.last().and_then(|&(_, theirs)| theirs);
+        Some(metrics::time_decision(&self.name, || self.opponent.pick(last)))
+    }
+}
+
+/// A house bot for every strategy of the computer: the built-in ones, then the scripted ones.
+fn house_bots() -> Vec<Box<Bot>> {
+    let bots: Vec<Box<Bot>> = Opponent::ALL.iter()
+        .map(|&opponent| {
+            Box::new(HouseBot {
+                name: format!("{}{}", HOUSE_PREFIX, opponent.name()),
+                opponent: opponent,
+            }) as Box<Bot>
+        })
+        .collect();
+    #[cfg(feature = "scripting")]
+    let bots: Vec<_> = bots.into_iter().chain(scripting::bots()).collect();
+    bots
+}
+
+/// A registered bot, whose hands are posted through the API.
+struct ApiBot {
+    name: String,
+    /// By opponent, for the current round.
+    picks: HashMap<String, Hand>,
+}
+
+impl Bot for ApiBot {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn hand(&self, opponent: &str, _: &[(Throw, Throw)]) -> Option<Hand> {
+        self.picks.get(opponent).cloned()
+    }
+
+    fn submit(&mut self, opponent: &str, hand: Hand) -> bool {
+        self.picks.insert(opponent.to_owned(), hand);
+        true
+    }
+
+    fn end_round(&mut self) {
+        self.picks.clear();
+    }
+}
+
+#[derive(Debug)]
+pub enum BotError {
+    /// Names are 1 to `MAX_NAME_LEN` lowercase letters, digits and dashes.
+    InvalidName,
+    NameTaken,
+    /// Unknown, or someone else's.
+    NoSuchBot,
+    TooFast,
+    /// The bot is not in the running tournament, if any.
+    NotPlaying,
+    /// Not an opponent of the bot in the running tournament.
+    NoSuchOpponent,
+}
+
+impl BotError {
+    pub fn status(&self) -> Status {
+        match *self {
+            BotError::InvalidName | BotError::NoSuchOpponent => Status::BadRequest,
+            BotError::NameTaken | BotError::NotPlaying => Status::Conflict,
+            BotError::NoSuchBot => Status::NotFound,
+            BotError::TooFast => Status::TooManyRequests,
+        }
+    }
+}
+
+struct Registration {
+    name: String,
+    owner_id: UserId,
+    owner_name: String,
+    last_call: Option<Instant>,
+}
+
+struct Pairing {
+    /// Indices into the entrants.
+    sides: (usize, usize),
+    /// The first side's throw first.
+    throws: Vec<(Throw, Throw)>,
+}
+
+impl Pairing {
+    /// The throws from the side of `entrant`, who is in the pairing.
+    fn throws_of(&self, entrant: usize) -> Vec<(Throw, Throw)> {
+        if entrant == self.sides.0 {
+            self.throws.clone()
+        } else {
+            self.throws.iter().map(|&(first, second)| (second, first)).collect()
+        }
+    }
+
+    /// The rounds from the first side's point of view, a forfeit losing to any hand.
+    fn score(&self) -> Scoreboard {
+        let mut score = Scoreboard::default();
+        for &(first, second) in &self.throws {
+            score.record(match (first, second) {
+                (Some(first), Some(second)) => Verdict::of(first, second),
+                (Some(_), None) => Verdict::Win,
+                (None, Some(_)) => Verdict::Loss,
+                (None, None) => Verdict::Tie,
+            });
+        }
+        score
+    }
+}
+
+struct Tournament {
+    id: u64,
+    entrants: Vec<Box<Bot>>,
+    pairings: Vec<Pairing>,
+    /// From 0; the tournament is over once it reaches `TOURNAMENT_ROUNDS`.
+    round: usize,
+    /// Unix time at which the current round closes.
+    deadline: u64,
+}
+
+impl Tournament {
+    fn position(&self, name: &str) -> Option<usize> {
+        self.entrants.iter().position(|bot| bot.name() == name)
+    }
+
+    /// The throws of `pairing` this round, if both sides have picked.
+    fn hands(&self, pairing: &Pairing) -> (Throw, Throw) {
+        let (first, second) = pairing.sides;
+        let first_hand = self.entrants[first]
+            .hand(self.entrants[second].name(), &pairing.throws_of(first));
+        let second_hand = self.entrants[second]
+            .hand(self.entrants[first].name(), &pairing.throws_of(second));
+        (first_hand, second_hand)
+    }
+
+    fn round_complete(&self) -> bool {
+        self.pairings.iter().all(|pairing| {
+            let (first, second) = self.hands(pairing);
+            first.is_some() && second.is_some()
+        })
+    }
+
+    /// Records the hands picked, forfeits the others and opens the next round.
+    fn close_round(&mut self, turn_secs: u64) {
+        let throws: Vec<_> = self.pairings.iter().map(|pairing| self.hands(pairing)).collect();
+        for (pairing, throw) in self.pairings.iter_mut().zip(throws) {
+            pairing.throws.push(throw);
+        }
+        for bot in &mut self.entrants {
+            bot.end_round();
+        }
+        self.round += 1;
+        self.deadline = unix_time() + turn_secs;
+    }
+}
+
+/// A hand a scripted bot has to pick, this round.
+struct ScriptTurn {
+    tournament: u64,
+    round: usize,
+    bot: String,
+    opponent: String,
+    /// Own throw first.
+    #[cfg_attr(not(feature = "scripting"), allow(dead_code))]
+    past: Vec<(Throw, Throw)>,
+}
+
+#[derive(Clone, Default, Serialize)]
+pub struct Standing {
+    pub bot: String,
+    /// `None` for house bots.
+    pub owner: Option<String>,
+    pub tournaments: u32,
+    pub points: u32,
+    pub won: u32,
+    pub drawn: u32,
+    pub lost: u32,
+}
+
+#[derive(Clone, Serialize)]
+pub struct PairingResult {
+    pub bots: (String, String),
+    /// From the first bot's side.
+    pub score: Scoreboard,
+}
+
+#[derive(Serialize)]
+pub struct Standings {
+    /// Unix time at which the next tournament starts, unless one is running.
+    pub next_start: Option<u64>,
+    pub tournaments: u64,
+    /// Most points first.
+    pub standings: Vec<Standing>,
+    /// The pairings of the latest tournament.
+    pub latest: Vec<PairingResult>,
+}
+
+#[derive(Serialize)]
+pub struct Matchup {
+    pub opponent: String,
+    /// Own throw first.
+    pub past: Vec<(Throw, Throw)>,
+    /// What the bot posted against them this round, if anything.
+    pub picked: Option<Hand>,
+}
+
+/// What a registered bot has to play.
+#[derive(Serialize)]
+pub struct Turn {
+    pub tournament: u64,
+    /// From 1.
+    pub round: usize,
+    pub rounds: usize,
+    /// Unix time at which the round closes, forfeiting the hands not posted.
+    pub deadline: u64,
+    pub matchups: Vec<Matchup>,
+}
+
+#[derive(Serialize)]
+pub struct BotStatus {
+    pub name: String,
+    /// `None` unless the bot plays in the running tournament.
+    pub turn: Option<Turn>,
+    pub next_start: Option<u64>,
+}
+
+#[derive(Deserialize)]
+pub struct Picks {
+    /// By opponent.
+    pub hands: HashMap<String, Hand>,
+}
+
+struct Tournaments {
+    interval_secs: u64,
+    turn_secs: u64,
+    bots: Vec<Registration>,
+    running: Option<Tournament>,
+    /// Unix time at which the next tournament starts, once none is running.
+    next_start: u64,
+    held: u64,
+    standings: HashMap<String, Standing>,
+    latest: Vec<PairingResult>,
+    /// The tournament id and round of the last scripts run, so that one that failed is not run
+    /// again until the round closes.
+    scripted: Option<(u64, usize)>,
+}
+
+impl Default for Tournaments {
+    fn default() -> Tournaments {
+        Tournaments {
+            interval_secs: DEFAULT_INTERVAL_SECS,
+            turn_secs: DEFAULT_TURN_SECS,
+            bots: Vec::new(),
+            running: None,
+            next_start: unix_time() + DEFAULT_INTERVAL_SECS,
+            held: 0,
+            standings: HashMap::new(),
+            latest: Vec::new(),
+            scripted: None,
+        }
+    }
+}
+
+impl Tournaments {
+    /// Starts the tournament when it is due, and closes the rounds that are complete or overdue.
+    ///
+    /// Takes the sessions' read lock under `TOURNAMENTS`, so never call anything here with
+    /// `SESSIONS` held.
+    fn tick(&mut self) {
+        let now = unix_time();
+        // Held back in maintenance mode, while the server drains; see `maintenance`.
+        if self.running.is_none() && now >= self.next_start && !maintenance::is_on() {
+            self.start();
+        }
+        let turn_secs = self.turn_secs;
+        let over = match self.running {
+            Some(ref mut tournament) => {
+                while tournament.round < TOURNAMENT_ROUNDS
+                    && (tournament.round_complete() || now >= tournament.deadline)
+                {
+                    tournament.close_round(turn_secs);
+                }
+                tournament.round == TOURNAMENT_ROUNDS
+            }
+            None => false,
+        };
+        if over {
+            let tournament = self.running.take().unwrap();
+            self.finish(tournament);
+        }
+    }
+
+    /// The hands the scripted bots have to pick in the running round, the first time it is
+    /// asked in the round.
+    fn scripted_turns(&mut self) -> Vec<ScriptTurn> {
+        let tournament = match self.running {
+            Some(ref tournament) => tournament,
+            None => return Vec::new(),
+        };
+        if self.scripted == Some((tournament.id, tournament.round)) {
+            return Vec::new();
+        }
+        self.scripted = Some((tournament.id, tournament.round));
+        let mut turns = Vec::new();
+        for pairing in &tournament.pairings {
+            let (first, second) = pairing.sides;
+            for &(bot, opponent) in &[(first, second), (second, first)] {
+                let past = pairing.throws_of(bot);
+                let (bot, opponent) = (&tournament.entrants[bot], &tournament.entrants[opponent]);
+                if bot.is_scripted() && bot.hand(opponent.name(), &past).is_none() {
+                    turns.push(ScriptTurn {
+                        tournament: tournament.id,
+                        round: tournament.round,
+                        bot: bot.name().to_owned(),
+                        opponent: opponent.name().to_owned(),
+                        past: past,
+                    });
+                }
+            }
+        }
+        turns
+    }
+
+    /// Submits the hand a script picked, unless its round is over.
+    fn submit_scripted(&mut self, turn: &ScriptTurn, hand: Hand) {
+        if let Some(ref mut tournament) = self.running {
+            if tournament.id == turn.tournament && tournament.round == turn.round {
+                if let Some(entrant) = tournament.position(&turn.bot) {
+                    tournament.entrants[entrant].submit(&turn.opponent, hand);
+                }
+            }
+        }
+    }
+
+    fn start(&mut self) {
+        {
+            let sessions = SESSIONS.read_all();
+            self.bots.retain(|bot| sessions.contains_key(&bot.owner_id));
+        }
+        let mut entrants = house_bots();
+        entrants.extend(self.bots.iter().map(|bot| {
+            Box::new(ApiBot {
+                name: bot.name.clone(),
+                picks: HashMap::new(),
+            }) as Box<Bot>
+        }));
+        let mut pairings = Vec::new();
+        for first in 0..entrants.len() {
+            for second in first + 1..entrants.len() {
+                pairings.push(Pairing {
+                    sides: (first, second),
+                    throws: Vec::new(),
+                });
+            }
+        }
+        self.held += 1;
+        self.running = Some(Tournament {
+            id: self.held,
+            entrants: entrants,
+            pairings: pairings,
+            round: 0,
+            deadline: unix_time() + self.turn_secs,
+        });
+    }
+
+    fn finish(&mut self, tournament: Tournament) {
+        let owners: HashMap<_, _> = self.bots.iter()
+            .map(|bot| (bot.name.clone(), bot.owner_name.clone()))
+            .collect();
+        for bot in &tournament.entrants {
+            let standing = self.standings.entry(bot.name().to_owned())
+                .or_insert_with(Standing::default);
+            standing.bot = bot.name().to_owned();
+            // Bots withdrawn during the tournament keep the owner they had.
+            standing.owner = owners.get(bot.name()).cloned().or_else(|| standing.owner.take());
+            standing.tournaments += 1;
+        }
+        self.latest.clear();
+        for pairing in &tournament.pairings {
+            let names = (tournament.entrants[pairing.sides.0].name().to_owned(),
+                         tournament.entrants[pairing.sides.1].name().to_owned());
+            let score = pairing.score();
+            let (first, second) = if score.wins > score.losses {
+                ((3, 1, 0, 0), (0, 0, 0, 1))
+            } else if score.wins < score.losses {
+                ((0, 0, 0, 1), (3, 1, 0, 0))
+            } else {
+                ((1, 0, 1, 0), (1, 0, 1, 0))
+            };
+            {
+                let sides = [(&names.0, first), (&names.1, second)];
+                for &(name, (points, won, drawn, lost)) in &sides {
+                    let standing = self.standings.get_mut(name).unwrap();
+                    standing.points += points;
+                    standing.won += won;
+                    standing.drawn += drawn;
+                    standing.lost += lost;
+                }
+            }
+            self.latest.push(PairingResult {
+                bots: names,
+                score: score,
+            });
+        }
+        self.next_start = unix_time() + self.interval_secs;
+    }
+
+    /// The registration of `name`, if it belongs to `owner_id`, as long as it does not call too
+    /// often.
+    fn owned(&mut self, name: &str, owner_id: &UserId) -> Result<&mut Registration, BotError> {
+        let bot = self.bots.iter_mut()
+            .find(|bot| bot.name == name && bot.owner_id == *owner_id)
+            .ok_or(BotError::NoSuchBot)?;
+        let now = Instant::now();
+        if let Some(last_call) = bot.last_call {
+            if now.duration_since(last_call) < Duration::from_millis(MIN_BOT_INTERVAL_MS) {
+                return Err(BotError::TooFast);
+            }
+        }
+        bot.last_call = Some(now);
+        Ok(bot)
+    }
+
+    fn next_start(&self) -> Option<u64> {
+        if self.running.is_some() { None } else { Some(self.next_start) }
+    }
+
+    fn status(&self, name: &str) -> BotStatus {
+        let turn = self.running.as_ref().and_then(|tournament| {
+            let entrant = tournament.position(name)?;
+            let matchups = tournament.pairings.iter()
+                .filter_map(|pairing| {
+                    let opponent = match pairing.sides {
+                        (first, second) if first == entrant => second,
+                        (first, second) if second == entrant => first,
+                        _ => return None,
+                    };
+                    let opponent = tournament.entrants[opponent].name();
+                    Some(Matchup {
+                        opponent: opponent.to_owned(),
+                        past: pairing.throws_of(entrant),
+                        picked: tournament.entrants[entrant].hand(opponent, &[]),
+                    })
+                })
+                .collect();
+            Some(Turn {
+                tournament: tournament.id,
+                round: tournament.round + 1,
+                rounds: TOURNAMENT_ROUNDS,
+                deadline: tournament.deadline,
+                matchups: matchups,
+            })
+        });
+        BotStatus {
+            name: name.to_owned(),
+            turn: turn,
+            next_start: self.next_start(),
+        }
+    }
+}
+
+/// Reads the schedule from the `tournament_interval_secs` and `tournament_turn_secs` config
+/// extras, and schedules the ticks.
+pub fn configure(config: &Config) -> Result<(), String> {
+    let secs = |key: &str, default: u64| match config.get_int(key) {
+        Ok(secs) if secs > 0 => Ok(secs as u64),
+        Ok(secs) => Err(format!("`{}` must be positive, got {}", key, secs)),
+        Err(_) => Ok(default),
+    };
+    let interval_secs = secs("tournament_interval_secs", DEFAULT_INTERVAL_SECS)?;
+    let turn_secs = secs("tournament_turn_secs", DEFAULT_TURN_SECS)?;
+    {
+        let mut tournaments = TOURNAMENTS.lock().recover();
+        tournaments.interval_secs = interval_secs;
+        tournaments.turn_secs = turn_secs;
+        if tournaments.running.is_none() {
+            tournaments.next_start = unix_time() + interval_secs;
+        }
+    }
+
+    scheduler::every("tournament-tick", Duration::from_secs(1), tick);
+    Ok(())
+}
+
+/// Ticks the tournaments, running the scripts of the round between two ticks.
+fn tick() {
+    let turns = {
+        let mut tournaments = TOURNAMENTS.lock().recover();
+        tournaments.tick();
+        tournaments.scripted_turns()
+    };
+    if turns.is_empty() {
+        return;
+    }
+    let hands: Vec<_> = turns.iter().map(decide).collect();
+    let mut tournaments = TOURNAMENTS.lock().recover();
+    for (turn, hand) in turns.iter().zip(hands) {
+        if let Some(hand) = hand {
+            tournaments.submit_scripted(turn, hand);
+        }
+    }
+    tournaments.tick();
+}
+
+#[cfg(feature = "scripting")]
+fn decide(turn: &ScriptTurn) -> Option<Hand> {
+    scripting::pick(&turn.bot, &turn.past)
+}
+
+#[cfg(not(feature = "scripting"))]
+fn decide(_: &ScriptTurn) -> Option<Hand> {
+    None
+}
+
+/// Whether `name` is 1 to `MAX_NAME_LEN` lowercase letters, digits and dashes, as bot and team
+/// names are.
+pub(crate) fn is_valid_name(name: &str) -> bool {
+    name.len() <= MAX_NAME_LEN
+        && !name.is_empty()
+        && name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+}
+
+/// Whether a tournament is being played.
+pub fn is_running() -> bool {
+    TOURNAMENTS.lock().recover().running.is_some()
+}
+
+/// Registers the bot `name` of `owner_id`, who is called `owner_name`, for the next tournaments.
+/// Names stay taken by whoever registered them first.
+pub fn register(name: &str, owner_id: &UserId, owner_name: &str) -> Result<BotStatus, BotError> {
+    if !is_valid_name(name) {
+        return Err(BotError::InvalidName);
+    }
+    let mut tournaments = TOURNAMENTS.lock().recover();
+    tournaments.tick();
+    let taken = name.starts_with(HOUSE_PREFIX)
+        || tournaments.bots.iter().any(|bot| bot.name == name)
+        || tournaments.standings.get(name).map_or(false, |standing| {
+            standing.owner.as_ref().map(String::as_str) != Some(owner_name)
+        });
+    if taken {
+        return Err(BotError::NameTaken);
+    }
+    tournaments.bots.push(Registration {
+        name: name.to_owned(),
+        owner_id: owner_id.clone(),
+        owner_name: owner_name.to_owned(),
+        last_call: None,
+    });
+    Ok(tournaments.status(name))
+}
+
+/// Withdraws the bot `name` of `owner_id` from the next tournaments. It forfeits the rest of the
+/// running one.
+pub fn withdraw(name: &str, owner_id: &UserId) -> Result<(), BotError> {
+    let mut tournaments = TOURNAMENTS.lock().recover();
+    tournaments.owned(name, owner_id)?;
+    tournaments.bots.retain(|bot| bot.name != name);
+    Ok(())
+}
+
+/// Withdraws the bots of `owner_name` and drops their standings, e.g. when their account is
+/// deleted. Their names are free again.
+pub fn forget_owner(owner_name: &str) {
+    let mut tournaments = TOURNAMENTS.lock().recover();
+    tournaments.bots.retain(|bot| bot.owner_name != owner_name);
+    tournaments.standings
+        .retain(|_, standing| standing.owner.as_ref().map(String::as_str) != Some(owner_name));
+}
+
+/// What the bot `name` of `owner_id` has to play.
+pub fn status(name: &str, owner_id: &UserId) -> Result<BotStatus, BotError> {
+    let mut tournaments = TOURNAMENTS.lock().recover();
+    tournaments.owned(name, owner_id)?;
+    tournaments.tick();
+    Ok(tournaments.status(name))
+}
+
+/// Posts the hands of the bot `name` of `owner_id` for the current round. The round closes as
+/// soon as every entrant has picked.
+pub fn play(name: &str, owner_id: &UserId, picks: &Picks) -> Result<BotStatus, BotError> {
+    let mut tournaments = TOURNAMENTS.lock().recover();
+    tournaments.owned(name, owner_id)?;
+    tournaments.tick();
+    {
+        let tournament = tournaments.running.as_mut().ok_or(BotError::NotPlaying)?;
+        let entrant = tournament.position(name).ok_or(BotError::NotPlaying)?;
+        for opponent in picks.hands.keys() {
+            if opponent == name || tournament.position(opponent).is_none() {
+                return Err(BotError::NoSuchOpponent);
+            }
+        }
+        for (opponent, &hand) in &picks.hands {
+            tournament.entrants[entrant].submit(opponent, hand);
+        }
+    }
+    tournaments.tick();
+    Ok(tournaments.status(name))
+}
+
+// ===== Routers =====
+
+#[get("/tournament/standings.json")]
+pub fn standings() -> Json<Standings> {
+    let mut tournaments = TOURNAMENTS.lock().recover();
+    tournaments.tick();
+    let mut standings: Vec<_> = tournaments.standings.values().cloned().collect();
+    standings.sort_by(|a, b| b.points.cmp(&a.points).then_with(|| a.bot.cmp(&b.bot)));
+    Json(Standings {
+        next_start: tournaments.next_start(),
+        tournaments: tournaments.held,
+        standings: standings,
+        latest: tournaments.latest.clone(),
+    })
+}
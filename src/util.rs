@@ -1,24 +1,117 @@
 //! Helper functions
 
 use std::fmt::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
 
-use rand::{thread_rng, Rng};
+use entropy;
 
 /// Converts byte slice into lower hex string.
 pub fn bytes_to_hex(bytes: &[u8]) -> String {
     let hex_len = bytes.len() * 2;
     let mut s = String::with_capacity(hex_len);
     for b in bytes.iter() {
-        write!(&mut s, "{:x}", *b).expect("Unable to write");
+        write!(&mut s, "{:02x}", *b).expect("Unable to write");
     }
     s
 }
 
-/// Generates random bytes
+/// Generates random bytes from the installed `Entropy`.
 pub fn gen_random_bytes(size: usize) -> Vec<u8> {
-    let mut random_bytes = Vec::with_capacity(size);
-    unsafe { random_bytes.set_len(size); }
-    let mut rng = thread_rng();
-    rng.fill(&mut random_bytes[..]);
-    random_bytes
+    entropy::get().bytes(size)
+}
+
+/// Seconds since the Unix epoch.
+pub fn unix_time() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+/// Compares in constant time, so that secrets cannot be guessed byte by byte.
+pub fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len() && a.bytes().zip(b.bytes()).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Parses a hex string, in either case, into bytes.
+pub fn hex_to_bytes(hex: &str) -> Option<Vec<u8>> {
+    // `from_str_radix` alone would take a leading sign, as in "+f".
+    if hex.len() % 2 != 0 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    (0..hex.len()).step_by(2).map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok()).collect()
+}
+
+const SECS_PER_DAY: u64 = 86_400;
+
+/// Parses a `YYYY-MM-DD` date into the Unix time at which it starts, in UTC.
+pub fn parse_date(date: &str) -> Option<u64> {
+    let mut parts = date.splitn(3, '-');
+    let mut field = |len: usize| {
+        parts.next()
+            .filter(|part| part.len() == len && part.bytes().all(|b| b.is_ascii_digit()))
+            .and_then(|part| part.parse::<i64>().ok())
+    };
+    let (year, month, day) = (field(4)?, field(2)?, field(2)?);
+    if year < 1970 || month < 1 || month > 12 || day < 1 || day > 31 {
+        return None;
+    }
+    // Days since the epoch of the proleptic Gregorian calendar, by Howard Hinnant's algorithm.
+    let (y, m) = if month <= 2 { (year - 1, month + 9) } else { (year, month - 3) };
+    let era = y / 400;
+    let year_of_era = y - era * 400;
+    let day_of_year = (153 * m + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+    if format_date(days as u64 * SECS_PER_DAY) != date {
+        // E.g. February 30th.
+        return None;
+    }
+    Some(days as u64 * SECS_PER_DAY)
+}
+
+/// Day 0 of the Unix epoch was a Thursday.
+const WEEKDAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+const MONTHS: [&str; 12] =
+    ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+
+/// Formats a Unix time as an HTTP date, e.g. `Sun, 06 Nov 1994 08:49:37 GMT`.
+pub fn format_http_date(time: u64) -> String {
+    let date = format_date(time);
+    let month: usize = date[5..7].parse().unwrap();
+    let secs = time % SECS_PER_DAY;
+    format!("{}, {} {} {} {:02}:{:02}:{:02} GMT",
+            WEEKDAYS[(time / SECS_PER_DAY % 7) as usize], &date[8..], MONTHS[month - 1],
+            &date[..4], secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+/// Parses an HTTP date in the format of `format_http_date`, the one clients send nowadays, into a
+/// Unix time. The weekday is not checked.
+pub fn parse_http_date(date: &str) -> Option<u64> {
+    let parts: Vec<_> = date.split(' ').collect();
+    if parts.len() != 6 || !parts[0].ends_with(',') || parts[4].len() != 8 || parts[5] != "GMT" {
+        return None;
+    }
+    let month = MONTHS.iter().position(|&month| month == parts[2])? + 1;
+    let day = parse_date(&format!("{}-{:02}-{}", parts[3], month, parts[1]))?;
+    let clock: Vec<u64> = parts[4].split(':')
+        .filter(|field| field.len() == 2 && field.bytes().all(|b| b.is_ascii_digit()))
+        .map(|field| field.parse().unwrap())
+        .collect();
+    if clock.len() != 3 || clock[0] > 23 || clock[1] > 59 || clock[2] > 59 {
+        return None;
+    }
+    Some(day + clock[0] * 3600 + clock[1] * 60 + clock[2])
+}
+
+/// Formats the UTC date of a Unix time as `YYYY-MM-DD`.
+pub fn format_date(time: u64) -> String {
+    let days = (time / SECS_PER_DAY) as i64 + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}", year, month, day)
 }
@@ -0,0 +1,245 @@
+//! Personal round webhooks
+//!
+//! A player can have the outcome of each of their rounds posted to a URL of their own, e.g. a
+//! personal tracker, set on `/profile`. Like the mails of play by email, the posts are queued until
+//! a gateway collects them with `POST /admin/webhooks/drain` and delivers them: every delivery
+//! holds the URL, the JSON body to post and the `X-Signature` header to send along. The signature
+//! is the same as the one `/inbound/email` checks, `sha256=` followed by the hex HMAC-SHA256 of the
+//! body, but under a secret of the player's own, shown to them with the URL.
+//!
+//! Since the gateway posts from inside the network, a URL is only taken if its host resolves to
+//! public addresses alone, not to loopback, private, link-local or other reserved ones. Each player
+//! has a queue of their own, so that a busy player only ever drops their own oldest posts, and
+//! test posts are limited to `TESTS_PER_HOUR`.
+
+use std::collections::{HashMap, VecDeque};
+use std::mem;
+use std::net::{IpAddr, ToSocketAddrs};
+use std::sync::Mutex;
+
+use rocket_contrib::Value;
+
+use commitment::hmac_sha256;
+use events::{Event, Subscriber};
+use poison::Recover;
+use profile;
+#[cfg(feature = "templates")]
+use throttle::Throttle;
+use util::{bytes_to_hex, gen_random_bytes, unix_time};
+
+pub const SIGNATURE_HEADER: &str = "X-Signature";
+pub const SIGNATURE_PREFIX: &str = "sha256=";
+const MAX_URL_LEN: usize = 2048;
+/// Deliveries kept at most per player; their oldest are dropped first if nobody collects them.
+const QUEUE_CAPACITY: usize = 100;
+#[cfg(feature = "templates")]
+const TESTS_PER_HOUR: u32 = 5;
+
+lazy_static! {
+    // User name -> deliveries, the oldest first
+    static ref QUEUES: Mutex<HashMap<String, VecDeque<Delivery>>> = Mutex::new(HashMap::new());
+    // User name -> test posts this hour
+    #[cfg(feature = "templates")]
+    static ref TESTS: Throttle = Throttle::new(TESTS_PER_HOUR, 3600);
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum WebhookError {
+    /// Not an HTTP(S) URL.
+    InvalidUrl,
+    /// The host does not resolve.
+    UnknownHost,
+    /// The host resolves to a loopback, private or otherwise reserved address.
+    PrivateAddress,
+    NoWebhook,
+    /// `TESTS_PER_HOUR` test posts were queued this hour already.
+    TooManyTests,
+}
+
+impl WebhookError {
+    /// The code of the message in `flash`.
+    pub fn code(&self) -> &'static str {
+        match *self {
+            WebhookError::InvalidUrl => "webhook.invalid-url",
+            WebhookError::UnknownHost => "webhook.unknown-host",
+            WebhookError::PrivateAddress => "webhook.private-address",
+            WebhookError::NoWebhook => "webhook.none",
+            WebhookError::TooManyTests => "webhook.too-many-tests",
+        }
+    }
+}
+
+/// Where a player's rounds are posted.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Webhook {
+    pub url: String,
+    /// Hex; the key of the signatures.
+    pub secret: String,
+}
+
+impl Webhook {
+    /// A webhook to `url` under a new secret, if `url` is an HTTP(S) URL whose host resolves to
+    /// public addresses only. Resolving blocks, so never call it with a lock held.
+    pub fn new(url: &str) -> Result<Webhook, WebhookError> {
+        let url = url.trim();
+        if url.len() > MAX_URL_LEN || url.contains(char::is_whitespace) {
+            return Err(WebhookError::InvalidUrl);
+        }
+        let (host, port) = host_and_port(url).ok_or(WebhookError::InvalidUrl)?;
+        let addresses: Vec<IpAddr> = (host, port).to_socket_addrs()
+            .map_err(|_| WebhookError::UnknownHost)?
+            .map(|address| address.ip())
+            .collect();
+        if addresses.is_empty() {
+            return Err(WebhookError::UnknownHost);
+        }
+        if !addresses.into_iter().all(is_public) {
+            return Err(WebhookError::PrivateAddress);
+        }
+        Ok(Webhook {
+            url: url.to_owned(),
+            secret: bytes_to_hex(&gen_random_bytes(32)),
+        })
+    }
+}
+
+/// The host and the port of an HTTP(S) URL, e.g. `("[::1]", 8080)` of `http://[::1]:8080/x`,
+/// without the brackets.
+fn host_and_port(url: &str) -> Option<(&str, u16)> {
+    let (rest, default_port) = if url.starts_with("https://") {
+        (&url["https://".len()..], 443)
+    } else if url.starts_with("http://") {
+        (&url["http://".len()..], 80)
+    } else {
+        return None;
+    };
+    let authority = rest.split(|c| c == '/' || c == '?' || c == '#').next().unwrap_or("");
+    // Whatever user info comes before the host.
+    let authority = authority.rsplit('@').next().unwrap_or("");
+    let (host, port) = if authority.starts_with('[') {
+        let end = authority.find(']')?;
+        (&authority[1..end], &authority[end + 1..])
+    } else {
+        match authority.find(':') {
+            Some(colon) => (&authority[..colon], &authority[colon..]),
+            None => (authority, ""),
+        }
+    };
+    let port = match port {
+        "" => default_port,
+        port if port.starts_with(':') => port[1..].parse().ok()?,
+        _ => return None,
+    };
+    if host.is_empty() { None } else { Some((host, port)) }
+}
+
+/// Whether `ip` is reachable on the internet at large, rather than a loopback, private,
+/// link-local, shared, multicast or documentation address, or one of IPv6's local ranges.
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let octets = ip.octets();
+            // 100.64.0.0/10, carrier-grade NAT
+            let shared = octets[0] == 100 && octets[1] & 0xc0 == 64;
+            !(ip.is_loopback() || ip.is_private() || ip.is_link_local() || ip.is_unspecified()
+                || ip.is_broadcast() || ip.is_multicast() || ip.is_documentation() || shared
+                || octets[0] == 0 || octets[0] >= 240)
+        }
+        IpAddr::V6(ip) => {
+            if let Some(mapped) = ip.to_ipv4() {
+                // IPv4-mapped and -compatible addresses, `::1` aside
+                if !ip.is_loopback() {
+                    return is_public(IpAddr::V4(mapped));
+                }
+            }
+            let first = ip.segments()[0];
+            // fc00::/7, unique local; fe80::/10, link-local; 2001:db8::/32, documentation
+            let local = first & 0xfe00 == 0xfc00 || first & 0xffc0 == 0xfe80;
+            let documentation = first == 0x2001 && ip.segments()[1] == 0x0db8;
+            !(ip.is_loopback() || ip.is_unspecified() || ip.is_multicast() || local
+                || documentation)
+        }
+    }
+}
+
+/// A post for the gateway to make.
+#[derive(Clone, Debug, Serialize)]
+pub struct Delivery {
+    pub url: String,
+    /// The value of the `X-Signature` header.
+    pub signature: String,
+    pub body: String,
+}
+
+/// The value of the `X-Signature` header of `body`, signed with `secret`.
+pub fn signature(secret: &str, body: &[u8]) -> String {
+    format!("{}{}", SIGNATURE_PREFIX, bytes_to_hex(&hmac_sha256(secret.as_bytes(), body)))
+}
+
+fn deliver(user_name: &str, webhook: &Webhook, payload: &Value) {
+    let body = payload.to_string();
+    let delivery = Delivery {
+        url: webhook.url.clone(),
+        signature: signature(&webhook.secret, body.as_bytes()),
+        body: body,
+    };
+    let mut queues = QUEUES.lock().recover();
+    let queue = queues.entry(user_name.to_owned()).or_insert_with(VecDeque::new);
+    if queue.len() >= QUEUE_CAPACITY {
+        queue.pop_front();
+    }
+    queue.push_back(delivery);
+}
+
+/// Queues a test post to the webhook of `user_name`, unless they have none or sent
+/// `TESTS_PER_HOUR` this hour.
+#[cfg(feature = "templates")]
+pub fn test(user_name: &str) -> Result<(), WebhookError> {
+    let webhook = profile::get(user_name)
+        .and_then(|profile| profile.webhook)
+        .ok_or(WebhookError::NoWebhook)?;
+    if !TESTS.admit(user_name) {
+        return Err(WebhookError::TooManyTests);
+    }
+    deliver(user_name, &webhook, &json!({
+        "event": "test",
+        "user_name": user_name,
+        "at": unix_time(),
+    }));
+    Ok(())
+}
+
+/// Takes every queued delivery, player by player.
+pub fn drain() -> Vec<Delivery> {
+    let queues = mem::replace(&mut *QUEUES.lock().recover(), HashMap::new());
+    queues.into_iter().flat_map(|(_, queue)| queue).collect()
+}
+
+/// Posts the rounds of players who set a webhook, and drops the deliveries of deleted accounts.
+pub struct Dispatcher;
+
+impl Subscriber for Dispatcher {
+    fn notify(&self, event: &Event) {
+        match *event {
+            Event::RoundPlayed { ref user_name, kind, ref human, ref computer, verdict, .. } => {
+                let webhook = match profile::get(user_name).and_then(|profile| profile.webhook) {
+                    Some(webhook) => webhook,
+                    None => return,
+                };
+                deliver(user_name, &webhook, &json!({
+                    "event": "round",
+                    "user_name": user_name,
+                    "kind": kind,
+                    "human": human,
+                    "computer": computer,
+                    "verdict": verdict,
+                    "at": unix_time(),
+                }));
+            }
+            Event::AccountDeleted { ref user_name } => {
+                QUEUES.lock().recover().remove(user_name);
+            }
+            _ => {}
+        }
+    }
+}
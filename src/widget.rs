@@ -0,0 +1,302 @@
+//! Embeddable widgets under `/widget`
+//!
+//! They need no login, so that other sites can embed them in an iframe (HTML) or fetch them from
+//! a script (JSON, with CORS allowed for the `widget_cors_origin` config extra). The leaderboard is
+//! also pushed as server-sent events from `/events/leaderboard`. Having no player to take a
+//! `Locale` from, the HTML leaderboard writes its counts in English unless the embedding site asks
+//! for another language, e.g. `/widget/leaderboard?lang=de`.
+
+use std::cmp;
+use std::io::{self, Read};
+use std::sync::atomic::{AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::time::Duration;
+
+use rocket::http::Status;
+use rocket::request::{Request, State};
+use rocket::response::{self, Failure, Responder, Response};
+use rocket::Config;
+use serde_json;
+#[cfg(feature = "templates")]
+use rocket_contrib::Template;
+use rocket_contrib::Json;
+
+use events::{self, Event};
+use game::{RoundKind, Scoreboard};
+#[cfg(feature = "templates")]
+use locale::{Language, Locale};
+#[cfg(feature = "templates")]
+use render::render;
+#[cfg(feature = "templates")]
+use team;
+use SESSIONS;
+
+const LEADERBOARD_SIZE: usize = 10;
+/// Widgets may be a little stale; this keeps embedding sites from hammering the server.
+const CACHE_CONTROL: &str = "public, max-age=30";
+/// Rocket 0.3 hands a chunked body to the connection only once a whole chunk is read, and the
+/// connection's buffer is flushed only once full, 8 KiB. Events are padded to fill chunks of that
+/// size, so that each goes out as soon as it happens.
+const EVENT_CHUNK_SIZE: usize = 8 * 1024;
+/// How often an idle stream sends a comment, to notice clients that went away.
+const KEEP_ALIVE: Duration = Duration::from_secs(15);
+
+static OPEN_STREAMS: AtomicUsize = ATOMIC_USIZE_INIT;
+
+/// The `widget_cors_origin` config extra, e.g. `"*"` or `"https://example.com"`.
+pub struct CorsOrigin(Option<String>);
+
+impl CorsOrigin {
+    pub fn from_config(config: &Config) -> CorsOrigin {
+        CorsOrigin(config.get_str("widget_cors_origin").ok().map(|origin| origin.to_owned()))
+    }
+}
+
+/// Each event stream holds on to a worker thread, so only the `max_event_streams` config extra
+/// (by default half of Rocket's workers) may be open at a time.
+pub struct EventStreams {
+    max_open: usize,
+}
+
+impl EventStreams {
+    pub fn from_config(config: &Config) -> Result<EventStreams, String> {
+        let max_open = match config.get_int("max_event_streams") {
+            Ok(n) if n >= 0 => n as usize,
+            Ok(n) => return Err(format!("`max_event_streams` must not be negative, got {}", n)),
+            Err(_) => cmp::max(config.workers as usize / 2, 1),
+        };
+        Ok(EventStreams { max_open: max_open })
+    }
+}
+
+#[derive(Clone, Serialize)]
+pub struct Entry {
+    pub rank: usize,
+    /// For a co-op team, its name followed by its members'.
+    pub user_name: String,
+    pub team: bool,
+    pub wins: usize,
+    pub ties: usize,
+    pub losses: usize,
+}
+
+/// The live sessions and co-op teams with the most classic wins.
+fn leaderboard() -> Vec<Entry> {
+    let mut entries = standings();
+    entries.truncate(LEADERBOARD_SIZE);
+    entries
+}
+
+/// Every live session and co-op team, ranked by classic wins; fewer losses break ties.
+pub fn standings() -> Vec<Entry> {
+    let mut scores: Vec<_> = {
+        let sessions = SESSIONS.read_all();
+        sessions.values()
+            .map(|session| (session.user_name.clone(), false, session.score))
+            .collect()
+    };
+    scores.extend(team_scores());
+    scores.sort_by(|a, b| b.2.wins.cmp(&a.2.wins).then(a.2.losses.cmp(&b.2.losses)));
+    scores.into_iter()
+        .enumerate()
+        .map(|(i, (user_name, team, score))| Entry {
+            rank: i + 1,
+            user_name: user_name,
+            team: team,
+            wins: score.wins,
+            ties: score.ties,
+            losses: score.losses,
+        })
+        .collect()
+}
+
+/// Every complete co-op team, as `(name and members, true, score)`.
+#[cfg(feature = "templates")]
+fn team_scores() -> Vec<(String, bool, Scoreboard)> {
+    team::scores().into_iter()
+        .map(|(name, members, score)| (format!("{} ({})", name, members), true, score))
+        .collect()
+}
+
+/// Teams are only formed on the pages.
+#[cfg(not(feature = "templates"))]
+fn team_scores() -> Vec<(String, bool, Scoreboard)> {
+    Vec::new()
+}
+
+/// Formats a server-sent event, padded with a comment line to a whole number of chunks.
+fn padded_event(event: &str) -> Vec<u8> {
+    let len = event.len() + 2;
+    let padded_len = (len + EVENT_CHUNK_SIZE - 1) / EVENT_CHUNK_SIZE * EVENT_CHUNK_SIZE;
+    let mut message = String::with_capacity(padded_len);
+    message.push_str(event);
+    message.push(':');
+    message.extend((len..padded_len).map(|_| ' '));
+    message.push('\n');
+    message.into_bytes()
+}
+
+pub fn moves_leaderboard(event: &Event) -> bool {
+    match *event {
+        Event::SessionStarted { .. } | Event::SessionEnded { .. } | Event::RoundVoided { .. } |
+        Event::TeamFormed { .. } | Event::TeamDisbanded { .. } |
+        Event::AccountDeleted { .. } => true,
+        Event::RoundPlayed { kind, ranked, .. } => ranked && kind == RoundKind::Classic,
+        Event::RoundCommitted { .. } | Event::MatchFinished { .. } => false,
+    }
+}
+
+/// The leaderboard as server-sent events: the current one first, then every change to it.
+pub struct LeaderboardEvents {
+    events: Receiver<Event>,
+    cors_origin: Option<String>,
+    last: Option<String>,
+    message: Vec<u8>,
+    sent: usize,
+}
+
+impl LeaderboardEvents {
+    fn next_message(&mut self) -> Option<Vec<u8>> {
+        loop {
+            let data = serde_json::to_string(&leaderboard()).unwrap();
+            if self.last.as_ref() != Some(&data) {
+                let event = format!("event: leaderboard\ndata: {}\n\n", data);
+                self.last = Some(data);
+                return Some(padded_event(&event));
+            }
+            loop {
+                match self.events.recv_timeout(KEEP_ALIVE) {
+                    Ok(ref event) if moves_leaderboard(event) => break,
+                    Ok(_) => {}
+                    Err(RecvTimeoutError::Timeout) => return Some(padded_event("")),
+                    Err(RecvTimeoutError::Disconnected) => return None,
+                }
+            }
+            // Catches up on a burst of events at once.
+            while self.events.try_recv().is_ok() {}
+        }
+    }
+}
+
+impl Read for LeaderboardEvents {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.sent == self.message.len() {
+            match self.next_message() {
+                Some(message) => {
+                    self.message = message;
+                    self.sent = 0;
+                }
+                None => return Ok(0),
+            }
+        }
+        let n = cmp::min(buf.len(), self.message.len() - self.sent);
+        buf[..n].copy_from_slice(&self.message[self.sent..self.sent + n]);
+        self.sent += n;
+        Ok(n)
+    }
+}
+
+impl Drop for LeaderboardEvents {
+    fn drop(&mut self) {
+        OPEN_STREAMS.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl<'r> Responder<'r> for LeaderboardEvents {
+    fn respond_to(mut self, _: &Request) -> response::Result<'r> {
+        let mut response = Response::build();
+        response.raw_header("Content-Type", "text/event-stream")
+            .raw_header("Cache-Control", "no-cache");
+        if let Some(origin) = self.cors_origin.take() {
+            response.raw_header("Access-Control-Allow-Origin", origin)
+                .raw_header("Vary", "Origin");
+        }
+        response.chunked_body(self, EVENT_CHUNK_SIZE as u64).ok()
+    }
+}
+
+/// Adds `Cache-Control` and, if configured, `Access-Control-Allow-Origin` to a response.
+pub struct Widget<R> {
+    inner: R,
+    cors_origin: Option<String>,
+}
+
+impl<'r, R: Responder<'r>> Responder<'r> for Widget<R> {
+    fn respond_to(self, request: &Request) -> response::Result<'r> {
+        let mut response = self.inner.respond_to(request)?;
+        response.set_raw_header("Cache-Control", CACHE_CONTROL);
+        if let Some(origin) = self.cors_origin {
+            response.set_raw_header("Access-Control-Allow-Origin", origin);
+            response.set_raw_header("Vary", "Origin");
+        }
+        Ok(response)
+    }
+}
+
+// ===== Routers =====
+
+#[cfg(feature = "templates")]
+#[derive(FromForm)]
+pub struct LeaderboardQuery {
+    lang: Option<String>,
+}
+
+#[cfg(feature = "templates")]
+#[get("/leaderboard")]
+pub fn leaderboard_html() -> Widget<Template> {
+    leaderboard_page(Language::default())
+}
+
+#[cfg(feature = "templates")]
+#[get("/leaderboard?<query>")]
+pub fn leaderboard_html_in(query: LeaderboardQuery) -> Widget<Template> {
+    let language = query.lang.and_then(|lang| lang.parse().ok()).unwrap_or_default();
+    leaderboard_page(language)
+}
+
+/// The HTML leaderboard, its counts written in `language`.
+#[cfg(feature = "templates")]
+fn leaderboard_page(language: Language) -> Widget<Template> {
+    let locale = Locale { language: language, utc_offset: 0 };
+    let entries: Vec<_> = leaderboard().into_iter()
+        .map(|entry| json!({
+            "rank": entry.rank,
+            "user_name": entry.user_name,
+            "team": entry.team,
+            "wins": locale.format_count(entry.wins as u64),
+            "ties": locale.format_count(entry.ties as u64),
+            "losses": locale.format_count(entry.losses as u64),
+        }))
+        .collect();
+    let context = json!({ "entries": entries, "lang": language.code() });
+    Widget {
+        inner: render("widget_leaderboard", &context),
+        cors_origin: None,
+    }
+}
+
+#[get("/leaderboard.json")]
+pub fn leaderboard_json(cors: State<CorsOrigin>) -> Widget<Json<Vec<Entry>>> {
+    Widget {
+        inner: Json(leaderboard()),
+        cors_origin: cors.0.clone(),
+    }
+}
+
+/// Fails with `503 Service Unavailable` while `max_event_streams` streams are open.
+#[get("/leaderboard")]
+pub fn leaderboard_events(streams: State<EventStreams>, cors: State<CorsOrigin>)
+    -> Result<LeaderboardEvents, Failure>
+{
+    if OPEN_STREAMS.fetch_add(1, Ordering::SeqCst) >= streams.max_open {
+        OPEN_STREAMS.fetch_sub(1, Ordering::SeqCst);
+        return Err(Failure(Status::ServiceUnavailable));
+    }
+    Ok(LeaderboardEvents {
+        events: events::subscribe(),
+        cors_origin: cors.0.clone(),
+        last: None,
+        message: Vec::new(),
+        sent: 0,
+    })
+}
@@ -0,0 +1,175 @@
+//! Background worker queues
+//!
+//! Work that follows from a request but need not hold up its response is handed to a `Queue`: a
+//! name, a capacity, a number of worker threads and the function that handles each item. The
+//! threads start with the first item submitted, and take the items in order.
+//!
+//! A queue is bounded. Once `capacity` items wait, `submit` refuses the next one and gives it back,
+//! and the caller does the work itself, on its own thread: a burst slows the requests behind it
+//! down instead of piling up in memory. An item whose handler panics is counted and dropped.
+//! How many items wait, how many were handled, refused and panicked is reported per queue on
+//! `/admin/metrics`.
+//!
+//! Once maintenance mode reports the server ready to shut down, see `maintenance`, every queue is
+//! paused like the `scheduler`: items already queued are still handled, new ones are refused, and
+//! the server is only ready once no item is left, so stopping the process loses none. The queues
+//! resume when maintenance mode is turned off.
+
+use std::collections::VecDeque;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Condvar, Mutex, Once, ONCE_INIT};
+use std::thread;
+
+use poison::Recover;
+
+lazy_static! {
+    /// Every queue an item was submitted to, in that order.
+    static ref QUEUES: Mutex<Vec<&'static Monitored>> = Mutex::new(Vec::new());
+}
+
+/// A bounded queue of items of type `T`, handled by worker threads of its own.
+pub struct Queue<T> {
+    name: &'static str,
+    capacity: usize,
+    threads: usize,
+    handler: fn(T),
+    state: Mutex<State<T>>,
+    /// Signalled when an item is queued.
+    work: Condvar,
+    start: Once,
+}
+
+struct State<T> {
+    items: VecDeque<T>,
+    paused: bool,
+    /// Items being handled right now, outside the lock.
+    busy: usize,
+    handled: u64,
+    rejected: u64,
+    panics: u64,
+}
+
+/// How a queue has been doing, for the metrics.
+pub struct QueueStats {
+    pub name: &'static str,
+    pub capacity: usize,
+    pub queued: usize,
+    pub handled: u64,
+    pub rejected: u64,
+    pub panics: u64,
+}
+
+/// What the pool needs of a queue, whatever its items.
+trait Monitored: Sync {
+    fn pause(&self) -> bool;
+    fn resume(&self);
+    fn stats(&self) -> QueueStats;
+}
+
+impl<T: Send + 'static> Queue<T> {
+    /// A queue of up to `capacity` items, handled by `threads` threads calling `handler`.
+    pub fn new(name: &'static str, capacity: usize, threads: usize, handler: fn(T)) -> Queue<T> {
+        Queue {
+            name: name,
+            capacity: capacity,
+            threads: threads.max(1),
+            handler: handler,
+            state: Mutex::new(State {
+                items: VecDeque::new(),
+                paused: false,
+                busy: 0,
+                handled: 0,
+                rejected: 0,
+                panics: 0,
+            }),
+            work: Condvar::new(),
+            start: ONCE_INIT,
+        }
+    }
+
+    /// Queues `item`. Gives it back if the queue is full or paused, for the caller to handle.
+    pub fn submit(&'static self, item: T) -> Result<(), T> {
+        self.start.call_once(|| {
+            QUEUES.lock().recover().push(self);
+            for _ in 0..self.threads {
+                thread::spawn(move || self.work());
+            }
+        });
+        let mut state = self.state.lock().recover();
+        if state.paused || state.items.len() >= self.capacity {
+            state.rejected += 1;
+            return Err(item);
+        }
+        state.items.push_back(item);
+        self.work.notify_one();
+        Ok(())
+    }
+
+    /// A worker thread.
+    fn work(&self) {
+        let mut state = self.state.lock().recover();
+        loop {
+            let item = match state.items.pop_front() {
+                Some(item) => item,
+                None => {
+                    state = self.work.wait(state).recover();
+                    continue;
+                }
+            };
+            state.busy += 1;
+            drop(state);
+
+            let handler = self.handler;
+            let panicked = panic::catch_unwind(AssertUnwindSafe(|| handler(item))).is_err();
+
+            state = self.state.lock().recover();
+            state.busy -= 1;
+            state.handled += 1;
+            if panicked {
+                state.panics += 1;
+                eprintln!("Worker of queue {} panicked", self.name);
+            }
+        }
+    }
+}
+
+impl<T: Send> Monitored for Queue<T> {
+    fn pause(&self) -> bool {
+        let mut state = self.state.lock().recover();
+        state.paused = true;
+        state.items.is_empty() && state.busy == 0
+    }
+
+    fn resume(&self) {
+        self.state.lock().recover().paused = false;
+    }
+
+    fn stats(&self) -> QueueStats {
+        let state = self.state.lock().recover();
+        QueueStats {
+            name: self.name,
+            capacity: self.capacity,
+            queued: state.items.len(),
+            handled: state.handled,
+            rejected: state.rejected,
+            panics: state.panics,
+        }
+    }
+}
+
+/// Refuses new items on every queue. Returns whether none is left queued or being handled either,
+/// which, once it is true, stays true until `resume`.
+pub fn pause() -> bool {
+    QUEUES.lock().recover().iter().fold(true, |drained, queue| queue.pause() && drained)
+}
+
+pub fn resume() {
+    for queue in QUEUES.lock().recover().iter() {
+        queue.resume();
+    }
+}
+
+/// Every queue, in the order they were first used.
+pub fn stats() -> Vec<QueueStats> {
+    QUEUES.lock().recover().iter().map(|queue| queue.stats()).collect()
+}
@@ -0,0 +1,59 @@
+// Plays through the JSON API under /api/v1, authenticated by the session cookies.
+(function () {
+    var digest = null;
+
+    function $(id) {
+        return document.getElementById(id);
+    }
+
+    function api(method, path, body) {
+        var init = { method: method, credentials: "same-origin", headers: {} };
+        if (body) {
+            init.headers["Content-Type"] = "application/json";
+            init.body = JSON.stringify(body);
+        }
+        return fetch("/api/v1" + path, init);
+    }
+
+    function showScore(stats) {
+        $("wins").textContent = stats.wins;
+        $("ties").textContent = stats.ties;
+        $("losses").textContent = stats.losses;
+    }
+
+    function showRound(nextDigest) {
+        digest = nextDigest;
+        $("digest").textContent = digest;
+    }
+
+    function play(hand) {
+        api("POST", "/play", { hand: hand, digest: digest })
+            .then(function (response) { return response.json(); })
+            .then(function (result) {
+                $("last").textContent = result.human + " (You) v.s. " + result.computer +
+                    " (Computer): " + result.verdict + ". Nonce " + result.random + ".";
+                showRound(result.next_digest);
+                return api("GET", "/stats").then(function (response) { return response.json(); });
+            })
+            .then(showScore);
+    }
+
+    api("GET", "/stats").then(function (response) {
+        if (!response.ok) {
+            $("login").hidden = false;
+            return;
+        }
+        $("game").hidden = false;
+        response.json().then(showScore);
+        api("GET", "/round")
+            .then(function (response) { return response.json(); })
+            .then(function (round) { showRound(round.digest); });
+    });
+
+    var buttons = document.querySelectorAll("button[data-hand]");
+    for (var i = 0; i < buttons.length; i++) {
+        buttons[i].addEventListener("click", function (event) {
+            play(event.target.getAttribute("data-hand"));
+        });
+    }
+})();
@@ -0,0 +1,27 @@
+// Polls /login/handoff while the login page shows a handoff QR code, and goes to the game as soon
+// as a logged-in device approved it.
+(function () {
+    var status = document.getElementById("handoff-status");
+    if (!status || !window.fetch) {
+        return;
+    }
+
+    function poll() {
+        fetch("/login/handoff", { credentials: "same-origin" })
+            .then(function (response) { return response.json(); })
+            .then(function (result) {
+                if (result.status === "approved") {
+                    window.location.href = "/";
+                } else if (result.status === "pending") {
+                    setTimeout(poll, 2000);
+                } else if (result.status === "refused") {
+                    status.textContent = "The login was refused; please log in with your name.";
+                } else {
+                    status.textContent = "The code expired; reload the page for a new one.";
+                }
+            })
+            .catch(function () { setTimeout(poll, 5000); });
+    }
+
+    setTimeout(poll, 2000);
+})();
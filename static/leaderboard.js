@@ -0,0 +1,40 @@
+// Keeps the leaderboard widget up to date from /events/leaderboard.
+(function () {
+    var table = document.getElementById("leaderboard");
+    if (!window.EventSource) {
+        return;
+    }
+
+    // Counts are written the way the page was, see its `lang`.
+    var lang = document.documentElement.lang || "en";
+    function count(n) {
+        return n.toLocaleString(lang);
+    }
+
+    function row(cells) {
+        var tr = document.createElement("tr");
+        cells.forEach(function (cell) {
+            var td = document.createElement("td");
+            td.textContent = cell;
+            tr.appendChild(td);
+        });
+        return tr;
+    }
+
+    new EventSource("/events/leaderboard").addEventListener("leaderboard", function (event) {
+        var entries = JSON.parse(event.data);
+        while (table.rows.length > 1) {
+            table.deleteRow(1);
+        }
+        entries.forEach(function (entry) {
+            var name = entry.team ? entry.user_name + " 👥" : entry.user_name;
+            table.appendChild(row([entry.rank, name, count(entry.wins), count(entry.ties),
+                                   count(entry.losses)]));
+        });
+        if (entries.length === 0) {
+            var empty = row(["Nobody is playing right now."]);
+            empty.firstChild.colSpan = 5;
+            table.appendChild(empty);
+        }
+    });
+})();
@@ -0,0 +1,88 @@
+// Passkey ceremonies: adds a passkey from the profile page, or logs in with one from the login
+// page. Both are hidden in browsers without WebAuthn, which keep the name form.
+(function () {
+    if (!window.PublicKeyCredential || !window.fetch) {
+        return;
+    }
+
+    function encode(buffer) {
+        var binary = "";
+        new Uint8Array(buffer).forEach(function (byte) { binary += String.fromCharCode(byte); });
+        return btoa(binary).replace(/\+/g, "-").replace(/\//g, "_").replace(/=+$/, "");
+    }
+
+    function decode(text) {
+        var binary = atob(text.replace(/-/g, "+").replace(/_/g, "/"));
+        return Uint8Array.from(binary, function (c) { return c.charCodeAt(0); });
+    }
+
+    function post(url, body) {
+        return fetch(url, {
+            method: "POST",
+            credentials: "same-origin",
+            headers: body ? { "Content-Type": "application/json" } : {},
+            body: body ? JSON.stringify(body) : undefined,
+        }).then(function (response) {
+            return response.json().then(function (result) {
+                if (!response.ok) {
+                    throw new Error(result.reason || "The server refused the passkey.");
+                }
+                return result;
+            });
+        });
+    }
+
+    function ceremony(id, run) {
+        var section = document.getElementById(id);
+        if (!section) {
+            return;
+        }
+        var status = section.querySelector(".passkey-status");
+        section.hidden = false;
+        section.querySelector("button").addEventListener("click", function () {
+            status.textContent = "Waiting for your authenticator…";
+            run().catch(function (error) {
+                status.textContent = error.name === "NotAllowedError"
+                    ? "Cancelled."
+                    : error.message;
+            });
+        });
+    }
+
+    ceremony("passkey-register", function () {
+        return post("/passkey/register/begin").then(function (options) {
+            options.challenge = decode(options.challenge);
+            options.user.id = decode(options.user.id);
+            options.excludeCredentials.forEach(function (credential) {
+                credential.id = decode(credential.id);
+            });
+            return navigator.credentials.create({ publicKey: options });
+        }).then(function (credential) {
+            return post("/passkey/register", {
+                id: credential.id,
+                clientDataJSON: encode(credential.response.clientDataJSON),
+                authenticatorData: encode(credential.response.getAuthenticatorData()),
+                publicKey: encode(credential.response.getPublicKey()),
+                publicKeyAlgorithm: credential.response.getPublicKeyAlgorithm(),
+            });
+        }).then(function () {
+            window.location.reload();
+        });
+    });
+
+    ceremony("passkey-login", function () {
+        return post("/passkey/login/begin").then(function (options) {
+            options.challenge = decode(options.challenge);
+            return navigator.credentials.get({ publicKey: options });
+        }).then(function (credential) {
+            return post("/passkey/login", {
+                id: credential.id,
+                clientDataJSON: encode(credential.response.clientDataJSON),
+                authenticatorData: encode(credential.response.authenticatorData),
+                signature: encode(credential.response.signature),
+            });
+        }).then(function () {
+            window.location.href = "/";
+        });
+    });
+})();
@@ -0,0 +1,35 @@
+// Draws the outcomes of the last classic rounds from /api/v1/stats/sparkline next to the
+// scoreboard: a bar up for a win, down for a loss, a dot for a tie.
+(function () {
+    var SVG = "http://www.w3.org/2000/svg";
+    var svg = document.getElementById("sparkline");
+    if (!svg || !window.fetch) {
+        return;
+    }
+
+    function bar(x, y, height, color) {
+        var rect = document.createElementNS(SVG, "rect");
+        rect.setAttribute("x", x);
+        rect.setAttribute("y", y);
+        rect.setAttribute("width", 3);
+        rect.setAttribute("height", height);
+        rect.setAttribute("fill", color);
+        svg.appendChild(rect);
+    }
+
+    fetch("/api/v1/stats/sparkline", { credentials: "same-origin" })
+        .then(function (response) { return response.ok ? response.json() : []; })
+        .then(function (outcomes) {
+            var middle = svg.getAttribute("height") / 2;
+            outcomes.forEach(function (outcome, i) {
+                var x = i * 4;
+                if (outcome > 0) {
+                    bar(x, 0, middle, "#2a2");
+                } else if (outcome < 0) {
+                    bar(x, middle, middle, "#c22");
+                } else {
+                    bar(x, middle - 1, 2, "#888");
+                }
+            });
+        });
+})();
@@ -0,0 +1,67 @@
+//! What a session has to prove before acting for the whole account
+
+#![cfg(feature = "templates")]
+
+extern crate rocket;
+extern crate rock_paper_scissors;
+
+use rocket::http::{ContentType, Cookie, Header, Status};
+use rocket::local::{Client, LocalRequest};
+
+fn client() -> Client {
+    Client::new(rock_paper_scissors::rocket()).expect("valid rocket")
+}
+
+/// The cookies of a session logged in as `user_name`, by name only.
+fn log_in(client: &Client, user_name: &str, user_agent: &str) -> Vec<Cookie<'static>> {
+    let login = client.post("/login")
+        .header(ContentType::Form)
+        .header(Header::new("User-Agent", user_agent.to_owned()))
+        .body(format!("user_name={}", user_name))
+        .dispatch();
+    login.headers()
+        .get("Set-Cookie")
+        .filter_map(|value| Cookie::parse_encoded(value.to_owned()).ok())
+        .collect()
+}
+
+fn with<'c>(mut request: LocalRequest<'c>, cookies: &[Cookie<'static>]) -> LocalRequest<'c> {
+    for cookie in cookies {
+        request = request.cookie(cookie.clone());
+    }
+    request
+}
+
+/// The token of the forms on `/profile`, for the session of `cookies`.
+fn csrf_token(client: &Client, cookies: &[Cookie<'static>]) -> String {
+    let page = with(client.get("/profile"), cookies).dispatch().body_string().unwrap();
+    let start = page.find(r#"name="csrf" value=""#).unwrap() + r#"name="csrf" value=""#.len();
+    page[start..].split('"').next().unwrap().to_owned()
+}
+
+#[test]
+fn a_login_by_name_only_does_not_log_the_owner_out_everywhere() {
+    let client = client();
+    // The first login opens the account, so it is verified; the next one by name is not.
+    let owner = log_in(&client, "everywhere-owner", "home");
+    let other = log_in(&client, "everywhere-owner", "elsewhere");
+
+    let token = csrf_token(&client, &other);
+    let response = with(client.post("/account/logout-everywhere"), &other)
+        .header(ContentType::Form)
+        .body(format!("csrf={}", token))
+        .dispatch();
+    assert_eq!(response.status(), Status::SeeOther);
+    assert_eq!(response.headers().get_one("Location"), Some("/profile"));
+    let flash = response.headers()
+        .get("Set-Cookie")
+        .filter_map(|value| Cookie::parse_encoded(value.to_owned()).ok())
+        .find(|cookie| cookie.name() == "_flash")
+        .unwrap();
+    assert!(flash.value().contains("account.unverified"));
+
+    let response = with(client.get("/api/v1/profile"), &owner).dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let response = with(client.get("/api/v1/profile"), &other).dispatch();
+    assert_eq!(response.status(), Status::Ok);
+}
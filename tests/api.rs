@@ -0,0 +1,165 @@
+//! Failures of the API, answered with the error envelope, and of the pages, answered without it,
+//! conditional GETs and resent plays
+
+extern crate rocket;
+extern crate rock_paper_scissors;
+extern crate serde_json;
+
+use std::thread;
+use std::time::Duration;
+
+use rocket::http::{ContentType, Cookie, Header, Status};
+use rocket::local::{Client, LocalRequest, LocalResponse};
+use serde_json::Value;
+
+fn client() -> Client {
+    Client::new(rock_paper_scissors::rocket()).expect("valid rocket")
+}
+
+/// The `code` of the error envelope `response` carries.
+fn code(response: &mut LocalResponse) -> String {
+    assert_eq!(response.content_type(), Some(ContentType::JSON));
+    let body: Value = serde_json::from_str(&response.body_string().unwrap()).unwrap();
+    assert!(body["message"].is_string());
+    assert!(body.get("details").is_some());
+    body["code"].as_str().unwrap().to_owned()
+}
+
+/// The cookies of a session logged in as `user_name`.
+fn log_in(client: &Client, user_name: &str) -> Vec<Cookie<'static>> {
+    let login = client.post("/login")
+        .header(ContentType::Form)
+        .body(format!("user_name={}", user_name))
+        .dispatch();
+    login.headers()
+        .get("Set-Cookie")
+        .filter_map(|value| Cookie::parse_encoded(value.to_owned()).ok())
+        .collect()
+}
+
+#[test]
+fn api_requests_without_a_session_have_no_session() {
+    let client = client();
+    let mut response = client.get("/api/v1/round").dispatch();
+    assert_eq!(response.status(), Status::Unauthorized);
+    assert_eq!(code(&mut response), "no-session");
+}
+
+#[test]
+fn api_bodies_that_do_not_parse_are_enveloped() {
+    let client = client();
+    let cookies = log_in(&client, "enveloped");
+    let mut request = client.post("/api/v1/play").header(ContentType::JSON).body("{\"hand\":");
+    for cookie in cookies {
+        request = request.cookie(cookie);
+    }
+    let mut response = request.dispatch();
+    assert!(response.status() == Status::BadRequest
+                || response.status() == Status::UnprocessableEntity);
+    let code = code(&mut response);
+    assert!(code == "bad-request" || code == "invalid-body", "unexpected code {}", code);
+}
+
+#[test]
+fn admin_failures_are_enveloped() {
+    // Without an `admin_token`, the admin routes are not found.
+    let client = client();
+    let mut response = client.get("/admin/bans").dispatch();
+    assert_eq!(response.status(), Status::NotFound);
+    assert_eq!(code(&mut response), "not-found");
+}
+
+#[test]
+fn page_failures_are_not_enveloped() {
+    let client = client();
+    let response = client.post("/login").header(ContentType::Form).body("nonsense=1").dispatch();
+    assert_eq!(response.status(), Status::UnprocessableEntity);
+    assert_eq!(response.content_type(), Some(ContentType::HTML));
+}
+
+#[test]
+fn only_the_etag_makes_a_get_conditional() {
+    let client = client();
+    let cookies = log_in(&client, "polling");
+    let get = |header: Option<(&'static str, String)>| {
+        let mut request = client.get("/api/v1/stats");
+        for cookie in &cookies {
+            request = request.cookie(cookie.clone());
+        }
+        if let Some((name, value)) = header {
+            request = request.header(Header::new(name, value));
+        }
+        request.dispatch()
+    };
+
+    let response = get(None);
+    assert_eq!(response.status(), Status::Ok);
+    let etag = response.headers().get_one("ETag").unwrap().to_owned();
+    assert_eq!(get(Some(("If-None-Match", etag))).status(), Status::NotModified);
+    let since = "Fri, 01 Jan 2100 00:00:00 GMT".to_owned();
+    assert_eq!(get(Some(("If-Modified-Since", since))).status(), Status::Ok);
+}
+
+#[test]
+fn a_play_sent_twice_is_answered_twice_within_the_window() {
+    let client = client();
+    let cookies = log_in(&client, "double-clicker");
+    let send = |mut request: LocalRequest| {
+        for cookie in &cookies {
+            request = request.cookie(cookie.clone());
+        }
+        request.dispatch()
+    };
+    let body = |response: &mut LocalResponse| -> Value {
+        serde_json::from_str(&response.body_string().unwrap()).unwrap()
+    };
+    let digest = body(&mut send(client.get("/api/v1/round")))["digest"].clone();
+    let play = || {
+        let play = format!(r#"{{"hand": "rock", "digest": {}}}"#, digest);
+        send(client.post("/api/v1/play").header(ContentType::JSON).body(play))
+    };
+
+    let mut first = play();
+    assert_eq!(first.status(), Status::Ok);
+    let mut again = play();
+    assert_eq!(again.status(), Status::Ok);
+    assert_eq!(body(&mut again)["receipt"], body(&mut first)["receipt"]);
+
+    // Past `REPEAT_WINDOW`, the round is no longer pending.
+    thread::sleep(Duration::from_millis(600));
+    let mut late = play();
+    assert_eq!(late.status(), Status::Conflict);
+    assert_eq!(code(&mut late), "no-pending-round");
+}
+
+#[test]
+fn only_a_verified_session_sees_the_owner_fields_of_its_profile() {
+    let client = client();
+    let owner = log_in(&client, "profile-owner");
+    // A second login under the name, from another device, is by name only.
+    let login = client.post("/login")
+        .header(ContentType::Form)
+        .header(Header::new("User-Agent", "elsewhere"))
+        .body("user_name=profile-owner")
+        .dispatch();
+    let other: Vec<_> = login.headers()
+        .get("Set-Cookie")
+        .filter_map(|value| Cookie::parse_encoded(value.to_owned()).ok())
+        .collect();
+    let profile = |cookies: &[Cookie<'static>]| -> Value {
+        let mut request = client.get("/api/v1/profile");
+        for cookie in cookies {
+            request = request.cookie(cookie.clone());
+        }
+        let mut response = request.dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        serde_json::from_str(&response.body_string().unwrap()).unwrap()
+    };
+
+    let seen = profile(&owner);
+    assert_eq!(seen["alerts"][0]["user_agent"], "elsewhere");
+    assert_eq!(seen["devices"].as_array().map(Vec::len), Some(2));
+    let seen = profile(&other);
+    assert!(seen.get("alerts").is_none() && seen.get("devices").is_none());
+    assert!(seen["classic"].is_object());
+}
@@ -0,0 +1,114 @@
+//! Static files: what escapes the root, content types and byte ranges
+
+#![cfg(feature = "templates")]
+
+extern crate rocket;
+extern crate rock_paper_scissors;
+
+use std::env;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::PathBuf;
+use std::process;
+use std::sync::{Once, ONCE_INIT};
+
+use rocket::http::{Header, Status};
+use rocket::local::{Client, LocalResponse};
+
+static STATIC_DIR: Once = ONCE_INIT;
+
+fn write(path: PathBuf, content: &[u8]) {
+    File::create(path).unwrap().write_all(content).unwrap();
+}
+
+/// A client serving a static root of its own, the same for every test of the run, with a file
+/// next to it that nothing under `/static` may reach.
+fn client() -> Client {
+    STATIC_DIR.call_once(|| {
+        let dir = env::temp_dir().join(format!("rps-static-{}", process::id()));
+        let root = dir.join("root");
+        fs::create_dir_all(&root).unwrap();
+        write(dir.join("outside.txt"), b"not for you");
+        write(root.join("digits.txt"), b"0123456789");
+        write(root.join(".secret"), b"hidden");
+        write(root.join("icon.svg"), b"<svg xmlns=\"http://www.w3.org/2000/svg\"/>");
+        write(root.join("font.woff2"), b"wOF2");
+        write(root.join("font.ttf"), b"true");
+        #[cfg(unix)]
+        ::std::os::unix::fs::symlink(dir.join("outside.txt"), root.join("escape.txt")).unwrap();
+        // As `--static-dir` does, see `main`.
+        env::set_var("ROCKET_STATIC_DIR", root.to_str().unwrap());
+    });
+    Client::new(rock_paper_scissors::rocket()).expect("valid rocket")
+}
+
+fn get<'c>(client: &'c Client, path: &str, range: Option<&str>) -> LocalResponse<'c> {
+    let mut request = client.get(path.to_owned());
+    if let Some(range) = range {
+        request = request.header(Header::new("Range", range.to_owned()));
+    }
+    request.dispatch()
+}
+
+#[test]
+fn nothing_outside_the_root_or_hidden_is_served() {
+    let client = client();
+    assert_eq!(get(&client, "/static/digits.txt", None).status(), Status::Ok);
+    for path in &["/static/../outside.txt", "/static/%2e%2e/outside.txt",
+                  "/static/%2E%2E/outside.txt", "/static/..%2foutside.txt",
+                  "/static/%2e%2e%2Foutside.txt", "/static/.secret", "/static/%2esecret"] {
+        assert_eq!(get(&client, path, None).status(), Status::NotFound, "{}", path);
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn symlinks_out_of_the_root_are_not_followed() {
+    let client = client();
+    assert_eq!(get(&client, "/static/escape.txt", None).status(), Status::NotFound);
+}
+
+#[test]
+fn fonts_and_svg_get_their_registered_types() {
+    let client = client();
+    let content_type = |path| {
+        get(&client, path, None).headers().get_one("Content-Type").map(str::to_owned)
+    };
+    assert_eq!(content_type("/static/icon.svg"), Some("image/svg+xml".to_owned()));
+    assert_eq!(content_type("/static/font.woff2"), Some("font/woff2".to_owned()));
+    assert_eq!(content_type("/static/font.ttf"), Some("font/ttf".to_owned()));
+}
+
+#[test]
+fn single_ranges_are_served_in_part() {
+    let client = client();
+    for &(range, content_range, body) in &[("bytes=2-5", "bytes 2-5/10", "2345"),
+                                           ("bytes=7-", "bytes 7-9/10", "789"),
+                                           ("bytes=-3", "bytes 7-9/10", "789"),
+                                           ("bytes=8-20", "bytes 8-9/10", "89")] {
+        let mut response = get(&client, "/static/digits.txt", Some(range));
+        assert_eq!(response.status(), Status::PartialContent, "{}", range);
+        assert_eq!(response.headers().get_one("Content-Range"), Some(content_range));
+        assert_eq!(response.headers().get_one("Accept-Ranges"), Some("bytes"));
+        assert_eq!(response.body_string(), Some(body.to_owned()));
+    }
+}
+
+#[test]
+fn unsatisfiable_ranges_are_refused_with_the_length() {
+    let client = client();
+    for range in &["bytes=10-", "bytes=20-30", "bytes=5-2", "bytes=-0"] {
+        let response = get(&client, "/static/digits.txt", Some(*range));
+        assert_eq!(response.status(), Status::RangeNotSatisfiable, "{}", range);
+        assert_eq!(response.headers().get_one("Content-Range"), Some("bytes */10"));
+    }
+}
+
+#[test]
+fn several_ranges_get_the_whole_file() {
+    let client = client();
+    let mut response = get(&client, "/static/digits.txt", Some("bytes=0-1,4-5"));
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.headers().get_one("Content-Range"), None);
+    assert_eq!(response.body_string(), Some("0123456789".to_owned()));
+}
@@ -0,0 +1,86 @@
+//! Backups of the sessions and the profiles, and restoring them
+
+extern crate rocket;
+extern crate rock_paper_scissors;
+#[macro_use] extern crate serde_json;
+extern crate sha2;
+
+use std::env;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::PathBuf;
+use std::process;
+
+use rocket::config::{Config, Environment};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use rock_paper_scissors::account::{self, Proof};
+use rock_paper_scissors::backup::{self, BackupError};
+use rock_paper_scissors::profile;
+use rock_paper_scissors::revocation::Revocations;
+use rock_paper_scissors::util::bytes_to_hex;
+
+/// Sets the `backup_dir`, the same for every test of the run.
+fn backup_dir() -> PathBuf {
+    let dir = env::temp_dir().join(format!("rps-backups-{}", process::id()));
+    let config = Config::build(Environment::Development)
+        .extra("backup_dir", dir.to_str().unwrap())
+        .finalize()
+        .unwrap();
+    backup::configure(&config).unwrap();
+    dir
+}
+
+/// Writes the backup file `name` by hand, with the hash of `hashed` and the content `backup`.
+fn write_backup(dir: &PathBuf, name: &str, hashed: &Value, backup: &Value) {
+    let sha256 = bytes_to_hex(&Sha256::digest(serde_json::to_string(hashed).unwrap().as_bytes()));
+    let file = json!({ "sha256": sha256, "backup": backup });
+    File::create(dir.join(name)).unwrap().write_all(file.to_string().as_bytes()).unwrap();
+}
+
+#[test]
+fn a_backup_that_does_not_match_its_hash_is_refused() {
+    let dir = backup_dir();
+    let empty = json!({ "taken_at": 1, "sessions": [], "profiles": {} });
+    write_backup(&dir, "backup-1.json", &empty, &empty);
+
+    let edited = json!({ "taken_at": 2, "sessions": [], "profiles": {} });
+    write_backup(&dir, "backup-2.json", &empty, &edited);
+    match backup::restore("backup-2.json", &Revocations::new()) {
+        Err(BackupError::Corrupt(_)) => (),
+        other => panic!("restored an edited backup: {:?}", other.map(|_| ())),
+    }
+
+    let cut_short = fs::read_to_string(dir.join("backup-1.json")).unwrap();
+    File::create(dir.join("backup-3.json")).unwrap()
+        .write_all(cut_short[..cut_short.len() / 2].as_bytes()).unwrap();
+    match backup::restore("backup-3.json", &Revocations::new()) {
+        Err(BackupError::Corrupt(_)) => (),
+        other => panic!("restored a backup cut short: {:?}", other.map(|_| ())),
+    }
+
+    match backup::restore("../backup-1.json", &Revocations::new()) {
+        Err(BackupError::NoSuchBackup) => (),
+        other => panic!("restored a file outside the backup_dir: {:?}", other.map(|_| ())),
+    }
+}
+
+#[test]
+fn restoring_does_not_bring_deleted_accounts_back() {
+    backup_dir();
+    profile::complete_onboarding("kept");
+    profile::complete_onboarding("deleted");
+    let file = backup::take().unwrap();
+    let name = serde_json::to_value(&file).unwrap()["name"].as_str().unwrap().to_owned();
+
+    account::delete("deleted", Proof::RecoveryCode, &Revocations::new()).unwrap();
+    assert!(account::deleted_since("deleted", 0));
+    assert!(!account::deleted_since("kept", 0));
+
+    let restored = backup::restore(&name, &Revocations::new()).unwrap();
+    let restored = serde_json::to_value(&restored).unwrap();
+    assert_eq!(restored["left_out"], json!(1));
+    assert!(profile::get("kept").is_some());
+    assert!(profile::get("deleted").is_none());
+}
@@ -0,0 +1,48 @@
+//! Digest comparisons and reveal padding
+
+extern crate rock_paper_scissors;
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rock_paper_scissors::commitment::{self, RevealPadding};
+use rock_paper_scissors::game::Hand;
+
+const NONCE: &str = "00112233445566778899aabbccddeeff00112233445566778899aabbccddeeff";
+const DELAY_MS: u64 = 50;
+
+#[test]
+fn same_digest() {
+    let digest = commitment::digest(NONCE, Hand::Rock);
+    assert!(commitment::same_digest(&digest, &digest));
+    assert!(!commitment::same_digest(&digest, &commitment::digest(NONCE, Hand::Paper)));
+    assert!(!commitment::same_digest(&digest[..63], &digest));
+    assert!(!commitment::same_digest("", &digest));
+}
+
+fn padded(work: Duration) -> Duration {
+    let started = Instant::now();
+    {
+        let _padding = RevealPadding::with_delay(Duration::from_millis(DELAY_MS));
+        thread::sleep(work);
+    }
+    started.elapsed()
+}
+
+#[test]
+fn padding_hides_the_work() {
+    let delay = Duration::from_millis(DELAY_MS);
+    let work = delay / 2;
+    let fast = padded(Duration::from_millis(0));
+    let slow = padded(work);
+    assert!(fast >= delay && slow >= delay);
+    // The work goes on while padding rather than adding to it, within half the delay's worth of
+    // slack for a busy scheduler.
+    assert!(slow < delay + work, "{:?} v.s. {:?}", fast, slow);
+}
+
+#[test]
+fn padding_never_cuts_work_short() {
+    let work = Duration::from_millis(DELAY_MS + 10);
+    assert!(padded(work) >= work);
+}
@@ -0,0 +1,41 @@
+//! How long a login cookie is accepted
+
+extern crate rock_paper_scissors;
+
+use rock_paper_scissors::credentials::{Credentials, CLOCK_SKEW_SECS, MAX_AGE_SECS,
+                                        REISSUE_AFTER_SECS};
+use rock_paper_scissors::ids::UserId;
+
+const NOW: u64 = 1_500_000_000;
+
+fn issued_at(issued_at: u64) -> Credentials {
+    Credentials {
+        user_id: UserId::from_string("session-1".to_owned()),
+        user_name: "ada".to_owned(),
+        issued_at: issued_at,
+    }
+}
+
+#[test]
+fn old_cookies_are_refused() {
+    assert!(issued_at(NOW).is_current_at(NOW));
+    assert!(issued_at(NOW - MAX_AGE_SECS).is_current_at(NOW));
+    assert!(!issued_at(NOW - MAX_AGE_SECS - 1).is_current_at(NOW));
+    assert!(!issued_at(0).is_current_at(NOW));
+}
+
+#[test]
+fn cookies_from_the_future_are_refused_beyond_the_clock_skew() {
+    assert!(issued_at(NOW + CLOCK_SKEW_SECS).is_current_at(NOW));
+    assert!(!issued_at(NOW + CLOCK_SKEW_SECS + 1).is_current_at(NOW));
+    assert!(!issued_at(u64::max_value()).is_current_at(NOW));
+}
+
+#[test]
+fn cookies_are_reissued_well_before_they_are_refused() {
+    assert!(!issued_at(NOW).is_due_at(NOW));
+    assert!(!issued_at(NOW - REISSUE_AFTER_SECS + 1).is_due_at(NOW));
+    assert!(issued_at(NOW - REISSUE_AFTER_SECS).is_due_at(NOW));
+    assert!(REISSUE_AFTER_SECS < MAX_AGE_SECS);
+    assert!(!issued_at(NOW + CLOCK_SKEW_SECS).is_due_at(NOW));
+}
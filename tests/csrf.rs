@@ -0,0 +1,37 @@
+//! Tokens against cross-site request forgery
+
+#![cfg(feature = "templates")]
+
+extern crate rock_paper_scissors;
+extern crate sha2;
+
+use sha2::{Digest, Sha256};
+
+use rock_paper_scissors::csrf;
+use rock_paper_scissors::ids::UserId;
+use rock_paper_scissors::util::bytes_to_hex;
+
+fn id(id: &str) -> UserId {
+    UserId::from_string(id.to_owned())
+}
+
+#[test]
+fn a_token_only_verifies_for_its_session() {
+    let token = csrf::token(&id("session-1"));
+    assert!(csrf::verify(&id("session-1"), &token));
+    assert!(!csrf::verify(&id("session-2"), &token));
+    assert!(!csrf::verify(&id("session-1"), ""));
+    assert!(!csrf::verify(&id("session-1"), &token[1..]));
+    let mut tampered = token;
+    let last = if tampered.pop() == Some('0') { '1' } else { '0' };
+    tampered.push(last);
+    assert!(!csrf::verify(&id("session-1"), &tampered));
+}
+
+#[test]
+fn a_token_cannot_be_made_from_the_session_id() {
+    let token = csrf::token(&id("session-3"));
+    assert_eq!(token.len(), 64);
+    let digest = Sha256::digest(b"csrf:session-3");
+    assert!(!csrf::verify(&id("session-3"), &bytes_to_hex(&digest)));
+}
@@ -0,0 +1,97 @@
+//! The event bus, and batches holding events back
+
+extern crate rock_paper_scissors;
+
+use std::sync::{Arc, Mutex};
+
+use rock_paper_scissors::events::{self, Event, Subscriber};
+
+/// Records the sessions started under its name, as other tests publish events too.
+struct Recorder {
+    user_name: &'static str,
+    seen: Arc<Mutex<usize>>,
+}
+
+impl Subscriber for Recorder {
+    fn notify(&self, event: &Event) {
+        if let Event::SessionStarted { ref user_name } = *event {
+            if user_name == self.user_name {
+                *self.seen.lock().unwrap() += 1;
+            }
+        }
+    }
+}
+
+fn record(user_name: &'static str) -> Arc<Mutex<usize>> {
+    let seen = Arc::new(Mutex::new(0));
+    events::register(Box::new(Recorder { user_name: user_name, seen: seen.clone() }));
+    seen
+}
+
+fn started(user_name: &str) -> Event {
+    Event::SessionStarted { user_name: user_name.to_owned() }
+}
+
+#[test]
+fn events_outside_batches_go_out_at_once() {
+    let seen = record("unbatched");
+    events::publish(started("unbatched"));
+    assert_eq!(*seen.lock().unwrap(), 1);
+}
+
+#[test]
+fn a_released_batch_passes_its_events_on() {
+    let seen = record("released");
+    let batch = events::batch();
+    events::publish(started("released"));
+    events::publish(started("released"));
+    assert_eq!(*seen.lock().unwrap(), 0);
+    assert_eq!(events::held().len(), 2);
+
+    batch.release();
+    assert_eq!(*seen.lock().unwrap(), 2);
+    assert!(events::held().is_empty());
+}
+
+#[test]
+fn a_dropped_batch_discards_its_events() {
+    let seen = record("dropped");
+    {
+        let _batch = events::batch();
+        events::publish(started("dropped"));
+    }
+    assert_eq!(*seen.lock().unwrap(), 0);
+    events::publish(started("dropped"));
+    assert_eq!(*seen.lock().unwrap(), 1);
+}
+
+#[test]
+fn an_inner_batch_releases_into_the_outer_one() {
+    let seen = record("nested");
+    let outer = events::batch();
+    events::publish(started("nested"));
+    let inner = events::batch();
+    events::publish(started("nested"));
+    assert_eq!(events::held().len(), 1);
+    inner.release();
+    assert_eq!(*seen.lock().unwrap(), 0);
+    assert_eq!(events::held().len(), 2);
+
+    let dropped = events::batch();
+    events::publish(started("nested"));
+    drop(dropped);
+    outer.release();
+    assert_eq!(*seen.lock().unwrap(), 2);
+}
+
+#[test]
+fn a_deferred_batch_is_released_once_dropped() {
+    let seen = record("deferred");
+    {
+        let _events = events::batch().defer();
+        events::publish(started("deferred"));
+        assert_eq!(*seen.lock().unwrap(), 0);
+    }
+    assert_eq!(*seen.lock().unwrap(), 1);
+    assert!(events::held().is_empty());
+}
@@ -0,0 +1,30 @@
+//! Flash notices, written in the language of the player
+
+#![cfg(feature = "templates")]
+
+extern crate rock_paper_scissors;
+
+use rock_paper_scissors::flash::Notice;
+use rock_paper_scissors::locale::Language;
+
+#[test]
+fn a_notice_is_written_in_the_language_asked_for() {
+    let notice = Notice::success("team.full");
+    assert_eq!(notice.text(Language::En), "That team is full.");
+    assert_eq!(notice.text(Language::De), "Dieses Team ist voll.");
+    assert_eq!(notice.text(Language::Fr), "Cette équipe est complète.");
+    assert_eq!(notice.text(Language::Es), "Ese equipo está completo.");
+}
+
+#[test]
+fn parameters_fill_the_message_in_order() {
+    let notice = Notice::success("pvp.invite-emailed").with("ivy@example.com").with("7");
+    assert_eq!(notice.text(Language::En), "Invite sent to ivy@example.com; it works for 7 days.");
+    assert_eq!(notice.text(Language::De),
+               "Einladung an ivy@example.com gesendet; sie gilt 7 Tage.");
+}
+
+#[test]
+fn an_unknown_code_is_shown_as_it_is() {
+    assert_eq!(Notice::success("no.such-code").text(Language::Fr), "no.such-code");
+}
@@ -0,0 +1,219 @@
+//! Searching a history by verdict, hand, opponent and date, rewinding it, voiding rounds and
+//! verifying exports
+
+extern crate rock_paper_scissors;
+extern crate serde_json;
+
+use rock_paper_scissors::commitment;
+use rock_paper_scissors::game::{Hand, RoundKind, Strategy, Verdict, MULTI_HANDS};
+use rock_paper_scissors::history::{self, Export, Filter, History, Latency, MAX_ROUNDS};
+
+const NONCE: &str = "00112233445566778899aabbccddeeff00112233445566778899aabbccddeeff";
+
+/// 150 classic rounds, spanning three chunks: the human cycles through the hands against a
+/// computer that always plays rock, the first 100 against the uniform strategy.
+fn played() -> History {
+    let mut history = History::new();
+    for i in 0..150 {
+        let human = Hand::CHOICES[i % 3];
+        let opponent = if i < 100 { Strategy::Uniform } else { Strategy::Mirror };
+        history.push(RoundKind::Classic, &[human], &[Hand::Rock], Verdict::of(human, Hand::Rock),
+                     NONCE, &commitment::digest(NONCE, Hand::Rock), None, Some(opponent));
+    }
+    history
+}
+
+fn indices(history: &History, filter: &Filter, skip: usize, count: usize) -> Vec<usize> {
+    history.search(filter, skip, count).into_iter().map(|(index, _)| index).collect()
+}
+
+fn indices_before(history: &History, filter: &Filter, before: usize, count: usize) -> Vec<usize> {
+    history.search_before(filter, before, 0, count).into_iter().map(|(index, _)| index).collect()
+}
+
+#[test]
+fn no_filter_pages_through_every_round() {
+    let history = played();
+    let all = Filter::default();
+    assert_eq!(indices(&history, &all, 0, 3), vec![149, 148, 147]);
+    assert_eq!(indices(&history, &all, 148, 5), vec![1, 0]);
+    assert!(indices(&history, &all, 150, 5).is_empty());
+}
+
+#[test]
+fn cursors_page_on_from_a_round() {
+    let mut history = played();
+    let all = Filter::default();
+    assert_eq!(indices_before(&history, &all, 150, 3), vec![149, 148, 147]);
+
+    // Rounds played meanwhile do not shift the next page.
+    history.push(RoundKind::Classic, &[Hand::Rock], &[Hand::Rock], Verdict::Tie,
+                 NONCE, &commitment::digest(NONCE, Hand::Rock), None, Some(Strategy::Mirror));
+    assert_eq!(indices_before(&history, &all, 147, 3), vec![146, 145, 144]);
+    let wins = Filter { verdict: Some(Verdict::Win), ..Filter::default() };
+    assert_eq!(indices_before(&history, &wins, 129, 2), vec![127, 124]);
+    assert!(indices_before(&history, &all, 0, 3).is_empty());
+}
+
+#[test]
+fn filters_combine() {
+    let history = played();
+    let wins = Filter { verdict: Some(Verdict::Win), ..Filter::default() };
+    let found = history.search(&wins, 0, 100);
+    assert_eq!(found.len(), 50);
+    assert!(found.iter().all(|&(_, ref record)| record.human == "paper"));
+
+    let rock_against_mirror = Filter {
+        hand: Some(Hand::Rock),
+        opponent: Some(Strategy::Mirror),
+        ..Filter::default()
+    };
+    assert_eq!(indices(&history, &rock_against_mirror, 0, 3), vec![147, 144, 141]);
+    assert_eq!(history.search(&rock_against_mirror, 0, 100).len(), 16);
+
+    let losses = Filter { verdict: Some(Verdict::Loss), ..rock_against_mirror };
+    assert!(history.search(&losses, 0, 100).is_empty());
+}
+
+#[test]
+fn dates_bound_the_search() {
+    let history = played();
+    let first = history.records()[0].played_at;
+    let last = history.last().unwrap().played_at;
+    let span = Filter { since: Some(first), until: Some(last + 1), ..Filter::default() };
+    assert_eq!(history.search(&span, 0, 200).len(), 150);
+    let later = Filter { since: Some(last + 1), ..Filter::default() };
+    assert!(history.search(&later, 0, 200).is_empty());
+}
+
+#[test]
+fn imported_rounds_have_no_opponent() {
+    let history = History::from_records(&played().records()).unwrap();
+    let uniform = Filter { opponent: Some(Strategy::Uniform), ..Filter::default() };
+    assert!(history.search(&uniform, 0, 200).is_empty());
+    assert_eq!(history.search(&Filter::default(), 0, 200).len(), 150);
+}
+
+#[test]
+fn rewinding_takes_back_rounds_and_notes() {
+    let mut history = played();
+    let first_link = history.records()[0].link.clone();
+    let mark = history.mark();
+    let (version, head) = (history.version(), history.head().to_owned());
+    let records = serde_json::to_string(&history.records()).unwrap();
+
+    // Across a checkpoint, whose times the new rounds widen.
+    for _ in 0..60 {
+        history.push(RoundKind::Classic, &[Hand::Paper], &[Hand::Rock], Verdict::Win, NONCE,
+                     &commitment::digest(NONCE, Hand::Rock), None, Some(Strategy::Uniform));
+    }
+    assert!(history.annotate(&first_link, "opening"));
+    history.rewind(mark);
+
+    assert_eq!(history.len(), 150);
+    assert_eq!(history.version(), version);
+    assert_eq!(history.head(), head);
+    assert_eq!(serde_json::to_string(&history.records()).unwrap(), records);
+    let wins = Filter { verdict: Some(Verdict::Win), ..Filter::default() };
+    assert_eq!(history.search(&wins, 0, 200).len(), 50);
+}
+
+fn export(history: &History) -> Export {
+    Export {
+        user_name: "ada".to_owned(),
+        chain_head: history.head().to_owned(),
+        first_round: history.first(),
+        prev_link: history.prev_link().to_owned(),
+        rounds: history.records(),
+    }
+}
+
+#[test]
+fn exports_are_checked_down_to_the_verdicts() {
+    let history = played();
+    assert_eq!(history::verify(&export(&history)), Ok(()));
+
+    let mut flipped = export(&history);
+    flipped.rounds[3].verdict = Verdict::Win;
+    assert_eq!(history::verify(&flipped),
+               Err("round 3: the verdict does not follow from the hands".to_owned()));
+    assert!(History::from_records(&flipped.rounds).is_err());
+
+    // A played hand rewritten along with its verdict still breaks the chain.
+    let mut rewritten = export(&history);
+    rewritten.rounds[3].human = "paper".to_owned();
+    rewritten.rounds[3].verdict = Verdict::Win;
+    assert_eq!(history::verify(&rewritten), Err("round 3: broken chain link".to_owned()));
+}
+
+#[test]
+fn a_voided_round_keeps_its_link() {
+    let mut history = played();
+    let hands = [Hand::Rock; MULTI_HANDS];
+    let committed = commitment::join_hands(&hands);
+    history.push(RoundKind::Chaos, &hands, &hands, Verdict::Tie, NONCE,
+                 &commitment::digest_str(NONCE, &committed), None, Some(Strategy::Uniform));
+    let (version, head) = (history.version(), history.head().to_owned());
+
+    // The latest classic round, a loss, not the chaos round after it.
+    let voided = history.void_last(RoundKind::Classic).unwrap();
+    assert_eq!((voided.kind, voided.verdict), (RoundKind::Classic, Verdict::Loss));
+    assert_ne!(history.version(), version);
+    assert_eq!(history.head(), head);
+    let records = history.records();
+    assert_eq!(records.iter().filter(|record| record.voided).count(), 1);
+    assert!(records[149].voided);
+    assert_eq!(history.outcomes(RoundKind::Classic, 2), vec![0, 1]);
+
+    assert_eq!(history::verify(&export(&history)), Ok(()));
+    let imported = History::from_records(&records).unwrap();
+    assert!(imported.records()[149].voided);
+}
+
+#[test]
+fn records_come_back_as_they_were_packed() {
+    let mut history = played();
+    let hands = [Hand::Paper, Hand::Rock, Hand::Scissors];
+    let committed = commitment::join_hands(&hands);
+    history.push(RoundKind::Chaos, &hands, &hands, Verdict::Tie, NONCE,
+                 &commitment::digest_str(NONCE, &committed), None, Some(Strategy::Uniform));
+    // A nonce that is not lower hex, a digest that is not the commitment, and a timed round.
+    history.push(RoundKind::Classic, &[Hand::Rock], &[Hand::Paper], Verdict::Loss, "Not Hex",
+                 "not-the-digest", Some(Latency { decided_ms: 1500, server_us: 70 }), None);
+    let mut records = history.records();
+    records[3].note = Some("opening".to_owned());
+    records[5].voided = true;
+    // Clocks may step back.
+    records[7].played_at = records[6].played_at - 3600;
+    records[140].played_at = 0;
+
+    let repacked = History::from_records(&records).unwrap();
+    assert_eq!(serde_json::to_value(&repacked.records()).unwrap(),
+               serde_json::to_value(&records).unwrap());
+    assert_eq!(repacked.head(), history.head());
+}
+
+#[test]
+fn long_histories_drop_their_oldest_rounds() {
+    let mut history = History::new();
+    for i in 0..MAX_ROUNDS + 1 {
+        let human = Hand::CHOICES[i % 3];
+        history.push(RoundKind::Classic, &[human], &[Hand::Rock], Verdict::of(human, Hand::Rock),
+                     NONCE, &commitment::digest(NONCE, Hand::Rock), None, None);
+    }
+    assert_eq!(history.len(), MAX_ROUNDS + 1);
+    let first = history.first();
+    assert!(first > 0 && history.len() - first <= MAX_ROUNDS);
+    assert!(history.range(0, first).is_empty());
+    let records = history.records();
+    assert_eq!(records.len(), history.len() - first);
+    assert_eq!(indices(&history, &Filter::default(), history.len() - first - 1, 5), vec![first]);
+    assert!(history.proof(&records[0].link).is_some());
+
+    // The rest of the chain still checks out, and comes back from an export.
+    let export = export(&history);
+    assert_eq!(history::verify(&export), Ok(()));
+    let imported = History::from_records_at(first, &export.prev_link, &export.rounds).unwrap();
+    assert_eq!((imported.len(), imported.head()), (history.len(), history.head()));
+    assert!(History::from_records(&export.rounds).is_err());
+}
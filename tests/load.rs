@@ -0,0 +1,94 @@
+//! Load test of the play path
+//!
+//! Simulates many concurrent players against one in-process Rocket instance and reports latency
+//! percentiles of `GET /?hand`. It is ignored by default; run it in release mode to compare builds:
+//!
+//! ```text
+//! RPS_PLAYERS=500 RPS_ROUNDS=50 cargo test --release --test load -- --ignored --nocapture
+//! ```
+
+extern crate rocket;
+extern crate rock_paper_scissors;
+
+use std::env;
+use std::sync::{Arc, Barrier};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rocket::http::{ContentType, Cookie, Status};
+use rocket::local::{Client, LocalRequest};
+
+fn env_or(name: &str, default: usize) -> usize {
+    env::var(name).ok().and_then(|v| v.parse().ok()).unwrap_or(default)
+}
+
+fn with_cookies<'c>(mut request: LocalRequest<'c>, cookies: &[Cookie<'static>]) -> LocalRequest<'c> {
+    for cookie in cookies {
+        request = request.cookie(cookie.clone());
+    }
+    request
+}
+
+fn login(client: &Client, name: &str) -> Vec<Cookie<'static>> {
+    let response = client.post("/login")
+        .header(ContentType::Form)
+        .body(format!("user_name={}", name))
+        .dispatch();
+    let cookies = response.headers()
+        .get("Set-Cookie")
+        .filter_map(|value| Cookie::parse_encoded(value.to_owned()).ok())
+        .collect();
+    cookies
+}
+
+fn percentile(sorted: &[Duration], p: f64) -> Duration {
+    let rank = ((sorted.len() as f64) * p).ceil() as usize;
+    sorted[rank.max(1).min(sorted.len()) - 1]
+}
+
+fn millis(d: Duration) -> f64 {
+    d.as_secs() as f64 * 1e3 + f64::from(d.subsec_nanos()) / 1e6
+}
+
+#[test]
+#[ignore]
+fn concurrent_players() {
+    let players = env_or("RPS_PLAYERS", 200);
+    let rounds = env_or("RPS_ROUNDS", 20);
+
+    let client = Arc::new(Client::new(rock_paper_scissors::rocket()).expect("valid rocket"));
+    let barrier = Arc::new(Barrier::new(players));
+
+    let handles: Vec<_> = (0..players)
+        .map(|i| {
+            let client = client.clone();
+            let barrier = barrier.clone();
+            thread::spawn(move || {
+                let cookies = login(&client, &format!("player{}", i));
+                with_cookies(client.get("/"), &cookies).dispatch();
+
+                // Starts everybody at once so the session lock is actually contended.
+                barrier.wait();
+                let mut latencies = Vec::with_capacity(rounds);
+                for _ in 0..rounds {
+                    let start = Instant::now();
+                    let response = with_cookies(client.get("/?hand=paper"), &cookies).dispatch();
+                    latencies.push(start.elapsed());
+                    assert_eq!(response.status(), Status::Ok);
+                }
+                latencies
+            })
+        })
+        .collect();
+
+    let mut latencies: Vec<Duration> = handles
+        .into_iter()
+        .flat_map(|h| h.join().expect("player thread panicked"))
+        .collect();
+    latencies.sort();
+
+    println!("{} players x {} rounds", players, rounds);
+    println!("p50 = {:.3} ms", millis(percentile(&latencies, 0.50)));
+    println!("p99 = {:.3} ms", millis(percentile(&latencies, 0.99)));
+    println!("max = {:.3} ms", millis(*latencies.last().unwrap()));
+}
@@ -0,0 +1,64 @@
+//! Times and numbers in the player's language
+
+extern crate rock_paper_scissors;
+
+use rock_paper_scissors::locale::{self, Language, Locale};
+
+// 2026-10-15 14:05:00 UTC.
+const TIME: u64 = 1_792_073_100;
+
+fn locale(language: Language, utc_offset: i32) -> Locale {
+    Locale { language: language, utc_offset: utc_offset }
+}
+
+#[test]
+fn counts_are_grouped_by_thousands() {
+    assert_eq!(locale(Language::En, 0).format_count(999), "999");
+    assert_eq!(locale(Language::En, 0).format_count(1_234_567), "1,234,567");
+    assert_eq!(locale(Language::De, 0).format_count(12_345), "12.345");
+    assert_eq!(locale(Language::Fr, 0).format_count(12_345), "12\u{202f}345");
+    assert_eq!(locale(Language::Es, 0).format_count(1_234), "1234");
+    assert_eq!(locale(Language::Es, 0).format_count(12_345), "12.345");
+}
+
+#[test]
+fn times_are_written_at_the_offset() {
+    assert_eq!(locale(Language::En, 0).format_time(TIME), "Oct 15, 2026, 14:05");
+    assert_eq!(locale(Language::De, 0).format_time(TIME), "15.10.2026, 14:05");
+    assert_eq!(locale(Language::Fr, 0).format_time(TIME), "15/10/2026 14:05");
+    assert_eq!(locale(Language::Es, 0).format_time(TIME), "15/10/2026, 14:05");
+    assert_eq!(locale(Language::En, 10 * 60).format_time(TIME), "Oct 16, 2026, 00:05");
+    assert_eq!(locale(Language::En, -(5 * 60 + 30)).format_time(TIME), "Oct 15, 2026, 08:35");
+}
+
+#[test]
+fn offsets_are_read_and_written() {
+    assert_eq!(locale::parse_utc_offset("+02:00"), Some(120));
+    assert_eq!(locale::parse_utc_offset("-0530"), Some(-330));
+    assert_eq!(locale::parse_utc_offset("UTC+9"), Some(540));
+    assert_eq!(locale::parse_utc_offset(""), Some(0));
+    assert_eq!(locale::parse_utc_offset("+15:00"), None);
+    assert_eq!(locale::parse_utc_offset("+01:60"), None);
+    assert_eq!(locale::parse_utc_offset("2"), None);
+
+    assert_eq!(locale(Language::En, 0).time_zone(), "UTC");
+    assert_eq!(locale(Language::En, 330).time_zone(), "UTC+05:30");
+    assert_eq!(locale(Language::En, -60).time_zone(), "UTC-01:00");
+}
+
+#[test]
+fn languages_are_read_from_tags() {
+    assert_eq!("de-AT".parse::<Language>().ok(), Some(Language::De));
+    assert_eq!("FR".parse::<Language>().ok(), Some(Language::Fr));
+    assert!("nl".parse::<Language>().is_err());
+}
+
+#[test]
+fn days_start_at_midnight_at_the_offset() {
+    // 2026-10-15 00:00:00 UTC.
+    let midnight = 1_792_022_400;
+    let tokyo = locale(Language::En, 9 * 60);
+    assert_eq!(tokyo.from_local_time(midnight), midnight - 9 * 3600);
+    assert_eq!(tokyo.local_time(tokyo.from_local_time(midnight)), midnight);
+    assert_eq!(tokyo.format_time(midnight - 9 * 3600), "Oct 15, 2026, 00:00");
+}
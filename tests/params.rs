@@ -0,0 +1,43 @@
+//! Regressions found by fuzzing the request parameters (see `fuzz/`)
+
+extern crate rock_paper_scissors;
+
+use rock_paper_scissors::game::Hand;
+use rock_paper_scissors::params::HandParam;
+use rock_paper_scissors::util;
+
+#[test]
+fn hands_ignore_ascii_case_only() {
+    assert_eq!("ROCK".parse::<HandParam>().ok(), Some(HandParam(Hand::Rock)));
+    assert_eq!("sCiSsOrS".parse::<HandParam>().ok(), Some(HandParam(Hand::Scissors)));
+    // "ROC" followed by the Kelvin sign, which lowercases to a `k`.
+    assert!("ROC\u{212A}".parse::<Hand>().is_err());
+}
+
+#[test]
+fn hex_takes_digits_only() {
+    assert_eq!(util::hex_to_bytes("0aFf"), Some(vec![0x0a, 0xff]));
+    assert_eq!(util::hex_to_bytes("+f"), None);
+    assert_eq!(util::hex_to_bytes("-0"), None);
+    assert_eq!(util::hex_to_bytes("0"), None);
+}
+
+#[test]
+fn dates_round_trip() {
+    assert_eq!(util::parse_date("1970-01-01"), Some(0));
+    assert_eq!(util::parse_date("2024-02-29"), Some(1_709_164_800));
+    assert_eq!(util::format_date(1_709_164_800 + 86_399), "2024-02-29");
+    assert_eq!(util::parse_date("2023-02-29"), None);
+    assert_eq!(util::parse_date("2024-2-29"), None);
+    assert_eq!(util::parse_date("+024-02-29"), None);
+}
+
+#[test]
+fn http_dates_round_trip() {
+    assert_eq!(util::format_http_date(784_111_777), "Sun, 06 Nov 1994 08:49:37 GMT");
+    assert_eq!(util::parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT"), Some(784_111_777));
+    assert_eq!(util::format_http_date(0), "Thu, 01 Jan 1970 00:00:00 GMT");
+    assert_eq!(util::parse_http_date("Sunday, 06-Nov-94 08:49:37 GMT"), None);
+    assert_eq!(util::parse_http_date("Sun, 06 Nov 1994 24:00:00 GMT"), None);
+    assert_eq!(util::parse_http_date("Sun, 06 Nov 1994 8:49:37 GMT"), None);
+}
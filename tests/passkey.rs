@@ -0,0 +1,103 @@
+//! Passkey checks on what the browser and the authenticator send
+
+extern crate rock_paper_scissors;
+extern crate sha2;
+
+use sha2::{Digest, Sha256};
+
+use rock_paper_scissors::passkey::{self, PasskeyError, RelyingParty};
+
+const SITE: &str = "https://rps.example.com";
+const USER_PRESENT: u8 = 0x01;
+const ATTESTED_CREDENTIAL: u8 = 0x40;
+
+fn authenticator_data(rp_id: &str, flags: u8, count: u32) -> Vec<u8> {
+    let mut data = Sha256::digest(rp_id.as_bytes()).to_vec();
+    data.push(flags);
+    data.extend_from_slice(&[(count >> 24) as u8, (count >> 16) as u8, (count >> 8) as u8,
+                             count as u8]);
+    data
+}
+
+/// `data` followed by an attested credential: a zero AAGUID, the length of `id`, then `id`.
+fn attested(mut data: Vec<u8>, id: &[u8]) -> Vec<u8> {
+    data.extend_from_slice(&[0; 16]);
+    data.extend_from_slice(&[(id.len() >> 8) as u8, id.len() as u8]);
+    data.extend_from_slice(id);
+    data
+}
+
+fn client_data(kind: &str, challenge: &str, origin: &str) -> Vec<u8> {
+    format!(r#"{{"type":"{}","challenge":"{}","origin":"{}"}}"#, kind, challenge, origin)
+        .into_bytes()
+}
+
+fn login_challenge(party: &RelyingParty) -> String {
+    passkey::login_options(party).unwrap()["challenge"].as_str().unwrap().to_owned()
+}
+
+#[test]
+fn client_data_answers_a_challenge_once_from_the_site() {
+    let party = RelyingParty::of(SITE);
+    let challenge = login_challenge(&party);
+    let data = client_data("webauthn.get", &challenge, SITE);
+    assert_eq!(passkey::check_client_data(&party, &data, "webauthn.get", None), Ok(()));
+    assert_eq!(passkey::check_client_data(&party, &data, "webauthn.get", None),
+               Err(PasskeyError::Expired));
+
+    let challenge = login_challenge(&party);
+    let data = client_data("webauthn.get", &challenge, "https://rps.example.com.evil");
+    assert_eq!(passkey::check_client_data(&party, &data, "webauthn.get", None),
+               Err(PasskeyError::WrongOrigin));
+
+    let challenge = login_challenge(&party);
+    let data = client_data("webauthn.create", &challenge, SITE);
+    assert_eq!(passkey::check_client_data(&party, &data, "webauthn.get", None),
+               Err(PasskeyError::Malformed("client data")));
+}
+
+#[test]
+fn authenticator_data_is_for_the_site_and_the_player_was_there() {
+    let party = RelyingParty::of(SITE);
+    let data = authenticator_data("rps.example.com", USER_PRESENT, 1);
+    assert_eq!(passkey::check_authenticator_data(&party, &data), Ok(()));
+
+    let data = authenticator_data("example.com", USER_PRESENT, 1);
+    assert_eq!(passkey::check_authenticator_data(&party, &data), Err(PasskeyError::WrongOrigin));
+    let data = authenticator_data("rps.example.com", 0, 1);
+    assert_eq!(passkey::check_authenticator_data(&party, &data), Err(PasskeyError::NotPresent));
+    assert_eq!(passkey::check_authenticator_data(&party, &[0; 36]),
+               Err(PasskeyError::Malformed("authenticator data")));
+
+    // The port is not part of the relying party id.
+    let party = RelyingParty::of("http://localhost:8000");
+    let data = authenticator_data("localhost", USER_PRESENT, 1);
+    assert_eq!(passkey::check_authenticator_data(&party, &data), Ok(()));
+}
+
+#[test]
+fn the_credential_id_is_read_from_the_attested_data() {
+    let flags = USER_PRESENT | ATTESTED_CREDENTIAL;
+    let data = attested(authenticator_data("rps.example.com", flags, 0), b"credential");
+    assert_eq!(passkey::credential_id(&data), Ok(&b"credential"[..]));
+
+    let mut cut_short = data.clone();
+    cut_short.pop();
+    assert_eq!(passkey::credential_id(&cut_short),
+               Err(PasskeyError::Malformed("authenticator data")));
+
+    let data = attested(authenticator_data("rps.example.com", USER_PRESENT, 0), b"credential");
+    assert_eq!(passkey::credential_id(&data), Err(PasskeyError::Malformed("authenticator data")));
+}
+
+#[test]
+fn the_signature_counter_must_go_up_unless_it_is_not_kept() {
+    let data = authenticator_data("rps.example.com", USER_PRESENT, 0x0102_0304);
+    assert_eq!(passkey::sign_count(&data), 0x0102_0304);
+
+    assert_eq!(passkey::check_sign_count(0, 0), Ok(()));
+    assert_eq!(passkey::check_sign_count(0, 1), Ok(()));
+    assert_eq!(passkey::check_sign_count(5, 6), Ok(()));
+    assert_eq!(passkey::check_sign_count(5, 5), Err(PasskeyError::Cloned));
+    assert_eq!(passkey::check_sign_count(5, 0), Err(PasskeyError::Cloned));
+}
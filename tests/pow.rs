@@ -0,0 +1,38 @@
+//! Proofs of work against round digests
+
+extern crate rock_paper_scissors;
+
+use rock_paper_scissors::commitment;
+use rock_paper_scissors::game::Hand;
+use rock_paper_scissors::pow;
+
+const NONCE: &str = "00112233445566778899aabbccddeeff00112233445566778899aabbccddeeff";
+
+#[test]
+fn solved_challenges_verify() {
+    let challenge = commitment::digest(NONCE, Hand::Rock);
+    for difficulty in 0..12 {
+        let nonce = pow::solve(&challenge, difficulty);
+        assert!(pow::solves(&challenge, &nonce, difficulty));
+    }
+}
+
+#[test]
+fn nothing_to_prove_at_zero() {
+    assert!(pow::solves("", "", 0));
+}
+
+#[test]
+fn a_proof_is_bound_to_its_challenge() {
+    let challenge = commitment::digest(NONCE, Hand::Rock);
+    let other = commitment::digest(NONCE, Hand::Paper);
+    let nonce = pow::solve(&challenge, 16);
+    assert_eq!(nonce, "11176");
+    assert!(!pow::solves(&other, &nonce, 16));
+}
+
+#[test]
+fn long_nonces_are_refused() {
+    let nonce = "0".repeat(pow::MAX_NONCE_LEN + 1);
+    assert!(!pow::solves("challenge", &nonce, 0));
+}
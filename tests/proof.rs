@@ -0,0 +1,96 @@
+//! Round trips of round proofs through their canonical encoding
+
+extern crate rock_paper_scissors;
+
+use rock_paper_scissors::commitment;
+use rock_paper_scissors::game::{Hand, RoundKind, Verdict};
+use rock_paper_scissors::history::{History, Latency, GENESIS};
+use rock_paper_scissors::proof::RoundProof;
+
+const LATENCY: Latency = Latency { decided_ms: 2300, server_us: 150 };
+const NONCE: &str = "00112233445566778899aabbccddeeff00112233445566778899aabbccddeeff";
+
+fn played() -> History {
+    let mut history = History::new();
+    history.push(RoundKind::Classic, &[Hand::Rock], &[Hand::Scissors], Verdict::Win, NONCE,
+                 &commitment::digest(NONCE, Hand::Scissors), Some(LATENCY), None);
+    let hands = [Hand::Paper, Hand::Rock, Hand::Rock];
+    history.push(RoundKind::Chaos, &hands, &hands, Verdict::Tie, NONCE,
+                 &commitment::digest_multi(NONCE, &hands), None, None);
+    history
+}
+
+#[test]
+fn canonical_form() {
+    let history = played();
+    let first = history.records()[0].clone();
+    let proof = history.proof(&first.link).expect("first round");
+    let expected = format!(
+        "{{\"algorithm\":\"sha256\",\"computer\":\"scissors\",\"digest\":\"{}\",\
+         \"human\":\"rock\",\"kind\":\"classic\",\"link\":\"{}\",\"prev_link\":\"{}\",\
+         \"random\":\"{}\",\"verdict\":\"win\"}}",
+        first.digest, first.link, GENESIS, NONCE);
+    assert_eq!(proof.to_canonical(), expected);
+}
+
+#[test]
+fn round_trip() {
+    let history = played();
+    for record in history.records() {
+        let proof = history.proof(&record.link).expect("recorded round");
+        assert_eq!(proof.verify(), Ok(()));
+        let canonical = proof.to_canonical();
+        let parsed = RoundProof::parse(&canonical).expect("canonical proof");
+        assert_eq!(parsed, proof);
+        assert_eq!(parsed.to_canonical(), canonical);
+        assert_eq!(parsed.fingerprint(), proof.fingerprint());
+    }
+    let last = history.proof(history.head()).expect("last round");
+    assert_eq!(last.prev_link, history.records()[0].link);
+    assert_eq!(last.computer, "paper,rock,rock");
+}
+
+#[test]
+fn rejects_non_canonical() {
+    let history = played();
+    let canonical = history.proof(history.head()).expect("last round").to_canonical();
+
+    let spaced = canonical.replace(",", ", ");
+    assert!(RoundProof::parse(&spaced).is_err());
+    let upper = canonical.replace(NONCE, &NONCE.to_uppercase());
+    assert!(RoundProof::parse(&upper).is_err());
+    let reordered = canonical.replacen("{\"algorithm\":\"sha256\",", "{", 1)
+        .replacen("}", ",\"algorithm\":\"sha256\"}", 1);
+    assert!(RoundProof::parse(&reordered).is_err());
+    let other = canonical.replace("sha256", "sha512");
+    assert!(RoundProof::parse(&other).is_err());
+}
+
+#[test]
+fn tampering_is_detected() {
+    let history = played();
+    let mut proof = history.proof(history.head()).expect("last round");
+    proof.computer = "rock,rock,rock".to_owned();
+    assert!(proof.verify().is_err());
+
+    let mut proof = history.proof(&history.records()[0].link).expect("first round");
+    proof.verdict = Verdict::Loss;
+    assert_eq!(proof.verify(), Err("the verdict does not follow from the hands".to_owned()));
+    proof.human = "paper".to_owned();
+    assert_eq!(proof.verify(), Err("broken chain link".to_owned()));
+    proof.human = "lizard".to_owned();
+    assert_eq!(proof.verify(), Err("unexpected hands".to_owned()));
+}
+
+#[test]
+fn latency_is_kept_out_of_proofs() {
+    let history = played();
+    let records = history.records();
+    assert_eq!(records[0].latency, Some(LATENCY));
+    assert_eq!(records[1].latency, None);
+    let repacked = History::from_records(&records).unwrap();
+    assert_eq!(repacked.records()[0].latency, Some(LATENCY));
+
+    let proof = history.proof(&records[0].link).unwrap();
+    assert!(!proof.to_canonical().contains("2300"));
+}
@@ -0,0 +1,67 @@
+//! Correspondence matches started from emailed invite links
+
+#![cfg(feature = "templates")]
+
+extern crate rock_paper_scissors;
+
+use std::net::{IpAddr, Ipv4Addr};
+
+use rock_paper_scissors::ids::UserId;
+use rock_paper_scissors::pvp::{self, Member, EMAILS_PER_ADDRESS};
+
+fn id(id: &str) -> UserId {
+    UserId::from_string(id.to_owned())
+}
+
+fn member(user_id: &str, name: &str) -> Member {
+    Member { id: id(user_id), name: name.to_owned() }
+}
+
+#[test]
+fn an_emailed_invite_starts_a_correspondence_match_once() {
+    let invite = pvp::invite_by_email("ada", "bob@example.com", None).unwrap();
+    let host = pvp::accept(member("bob-1", "bob"), &invite.token).unwrap();
+    assert_eq!(host.name, "ada");
+    assert_eq!(pvp::with_match(&id("bob-1"), |m| m.is_correspondence()), Some(true));
+
+    let error = pvp::accept(member("cy-1", "cy"), &invite.token).unwrap_err();
+    assert_eq!(error.code(), "pvp.no-such-invite");
+}
+
+#[test]
+fn an_emailed_invite_expires() {
+    let invite = pvp::invite_by_email("dee", "eve@example.com", None).unwrap();
+    let error = pvp::accept_at(member("eve-1", "eve"), &invite.token, invite.expires_at)
+        .unwrap_err();
+    assert_eq!(error.code(), "pvp.no-such-invite");
+    assert!(pvp::accept_at(member("eve-1", "eve"), &invite.token, invite.expires_at - 1).is_ok());
+}
+
+#[test]
+fn logging_in_again_takes_the_seat_back() {
+    let invite = pvp::invite_by_email("fay", "gus@example.com", None).unwrap();
+    pvp::accept(member("gus-1", "gus"), &invite.token).unwrap();
+
+    pvp::rebind(&member("gus-2", "gus"));
+    assert!(pvp::with_match(&id("gus-1"), |_| ()).is_none());
+    let opponent = pvp::with_match(&id("gus-2"), |m| m.opponent(&id("gus-2")).name.clone());
+    assert_eq!(opponent, Some("fay".to_owned()));
+}
+
+#[test]
+fn names_that_would_break_the_subject_line_are_refused() {
+    let error = pvp::invite_by_email("mallory\r\nBcc: all@example.com", "hal@example.com", None)
+        .unwrap_err();
+    assert_eq!(error.code(), "pvp.unfit-name");
+}
+
+#[test]
+fn an_address_emails_only_so_many_invites_an_hour() {
+    let remote = Some(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1)));
+    for i in 0..EMAILS_PER_ADDRESS {
+        let host = format!("host-{}", i);
+        assert!(pvp::invite_by_email(&host, "ivy@example.com", remote).is_ok());
+    }
+    let error = pvp::invite_by_email("one-more", "ivy@example.com", remote).unwrap_err();
+    assert_eq!(error.code(), "pvp.too-many-emails");
+}
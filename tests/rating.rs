@@ -0,0 +1,57 @@
+//! Elo and Glicko-2 ratings
+
+extern crate rock_paper_scissors;
+
+use rock_paper_scissors::rating::{self, Elo, Glicko2, Rating, RatingSystem};
+
+fn rating(rating: f64, deviation: f64) -> Rating {
+    Rating {
+        rating: rating,
+        deviation: deviation,
+        volatility: rating::INITIAL_VOLATILITY,
+    }
+}
+
+#[test]
+fn glicko2_matches_the_example_of_the_paper() {
+    let mut player = rating(1500.0, 200.0);
+    let games = [
+        (rating(1400.0, 30.0), 1.0),
+        (rating(1550.0, 100.0), 0.0),
+        (rating(1700.0, 300.0), 0.0),
+    ];
+    Glicko2.rate(&mut player, &games);
+    assert!((player.rating - 1464.06).abs() < 0.05, "{:?}", player);
+    assert!((player.deviation - 151.52).abs() < 0.01, "{:?}", player);
+    assert!((player.volatility - 0.05999).abs() < 1e-5, "{:?}", player);
+}
+
+#[test]
+fn glicko2_grows_unsure_of_idle_players() {
+    let mut player = rating(1500.0, 50.0);
+    Glicko2.rate(&mut player, &[]);
+    assert_eq!(player.rating, 1500.0);
+    assert!(player.deviation > 50.0);
+
+    let mut player = rating(1500.0, rating::INITIAL_DEVIATION);
+    Glicko2.rate(&mut player, &[]);
+    assert_eq!(player.deviation, rating::INITIAL_DEVIATION);
+}
+
+#[test]
+fn elo_leaves_the_deviation_alone() {
+    let mut player = rating(1500.0, 200.0);
+    Elo.rate(&mut player, &[(rating::COMPUTER, 1.0)]);
+    assert_eq!(player.rating, 1508.0);
+    assert_eq!(player.deviation, 200.0);
+}
+
+#[test]
+fn migrated_ratings_are_surer_the_more_rounds_they_come_from() {
+    assert_eq!(Rating::migrated(1600.0, 0).deviation, rating::INITIAL_DEVIATION);
+    let few = Rating::migrated(1600.0, 10);
+    let many = Rating::migrated(1600.0, 1000);
+    assert_eq!(many.rating, 1600.0);
+    assert!(many.deviation < few.deviation && few.deviation < rating::INITIAL_DEVIATION);
+    assert!(Rating::migrated(1600.0, 1_000_000).deviation >= 50.0);
+}
@@ -0,0 +1,83 @@
+//! Relay tournaments, from the rosters to the standings
+
+extern crate rock_paper_scissors;
+
+use rock_paper_scissors::game::Hand;
+use rock_paper_scissors::relay::{self, RelayError, LEG_ROUNDS};
+use rock_paper_scissors::util::unix_time;
+
+const DAY_SECS: u64 = 24 * 3600;
+const HOUR_SECS: u64 = 3600;
+
+/// A full team of `prefix-1`, its captain, `prefix-2` and `prefix-3`.
+fn full_team(name: &str, prefix: &str) {
+    let code = relay::register(name, &format!("{}-1", prefix)).unwrap().code;
+    for i in 2..4 {
+        relay::join(&format!("{}-{}", prefix, i), &code).unwrap();
+    }
+}
+
+#[test]
+fn rosters_are_kept_by_the_captain() {
+    assert_eq!(relay::register("Bad Name", "cat-1").unwrap_err(), RelayError::InvalidName);
+    let team = relay::register("relay-cats", "cat-1").unwrap();
+    assert_eq!(relay::register("relay-cats", "cat-2").unwrap_err(), RelayError::NameTaken);
+    assert_eq!(relay::register("relay-kits", "cat-1").unwrap_err(), RelayError::AlreadyInTeam);
+    assert_eq!(relay::join("cat-2", "no-such-code").unwrap_err(), RelayError::NoSuchTeam);
+
+    relay::join("cat-2", &team.code).unwrap();
+    assert_eq!(relay::drop_member("cat-2", "cat-1").unwrap_err(), RelayError::NotCaptain);
+    assert_eq!(relay::drop_member("cat-1", "cat-9").unwrap_err(), RelayError::NoSuchMember);
+    let team = relay::drop_member("cat-1", "cat-2").unwrap();
+    assert_eq!(team.members, vec!["cat-1".to_owned()]);
+
+    relay::leave("cat-1").unwrap();
+    assert_eq!(relay::team("cat-1").unwrap_err(), RelayError::NotInTeam);
+}
+
+#[test]
+fn a_relay_passes_the_baton_leg_by_leg() {
+    full_team("relay-ants", "ant");
+    full_team("relay-bees", "bee");
+    let start = unix_time() + 2 * DAY_SECS;
+    relay::tick_at(start);
+
+    let team = relay::team("ant-1").unwrap();
+    assert!(team.full);
+    let status = team.relay.unwrap();
+    assert_eq!((status.leg, status.runner), (Some(1), Some("ant-1".to_owned())));
+    assert!(status.digest.is_some());
+    assert_eq!(relay::join("ant-4", &team.code).unwrap_err(), RelayError::TeamFull);
+    assert_eq!(relay::leave("ant-2").unwrap_err(), RelayError::RosterLocked);
+    assert_eq!(relay::play("ant-2", Hand::Rock, false).unwrap_err(), RelayError::NotYourLeg);
+
+    let mut first_leg = 0;
+    for _ in 0..LEG_ROUNDS {
+        let round = relay::play("ant-1", Hand::Rock, false).unwrap();
+        first_leg = round.relay.score;
+    }
+    let status = relay::team("ant-1").unwrap().relay.unwrap();
+    assert_eq!((status.leg, status.runner), (Some(2), Some("ant-2".to_owned())));
+    assert_eq!(status.score, first_leg);
+
+    // A member whose account is deleted loses the rest of their legs, and the next one runs.
+    relay::forget_member("ant-2");
+    let status = relay::team("ant-1").unwrap().relay.unwrap();
+    assert_eq!((status.leg, status.runner), (Some(3), Some("ant-3".to_owned())));
+    assert_eq!(status.legs[1].score.losses, LEG_ROUNDS);
+    assert!(status.digest.is_some());
+    relay::play("ant-3", Hand::Paper, false).unwrap();
+
+    // Time is up: the rounds left are lost and the tournament is over.
+    relay::tick_at(start + HOUR_SECS);
+    assert!(relay::team("ant-1").unwrap().relay.is_none());
+    let standings = relay::standings().0;
+    assert_eq!(standings.latest.len(), 1);
+    let (ants, bees) = standings.latest[0].scores;
+    assert_eq!(bees, -3 * LEG_ROUNDS as i64);
+    // The second leg is lost, and all but the one round played of the third.
+    let lost = 2 * LEG_ROUNDS as i64 - 1;
+    assert!(ants >= first_leg - lost - 1 && ants <= first_leg - lost + 1);
+    let bees = standings.standings.iter().find(|standing| standing.team == "relay-bees").unwrap();
+    assert_eq!((bees.tournaments, bees.best_score), (1, -3 * LEG_ROUNDS as i64));
+}
@@ -0,0 +1,61 @@
+//! Revoked sessions, remembered across restarts for as long as their cookies could be replayed
+
+extern crate rocket;
+extern crate rock_paper_scissors;
+#[macro_use] extern crate serde_json;
+
+use std::env;
+use std::fs::{self, File};
+use std::io::Write;
+use std::process;
+
+use rocket::config::{Config, Environment};
+
+use rock_paper_scissors::ids::UserId;
+use rock_paper_scissors::revocation::{Revocations, REVOCATION_TTL};
+use rock_paper_scissors::util::unix_time;
+
+fn id(id: &str) -> UserId {
+    UserId::from_string(id.to_owned())
+}
+
+fn config(name: &str) -> Config {
+    let file = env::temp_dir().join(format!("rps-revocations-{}-{}.json", process::id(), name));
+    let _ = fs::remove_file(&file);
+    Config::build(Environment::Development)
+        .extra("revocation_file", file.to_str().unwrap())
+        .finalize()
+        .unwrap()
+}
+
+#[test]
+fn revocations_outlive_a_restart() {
+    let config = config("restart");
+    let revocations = Revocations::from_config(&config).unwrap();
+    revocations.revoke(&id("session-1"));
+    assert!(revocations.is_revoked(&id("session-1")));
+
+    let restarted = Revocations::from_config(&config).unwrap();
+    assert!(restarted.is_revoked(&id("session-1")));
+    assert!(!restarted.is_revoked(&id("session-2")));
+}
+
+#[test]
+fn revocations_are_forgotten_once_no_cookie_of_theirs_is_accepted() {
+    let config = config("expired");
+    let path = config.get_str("revocation_file").unwrap().to_owned();
+    let now = unix_time();
+    let file = json!({
+        "format": "revocations",
+        "version": 0,
+        "data": [
+            { "user_id": "old", "at": now - REVOCATION_TTL },
+            { "user_id": "recent", "at": now - REVOCATION_TTL + 60 },
+        ],
+    });
+    File::create(&path).unwrap().write_all(file.to_string().as_bytes()).unwrap();
+
+    let revocations = Revocations::from_config(&config).unwrap();
+    assert!(!revocations.is_revoked(&id("old")));
+    assert!(revocations.is_revoked(&id("recent")));
+}
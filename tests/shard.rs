@@ -0,0 +1,42 @@
+//! Sharded maps surviving a panic while locked
+
+extern crate rock_paper_scissors;
+
+use std::sync::Arc;
+use std::thread;
+
+use rock_paper_scissors::shard::ShardedMap;
+
+#[test]
+fn a_panic_drops_only_the_entry_being_changed() {
+    let map = Arc::new(ShardedMap::new(1));
+    map.write("alice").insert("alice".to_owned(), 1);
+    map.write("bob").insert("bob".to_owned(), 2);
+
+    let panicking = map.clone();
+    let result = thread::spawn(move || {
+        let mut shard = panicking.write("alice");
+        *shard.get_mut("alice").unwrap() += 1;
+        panic!("halfway through");
+    }).join();
+    assert!(result.is_err());
+
+    // The single shard is poisoned, but still usable, and only Alice's entry is gone.
+    assert!(!map.read("alice").contains_key("alice"));
+    assert_eq!(map.read("bob").get("bob"), Some(&2));
+    map.write("alice").insert("alice".to_owned(), 1);
+    assert_eq!(map.read_all().len(), 2);
+}
+
+#[test]
+fn a_panic_without_an_entry_changes_nothing() {
+    let map = Arc::new(ShardedMap::new(1));
+    map.write("bob").insert("bob".to_owned(), 2);
+    let panicking = map.clone();
+    let result = thread::spawn(move || {
+        let _shard = panicking.write("nobody");
+        panic!("before anything");
+    }).join();
+    assert!(result.is_err());
+    assert_eq!(map.write_all().get("bob"), Some(&2));
+}
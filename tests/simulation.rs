@@ -0,0 +1,37 @@
+//! Simulated matches against scripted players
+
+extern crate rock_paper_scissors;
+
+use rock_paper_scissors::simulation::{self, Computer, Policy};
+
+#[test]
+fn custom_weights_must_not_all_be_zero() {
+    assert_eq!(Policy::from_name("custom", [0, 0, 0]), None);
+    assert_eq!(Policy::from_name("custom", [0, 1, 0]), Some(Policy::Custom([0, 1, 0])));
+}
+
+#[test]
+fn the_largest_weights_do_not_overflow() {
+    let policy = Policy::from_name("custom", [u32::max_value(); 3]).unwrap();
+    let computer = Computer::from_name("random").unwrap();
+    let simulation = simulation::run(computer, policy, 100);
+    let score = simulation.score;
+    assert_eq!(score.wins + score.ties + score.losses, 100);
+}
+
+#[test]
+fn a_single_weight_always_plays_its_hand() {
+    // The mirror plays the player's previous hand, so paper every round ties every round but the
+    // first.
+    let policy = Policy::from_name("custom", [0, u32::max_value(), 0]).unwrap();
+    let computer = Computer::from_name("mirror").unwrap();
+    let simulation = simulation::run(computer, policy, 100);
+    assert!(simulation.score.ties >= 99);
+}
+
+#[test]
+fn simulations_play_at_most_max_rounds() {
+    let computer = Computer::from_name("markov").unwrap();
+    let simulation = simulation::run(computer, Policy::Uniform, simulation::MAX_ROUNDS + 1);
+    assert_eq!(simulation.rounds, simulation::MAX_ROUNDS);
+}
@@ -0,0 +1,127 @@
+//! Versioned data files and their migrations
+
+extern crate rock_paper_scissors;
+#[macro_use] extern crate serde_json;
+
+use std::collections::HashMap;
+use std::env;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::PathBuf;
+use std::process;
+
+use serde_json::Value;
+
+use rock_paper_scissors::store::{self, Format, Migration, Options};
+
+fn rename_score(data: &mut Value) -> Result<(), String> {
+    let score = data.as_object_mut().and_then(|object| object.remove("score"))
+        .ok_or("no score")?;
+    data["points"] = score;
+    Ok(())
+}
+
+fn double_points(data: &mut Value) -> Result<(), String> {
+    let points = data["points"].as_u64().ok_or("no points")?;
+    data["points"] = json!(points * 2);
+    Ok(())
+}
+
+const FORMAT: Format = Format {
+    name: "scores",
+    migrations: &[
+        Migration { version: 1, description: "score renamed points", upgrade: rename_score },
+        Migration { version: 2, description: "points doubled", upgrade: double_points },
+    ],
+};
+
+fn temporary_file(name: &str) -> PathBuf {
+    env::temp_dir().join(format!("rps-store-{}-{}.json", process::id(), name))
+}
+
+#[test]
+fn files_from_before_the_envelope_are_version_0() {
+    let (version, data) = FORMAT.unwrap(json!({ "score": 3 }));
+    assert_eq!(version, 0);
+    assert_eq!(data, json!({ "score": 3 }));
+
+    let (version, data) = FORMAT.unwrap(FORMAT.wrap(json!({ "points": 6 })));
+    assert_eq!(version, 2);
+    assert_eq!(data, json!({ "points": 6 }));
+}
+
+#[test]
+fn migrations_run_in_order_from_the_file_version() {
+    let mut data = json!({ "score": 3 });
+    let run = FORMAT.upgrade(0, &mut data).unwrap();
+    assert_eq!(run.iter().map(|migration| migration.version).collect::<Vec<_>>(), vec![1, 2]);
+    assert_eq!(data, json!({ "points": 6 }));
+
+    let mut data = json!({ "points": 3 });
+    assert_eq!(FORMAT.upgrade(1, &mut data).unwrap().len(), 1);
+    assert_eq!(data, json!({ "points": 6 }));
+
+    assert!(FORMAT.upgrade(2, &mut data).unwrap().is_empty());
+    assert_eq!(data, json!({ "points": 6 }));
+}
+
+#[test]
+fn newer_and_malformed_files_are_refused() {
+    assert!(FORMAT.upgrade(3, &mut json!({ "points": 6 })).is_err());
+    assert!(FORMAT.upgrade(0, &mut json!({ "points": 6 })).is_err());
+}
+
+#[test]
+fn loading_upgrades_the_file_and_backs_it_up() {
+    let path = temporary_file("upgrade");
+    File::create(&path).unwrap().write_all(br#"{"score": 3}"#).unwrap();
+
+    let loaded = store::load::<HashMap<String, u64>>(&path, &FORMAT, Options::default())
+        .unwrap()
+        .unwrap();
+    assert_eq!(loaded.data["points"], 6);
+    assert!(!loaded.read_only);
+    let saved: Value = serde_json::from_reader(File::open(&path).unwrap()).unwrap();
+    assert_eq!(saved, json!({ "format": "scores", "version": 2, "data": { "points": 6 } }));
+    let backup = store::backup_path(&path, 0);
+    let original: Value = serde_json::from_reader(File::open(&backup).unwrap()).unwrap();
+    assert_eq!(original, json!({ "score": 3 }));
+
+    fs::remove_file(&path).unwrap();
+    fs::remove_file(&backup).unwrap();
+}
+
+#[test]
+fn a_dry_run_leaves_the_file_alone() {
+    let path = temporary_file("dry-run");
+    File::create(&path).unwrap().write_all(br#"{"score": 3}"#).unwrap();
+    let options = Options { dry_run: true, backup: true };
+
+    let loaded = store::load::<HashMap<String, u64>>(&path, &FORMAT, options).unwrap().unwrap();
+    assert_eq!(loaded.data["points"], 6);
+    assert!(loaded.read_only);
+    assert_eq!(fs::read_to_string(&path).unwrap(), r#"{"score": 3}"#);
+    assert!(!store::backup_path(&path, 0).exists());
+
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn a_failed_write_leaves_the_file_as_it_was() {
+    let path = temporary_file("failed-write");
+    File::create(&path).unwrap().write_all(br#"{"points": 6}"#).unwrap();
+    let temporary = PathBuf::from(format!("{}.tmp", path.display()));
+    fs::create_dir(&temporary).unwrap();
+
+    assert!(store::write(&path, &json!({ "points": 8 })).is_err());
+    assert_eq!(fs::read_to_string(&path).unwrap(), r#"{"points": 6}"#);
+
+    // Nor is anything left behind when the rename fails, here over a directory.
+    fs::remove_dir(&temporary).unwrap();
+    fs::remove_file(&path).unwrap();
+    fs::create_dir(&path).unwrap();
+    assert!(store::write(&path, &json!({ "points": 8 })).is_err());
+    assert!(!temporary.exists());
+
+    fs::remove_dir(&path).unwrap();
+}
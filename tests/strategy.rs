@@ -0,0 +1,85 @@
+//! Fun mode's meta-strategy holding the player's win rate to its target
+
+extern crate rock_paper_scissors;
+
+use rock_paper_scissors::game::{Hand, Verdict};
+use rock_paper_scissors::strategy::{Adaptive, PlayerModel, DEFAULT_TARGET_WIN_RATE};
+
+/// A model of a player who always plays rock.
+fn rock_player() -> PlayerModel {
+    let mut model = PlayerModel::new();
+    for _ in 0..20 {
+        model.observe(Hand::Rock);
+    }
+    model
+}
+
+fn assert_close(a: [f64; 3], b: [f64; 3]) {
+    for (a, b) in a.iter().zip(b.iter()) {
+        assert!((a - b).abs() < 1e-9, "{:?} is not {:?}", a, b);
+    }
+}
+
+#[test]
+fn ties_are_not_recorded() {
+    let mut adaptive = Adaptive::new();
+    adaptive.record(Verdict::Tie);
+    let snapshot = adaptive.snapshot();
+    assert_eq!(snapshot.decided, 0);
+    assert_eq!(snapshot.win_rate, None);
+    assert_eq!(snapshot.exploitation, 0.0);
+}
+
+#[test]
+fn the_exploitation_level_stays_between_minus_one_and_one() {
+    let mut adaptive = Adaptive::new();
+    for _ in 0..1000 {
+        adaptive.record(Verdict::Win);
+    }
+    assert_eq!(adaptive.snapshot().exploitation, 1.0);
+    assert_eq!(adaptive.snapshot().win_rate, Some(1.0));
+
+    for _ in 0..1000 {
+        adaptive.record(Verdict::Loss);
+    }
+    assert_eq!(adaptive.snapshot().exploitation, -1.0);
+    assert!(adaptive.snapshot().win_rate.unwrap() < 1e-9);
+}
+
+#[test]
+fn the_distribution_follows_the_exploitation_level() {
+    let model = rock_player();
+    let mut adaptive = Adaptive::new();
+    assert_close(adaptive.distribution(&model), [1.0 / 3.0; 3]);
+
+    // Winning players meet the best response to rock; losing ones the hand rock beats.
+    for _ in 0..1000 {
+        adaptive.record(Verdict::Win);
+    }
+    assert_close(adaptive.distribution(&model), [0.0, 1.0, 0.0]);
+    for _ in 0..1000 {
+        adaptive.record(Verdict::Loss);
+    }
+    assert_close(adaptive.distribution(&model), [0.0, 0.0, 1.0]);
+}
+
+#[test]
+fn a_predictable_player_wins_near_the_target() {
+    let mut model = rock_player();
+    let mut adaptive = Adaptive::new();
+    let (mut wins, mut decided) = (0, 0);
+    for round in 0..6000 {
+        let verdict = Verdict::of(Hand::Rock, adaptive.pick(&model));
+        adaptive.record(verdict);
+        model.observe(Hand::Rock);
+        // Once the level settled.
+        if round >= 2000 && verdict != Verdict::Tie {
+            decided += 1;
+            if verdict == Verdict::Win {
+                wins += 1;
+            }
+        }
+    }
+    let win_rate = f64::from(wins) / f64::from(decided);
+    assert!((win_rate - DEFAULT_TARGET_WIN_RATE).abs() < 0.1, "won {} of the rounds", win_rate);
+}